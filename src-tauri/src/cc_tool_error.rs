@@ -0,0 +1,150 @@
+//! This module contains the error type returned by all Tauri commands of the backend.
+//! It is serialized as `{ "kind": "<Variant>", ...fields }`, so the frontend and scripts can
+//! react to specific failures instead of parsing messages.
+
+use std::fmt;
+use std::sync::PoisonError;
+
+use crate::input_processing::InputError;
+use crate::tinymesh_serial_util::FrameError;
+use crate::tx_guard::TxGuardLimit;
+
+#[derive(Clone, Debug, PartialEq, serde::Serialize)]
+#[serde(tag = "kind")]
+pub enum CcToolError {
+    /// No device is connected
+    NotConnected,
+    /// There is no session with the given ID
+    UnknownSession { session_id: String },
+    /// There is no task with the given ID
+    UnknownTask { task_id: u64 },
+    /// The port is in use by another operation (e.g. the RSSI stream)
+    PortBusy,
+    /// The device did not answer in time
+    Timeout { received: Vec<u8> },
+    /// The device answered, but not with what we expected
+    UnexpectedResponse { expected: String, got: Vec<u8> },
+    /// No RMD file matching the device model was found
+    RmdNotFound {
+        model: String,
+        searched_paths: Vec<String>,
+    },
+    /// The RMD file could not be parsed
+    RmdParse { line: usize, message: String },
+    /// The device configuration / calibration has to be read before it can be changed
+    ConfigNotRead,
+    /// A cell value is not allowed by the module description
+    ValidationFailed { address: usize, message: String },
+    /// A TX test mode can't be started, the limit of the TX guard is reached
+    TxLimitReached {
+        limit: TxGuardLimit,
+        retry_after_ms: u64,
+    },
+    /// The user input could not be converted to bytes
+    InvalidInput { message: String },
+    /// Reading from or writing to the device (or a file) failed
+    Io { message: String },
+    /// The program state could not be accessed
+    Internal { message: String },
+}
+
+impl fmt::Display for CcToolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CcToolError::NotConnected => write!(f, "No device connected"),
+            CcToolError::UnknownSession { session_id } => {
+                write!(f, "No session with ID '{}'", session_id)
+            }
+            CcToolError::UnknownTask { task_id } => write!(f, "No task with ID {}", task_id),
+            CcToolError::PortBusy => write!(f, "The port is busy with another operation"),
+            CcToolError::Timeout { received } => write!(
+                f,
+                "Timed out waiting for the device ({} bytes received)",
+                received.len()
+            ),
+            CcToolError::UnexpectedResponse { expected, got } => write!(
+                f,
+                "Unexpected response from device: expected {}, got {:02X?}",
+                expected, got
+            ),
+            CcToolError::RmdNotFound {
+                model,
+                searched_paths,
+            } => write!(
+                f,
+                "No RMD file found for model '{}'. Searched: {}",
+                model,
+                searched_paths.join(", ")
+            ),
+            CcToolError::RmdParse { line, message } => {
+                write!(f, "Error in RMD file at line {}: {}", line, message)
+            }
+            CcToolError::ConfigNotRead => write!(f, "Read the device memory first"),
+            CcToolError::ValidationFailed { address, message } => {
+                write!(f, "Invalid value for cell 0x{:02X}: {}", address, message)
+            }
+            CcToolError::TxLimitReached {
+                limit,
+                retry_after_ms,
+            } => write!(
+                f,
+                "TX limit reached ({:?}), TX test modes can be started again in {} s",
+                limit,
+                retry_after_ms.div_ceil(1000)
+            ),
+            CcToolError::InvalidInput { message } => write!(f, "Invalid input: {}", message),
+            CcToolError::Io { message } => write!(f, "I/O error: {}", message),
+            CcToolError::Internal { message } => write!(f, "Internal error: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for CcToolError {}
+
+impl From<std::io::Error> for CcToolError {
+    fn from(err: std::io::Error) -> Self {
+        match err.kind() {
+            std::io::ErrorKind::TimedOut => CcToolError::Timeout { received: vec![] },
+            _ => CcToolError::Io {
+                message: err.to_string(),
+            },
+        }
+    }
+}
+
+impl From<serialport::Error> for CcToolError {
+    fn from(err: serialport::Error) -> Self {
+        CcToolError::Io {
+            message: err.to_string(),
+        }
+    }
+}
+
+impl From<FrameError> for CcToolError {
+    fn from(err: FrameError) -> Self {
+        match err {
+            FrameError::Timeout { received } => CcToolError::Timeout { received },
+            FrameError::Overflow { received } => CcToolError::UnexpectedResponse {
+                expected: "a response terminated by '>'".to_string(),
+                got: received,
+            },
+            FrameError::Io(message) => CcToolError::Io { message },
+        }
+    }
+}
+
+impl From<InputError> for CcToolError {
+    fn from(err: InputError) -> Self {
+        CcToolError::InvalidInput {
+            message: err.to_string(),
+        }
+    }
+}
+
+impl<T> From<PoisonError<T>> for CcToolError {
+    fn from(err: PoisonError<T>) -> Self {
+        CcToolError::Internal {
+            message: err.to_string(),
+        }
+    }
+}
@@ -1,9 +1,23 @@
 //! Data types used in the app backend
 
-use std::sync::{Arc, Mutex};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
 
-use serialport::SerialPort;
-use tauri::async_runtime::JoinHandle;
+use tauri::AppHandle;
+
+use crate::cc_tool_error::CcToolError;
+use crate::mesh_topology::{MeshChange, MeshNode, MeshTable};
+use crate::packet_sniffer::{PacketSniffer, SnifferReport};
+use crate::rssi_sniffer::{RssiReport, RssiSniffer};
+use crate::port_actor::PortActor;
+use crate::serial_settings::SerialSettings;
+use crate::task_manager::TaskManager;
+use crate::tinymesh_packet::{TinyMeshPacket, PROTOCOL_MODE_ADDRESS, PROTOCOL_MODE_PACKET};
+use crate::tinymesh_serial_util::tear_down_session;
+use crate::transport::Transport;
+use crate::tx_guard::{TxGuard, TxGuardConfig, TxGuardLimit, TxGuardStatus};
 
 /// Data type for the testmode sequence.
 /// Conceptually, both testmode and quickmode sequence datatypes are the same.
@@ -15,6 +29,8 @@ pub struct MkDeviceTestMode {
     pub description: String,
     pub sequence_on: String,
     pub sequence_off: String,
+    /// Whether the module transmits in this mode, from the `TESTMODE n TX` entry of the RMD file
+    pub is_tx: Option<bool>,
 }
 
 /// Data type for the quickmode sequence.
@@ -27,6 +43,8 @@ pub struct MkDeviceQuickMode {
     pub description: String,
     pub sequence_on: String,
     pub sequence_off: String,
+    /// Whether the module transmits in this mode, from the `QUICKMODE n TX` entry of the RMD file
+    pub is_tx: Option<bool>,
 }
 
 /// Data type for the device configuration cell
@@ -42,28 +60,441 @@ pub struct MkDeviceCell {
     pub current_value: u8,
 }
 
-/// DeviceEntity contains the state of the program
+/// DeviceEntity contains the state of the program: a registry of device sessions keyed by session ID.
+/// The session ID is the name of the port (or `tcp://` address) the session is connected to.
+#[derive(Default)]
 pub struct DeviceEntity {
-    /// The device serial port connection that can be shared across threads
-    pub port: Arc<Mutex<Option<Box<dyn SerialPort>>>>,
+    pub sessions: Mutex<HashMap<String, Arc<DeviceSession>>>,
+    /// The session used by commands that are called without a session ID (the last connected one)
+    pub default_session_id: Mutex<Option<String>>,
+    /// Whether the port watcher reconnects when the adapter of a lost connection reappears
+    pub auto_reconnect: Arc<Mutex<bool>>,
+    /// Whether the output of the sniffer test modes is parsed. Off by default, as the output format is unverified.
+    pub parse_sniffer_output: Arc<Mutex<bool>>,
+    /// The limits of the TX test modes set by the user, replacing the ones of the RMD files
+    pub tx_guard_config: Arc<Mutex<Option<TxGuardConfig>>>,
+}
+
+/// DeviceSession contains the state of a single connected device.
+/// Cloning it shares the state, e.g. with background tasks.
+#[derive(Clone)]
+pub struct DeviceSession {
+    /// The actor owning the device connection (serial port, TCP socket etc.).
+    /// It runs the transactions of all commands and streams received bytes in between.
+    pub port: Arc<PortActor>,
 
-    /// Tokio tasks for streaming RSSI in spectrum analyzer mode and background communication
-    pub rssi_task: Mutex<Option<JoinHandle<()>>>,
-    pub is_rssi_task_running: Arc<Mutex<bool>>,
-    pub communication_task: Mutex<Option<JoinHandle<()>>>,
-    pub is_communication_task_running: Arc<Mutex<bool>>,
+    /// The background jobs of the session (RSSI scan, RX streaming etc.)
+    pub tasks: TaskManager,
 
-    /// Device config is stored inside the state of the program
+    /// Device config is stored inside the state of the session
     pub device_config: Arc<Mutex<Option<MkDeviceConfig>>>,
-    /// Device calibration is stored inside the state of the program
+    /// Device calibration is stored inside the state of the session
     pub device_calib: Arc<Mutex<Option<MkDeviceCalib>>>,
+    /// Whether the device is a gateway in packet mode, according to the configuration last read from it.
+    /// Kept apart from `device_config`, whose lock may be held while a transaction runs.
+    pub is_packet_mode: Arc<Mutex<bool>>,
+    /// The configurations last read from remote nodes through the gateway, keyed by unique ID
+    pub remote_configs: Arc<Mutex<HashMap<u32, MkDeviceConfig>>>,
+    /// The nodes heard through the gateway, built from the received packets
+    pub mesh_table: Arc<Mutex<MeshTable>>,
+    /// The parser of the packet sniffer test mode output and its per-node statistics
+    pub packet_sniffer: Arc<Mutex<PacketSniffer>>,
+    /// The parser of the RSSI sniffer test mode output and its RSSI history per source
+    pub rssi_sniffer: Arc<Mutex<RssiSniffer>>,
+    /// The test or quick mode the device was put in, whose `SEQUENCE_OFF` has not been executed yet
+    pub active_mode: Arc<Mutex<Option<ActiveMode>>>,
+    /// The on-time and duty cycle bookkeeping of the TX test modes
+    pub tx_guard: Arc<Mutex<TxGuard>>,
+
+    /// The serial connection that was made by the user, kept after the adapter is unplugged
+    /// so the port watcher can reconnect to it
+    pub connection: Arc<Mutex<Option<ConnectionInfo>>>,
+}
+
+impl DeviceSession {
+    /// Creates a session for a freshly opened connection and starts the actor owning it
+    /// # Arguments
+    /// * `port` - The opened connection
+    /// * `connection` - The serial port and settings of the connection (`None` for TCP connections)
+    /// * `app_handle` - An optional Tauri application handle, used for emitting the received bytes
+    ///
+    /// # Returns
+    /// The session, or an error if the actor could not take over the connection.
+    pub fn new(
+        port: Box<dyn Transport>,
+        connection: Option<ConnectionInfo>,
+        app_handle: Option<AppHandle>,
+    ) -> Result<DeviceSession, CcToolError> {
+        return Ok(DeviceSession {
+            port: Arc::new(PortActor::spawn(Some(port), app_handle)?),
+            tasks: Default::default(),
+            device_config: Default::default(),
+            device_calib: Default::default(),
+            is_packet_mode: Default::default(),
+            remote_configs: Default::default(),
+            mesh_table: Default::default(),
+            packet_sniffer: Default::default(),
+            rssi_sniffer: Default::default(),
+            active_mode: Default::default(),
+            tx_guard: Default::default(),
+            connection: Arc::new(Mutex::new(connection)),
+        });
+    }
+
+    /// Updates `is_packet_mode` from the configuration read from the device.
+    /// # Arguments
+    /// * `device_config` - The configuration, or `None` if it was dropped
+    pub fn update_packet_mode(&self, device_config: Option<&MkDeviceConfig>) -> Result<(), CcToolError> {
+        *self.is_packet_mode.lock()? = device_config.is_some_and(MkDeviceConfig::is_packet_mode);
+        return Ok(());
+    }
+}
+
+impl DeviceEntity {
+    /// Returns the session with the given ID, or the default session if no ID is given.
+    /// # Arguments
+    /// * `session_id` - The ID of the session, or `None` for the default session
+    ///
+    /// # Returns
+    /// The session, `CcToolError::NotConnected` if no ID was given and there is no default session,
+    /// or `CcToolError::UnknownSession` if there is no session with the given ID.
+    pub fn session(&self, session_id: Option<&str>) -> Result<Arc<DeviceSession>, CcToolError> {
+        let sessions = self.sessions.lock()?;
+        let session_id = match session_id {
+            Some(session_id) => session_id.to_string(),
+            None => self
+                .default_session_id
+                .lock()?
+                .clone()
+                .ok_or(CcToolError::NotConnected)?,
+        };
+        return sessions
+            .get(&session_id)
+            .cloned()
+            .ok_or(CcToolError::UnknownSession { session_id });
+    }
+
+    /// Adds a session (replacing any session with the same ID) and makes it the default session.
+    pub fn insert_session(
+        &self,
+        session_id: &str,
+        session: DeviceSession,
+    ) -> Result<Arc<DeviceSession>, CcToolError> {
+        // `set_tx_guard_config` changes the limits while holding the sessions lock as well,
+        // so the new session can't miss a change
+        let mut sessions = self.sessions.lock()?;
+        session
+            .tx_guard
+            .lock()?
+            .set_user_config(*self.tx_guard_config.lock()?);
+        let session = Arc::new(session);
+        sessions.insert(session_id.to_string(), session.clone());
+        drop(sessions);
+        *self.default_session_id.lock()? = Some(session_id.to_string());
+        return Ok(session);
+    }
+
+    /// Removes the session with the given ID, or the default session if no ID is given.
+    /// # Returns
+    /// The removed session, or `None` if there was no such session.
+    pub fn remove_session(
+        &self,
+        session_id: Option<&str>,
+    ) -> Result<Option<Arc<DeviceSession>>, CcToolError> {
+        let mut sessions = self.sessions.lock()?;
+        let mut default_session_id = self.default_session_id.lock()?;
+        let Some(session_id) = session_id
+            .map(str::to_string)
+            .or_else(|| default_session_id.clone())
+        else {
+            return Ok(None);
+        };
+        if default_session_id.as_deref() == Some(session_id.as_str()) {
+            *default_session_id = None;
+        }
+        return Ok(sessions.remove(&session_id));
+    }
+
+    /// Moves a session to a new ID, e.g. when its adapter reappeared under another port name.
+    /// The session stays the default session if it was before.
+    /// A session already registered under the new ID is torn down and replaced.
+    pub fn rename_session(&self, session_id: &str, new_session_id: &str) -> Result<(), CcToolError> {
+        if session_id == new_session_id {
+            return Ok(());
+        }
+        let replaced_session = {
+            let mut sessions = self.sessions.lock()?;
+            let Some(session) = sessions.remove(session_id) else {
+                return Ok(());
+            };
+            let mut default_session_id = self.default_session_id.lock()?;
+            if default_session_id.as_deref() == Some(session_id) {
+                *default_session_id = Some(new_session_id.to_string());
+            }
+            sessions.insert(new_session_id.to_string(), session)
+        };
+        // The replaced session's tasks and active mode are stopped outside the registry lock
+        if let Some(replaced_session) = replaced_session {
+            tear_down_session(&replaced_session)?;
+        }
+        return Ok(());
+    }
+
+    /// Returns the IDs and sessions of all sessions, sorted by ID.
+    pub fn all_sessions(&self) -> Result<Vec<(String, Arc<DeviceSession>)>, CcToolError> {
+        let mut sessions: Vec<(String, Arc<DeviceSession>)> = self
+            .sessions
+            .lock()?
+            .iter()
+            .map(|(session_id, session)| (session_id.clone(), session.clone()))
+            .collect();
+        sessions.sort_by(|a, b| a.0.cmp(&b.0));
+        return Ok(sessions);
+    }
+}
+
+/// Summary of a session, returned by `list_sessions`
+#[derive(Clone, Debug, PartialEq, serde::Serialize)]
+pub struct SessionInfo {
+    pub session_id: String,
+    /// Whether the session has an open port (it doesn't after its adapter was unplugged)
+    pub is_connected: bool,
+    /// Whether the session is used by commands that are called without a session ID
+    pub is_default: bool,
+    /// The serial connection of the session (`None` for TCP connections)
+    pub connection: Option<ConnectionInfo>,
+}
+
+/// The serial port a connection was made to and the settings it was made with
+#[derive(Clone, Debug, PartialEq, serde::Serialize)]
+pub struct ConnectionInfo {
+    pub port_name: String,
+    /// USB serial number of the adapter, used to recognise it when it reappears under another port name
+    pub serial_number: Option<String>,
+    pub serial_settings: SerialSettings,
+}
+
+/// How a serial port is connected to the system
+#[derive(Clone, Debug, PartialEq, serde::Serialize)]
+pub enum PortType {
+    Usb,
+    Pci,
+    Bluetooth,
+    Unknown,
+}
+
+/// A serial port available on the system, with the USB metadata of its adapter (if any)
+#[derive(Clone, Debug, PartialEq, serde::Serialize)]
+pub struct SerialPortEntry {
+    pub port_name: String,
+    pub port_type: PortType,
+    pub vid: Option<u16>,
+    pub pid: Option<u16>,
+    pub serial_number: Option<String>,
+    pub manufacturer: Option<String>,
+    pub product: Option<String>,
+    /// Whether the adapter is a TinyMesh, FTDI or CP210x USB-serial adapter
+    pub is_known_adapter: bool,
+}
+
+/// The kind of an entry of the frontend logging panel
+#[derive(Clone, Copy, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum EventKind {
+    /// Bytes sent to the device
+    TX,
+    /// Bytes received from the device
+    RX,
+    /// A message of the backend, `data` is UTF-8 text
+    INFO,
+    /// An error of the backend, `data` is UTF-8 text
+    ERROR,
 }
 
 /// EventPayload contains the data that is sent to the frontend logging panel
-#[derive(Clone, serde::Serialize)]
+#[derive(Clone, Debug, serde::Serialize)]
 pub struct EventPayload {
-    pub data_type: String,
+    /// The session the bytes were exchanged in
+    pub session_id: Option<String>,
+    pub kind: EventKind,
     pub data: Vec<u8>,
+    /// Time at which the bytes were written or read, in microseconds since the UNIX epoch.
+    /// It is derived from the monotonic clock, so it never goes backwards while the app is running.
+    pub timestamp_us: u64,
+    /// Increases by one with every event, so the frontend can restore the order of the events
+    pub sequence: u64,
+}
+
+static NEXT_EVENT_SEQUENCE: AtomicU64 = AtomicU64::new(0);
+
+/// The monotonic clock and the wall clock at the same moment, used for converting `Instant`s to wall clock time
+static CLOCK_ANCHOR: OnceLock<(Instant, SystemTime)> = OnceLock::new();
+
+/// Converts a reading of the monotonic clock to microseconds since the UNIX epoch.
+pub fn unix_timestamp_us(at: Instant) -> u64 {
+    let (anchor_instant, anchor_time) =
+        CLOCK_ANCHOR.get_or_init(|| (Instant::now(), SystemTime::now()));
+    // Calculated in nanoseconds, so the difference of two timestamps doesn't depend on rounding
+    let anchor_ns = anchor_time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+    let at_ns = match at.checked_duration_since(*anchor_instant) {
+        Some(elapsed) => anchor_ns.saturating_add(elapsed.as_nanos()),
+        None => anchor_ns.saturating_sub(anchor_instant.duration_since(at).as_nanos()),
+    };
+    return (at_ns / 1000) as u64;
+}
+
+impl EventPayload {
+    /// Creates an event with the next sequence number.
+    /// # Arguments
+    /// * `session_id` - The session the bytes were exchanged in
+    /// * `kind` - The kind of the event
+    /// * `data` - The exchanged bytes (or the UTF-8 text of an `INFO` / `ERROR` event)
+    /// * `captured_at` - The time at which the bytes were written or read
+    pub fn new(
+        session_id: Option<String>,
+        kind: EventKind,
+        data: Vec<u8>,
+        captured_at: Instant,
+    ) -> EventPayload {
+        return EventPayload {
+            session_id,
+            kind,
+            data,
+            timestamp_us: unix_timestamp_us(captured_at),
+            sequence: NEXT_EVENT_SEQUENCE.fetch_add(1, Ordering::Relaxed),
+        };
+    }
+}
+
+/// PacketEventPayload contains a decoded TinyMesh packet, sent to the frontend as `packet_event`
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct PacketEventPayload {
+    /// The session the packet was received in
+    pub session_id: Option<String>,
+    pub packet: TinyMeshPacket,
+    /// Time at which the first byte of the packet was read, in microseconds since the UNIX epoch
+    pub timestamp_us: u64,
+    /// Shares the sequence of `EventPayload`, so packets can be matched with the raw RX events
+    pub sequence: u64,
+}
+
+impl PacketEventPayload {
+    /// Creates an event with the next sequence number.
+    /// # Arguments
+    /// * `session_id` - The session the packet was received in
+    /// * `packet` - The decoded packet
+    /// * `received_at` - The time at which the first byte of the packet was read
+    pub fn new(
+        session_id: Option<String>,
+        packet: TinyMeshPacket,
+        received_at: Instant,
+    ) -> PacketEventPayload {
+        return PacketEventPayload {
+            session_id,
+            packet,
+            timestamp_us: unix_timestamp_us(received_at),
+            sequence: NEXT_EVENT_SEQUENCE.fetch_add(1, Ordering::Relaxed),
+        };
+    }
+}
+
+/// MeshEventPayload contains a node of the mesh node table, sent to the frontend as `mesh_event`
+/// whenever a packet of the node is received
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct MeshEventPayload {
+    /// The session the packet was received in
+    pub session_id: Option<String>,
+    pub change: MeshChange,
+    pub node: MeshNode,
+}
+
+/// SnifferEventPayload contains a line of the packet sniffer test mode output, sent to the frontend as `sniffer_event`
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct SnifferEventPayload {
+    /// The session the line was received in
+    pub session_id: Option<String>,
+    pub report: SnifferReport,
+    /// Time at which the line was received, in microseconds since the UNIX epoch
+    pub timestamp_us: u64,
+}
+
+/// RssiSnifferEventPayload contains an RSSI report of the RSSI sniffer test mode, sent to the frontend as `rssi_sniffer_event`
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct RssiSnifferEventPayload {
+    /// The session the report was received in
+    pub session_id: Option<String>,
+    pub report: RssiReport,
+    /// Time at which the report was received, in microseconds since the UNIX epoch
+    pub timestamp_us: u64,
+}
+
+/// Whether a mode is one of the test modes or one of the quick modes of the RMD file
+#[derive(Clone, Copy, Debug, PartialEq, serde::Serialize)]
+pub enum ModeKind {
+    TestMode,
+    QuickMode,
+}
+
+/// The test or quick mode the device of a session was put in by executing its `SEQUENCE_ON`
+#[derive(Clone, Debug, PartialEq, serde::Serialize)]
+pub struct ActiveMode {
+    pub kind: ModeKind,
+    /// The `testmode_id` of the mode
+    pub mode_id: usize,
+    pub name: String,
+    pub sequence_on: String,
+    pub sequence_off: String,
+    /// Whether the module transmits in this mode, e.g. in "Test Mode 1 (TX On)"
+    pub is_tx: bool,
+}
+
+/// TestModeEventPayload contains the mode a device was put in or taken out of, sent to the frontend as `test_mode_event`
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct TestModeEventPayload {
+    /// The session of the device
+    pub session_id: Option<String>,
+    /// The active mode, `None` if the last mode was turned off
+    pub active_mode: Option<ActiveMode>,
+}
+
+/// Payload of `tx_guard_event`, emitted when a TX test mode was turned off because it reached a limit
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct TxGuardEventPayload {
+    /// The session of the device
+    pub session_id: Option<String>,
+    /// The limit that was reached
+    pub limit: TxGuardLimit,
+    /// The name of the test mode that was turned off
+    pub mode_name: String,
+    /// The state of the guard when the limit was reached
+    pub status: TxGuardStatus,
+}
+
+/// The conversion of raw RSSI values to dBm, given by the `RSSI_OFFSET` and `RSSI_SCALE_FACTOR`
+/// entries of the RMD file: `dBm = raw * scale_factor + offset`
+#[derive(Clone, Copy, serde::Serialize, Debug, PartialEq)]
+pub struct RssiScaling {
+    pub offset: f64,
+    pub scale_factor: f64,
+}
+
+impl Default for RssiScaling {
+    /// The scaling of the TM4070-class modules, used if the RMD file does not specify one
+    fn default() -> Self {
+        RssiScaling {
+            offset: 0.0,
+            scale_factor: -0.5,
+        }
+    }
+}
+
+impl RssiScaling {
+    /// Converts a raw RSSI value reported by the module to dBm.
+    pub fn to_dbm(&self, raw_rssi: i32) -> f64 {
+        return raw_rssi as f64 * self.scale_factor + self.offset;
+    }
 }
 
 /// This struct represents the decoded device config fetched from device
@@ -76,7 +507,85 @@ pub struct MkDeviceConfig {
     pub test_modes: Vec<MkDeviceTestMode>,
     pub quick_modes: Vec<MkDeviceQuickMode>,
     pub editable_cells: Vec<usize>,
-    pub locked_cells: Vec<usize>
+    pub locked_cells: Vec<usize>,
+    pub rssi_scaling: RssiScaling,
+    /// The limits of the TX test modes given by the RMD file
+    pub tx_guard: TxGuardConfig,
+}
+
+impl MkDeviceConfig {
+    /// Returns whether the Protocol mode cell (0x03) selects packet mode.
+    pub fn is_packet_mode(&self) -> bool {
+        return self
+            .cells
+            .iter()
+            .any(|cell| cell.address == PROTOCOL_MODE_ADDRESS && cell.current_value == PROTOCOL_MODE_PACKET);
+    }
+
+    /// Returns the test mode whose `SEQUENCE_ON` is the given mode sequence.
+    /// # Arguments
+    /// * `sequence_str` - The executed mode sequence
+    ///
+    /// # Returns
+    /// The test mode, or `None` if the sequence is empty or does not start a test mode of the RMD file.
+    pub fn test_mode_started_by(&self, sequence_str: &str) -> Option<&MkDeviceTestMode> {
+        if sequence_str.trim().is_empty() {
+            return None;
+        }
+        return self
+            .test_modes
+            .iter()
+            .find(|test_mode| test_mode.sequence_on.trim() == sequence_str.trim());
+    }
+
+    /// Returns the test or quick mode whose `SEQUENCE_ON` is the given mode sequence.
+    /// Test modes are preferred if a quick mode has the same sequence.
+    /// # Arguments
+    /// * `sequence_str` - The executed mode sequence
+    ///
+    /// # Returns
+    /// The mode, or `None` if the sequence is empty or does not start a mode of the RMD file.
+    pub fn mode_started_by(&self, sequence_str: &str) -> Option<ActiveMode> {
+        if let Some(test_mode) = self.test_mode_started_by(sequence_str) {
+            return Some(ActiveMode {
+                kind: ModeKind::TestMode,
+                mode_id: test_mode.testmode_id,
+                name: test_mode.name.clone(),
+                sequence_on: test_mode.sequence_on.clone(),
+                sequence_off: test_mode.sequence_off.clone(),
+                // Without a `TX` entry in the RMD file, the name of the test mode decides
+                is_tx: test_mode.is_tx.unwrap_or_else(|| is_tx_mode_name(&test_mode.name)),
+            });
+        }
+        if sequence_str.trim().is_empty() {
+            return None;
+        }
+        return self
+            .quick_modes
+            .iter()
+            .find(|quick_mode| quick_mode.sequence_on.trim() == sequence_str.trim())
+            .map(|quick_mode| ActiveMode {
+                kind: ModeKind::QuickMode,
+                mode_id: quick_mode.testmode_id,
+                name: quick_mode.name.clone(),
+                sequence_on: quick_mode.sequence_on.clone(),
+                sequence_off: quick_mode.sequence_off.clone(),
+                is_tx: quick_mode.is_tx.unwrap_or(false),
+            });
+    }
+}
+
+/// This function tells whether the name of a test mode describes a mode in which the module transmits.
+/// It is used for the test modes without a `TESTMODE n TX` entry in the RMD file.
+/// # Arguments
+/// * `name` - The name of the test mode, e.g. `Test Mode 2 (Modulated TX On)`
+///
+/// # Returns
+/// `true` if the name contains the word `TX` (in any case), `false` otherwise.
+pub fn is_tx_mode_name(name: &str) -> bool {
+    return name
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .any(|word| word.eq_ignore_ascii_case("tx"));
 }
 
 /// This struct represents the decoded device calib fetched from device
@@ -2,6 +2,7 @@ use std::path::Path;
 
 use tauri::AppHandle;
 
+use crate::cc_tool_error::CcToolError;
 use crate::data_types::{MkDeviceCell, MkDeviceCalib};
 use crate::mk_module_description::MkModuleDescription;
 
@@ -13,24 +14,30 @@ use crate::mk_module_description::MkModuleDescription;
 /// * `app_handle` - An optional reference to an `AppHandle` object
 ///
 /// # Returns
-/// A `Result` containing a `MkDeviceCalib` object if parsing is successful, or a `String` containing an error message if parsing fails
+/// A `Result` containing a `MkDeviceCalib` object if parsing is successful, or a `CcToolError` if parsing fails
 pub fn parse_device_calib(
     data: &[u8],
     model: String,
     rmd_file_path: Option<&Path>,
     app_handle: Option<&AppHandle>,
-) -> Result<MkDeviceCalib, String> {
+) -> Result<MkDeviceCalib, CcToolError> {
     // info!("\n\ndevice_calibration_parser::parse_device_calib(data, rmd_file_path, app_handle)\n\n");
     // info!("Data we get in device_calibration_parser::parse_device_calib-> {:?}", data);
     // let (model, _hw_version, _firmware_version) = get_device_information(data)?;
     // from the modules folder in the current working directory, read the contents of a file named <model>.rmd
     let module_description = if let Some(rmd_file_path) = rmd_file_path {
-        let file_contents =
-            std::fs::read_to_string(rmd_file_path).map_err(|err| err.to_string())?;
-        MkModuleDescription::new(&file_contents)
+        let file_contents = std::fs::read_to_string(rmd_file_path).map_err(|_| {
+            CcToolError::RmdNotFound {
+                model: model.clone(),
+                searched_paths: vec![rmd_file_path.display().to_string()],
+            }
+        })?;
+        MkModuleDescription::new(&file_contents)?
     } else {
         if app_handle.is_none() {
-            return Err("App handle is None".to_string());
+            return Err(CcToolError::Internal {
+                message: "App handle is None".to_string(),
+            });
         }
         // let file_contents =
         //     std::fs::read_to_string("resources/modules/RF TM4070.rmd").map_err(|err| err.to_string())?;
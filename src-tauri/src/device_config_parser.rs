@@ -3,6 +3,7 @@ use std::path::Path;
 
 use tauri::AppHandle;
 
+use crate::cc_tool_error::CcToolError;
 use crate::data_types::{MkDeviceCell, MkDeviceConfig};
 use crate::mk_module_description::MkModuleDescription;
 
@@ -14,26 +15,32 @@ use crate::mk_module_description::MkModuleDescription;
 /// * `app_handle` - An optional reference to an `AppHandle` object
 ///
 /// # Returns
-/// A `Result` containing a `MkDeviceConfig` object if parsing is successful, or a `String` containing an error message if parsing fails
+/// A `Result` containing a `MkDeviceConfig` object if parsing is successful, or a `CcToolError` if parsing fails
 use log::info;
 
 pub fn parse_device_config(
     data: &[u8],
     rmd_file_path: Option<&Path>,
     app_handle: Option<&AppHandle>,
-) -> Result<MkDeviceConfig, String> {
+) -> Result<MkDeviceConfig, CcToolError> {
     // info!("\ndevice_config_parser::parse_device_config(data, rmd_file_path, app_handle)\n");
     // info!("\ndevice_config_parser::parse_device_config---> data = {:?}", data);
     // info!("Data we get-> {:?}", data);
     let (model, hw_version, firmware_version) = get_device_information(data)?;
     // from the modules folder in the current working directory, read the contents of a file named <model>.rmd
     let module_description = if let Some(rmd_file_path) = rmd_file_path {
-        let file_contents =
-            std::fs::read_to_string(rmd_file_path).map_err(|err| err.to_string())?;
-        MkModuleDescription::new(&file_contents)
+        let file_contents = std::fs::read_to_string(rmd_file_path).map_err(|_| {
+            CcToolError::RmdNotFound {
+                model: model.clone(),
+                searched_paths: vec![rmd_file_path.display().to_string()],
+            }
+        })?;
+        MkModuleDescription::new(&file_contents)?
     } else {
         if app_handle.is_none() {
-            return Err("App handle is None".to_string());
+            return Err(CcToolError::Internal {
+                message: "App handle is None".to_string(),
+            });
         }
         MkModuleDescription::new_from_device_model(&model, app_handle.unwrap())?
     };
@@ -43,6 +50,8 @@ pub fn parse_device_config(
     let quick_modes = module_description.quickmodes;
     let editable_cells = module_description.editable_cells;
     let locked_cells = module_description.locked_cells;
+    let rssi_scaling = module_description.rssi_scaling;
+    let tx_guard = module_description.tx_guard;
 
     let result = MkDeviceConfig {
         model,
@@ -52,7 +61,9 @@ pub fn parse_device_config(
         test_modes,
         quick_modes,
         editable_cells,
-        locked_cells
+        locked_cells,
+        rssi_scaling,
+        tx_guard
     };
     Ok(result)
 }
@@ -80,32 +91,40 @@ fn read_unlocked_cells(data: &[u8], module_description: &MkModuleDescription) ->
 /// * `data` - A slice of bytes representing the device config data
 ///
 /// # Returns
-/// A `Result` containing a tuple of three `String` objects if parsing is successful, or a `String` containing an error message if parsing fails
+/// A `Result` containing a tuple of three `String` objects if parsing is successful,
+/// or `CcToolError::UnexpectedResponse` if the data does not contain the device information
 
-pub fn get_device_information(data: &[u8]) -> Result<(String, String, String), String> {
+pub fn get_device_information(data: &[u8]) -> Result<(String, String, String), CcToolError> {
     let mut offset = 0x3c;
     info!("\ndevice_config_parser::get_device_information(data)");
     // info!("\ndevice_config_parser::get_device_information---> data = {:?}", data);
     if offset >= data.len() {
-        return Err("Invalid data format".to_string());
+        return Err(invalid_device_information(data, "model"));
     }
     let model_end = data[offset..]
         .iter()
         .position(|&x| x == b',')
-        .ok_or("Invalid data format. Could not find model end")?;
+        .ok_or_else(|| invalid_device_information(data, "model"))?;
     let model = String::from_utf8_lossy(&data[offset..offset + model_end]).to_string();
     offset += model_end + 1;
     let hw_end = data[offset..]
         .iter()
         .position(|&x| x == b',')
-        .ok_or("Invalid data format. Could not find hw end")?;
+        .ok_or_else(|| invalid_device_information(data, "hardware version"))?;
     let hw_version = String::from_utf8_lossy(&data[offset..offset + hw_end]).to_string();
     offset += hw_end + 1;
     let firmware_end = data[offset..]
         .iter()
         .position(|&x| !x.is_ascii())
-        .ok_or("Invalid data format. Could not find fw end")?;
+        .ok_or_else(|| invalid_device_information(data, "firmware version"))?;
     let firmware_version =
         String::from_utf8_lossy(&data[offset..offset + min(firmware_end, 4)]).to_string();
     Ok((model, hw_version, firmware_version))
 }
+
+fn invalid_device_information(data: &[u8], field: &str) -> CcToolError {
+    CcToolError::UnexpectedResponse {
+        expected: format!("{} in the device information at 0x3C", field),
+        got: data.to_vec(),
+    }
+}
@@ -1,73 +1,267 @@
 //! Converts a string read from the Communication Panel's textarea into a vector of bytes.
+//!
+//! The input language:
+//! - Text is sent as its UTF-8 encoding, e.g. `é` is sent as `0xC3 0xA9`.
+//! - C-style escapes: `\r`, `\n`, `\t`, `\0`, `\\`, `\'`, `\xNN` (a single byte, two hex digits)
+//!   and `\u{NNNN}` (the UTF-8 encoding of a Unicode code point).
+//! - Single quotes enclose whitespace-separated byte values: decimal (`'13'`), hex (`'0x0D'`) or
+//!   binary (`'0b1101'`). A value can be repeated with `*count`, e.g. `'0x00*16'` sends 16 zero bytes.
+//! - An input starting with `hex:` is read in raw-hex mode: the rest of it are hex digit pairs, with
+//!   optional whitespace in between, e.g. `hex: 0D 0A` or `hex:0d0a`.
+//!
+//! Errors report the column (1-based, counted in characters) at which the invalid part starts.
 
+use std::fmt;
+
+/// Prefix of the inputs that are read in raw-hex mode
+pub const RAW_HEX_PREFIX: &str = "hex:";
+
+/// Maximum count of a repeated byte value (`'0x00*16'`)
+pub const MAX_REPEAT: usize = 4096;
+
+/// An error in the input, with the column (1-based, counted in characters) at which it starts
 #[derive(Debug, PartialEq)]
 pub enum InputError {
-    InvalidByteSequence(String),
+    /// A single quote is not closed
+    UnclosedQuote { column: usize },
+    /// A value inside single quotes is not a decimal, hex or binary byte
+    InvalidByte { column: usize, token: String },
+    /// The count of a repeated value is not a number from 1 to `MAX_REPEAT`
+    InvalidRepeat { column: usize, token: String },
+    /// An escape sequence is unknown or incomplete
+    InvalidEscape { column: usize, escape: String },
+    /// A character of a raw-hex input is not a hex digit
+    InvalidHexDigit { column: usize, character: char },
+    /// A raw-hex input ends with half a byte
+    OddHexDigits { column: usize },
+}
+
+impl InputError {
+    /// Returns the column (1-based, counted in characters) at which the invalid part of the input starts.
+    pub fn column(&self) -> usize {
+        match self {
+            InputError::UnclosedQuote { column }
+            | InputError::InvalidByte { column, .. }
+            | InputError::InvalidRepeat { column, .. }
+            | InputError::InvalidEscape { column, .. }
+            | InputError::InvalidHexDigit { column, .. }
+            | InputError::OddHexDigits { column } => *column,
+        }
+    }
+}
+
+impl fmt::Display for InputError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Column {}: ", self.column())?;
+        match self {
+            InputError::UnclosedQuote { .. } => write!(f, "Unclosed single quote"),
+            InputError::InvalidByte { token, .. } => write!(
+                f,
+                "'{}' is not a decimal, 0x hex or 0b binary byte value",
+                token
+            ),
+            InputError::InvalidRepeat { token, .. } => write!(
+                f,
+                "'{}' is not a repeat count from 1 to {}",
+                token, MAX_REPEAT
+            ),
+            InputError::InvalidEscape { escape, .. } => {
+                write!(f, "Invalid escape sequence '{}'", escape)
+            }
+            InputError::InvalidHexDigit { character, .. } => {
+                write!(f, "'{}' is not a hex digit", character)
+            }
+            InputError::OddHexDigits { .. } => write!(f, "Incomplete hex byte"),
+        }
+    }
 }
 
 /// Converts a string read from the Communication Panel's textarea into a vector of bytes.
 ///
 /// # Arguments
-/// * `input` - A string read from the Communication Panel's textarea. For examples, see
-/// `/tests/input_processing_test.rs`.
+/// * `input` - A string read from the Communication Panel's textarea, in the input language described
+///   in the module documentation. For examples, see `/tests/input_processing_test.rs`.
 ///
 /// # Returns
-/// Returns a vector of bytes or an error if the input is invalid.
+/// Returns a vector of bytes or an error with the column of the invalid part of the input.
 pub fn process_input(input: &str) -> Result<Vec<u8>, InputError> {
-    let mut result = Vec::new();
-    let mut in_quote = false;
-    let mut number_buffer = String::new();
+    let chars: Vec<char> = input.chars().collect();
+    if input.starts_with(RAW_HEX_PREFIX) {
+        let prefix_len = RAW_HEX_PREFIX.chars().count();
+        return parse_raw_hex(&chars[prefix_len..], prefix_len + 1);
+    }
 
-    for c in input.chars() {
-        match c {
+    let mut result = Vec::new();
+    let mut index = 0;
+    while index < chars.len() {
+        match chars[index] {
             '\'' => {
-                if in_quote {
-                    if !number_buffer.is_empty() {
-                        let num_buf_res: Vec<Result<u8, _>> = number_buffer
-                            .split_whitespace()
-                            .map(|s| {
-                                if let Ok(byte) = if s.starts_with("0x") {
-                                    u8::from_str_radix(&s[2..], 16)
-                                } else {
-                                    s.parse()
-                                } {
-                                    Ok(byte)
-                                } else {
-                                    Err(InputError::InvalidByteSequence(
-                                        "Non-numeric character inside quote".to_string(),
-                                    ))
-                                }
-                            })
-                            .collect();
-                        if num_buf_res.iter().all(|x| x.is_ok()) {
-                            for byte in num_buf_res {
-                                result.push(byte.unwrap());
-                            }
-                        } else {
-                            return Err(InputError::InvalidByteSequence(
-                                "Non-numeric character inside quote".to_string(),
-                            ));
-                        }
-                        number_buffer.clear();
-                    }
-                }
-                in_quote = !in_quote;
+                let Some(length) = chars[index + 1..].iter().position(|&c| c == '\'') else {
+                    return Err(InputError::UnclosedQuote { column: index + 1 });
+                };
+                let end = index + 1 + length;
+                parse_quoted_bytes(&chars[index + 1..end], index + 2, &mut result)?;
+                index = end + 1;
             }
-            _ => {
-                if in_quote {
-                    number_buffer.push(c);
-                } else {
-                    result.push(c as u8);
-                }
+            '\\' => {
+                index = parse_escape(&chars, index, &mut result)?;
+            }
+            c => {
+                let mut buffer = [0; 4];
+                result.extend_from_slice(c.encode_utf8(&mut buffer).as_bytes());
+                index += 1;
             }
         }
     }
+    return Ok(result);
+}
 
-    if in_quote {
-        return Err(InputError::InvalidByteSequence(
-            "Unclosed single quote".to_string(),
-        ));
+/// Parses the whitespace-separated byte values between single quotes.
+/// # Arguments
+/// * `chars` - The characters between the quotes
+/// * `first_column` - The column of the first character
+/// * `result` - The bytes to append the values to
+fn parse_quoted_bytes(
+    chars: &[char],
+    first_column: usize,
+    result: &mut Vec<u8>,
+) -> Result<(), InputError> {
+    let mut index = 0;
+    while index < chars.len() {
+        if chars[index].is_whitespace() {
+            index += 1;
+            continue;
+        }
+        let start = index;
+        while index < chars.len() && !chars[index].is_whitespace() {
+            index += 1;
+        }
+        let token: String = chars[start..index].iter().collect();
+        let (byte, count) = parse_byte_token(&token, first_column + start)?;
+        result.extend(std::iter::repeat_n(byte, count));
     }
+    return Ok(());
+}
+
+/// Parses a byte value inside single quotes, e.g. `13`, `0x0D`, `0b1101` or `0x00*16`.
+/// # Returns
+/// The byte and how often it is repeated.
+fn parse_byte_token(token: &str, column: usize) -> Result<(u8, usize), InputError> {
+    let (value, count) = match token.split_once('*') {
+        Some((value, count)) => (value, Some(count)),
+        None => (token, None),
+    };
+    let invalid_byte = || InputError::InvalidByte {
+        column,
+        token: value.to_string(),
+    };
+    let (digits, radix) = if let Some(digits) = strip_radix_prefix(value, 'x') {
+        (digits, 16)
+    } else if let Some(digits) = strip_radix_prefix(value, 'b') {
+        (digits, 2)
+    } else {
+        (value, 10)
+    };
+    if digits.is_empty() || !digits.chars().all(|c| c.is_digit(radix)) {
+        return Err(invalid_byte());
+    }
+    let byte = u8::from_str_radix(digits, radix).map_err(|_| invalid_byte())?;
+
+    let Some(count) = count else {
+        return Ok((byte, 1));
+    };
+    let count_column = column + value.chars().count() + 1;
+    return match count.parse::<usize>() {
+        Ok(count) if (1..=MAX_REPEAT).contains(&count) => Ok((byte, count)),
+        _ => Err(InputError::InvalidRepeat {
+            column: count_column,
+            token: count.to_string(),
+        }),
+    };
+}
+
+/// Returns the digits of a `0x` / `0b` prefixed value (either case), `None` if it has another prefix.
+fn strip_radix_prefix(value: &str, prefix: char) -> Option<&str> {
+    let rest = value.strip_prefix('0')?;
+    return rest
+        .strip_prefix(prefix)
+        .or_else(|| rest.strip_prefix(prefix.to_ascii_uppercase()));
+}
+
+/// Parses the escape sequence starting with the backslash at `index`.
+/// # Returns
+/// The index of the character after the escape sequence.
+fn parse_escape(chars: &[char], index: usize, result: &mut Vec<u8>) -> Result<usize, InputError> {
+    let invalid_escape = |length: usize| InputError::InvalidEscape {
+        column: index + 1,
+        escape: chars[index..(index + length).min(chars.len())]
+            .iter()
+            .collect(),
+    };
+    let Some(&escape) = chars.get(index + 1) else {
+        return Err(invalid_escape(1));
+    };
+    let byte = match escape {
+        'r' => b'\r',
+        'n' => b'\n',
+        't' => b'\t',
+        '0' => 0,
+        '\\' => b'\\',
+        '\'' => b'\'',
+        'x' => {
+            let digits: String = chars.iter().skip(index + 2).take(2).collect();
+            if digits.chars().count() != 2 || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+                return Err(invalid_escape(4));
+            }
+            result.push(u8::from_str_radix(&digits, 16).map_err(|_| invalid_escape(4))?);
+            return Ok(index + 4);
+        }
+        'u' => {
+            let Some(length) = chars[index..].iter().position(|&c| c == '}') else {
+                return Err(invalid_escape(chars.len() - index));
+            };
+            let digits: String = chars[index + 2..index + length].iter().collect();
+            let character = digits
+                .strip_prefix('{')
+                .filter(|digits| (1..=6).contains(&digits.len()))
+                .and_then(|digits| u32::from_str_radix(digits, 16).ok())
+                .and_then(char::from_u32)
+                .ok_or_else(|| invalid_escape(length + 1))?;
+            let mut buffer = [0; 4];
+            result.extend_from_slice(character.encode_utf8(&mut buffer).as_bytes());
+            return Ok(index + length + 1);
+        }
+        _ => return Err(invalid_escape(2)),
+    };
+    result.push(byte);
+    return Ok(index + 2);
+}
 
-    Ok(result)
+/// Parses the hex digit pairs of a raw-hex input, ignoring whitespace.
+/// # Arguments
+/// * `chars` - The characters after the `hex:` prefix
+/// * `first_column` - The column of the first character
+fn parse_raw_hex(chars: &[char], first_column: usize) -> Result<Vec<u8>, InputError> {
+    let mut result = Vec::new();
+    let mut high_nibble: Option<(u8, usize)> = None;
+    for (index, &c) in chars.iter().enumerate() {
+        if c.is_whitespace() {
+            continue;
+        }
+        let column = first_column + index;
+        let Some(nibble) = c.to_digit(16) else {
+            return Err(InputError::InvalidHexDigit {
+                column,
+                character: c,
+            });
+        };
+        match high_nibble.take() {
+            Some((high, _)) => result.push(high << 4 | nibble as u8),
+            None => high_nibble = Some((nibble as u8, column)),
+        }
+    }
+    if let Some((_, column)) = high_nibble {
+        return Err(InputError::OddHexDigits { column });
+    }
+    return Ok(result);
 }
@@ -1,13 +1,35 @@
+pub mod cc_tool_error;
 pub mod data_types;
 pub mod device_config_parser;
 pub mod device_calibration_parser;
 pub mod input_processing;
+pub mod mesh_topology;
 pub mod mk_module_description;
+pub mod mode_sequence;
 pub mod module_description_parser;
+pub mod packet_sniffer;
+pub mod port_actor;
+pub mod rssi_sniffer;
+pub mod rx_packetizer;
+pub mod serial_settings;
+pub mod task_manager;
+pub mod tinymesh_emulator;
+pub mod tinymesh_packet;
+pub mod traffic_capture;
+pub mod transport;
+pub mod tx_guard;
 
 // Modules containing functions for communicating with Tauri frontend
+pub mod tinymesh_autodetect_mod;
+pub mod tinymesh_capture_mod;
 pub mod tinymesh_comm_mod;
 pub mod tinymesh_config_mod;
 pub mod tinymesh_calibration_mod;
 pub mod tinymesh_device_info_mod;
+pub mod tinymesh_mesh_mod;
+pub mod tinymesh_port_watcher_mod;
+pub mod tinymesh_remote_config_mod;
 pub mod tinymesh_serial_util;
+pub mod tinymesh_sniffer_mod;
+pub mod tinymesh_task_mod;
+pub mod tinymesh_test_mode_mod;
@@ -1,14 +1,25 @@
 // Prevents additional console window on Windows in release, DO NOT REMOVE!!
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
-use std::sync::{Arc, Mutex};
+use tauri::{Manager, RunEvent};
 use tauri_plugin_log::{LogTarget, RotationStrategy, TimezoneStrategy};
 use tinymesh_cc_tool::data_types::DeviceEntity;
+use tinymesh_cc_tool::serial_settings::get_serial_settings;
+use tinymesh_cc_tool::traffic_capture::TrafficCapture;
+use tinymesh_cc_tool::tinymesh_autodetect_mod::*;
+use tinymesh_cc_tool::tinymesh_capture_mod::*;
 use tinymesh_cc_tool::tinymesh_comm_mod::*;
 use tinymesh_cc_tool::tinymesh_config_mod::*;
 use tinymesh_cc_tool::tinymesh_calibration_mod::*;
 use tinymesh_cc_tool::tinymesh_device_info_mod::*;
+use tinymesh_cc_tool::tinymesh_mesh_mod::*;
+use tinymesh_cc_tool::tinymesh_port_watcher_mod::*;
+use tinymesh_cc_tool::tinymesh_remote_config_mod::*;
 use tinymesh_cc_tool::tinymesh_serial_util::*;
+use tinymesh_cc_tool::tinymesh_sniffer_mod::*;
+use tinymesh_cc_tool::tinymesh_task_mod::*;
+use tinymesh_cc_tool::tinymesh_test_mode_mod::*;
+use tinymesh_cc_tool::tx_guard::{load_tx_guard_config, tx_guard_config_file_path};
 
 #[cfg(debug_assertions)]
 const LOG_TARGETS: [LogTarget; 2] = [LogTarget::Stdout, LogTarget::LogDir];
@@ -25,24 +36,46 @@ fn main() {
                 .timezone_strategy(TimezoneStrategy::UseLocal)
                 .build(),
         )
-        .manage(DeviceEntity {
-            port: Default::default(),
-            rssi_task: Default::default(),
-            is_rssi_task_running: Arc::new(Mutex::new(false)),
-            communication_task: Default::default(),
-            is_communication_task_running: Arc::new(Mutex::new(false)),
-            device_config: Arc::new(Mutex::new(None)),
-            device_calib: Arc::new(Mutex::new(None)),
+        .manage(DeviceEntity::default())
+        .manage(TrafficCapture::default())
+        .setup(|app| {
+            if let Some(file_path) = tx_guard_config_file_path(&app.handle()) {
+                *app.state::<DeviceEntity>().tx_guard_config.lock().unwrap() =
+                    load_tx_guard_config(&file_path);
+            }
+            start_port_watcher(app.handle());
+            Ok(())
         })
         .invoke_handler(tauri::generate_handler![
             // communication functions
             start_communication_task,
             stop_communication_task,
+            set_rx_packetizer,
+            get_rx_packetizer,
             // config functions
             get_device_config,
             set_device_config,
             execute_mode_sequence,
             factory_reset,
+            // test mode functions
+            get_active_mode,
+            stop_active_mode,
+            set_tx_guard_config,
+            get_tx_guard_status,
+            // remote config functions
+            get_remote_device_config,
+            set_remote_device_config,
+            // mesh topology functions
+            get_mesh_nodes,
+            clear_mesh_nodes,
+            export_mesh_topology,
+            // sniffer functions
+            set_sniffer_output_parsing,
+            get_sniffer_stats,
+            clear_sniffer_stats,
+            get_rssi_sniffer_stats,
+            clear_rssi_sniffer_history,
+            export_rssi_sniffer_csv,
             // calibration functions
             get_device_calib,
             set_device_calib,
@@ -52,9 +85,14 @@ fn main() {
             connect_to_device,
             disconnect_from_device,
             send_bytes,
+            send_packet_command,
             read_bytes,
             clear_buffer,
             get_connected_device,
+            list_sessions,
+            get_serial_settings,
+            autodetect_connection,
+            set_auto_reconnect,
             // device info functions
             get_device_rssi,
             get_device_analog,
@@ -63,7 +101,20 @@ fn main() {
             get_device_voltage,
             start_rssi_stream,
             stop_rssi_stream,
+            // task functions
+            list_tasks,
+            cancel_task,
+            // capture functions
+            start_capture,
+            stop_capture,
+            replay_capture,
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while running tauri application")
+        .run(|app_handle, event| {
+            // A module must not be left transmitting in a TX test mode when the app is closed
+            if let RunEvent::Exit = event {
+                shut_down_all_sessions(&app_handle.state::<DeviceEntity>());
+            }
+        });
 }
@@ -0,0 +1,206 @@
+//! This module contains the mesh node table of a gateway session, which is built from the packets the
+//! gateway outputs in packet mode, and its export to JSON and GraphViz DOT.
+//!
+//! The packets don't tell which router forwarded them, so the topology is inferred: the network level of
+//! a node is its distance to the gateway, and its parent is the node one level closer to the gateway that
+//! was seen most recently. Nodes on level 1 (or nodes whose packets took a single hop) are connected to
+//! the gateway itself.
+
+use crate::cc_tool_error::CcToolError;
+use crate::data_types::unix_timestamp_us;
+use crate::tinymesh_packet::{TinyMeshPacket, TinyMeshPacketBody};
+use std::collections::BTreeMap;
+use std::time::Instant;
+
+/// The role of a node in the mesh
+#[derive(Clone, Copy, Debug, PartialEq, serde::Serialize)]
+pub enum MeshDeviceType {
+    /// Other nodes are connected to the gateway through this node
+    Router,
+    /// No node is known to be connected through this node
+    Node,
+}
+
+/// An entry of the mesh node table
+#[derive(Clone, Debug, PartialEq, serde::Serialize)]
+pub struct MeshNode {
+    pub unique_id: u32,
+    pub system_id: u32,
+    pub device_type: MeshDeviceType,
+    /// Distance of the node to the gateway, 1 for nodes connected to the gateway directly
+    pub network_level: u8,
+    /// Number of hops the last packet of the node took to the gateway
+    pub hops: u8,
+    /// RSSI of the last hop of the last packet of the node
+    pub rssi: u8,
+    /// Time at which the last packet of the node was received, in microseconds since the UNIX epoch
+    pub last_seen_us: u64,
+    /// Reported by event, status and acknowledge packets
+    pub hardware_revision: Option<u16>,
+    /// Reported by event, status and acknowledge packets
+    pub firmware_revision: Option<u16>,
+    pub packet_count: u64,
+    /// The inferred parent of the node, `None` if the node is connected to the gateway
+    pub parent_id: Option<u32>,
+}
+
+/// How a node table changed
+#[derive(Clone, Copy, Debug, PartialEq, serde::Serialize)]
+pub enum MeshChange {
+    /// The first packet of the node was received
+    Added,
+    /// Another packet of the node was received
+    Updated,
+}
+
+/// Format of an exported node table
+#[derive(Clone, Copy, Debug, PartialEq, serde::Deserialize)]
+pub enum MeshExportFormat {
+    Json,
+    Dot,
+}
+
+/// The nodes that were heard through a gateway, keyed by unique ID
+#[derive(Clone, Debug, Default)]
+pub struct MeshTable {
+    nodes: BTreeMap<u32, MeshNode>,
+}
+
+impl MeshTable {
+    /// Updates the table with a received packet and infers the topology again.
+    /// # Arguments
+    /// * `packet` - The decoded packet
+    /// * `received_at` - The time at which the packet was received
+    ///
+    /// # Returns
+    /// Whether the node of the packet was added or updated.
+    pub fn update(&mut self, packet: &TinyMeshPacket, received_at: Instant) -> MeshChange {
+        let status = match &packet.body {
+            TinyMeshPacketBody::Event(status) | TinyMeshPacketBody::Status(status) => Some(status),
+            TinyMeshPacketBody::CommandAck { status, .. } => Some(status),
+            TinyMeshPacketBody::SerialData { .. } | TinyMeshPacketBody::ConfigData { .. } => None,
+        };
+        let change = if self.nodes.contains_key(&packet.origin_id) {
+            MeshChange::Updated
+        } else {
+            MeshChange::Added
+        };
+        let node = self
+            .nodes
+            .entry(packet.origin_id)
+            .or_insert_with(|| MeshNode {
+                unique_id: packet.origin_id,
+                system_id: packet.system_id,
+                device_type: MeshDeviceType::Node,
+                network_level: packet.network_level,
+                hops: packet.hops,
+                rssi: packet.rssi,
+                last_seen_us: 0,
+                hardware_revision: None,
+                firmware_revision: None,
+                packet_count: 0,
+                parent_id: None,
+            });
+        node.system_id = packet.system_id;
+        node.network_level = packet.network_level;
+        node.hops = packet.hops;
+        node.rssi = packet.rssi;
+        node.last_seen_us = unix_timestamp_us(received_at);
+        node.packet_count += 1;
+        if let Some(status) = status {
+            node.hardware_revision = Some(status.hardware_revision);
+            node.firmware_revision = Some(status.firmware_revision);
+        }
+        self.infer_topology();
+        return change;
+    }
+
+    /// Returns the node with the given unique ID.
+    pub fn node(&self, unique_id: u32) -> Option<&MeshNode> {
+        return self.nodes.get(&unique_id);
+    }
+
+    /// Returns all nodes, ordered by unique ID.
+    pub fn nodes(&self) -> Vec<MeshNode> {
+        return self.nodes.values().cloned().collect();
+    }
+
+    /// Forgets all nodes.
+    pub fn clear(&mut self) {
+        self.nodes.clear();
+    }
+
+    /// Assigns the parent and the device type of every node.
+    fn infer_topology(&mut self) {
+        let parents: Vec<(u32, Option<u32>)> = self
+            .nodes
+            .values()
+            .map(|node| {
+                if node.network_level <= 1 || node.hops <= 1 {
+                    return (node.unique_id, None);
+                }
+                let parent = self
+                    .nodes
+                    .values()
+                    .filter(|candidate| candidate.network_level + 1 == node.network_level)
+                    .max_by_key(|candidate| candidate.last_seen_us)
+                    .map(|candidate| candidate.unique_id);
+                return (node.unique_id, parent);
+            })
+            .collect();
+        for node in self.nodes.values_mut() {
+            node.device_type = MeshDeviceType::Node;
+        }
+        for (unique_id, parent_id) in parents {
+            if let Some(node) = self.nodes.get_mut(&unique_id) {
+                node.parent_id = parent_id;
+            }
+            if let Some(parent) = parent_id.and_then(|parent_id| self.nodes.get_mut(&parent_id)) {
+                parent.device_type = MeshDeviceType::Router;
+            }
+        }
+    }
+
+    /// This function exports the node table.
+    /// # Arguments
+    /// * `format` - `Json` for the list of nodes, `Dot` for a GraphViz graph of the topology
+    ///
+    /// # Returns
+    /// The exported table, or `CcToolError::Internal` if it could not be serialized.
+    pub fn export(&self, format: MeshExportFormat) -> Result<String, CcToolError> {
+        return match format {
+            MeshExportFormat::Json => {
+                serde_json::to_string_pretty(&self.nodes()).map_err(|err| CcToolError::Internal {
+                    message: err.to_string(),
+                })
+            }
+            MeshExportFormat::Dot => Ok(self.to_dot()),
+        };
+    }
+
+    /// Returns the topology as a GraphViz DOT graph, with an edge from every node to its parent.
+    fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph mesh {\n    gateway [shape=doublecircle];\n");
+        for node in self.nodes.values() {
+            let shape = match node.device_type {
+                MeshDeviceType::Router => "box",
+                MeshDeviceType::Node => "ellipse",
+            };
+            dot.push_str(&format!(
+                "    \"{:08X}\" [shape={}, label=\"{:08X}\\nlevel {}, RSSI {}\"];\n",
+                node.unique_id, shape, node.unique_id, node.network_level, node.rssi
+            ));
+        }
+        for node in self.nodes.values() {
+            let parent = node.parent_id.map_or("gateway".to_string(), |parent_id| {
+                format!("\"{:08X}\"", parent_id)
+            });
+            dot.push_str(&format!(
+                "    \"{:08X}\" -> {} [label=\"{} hops\"];\n",
+                node.unique_id, parent, node.hops
+            ));
+        }
+        dot.push_str("}\n");
+        return dot;
+    }
+}
@@ -3,9 +3,12 @@
 use log::info;
 use tauri::AppHandle;
 
-use crate::data_types::{MkDeviceCell, MkDeviceQuickMode, MkDeviceTestMode};
+use crate::cc_tool_error::CcToolError;
+use crate::data_types::{MkDeviceCell, MkDeviceQuickMode, MkDeviceTestMode, RssiScaling};
 use crate::module_description_parser::parse_module_description;
+use crate::tx_guard::TxGuardConfig;
 use std::collections::HashMap;
+use std::path::PathBuf;
 
 /// This struct holds all the data from the RMD module description.
 #[derive(Default, Debug)]
@@ -23,25 +26,101 @@ pub struct MkModuleDescription {
     pub c_editable_cells: Vec<usize>,
     pub c_locked_cells: Vec<usize>,
 
+    /// Conversion of the raw RSSI values of the module to dBm
+    pub rssi_scaling: RssiScaling,
+    /// The limits of the TX test modes
+    pub tx_guard: TxGuardConfig,
+
     pub unknown_data: HashMap<String, String>,
 }
 
+/// An RMD entry whose value could not be parsed.
+/// It is converted to `CcToolError::RmdParse` once the line of the entry is known.
+struct InvalidEntry {
+    key: String,
+    message: String,
+}
+
+impl InvalidEntry {
+    fn new(key: &str, value: &str) -> InvalidEntry {
+        InvalidEntry {
+            key: key.to_string(),
+            message: format!("Invalid value '{}' in section [{}]", value, key),
+        }
+    }
+
+    /// Finds the line of the section header in the RMD string (1-based, 0 if it could not be found).
+    fn into_error(self, input: &str) -> CcToolError {
+        let section_header = format!("[{}]", self.key);
+        let line = input
+            .lines()
+            .position(|line| line.trim().starts_with(&section_header))
+            .map_or(0, |index| index + 1);
+        CcToolError::RmdParse {
+            line,
+            message: self.message,
+        }
+    }
+}
+
+fn parse_u8(key: &str, value: &str) -> Result<u8, InvalidEntry> {
+    value
+        .trim()
+        .parse::<u8>()
+        .map_err(|_| InvalidEntry::new(key, value))
+}
+
+/// Stores the value of a `NAME`, `HINT`, `DEF`, `MIN_MAX` or `ALLOW` entry in the cell.
+fn apply_cell_entry(
+    cell: &mut MkDeviceCell,
+    name: &str,
+    key: &str,
+    value: &str,
+) -> Result<(), InvalidEntry> {
+    if name == "NAME" {
+        cell.name = value.to_string();
+    } else if name == "HINT" {
+        cell.description = value.to_string();
+    } else if name == "DEF" {
+        cell.default_value = parse_u8(key, value)?;
+        cell.current_value = cell.default_value;
+    } else if name == "MIN_MAX" {
+        let (min, max) = value
+            .split_once(' ')
+            .ok_or_else(|| InvalidEntry::new(key, value))?;
+        cell.min_value = parse_u8(key, min)?;
+        cell.max_value = parse_u8(key, max)?;
+    } else if name == "ALLOW" {
+        cell.allowed_values = value
+            .split_whitespace()
+            .map(|s| parse_u8(key, s))
+            .collect::<Result<Vec<u8>, InvalidEntry>>()?;
+    }
+    Ok(())
+}
+
 fn get_number_of_testmodes_and_remove_from_unknown(
     module_description: &mut MkModuleDescription,
-) -> usize {
+) -> Result<usize, InvalidEntry> {
     if let Some(number_of_testmodes) = module_description.unknown_data.remove("TESTMODE NUMBER") {
-        return number_of_testmodes.parse::<usize>().unwrap();
+        return number_of_testmodes
+            .trim()
+            .parse::<usize>()
+            .map_err(|_| InvalidEntry::new("TESTMODE NUMBER", &number_of_testmodes));
     }
-    return 0;
+    return Ok(0);
 }
 
 fn get_number_of_quickmodes_and_remove_from_unknown(
     module_description: &mut MkModuleDescription,
-) -> usize {
+) -> Result<usize, InvalidEntry> {
     if let Some(number_of_quickmodes) = module_description.unknown_data.remove("QUICKMODE NUMBER") {
-        return number_of_quickmodes.parse::<usize>().unwrap();
+        return number_of_quickmodes
+            .trim()
+            .parse::<usize>()
+            .map_err(|_| InvalidEntry::new("QUICKMODE NUMBER", &number_of_quickmodes));
     }
-    return 0;
+    return Ok(0);
 }
 
 fn get_editable_cells_and_remove_from_unknown(
@@ -125,6 +204,55 @@ fn get_device_model_and_remove_from_unknown(
     return String::new();
 }
 
+fn parse_f64(key: &str, value: &str) -> Result<f64, InvalidEntry> {
+    value
+        .trim()
+        .parse::<f64>()
+        .map_err(|_| InvalidEntry::new(key, value))
+}
+
+fn get_rssi_scaling_and_remove_from_unknown(
+    module_description: &mut MkModuleDescription,
+) -> Result<RssiScaling, InvalidEntry> {
+    let mut rssi_scaling = RssiScaling::default();
+    if let Some(offset) = module_description.unknown_data.remove("RSSI_OFFSET") {
+        rssi_scaling.offset = parse_f64("RSSI_OFFSET", &offset)?;
+    }
+    if let Some(scale_factor) = module_description.unknown_data.remove("RSSI_SCALE_FACTOR") {
+        rssi_scaling.scale_factor = parse_f64("RSSI_SCALE_FACTOR", &scale_factor)?;
+    }
+    return Ok(rssi_scaling);
+}
+
+/// Parses a duration in seconds (e.g. `TX_MAX_ON_TIME`) to milliseconds.
+fn parse_seconds_as_ms(key: &str, value: &str) -> Result<u64, InvalidEntry> {
+    let seconds = parse_f64(key, value)?;
+    if seconds.is_nan() || seconds <= 0.0 {
+        return Err(InvalidEntry::new(key, value));
+    }
+    return Ok((seconds * 1000.0).round() as u64);
+}
+
+fn get_tx_guard_config_and_remove_from_unknown(
+    module_description: &mut MkModuleDescription,
+) -> Result<TxGuardConfig, InvalidEntry> {
+    let mut tx_guard = TxGuardConfig::default();
+    if let Some(max_on_time) = module_description.unknown_data.remove("TX_MAX_ON_TIME") {
+        tx_guard.max_on_time_ms = Some(parse_seconds_as_ms("TX_MAX_ON_TIME", &max_on_time)?);
+    }
+    if let Some(duty_cycle) = module_description.unknown_data.remove("TX_DUTY_CYCLE") {
+        let percent = parse_f64("TX_DUTY_CYCLE", &duty_cycle)?;
+        if !(0.0..=100.0).contains(&percent) {
+            return Err(InvalidEntry::new("TX_DUTY_CYCLE", &duty_cycle));
+        }
+        tx_guard.duty_cycle_percent = Some(percent);
+    }
+    if let Some(window) = module_description.unknown_data.remove("TX_DUTY_CYCLE_WINDOW") {
+        tx_guard.duty_cycle_window_ms = parse_seconds_as_ms("TX_DUTY_CYCLE_WINDOW", &window)?;
+    }
+    return Ok(tx_guard);
+}
+
 fn check_cell_key(starting_str: &str, input: &str) -> Result<(usize, String), ()> {
     let parts: Vec<&str> = input.split_whitespace().collect();
     if parts.len() == 3 && parts[0] == starting_str && parts[1].starts_with("0x") {
@@ -137,7 +265,7 @@ fn check_cell_key(starting_str: &str, input: &str) -> Result<(usize, String), ()
 
 fn get_cells_and_remove_from_unknown(
     module_description: &mut MkModuleDescription,
-) -> Vec<MkDeviceCell> {
+) -> Result<Vec<MkDeviceCell>, InvalidEntry> {
     info!("\n\nmk_module_description::get_cells_and_remove_from_unknown(module_description)\n");
     // info!("\nmk_module_description::get_cells_and_remove_from_unknown---> module_description = {:?}\n", module_description);
 
@@ -155,23 +283,7 @@ fn get_cells_and_remove_from_unknown(
                 result.resize(address as usize + 1, Default::default());
                 result[address as usize].address = address;
             }
-            if name == "NAME" {
-                result[address as usize].name = value.clone();
-            } else if name == "HINT" {
-                result[address as usize].description = value.clone();
-            } else if name == "DEF" {
-                result[address as usize].default_value = value.parse::<u8>().unwrap();
-                result[address as usize].current_value = result[address as usize].default_value;
-            } else if name == "MIN_MAX" {
-                let (min, max) = value.split_once(' ').unwrap();
-                result[address as usize].min_value = min.parse::<u8>().unwrap();
-                result[address as usize].max_value = max.parse::<u8>().unwrap();
-            } else if name == "ALLOW" {
-                result[address as usize].allowed_values = value
-                    .split_whitespace()
-                    .map(|s| s.parse::<u8>().unwrap())
-                    .collect();
-            }
+            apply_cell_entry(&mut result[address as usize], &name, key, value)?;
         }
     }
 
@@ -180,12 +292,12 @@ fn get_cells_and_remove_from_unknown(
         .unknown_data
         .retain(|k, _| !k.starts_with("M "));
     // info!("\nmk_module_description::get_cells_and_remove_from_unknown---> result = {:?}\n", result);
-    return result;
+    return Ok(result);
 }
 
 fn get_calibration_cells_and_remove_from_unknown(
     module_description: &mut MkModuleDescription,
-) -> Vec<MkDeviceCell> {
+) -> Result<Vec<MkDeviceCell>, InvalidEntry> {
     info!("\n\nmk_module_description::get_calibration_cells_and_remove_from_unknown(module_description)\n");
     // info!("\nmk_module_description::get_calibration_cells_and_remove_from_unknown---> module_description = {:?}\n", module_description);
 
@@ -203,23 +315,7 @@ fn get_calibration_cells_and_remove_from_unknown(
                 result.resize(address as usize + 1, Default::default());
                 result[address as usize].address = address;
             }
-            if name == "NAME" {
-                result[address as usize].name = value.clone();
-            } else if name == "HINT" {
-                result[address as usize].description = value.clone();
-            } else if name == "DEF" {
-                result[address as usize].default_value = value.parse::<u8>().unwrap();
-                result[address as usize].current_value = result[address as usize].default_value;
-            } else if name == "MIN_MAX" {
-                let (min, max) = value.split_once(' ').unwrap();
-                result[address as usize].min_value = min.parse::<u8>().unwrap();
-                result[address as usize].max_value = max.parse::<u8>().unwrap();
-            } else if name == "ALLOW" {
-                result[address as usize].allowed_values = value
-                    .split_whitespace()
-                    .map(|s| s.parse::<u8>().unwrap())
-                    .collect();
-            }
+            apply_cell_entry(&mut result[address as usize], &name, key, value)?;
         }
     }
 
@@ -228,12 +324,28 @@ fn get_calibration_cells_and_remove_from_unknown(
         .unknown_data
         .retain(|k, _| !k.starts_with("C "));
     // info!("\nmk_module_description::get_calibration_cells_and_remove_from_unknown---> result = {:?}\n", result);
-    return result;
+    return Ok(result);
+}
+
+/// Parses the optional `TESTMODE n TX` or `QUICKMODE n TX` entry, which tells whether the module
+/// transmits in the mode (`1`) or not (`0`).
+fn get_tx_flag_and_remove_from_unknown(
+    module_description: &mut MkModuleDescription,
+    key: &str,
+) -> Result<Option<bool>, InvalidEntry> {
+    let Some(value) = module_description.unknown_data.remove(key) else {
+        return Ok(None);
+    };
+    return match value.trim() {
+        "1" => Ok(Some(true)),
+        "0" => Ok(Some(false)),
+        _ => Err(InvalidEntry::new(key, &value)),
+    };
 }
 
 fn get_testmodes_and_remove_from_unknown(
     module_description: &mut MkModuleDescription,
-) -> Vec<MkDeviceTestMode> {
+) -> Result<Vec<MkDeviceTestMode>, InvalidEntry> {
     let mut result = vec![];
     let number_of_testmodes = module_description.number_of_testmodes;
     for i in 1..=number_of_testmodes {
@@ -255,14 +367,18 @@ fn get_testmodes_and_remove_from_unknown(
                 .unknown_data
                 .remove(&format!("TESTMODE {} SEQUENCE_OFF", i))
                 .unwrap_or(String::new()),
+            is_tx: get_tx_flag_and_remove_from_unknown(
+                module_description,
+                &format!("TESTMODE {} TX", i),
+            )?,
         });
     }
-    return result;
+    return Ok(result);
 }
 
 fn get_quick_modes_and_remove_from_unknown(
     module_description: &mut MkModuleDescription,
-) -> Vec<MkDeviceQuickMode> {
+) -> Result<Vec<MkDeviceQuickMode>, InvalidEntry> {
     let mut result = vec![];
     let number_of_quickmodes = module_description.number_of_quickmodes;
     for i in 1..=number_of_quickmodes {
@@ -284,9 +400,13 @@ fn get_quick_modes_and_remove_from_unknown(
                 .unknown_data
                 .remove(&format!("QUICKMODE {} SEQUENCE_OFF", i))
                 .unwrap_or(String::new()),
+            is_tx: get_tx_flag_and_remove_from_unknown(
+                module_description,
+                &format!("QUICKMODE {} TX", i),
+            )?,
         });
     }
-    return result;
+    return Ok(result);
 }
 
 impl MkModuleDescription {
@@ -299,8 +419,13 @@ impl MkModuleDescription {
     /// * `input` - The RMD string to parse.
     ///
     /// # Returns
-    /// A `MkModuleDescription` struct containing the parsed data.
-    pub fn new(input: &str) -> MkModuleDescription {
+    /// A `MkModuleDescription` struct containing the parsed data,
+    /// or `CcToolError::RmdParse` with the line of the first invalid section.
+    pub fn new(input: &str) -> Result<MkModuleDescription, CcToolError> {
+        return MkModuleDescription::parse_entries(input).map_err(|err| err.into_error(input));
+    }
+
+    fn parse_entries(input: &str) -> Result<MkModuleDescription, InvalidEntry> {
         let mut result: MkModuleDescription = Default::default();
         result.unknown_data = parse_module_description(input);
         // info!("\n\nUNKNOWN DATA --->   {:?}\n\n", result.unknown_data);?
@@ -309,17 +434,21 @@ impl MkModuleDescription {
         result.c_editable_cells = get_c_editable_cells_and_remove_from_unknown(&mut result);
         result.c_locked_cells = get_c_locked_cells_and_remove_from_unknown(&mut result);
         result.device_model = get_device_model_and_remove_from_unknown(&mut result);
-        result.number_of_testmodes = get_number_of_testmodes_and_remove_from_unknown(&mut result);
-        result.testmodes = get_testmodes_and_remove_from_unknown(&mut result);
-        result.number_of_quickmodes = get_number_of_quickmodes_and_remove_from_unknown(&mut result);
-        result.quickmodes = get_quick_modes_and_remove_from_unknown(&mut result);
-        result.cells = get_cells_and_remove_from_unknown(&mut result);
-        result.calibration_cells = get_calibration_cells_and_remove_from_unknown(&mut result);
-        result
+        result.rssi_scaling = get_rssi_scaling_and_remove_from_unknown(&mut result)?;
+        result.tx_guard = get_tx_guard_config_and_remove_from_unknown(&mut result)?;
+        result.number_of_testmodes = get_number_of_testmodes_and_remove_from_unknown(&mut result)?;
+        result.testmodes = get_testmodes_and_remove_from_unknown(&mut result)?;
+        result.number_of_quickmodes =
+            get_number_of_quickmodes_and_remove_from_unknown(&mut result)?;
+        result.quickmodes = get_quick_modes_and_remove_from_unknown(&mut result)?;
+        result.cells = get_cells_and_remove_from_unknown(&mut result)?;
+        result.calibration_cells = get_calibration_cells_and_remove_from_unknown(&mut result)?;
+        Ok(result)
     }
 
     /// Creates a new MkModuleDescription from RMD file.
-    /// Reads the contents of the RMD file matching the model of the device and calls `new`.
+    /// Looks for `<model>.rmd` in the `modules` directories next to the executable,
+    /// in the app resources and in the current working directory, and calls `new` on the first match.
     ///
     /// # Arguments
     /// * `model` - The model of the device.
@@ -327,38 +456,46 @@ impl MkModuleDescription {
     ///
     /// # Returns
     /// A Result Ok containing a `MkModuleDescription` struct containing the parsed data if the parsing was successful.
-    /// An error of type `String` if the parsing failed.
+    /// `CcToolError::RmdNotFound` listing the searched paths if no matching file exists,
+    /// or `CcToolError::RmdParse` if the file could not be parsed.
     pub fn new_from_device_model(
         model: &str,
-        _app_handle: &AppHandle,
-    ) -> Result<MkModuleDescription, String> {
+        app_handle: &AppHandle,
+    ) -> Result<MkModuleDescription, CcToolError> {
         info!("\nmk_module_description::new_from_device_model(model, app_handle)\n");
 
-        // let file_path = app_handle
-        //     .path_resolver()
-        //     .resolve_resource(format!("/modules/{}.rmd", model))
-        //     .ok_or("File not found".to_string())?;
-        // // info!("\nmk_module_description::new_from_device_model---> File path: {:?}\n", file_path);
-        // let file_contents = std::fs::read_to_string(file_path).map_err(|err| err.to_string())?;
-        // // info!("\nmk_module_description::new_from_device_model---> File contents: {:?}\n", file_contents);
-
-
-        let exe_path = std::env::current_exe().map_err(|err| err.to_string())?;
-    
-        // Construct the path to the resource directory relative to the binary
-        let resource_dir = exe_path
-            .parent()
-            .ok_or("Failed to determine parent directory of executable".to_string())?
-            .join("modules");
-    
-        // Append the filename to the resource directory path
-        let file_path = resource_dir.join(format!("{}.rmd", model)); // Assuming `model` is defined elsewhere
-        println!("file path --> {:?}", file_path);
-
-        // Read the contents of the file
-        let file_contents = std::fs::read_to_string(&file_path)
-            .map_err(|err| format!("Failed to read file '{}': {}", file_path.display(), err))?;
-
-        Ok(MkModuleDescription::new(&file_contents))
+        let file_name = format!("{}.rmd", model);
+        let mut candidates: Vec<PathBuf> = vec![];
+        if let Some(exe_dir) = std::env::current_exe()
+            .ok()
+            .and_then(|exe_path| exe_path.parent().map(|dir| dir.to_path_buf()))
+        {
+            candidates.push(exe_dir.join("modules").join(&file_name));
+            // macOS app bundles keep the resources next to the `MacOS` directory
+            candidates.push(exe_dir.join("../Resources/modules").join(&file_name));
+        }
+        if let Some(resource_path) = app_handle
+            .path_resolver()
+            .resolve_resource(format!("modules/{}", file_name))
+        {
+            candidates.push(resource_path);
+        }
+        if let Ok(current_dir) = std::env::current_dir() {
+            candidates.push(current_dir.join("modules").join(&file_name));
+        }
+
+        for file_path in &candidates {
+            if let Ok(file_contents) = std::fs::read_to_string(file_path) {
+                info!("Using RMD file {}", file_path.display());
+                return MkModuleDescription::new(&file_contents);
+            }
+        }
+        Err(CcToolError::RmdNotFound {
+            model: model.to_string(),
+            searched_paths: candidates
+                .iter()
+                .map(|path| path.display().to_string())
+                .collect(),
+        })
     }
 }
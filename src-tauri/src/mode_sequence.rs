@@ -0,0 +1,372 @@
+//! This module contains the parser and the runner of mode sequences, the `SEQUENCE_ON` / `SEQUENCE_OFF`
+//! values of the test and quick modes of an RMD file and the macros entered by the user.
+//!
+//! A sequence consists of tokens separated by whitespace. Every `a` or `d` token starts a new step,
+//! the other tokens change the step before them:
+//! - `a<bytes>`: sends the bytes, e.g. `aG`. A bare `a` sends nothing, so the step only waits for its expectation.
+//! - `#<bytes>`: expects exactly these bytes. A trailing `>` reads up to the prompt, e.g. `#>`.
+//! - `~<regex>`: expects received bytes matching the regular expression, e.g. `~OK\r\n$`
+//! - `?#<bytes>`, `?~<regex>`: an optional expectation, the sequence continues if it is not met
+//! - `t<ms>`: the timeout of the expectation, 1000 ms by default
+//! - `x<count>`: repeats the step, e.g. `aS #> x3`
+//! - `d<ms>`: waits before the next step
+//!
+//! The bytes may contain the escapes `\xNN`, `\r`, `\n`, `\t`, `\s` (a space) and `\\`.
+//! The sequences of the RMD files, e.g. `aG #>`, `a5` or an empty `SEQUENCE_OFF`, are valid sequences.
+
+use crate::cc_tool_error::CcToolError;
+use crate::tinymesh_serial_util::{
+    clear_output_buffer_of_device, read_bytes_from_device_to_buffer, read_frame_from_device,
+    send_bytes_to_device, FrameError, FrameSpec,
+};
+use crate::transport::Transport;
+use regex::bytes::Regex;
+use std::time::{Duration, Instant};
+use tauri::AppHandle;
+
+/// Timeout of an expectation without a `t` token
+pub const DEFAULT_STEP_TIMEOUT: Duration = Duration::from_millis(1000);
+/// Longest delay or timeout of a step, so a sequence can't block the port for long
+pub const MAX_STEP_DURATION: Duration = Duration::from_millis(60000);
+/// Highest repeat count of a step
+pub const MAX_STEP_REPEAT: u32 = 1000;
+/// Time between reads while waiting for an expectation that is not terminated by the prompt
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// What a step expects to receive from the device
+#[derive(Clone, Debug)]
+pub enum SequenceExpectation {
+    /// Exactly these bytes
+    Exact(Vec<u8>),
+    /// Bytes matching the regular expression
+    Pattern(Regex),
+}
+
+impl PartialEq for SequenceExpectation {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (SequenceExpectation::Exact(a), SequenceExpectation::Exact(b)) => a == b,
+            (SequenceExpectation::Pattern(a), SequenceExpectation::Pattern(b)) => {
+                a.as_str() == b.as_str()
+            }
+            _ => false,
+        }
+    }
+}
+
+impl SequenceExpectation {
+    /// Returns whether the received bytes meet the expectation.
+    pub fn is_met_by(&self, received: &[u8]) -> bool {
+        return match self {
+            SequenceExpectation::Exact(expected) => received == expected.as_slice(),
+            SequenceExpectation::Pattern(pattern) => pattern.is_match(received),
+        };
+    }
+
+    /// Returns the expectation as shown in errors.
+    pub fn describe(&self) -> String {
+        return match self {
+            SequenceExpectation::Exact(expected) => String::from_utf8_lossy(expected).to_string(),
+            SequenceExpectation::Pattern(pattern) => format!("/{}/", pattern.as_str()),
+        };
+    }
+}
+
+/// A step that sends bytes to the device and optionally matches its answer
+#[derive(Clone, Debug, PartialEq)]
+pub struct SequenceExchange {
+    pub send: Vec<u8>,
+    pub expect: Option<SequenceExpectation>,
+    /// Whether the sequence continues if the expectation is not met
+    pub optional: bool,
+    pub timeout: Duration,
+    /// How often the step is executed
+    pub repeat: u32,
+}
+
+impl SequenceExchange {
+    fn new(send: Vec<u8>) -> SequenceExchange {
+        return SequenceExchange {
+            send,
+            expect: None,
+            optional: false,
+            timeout: DEFAULT_STEP_TIMEOUT,
+            repeat: 1,
+        };
+    }
+}
+
+/// A step of a mode sequence
+#[derive(Clone, Debug, PartialEq)]
+pub enum SequenceStep {
+    Exchange(SequenceExchange),
+    Delay(Duration),
+}
+
+/// The result of an executed step
+#[derive(Clone, Debug, PartialEq, serde::Serialize)]
+pub enum SequenceStepOutcome {
+    /// The bytes were sent, nothing was expected
+    Sent,
+    /// The expectation was met
+    Matched,
+    /// The optional expectation was not met
+    NotMatched,
+    /// The delay has passed
+    Waited,
+}
+
+/// The trace of an executed step. A repeated step has one trace per repetition.
+#[derive(Clone, Debug, PartialEq, serde::Serialize)]
+pub struct SequenceStepTrace {
+    /// Index of the step in the sequence
+    pub step_index: usize,
+    /// Number of the repetition, starting at 1
+    pub repetition: u32,
+    pub sent: Vec<u8>,
+    pub received: Vec<u8>,
+    pub outcome: SequenceStepOutcome,
+    pub elapsed_ms: u64,
+}
+
+/// This function parses a mode sequence.
+/// # Arguments
+/// * `sequence_str` - The mode sequence, e.g. `aG #>`. See the module documentation for the syntax.
+///
+/// # Returns
+/// The steps of the sequence, or `CcToolError::InvalidInput` naming the invalid token.
+pub fn parse_mode_sequence(sequence_str: &str) -> Result<Vec<SequenceStep>, CcToolError> {
+    let invalid = |message: String| CcToolError::InvalidInput {
+        message: format!("Invalid mode sequence '{}': {}", sequence_str, message),
+    };
+    let mut steps: Vec<SequenceStep> = vec![];
+    for token in sequence_str.split_whitespace() {
+        let (optional, body) = match token.strip_prefix('?') {
+            Some(body) => (true, body),
+            None => (false, token),
+        };
+        let mut chars = body.chars();
+        let Some(kind) = chars.next() else {
+            return Err(invalid(format!(
+                "'{}' is not followed by an expectation",
+                token
+            )));
+        };
+        let value = chars.as_str();
+        match kind {
+            'a' if !optional => {
+                let send = decode_sequence_bytes(value).map_err(&invalid)?;
+                steps.push(SequenceStep::Exchange(SequenceExchange::new(send)));
+                continue;
+            }
+            'd' if !optional => {
+                let delay = parse_step_duration(value).map_err(&invalid)?;
+                steps.push(SequenceStep::Delay(delay));
+                continue;
+            }
+            '#' | '~' | 't' | 'x' => {}
+            _ => return Err(invalid(format!("unknown token '{}'", token))),
+        }
+        let Some(SequenceStep::Exchange(exchange)) = steps.last_mut() else {
+            return Err(invalid(format!("'{}' does not follow an 'a' token", token)));
+        };
+        match kind {
+            '#' | '~' if exchange.expect.is_some() => {
+                return Err(invalid(format!(
+                    "'{}' is a second expectation of a step",
+                    token
+                )));
+            }
+            // An empty expectation is no expectation, like in the sequences of older RMD files
+            '#' if value.is_empty() => {}
+            '#' => {
+                let expected = decode_sequence_bytes(value).map_err(&invalid)?;
+                exchange.expect = Some(SequenceExpectation::Exact(expected));
+                exchange.optional = optional;
+            }
+            '~' => {
+                let pattern = Regex::new(value).map_err(|e| {
+                    invalid(format!("invalid regular expression '{}': {}", value, e))
+                })?;
+                exchange.expect = Some(SequenceExpectation::Pattern(pattern));
+                exchange.optional = optional;
+            }
+            't' if !optional => exchange.timeout = parse_step_duration(value).map_err(&invalid)?,
+            'x' if !optional => {
+                exchange.repeat = match value.parse::<u32>() {
+                    Ok(repeat) if (1..=MAX_STEP_REPEAT).contains(&repeat) => repeat,
+                    _ => {
+                        return Err(invalid(format!(
+                            "'{}' is not a repeat count between 1 and {}",
+                            value, MAX_STEP_REPEAT
+                        )))
+                    }
+                };
+            }
+            _ => return Err(invalid(format!("unknown token '{}'", token))),
+        }
+    }
+    return Ok(steps);
+}
+
+/// Decodes the bytes of an `a` or `#` token, see the module documentation for the escapes.
+/// # Returns
+/// The bytes (characters outside of ASCII as UTF-8), or a message describing the invalid escape.
+pub fn decode_sequence_bytes(text: &str) -> Result<Vec<u8>, String> {
+    let mut bytes = vec![];
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            let mut utf8 = [0u8; 4];
+            bytes.extend_from_slice(c.encode_utf8(&mut utf8).as_bytes());
+            continue;
+        }
+        match chars.next() {
+            Some('r') => bytes.push(b'\r'),
+            Some('n') => bytes.push(b'\n'),
+            Some('t') => bytes.push(b'\t'),
+            Some('s') => bytes.push(b' '),
+            Some('\\') => bytes.push(b'\\'),
+            Some('x') => {
+                let digits: String = chars.by_ref().take(2).collect();
+                match u8::from_str_radix(&digits, 16) {
+                    Ok(byte) if digits.len() == 2 => bytes.push(byte),
+                    _ => return Err(format!("'\\x{}' is not a hexadecimal byte", digits)),
+                }
+            }
+            Some(other) => return Err(format!("unknown escape '\\{}'", other)),
+            None => return Err("'\\' at the end of a token".to_string()),
+        }
+    }
+    return Ok(bytes);
+}
+
+fn parse_step_duration(value: &str) -> Result<Duration, String> {
+    return match value.parse::<u64>() {
+        Ok(ms) if Duration::from_millis(ms) <= MAX_STEP_DURATION => Ok(Duration::from_millis(ms)),
+        _ => Err(format!(
+            "'{}' is not a duration between 0 and {} ms",
+            value,
+            MAX_STEP_DURATION.as_millis()
+        )),
+    };
+}
+
+/// This function executes the steps of a mode sequence on the device.
+/// # Arguments
+/// * `device` - The transport connected to the device
+/// * `steps` - The parsed mode sequence
+/// * `app_handle` - An optional Tauri application handle, used for emitting the exchanged bytes
+///
+/// # Returns
+/// The trace of all executed steps, `CcToolError::UnexpectedResponse` (or `CcToolError::Timeout` if nothing
+/// was received) at the first expectation that was not met and is not optional, or the error of the failed
+/// transfer.
+pub fn run_mode_sequence(
+    device: &mut dyn Transport,
+    steps: &[SequenceStep],
+    app_handle: Option<&AppHandle>,
+) -> Result<Vec<SequenceStepTrace>, CcToolError> {
+    let mut trace = vec![];
+    for (step_index, step) in steps.iter().enumerate() {
+        let exchange = match step {
+            SequenceStep::Exchange(exchange) => exchange,
+            SequenceStep::Delay(delay) => {
+                std::thread::sleep(*delay);
+                trace.push(SequenceStepTrace {
+                    step_index,
+                    repetition: 1,
+                    sent: vec![],
+                    received: vec![],
+                    outcome: SequenceStepOutcome::Waited,
+                    elapsed_ms: delay.as_millis() as u64,
+                });
+                continue;
+            }
+        };
+        for repetition in 1..=exchange.repeat {
+            let started_at = Instant::now();
+            let (received, outcome) = run_exchange(device, exchange, app_handle)?;
+            trace.push(SequenceStepTrace {
+                step_index,
+                repetition,
+                sent: exchange.send.clone(),
+                received,
+                outcome,
+                elapsed_ms: started_at.elapsed().as_millis() as u64,
+            });
+        }
+    }
+    return Ok(trace);
+}
+
+fn run_exchange(
+    device: &mut dyn Transport,
+    exchange: &SequenceExchange,
+    app_handle: Option<&AppHandle>,
+) -> Result<(Vec<u8>, SequenceStepOutcome), CcToolError> {
+    if !exchange.send.is_empty() {
+        clear_output_buffer_of_device(device)?;
+        send_bytes_to_device(device, &exchange.send, app_handle)?;
+    }
+    let Some(expect) = &exchange.expect else {
+        // Nothing to match, the output of the device is left to passive RX
+        return Ok((vec![], SequenceStepOutcome::Sent));
+    };
+    let received = match expect {
+        SequenceExpectation::Exact(expected) if expected.ends_with(b">") => {
+            let frame_spec = FrameSpec::prompt_only().with_deadline(exchange.timeout);
+            match read_frame_from_device(device, &frame_spec, app_handle) {
+                Ok(frame) => [frame, vec![b'>']].concat(),
+                Err(FrameError::Timeout { received }) | Err(FrameError::Overflow { received }) => {
+                    received
+                }
+                Err(err) => return Err(CcToolError::from(err)),
+            }
+        }
+        SequenceExpectation::Exact(expected) => {
+            read_until(device, exchange.timeout, app_handle, |received| {
+                received.len() >= expected.len()
+            })?
+        }
+        SequenceExpectation::Pattern(pattern) => {
+            read_until(device, exchange.timeout, app_handle, |received| {
+                pattern.is_match(received)
+            })?
+        }
+    };
+    if expect.is_met_by(&received) {
+        return Ok((received, SequenceStepOutcome::Matched));
+    }
+    if exchange.optional {
+        return Ok((received, SequenceStepOutcome::NotMatched));
+    }
+    if received.is_empty() {
+        return Err(CcToolError::Timeout { received });
+    }
+    return Err(CcToolError::UnexpectedResponse {
+        expected: expect.describe(),
+        got: received,
+    });
+}
+
+/// Reads from the device until `is_complete` returns `true` for the received bytes or the timeout has passed.
+fn read_until(
+    device: &mut dyn Transport,
+    timeout: Duration,
+    app_handle: Option<&AppHandle>,
+    is_complete: impl Fn(&[u8]) -> bool,
+) -> Result<Vec<u8>, CcToolError> {
+    let started_at = Instant::now();
+    let mut received = vec![];
+    loop {
+        let received_len = received.len();
+        read_bytes_from_device_to_buffer(device, &mut received, None, app_handle)?;
+        if is_complete(&received) || started_at.elapsed() >= timeout {
+            return Ok(received);
+        }
+        if received.len() == received_len {
+            std::thread::sleep(POLL_INTERVAL);
+        }
+    }
+}
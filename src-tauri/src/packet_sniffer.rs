@@ -0,0 +1,286 @@
+//! This module contains the parser of the output of the Simple Packet Sniffer test mode, and the
+//! per-node statistics built from it.
+//!
+//! In the packet sniffer test mode the module prints one line of basic info for every valid TinyMesh
+//! packet it receives. A line consists of `key:value` (or `key=value`) fields separated by spaces or
+//! commas, e.g. `ORG:01020304 RSSI:72 TYPE:02 LEN:35`. The keys are matched case-insensitively:
+//! - `ORG`, `ORIGIN`, `UID`, `SRC`: the unique ID of the sender (hexadecimal, required)
+//! - `RSSI`: the RSSI of the packet as printed by the module (decimal)
+//! - `TYPE`, `PT`: the packet type (hexadecimal)
+//! - `LEN`, `LENGTH`: the length of the packet (decimal)
+//!
+//! Decimal values may be given in hexadecimal with a `0x` prefix. Lines without a sender (banners,
+//! prompts etc.) are skipped.
+//!
+//! The format has not been verified against a real module yet: no capture of the `a6` output of a
+//! module is available. The output is therefore only parsed if the user enables it with
+//! `set_sniffer_output_parsing`, otherwise it is only emitted as received bytes. Once a capture of
+//! "Test Mode 6 Simple Packet Sniffer" is available, add it as a test fixture, fit the keys to it and
+//! parse the output by default.
+
+use crate::data_types::{unix_timestamp_us, MkDeviceConfig};
+use std::collections::BTreeMap;
+use std::time::Instant;
+
+/// Test modes whose name contains this text are parsed as packet sniffer output
+pub const PACKET_SNIFFER_TEST_MODE_NAME: &str = "packet sniffer";
+/// Keys of the field naming the sender of a packet
+pub const ORIGIN_KEYS: [&str; 4] = ["ORG", "ORIGIN", "UID", "SRC"];
+/// Longest line that is buffered while waiting for its end, the rest of longer lines is dropped
+const MAX_LINE_LEN: usize = 256;
+
+/// The info printed by the packet sniffer for a received packet
+#[derive(Clone, Debug, PartialEq, serde::Serialize)]
+pub struct SnifferReport {
+    pub origin_id: u32,
+    pub rssi: Option<i32>,
+    pub packet_type: Option<u8>,
+    pub length: Option<u16>,
+}
+
+/// The statistics of the packets of a single sender
+#[derive(Clone, Debug, PartialEq, serde::Serialize)]
+pub struct SnifferNodeStats {
+    pub origin_id: u32,
+    pub packet_count: u64,
+    /// Sum of the lengths of the packets that reported their length
+    pub total_length: u64,
+    pub last_rssi: Option<i32>,
+    pub min_rssi: Option<i32>,
+    pub max_rssi: Option<i32>,
+    pub mean_rssi: Option<f64>,
+    /// Number of packets of every reported packet type
+    pub packet_types: BTreeMap<u8, u64>,
+    /// Time at which the first packet was reported, in microseconds since the UNIX epoch
+    pub first_seen_us: u64,
+    /// Time at which the last packet was reported, in microseconds since the UNIX epoch
+    pub last_seen_us: u64,
+    #[serde(skip)]
+    rssi_count: u64,
+    #[serde(skip)]
+    rssi_sum: i64,
+}
+
+impl SnifferNodeStats {
+    fn new(origin_id: u32, seen_us: u64) -> SnifferNodeStats {
+        return SnifferNodeStats {
+            origin_id,
+            packet_count: 0,
+            total_length: 0,
+            last_rssi: None,
+            min_rssi: None,
+            max_rssi: None,
+            mean_rssi: None,
+            packet_types: BTreeMap::new(),
+            first_seen_us: seen_us,
+            last_seen_us: seen_us,
+            rssi_count: 0,
+            rssi_sum: 0,
+        };
+    }
+
+    fn record(&mut self, report: &SnifferReport, seen_us: u64) {
+        self.packet_count += 1;
+        self.last_seen_us = seen_us;
+        if let Some(length) = report.length {
+            self.total_length += length as u64;
+        }
+        if let Some(packet_type) = report.packet_type {
+            *self.packet_types.entry(packet_type).or_insert(0) += 1;
+        }
+        if let Some(rssi) = report.rssi {
+            self.rssi_count += 1;
+            self.rssi_sum += rssi as i64;
+            self.last_rssi = Some(rssi);
+            self.min_rssi = Some(self.min_rssi.map_or(rssi, |min| min.min(rssi)));
+            self.max_rssi = Some(self.max_rssi.map_or(rssi, |max| max.max(rssi)));
+            self.mean_rssi = Some(self.rssi_sum as f64 / self.rssi_count as f64);
+        }
+    }
+}
+
+/// Collects the received output of a sniffer test mode into lines
+#[derive(Clone, Debug, Default)]
+pub struct SnifferLineBuffer {
+    /// The received bytes of the current, unfinished line
+    line: Vec<u8>,
+}
+
+impl SnifferLineBuffer {
+    /// Appends received bytes, which don't have to end at a line break.
+    /// # Returns
+    /// The lines completed by the bytes, without their line breaks.
+    pub fn push(&mut self, bytes: &[u8]) -> Vec<String> {
+        let mut lines = vec![];
+        for &byte in bytes {
+            if byte != b'\r' && byte != b'\n' {
+                if self.line.len() < MAX_LINE_LEN {
+                    self.line.push(byte);
+                }
+                continue;
+            }
+            if !self.line.is_empty() {
+                let line = std::mem::take(&mut self.line);
+                lines.push(String::from_utf8_lossy(&line).to_string());
+            }
+        }
+        return lines;
+    }
+
+    /// Drops the unfinished line.
+    pub fn clear(&mut self) {
+        self.line.clear();
+    }
+}
+
+/// The parser of the packet sniffer output of a session.
+/// It only parses while the test mode is running, and keeps the statistics after it was stopped.
+#[derive(Clone, Debug, Default)]
+pub struct PacketSniffer {
+    is_running: bool,
+    lines: SnifferLineBuffer,
+    stats: BTreeMap<u32, SnifferNodeStats>,
+}
+
+impl PacketSniffer {
+    /// Starts or stops parsing. Starting clears the statistics of the previous run.
+    pub fn set_running(&mut self, is_running: bool) {
+        if is_running && !self.is_running {
+            self.stats.clear();
+        }
+        self.is_running = is_running;
+        self.lines.clear();
+    }
+
+    /// Returns whether the packet sniffer test mode is running.
+    pub fn is_running(&self) -> bool {
+        return self.is_running;
+    }
+
+    /// Parses received bytes and updates the statistics. Bytes are ignored while the sniffer is stopped.
+    /// # Arguments
+    /// * `bytes` - The received bytes, which don't have to end at a line break
+    /// * `received_at` - The time at which the bytes were received
+    ///
+    /// # Returns
+    /// The reports of the lines completed by the bytes.
+    pub fn push(&mut self, bytes: &[u8], received_at: Instant) -> Vec<SnifferReport> {
+        if !self.is_running {
+            return vec![];
+        }
+        let seen_us = unix_timestamp_us(received_at);
+        let mut reports = vec![];
+        for line in self.lines.push(bytes) {
+            if let Some(report) = parse_sniffer_line(&line) {
+                self.stats
+                    .entry(report.origin_id)
+                    .or_insert_with(|| SnifferNodeStats::new(report.origin_id, seen_us))
+                    .record(&report, seen_us);
+                reports.push(report);
+            }
+        }
+        return reports;
+    }
+
+    /// Returns the statistics of all senders, ordered by unique ID.
+    pub fn stats(&self) -> Vec<SnifferNodeStats> {
+        return self.stats.values().cloned().collect();
+    }
+
+    /// Forgets the statistics of all senders.
+    pub fn clear_stats(&mut self) {
+        self.stats.clear();
+    }
+}
+
+/// This function parses a line of packet sniffer output.
+/// # Arguments
+/// * `line` - The line, without the line break
+///
+/// # Returns
+/// The report of the line, or `None` if the line does not name a sender or a value is malformed.
+pub fn parse_sniffer_line(line: &str) -> Option<SnifferReport> {
+    let mut origin_id = None;
+    let mut rssi = None;
+    let mut packet_type = None;
+    let mut length = None;
+    for (key, value) in sniffer_fields(line) {
+        match key.as_str() {
+            key if ORIGIN_KEYS.contains(&key) => origin_id = Some(parse_hex(value)?),
+            "RSSI" => rssi = Some(parse_decimal(value)?),
+            "TYPE" | "PT" => packet_type = Some(u8::try_from(parse_hex(value)?).ok()?),
+            "LEN" | "LENGTH" => length = Some(u16::try_from(parse_decimal(value)?).ok()?),
+            _ => {}
+        }
+    }
+    return Some(SnifferReport {
+        origin_id: origin_id?,
+        rssi,
+        packet_type,
+        length,
+    });
+}
+
+/// Splits a line of sniffer output into its `key:value` (or `key=value`) fields.
+/// # Returns
+/// The upper-case keys with their values, fields without a value are skipped.
+pub fn sniffer_fields(line: &str) -> Vec<(String, &str)> {
+    return line
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .filter_map(|field| field.split_once([':', '=']))
+        .map(|(key, value)| (key.trim().to_ascii_uppercase(), value))
+        .collect();
+}
+
+/// Parses a hexadecimal value, with or without a `0x` prefix.
+pub fn parse_hex(value: &str) -> Option<u32> {
+    let value = value.trim();
+    let digits = value
+        .strip_prefix("0x")
+        .or_else(|| value.strip_prefix("0X"))
+        .unwrap_or(value);
+    return u32::from_str_radix(digits, 16).ok();
+}
+
+/// Parses a decimal value, or a hexadecimal one with a `0x` prefix.
+pub fn parse_decimal(value: &str) -> Option<i32> {
+    let value = value.trim();
+    if value.starts_with("0x") || value.starts_with("0X") {
+        return parse_hex(value).and_then(|value| i32::try_from(value).ok());
+    }
+    return value.parse().ok();
+}
+
+/// This function tells whether a mode sequence starts the packet sniffer test mode of the device.
+/// # Arguments
+/// * `device_config` - The configuration read from the device, which contains the test modes of its RMD file
+/// * `sequence_str` - The executed mode sequence
+///
+/// # Returns
+/// `true` if the sequence is the `SEQUENCE_ON` of a test mode named like the packet sniffer,
+/// `false` otherwise or if the configuration was not read.
+pub fn is_packet_sniffer_sequence(
+    device_config: Option<&MkDeviceConfig>,
+    sequence_str: &str,
+) -> bool {
+    return starts_test_mode_named(device_config, PACKET_SNIFFER_TEST_MODE_NAME, sequence_str);
+}
+
+/// This function tells whether a mode sequence starts a test mode whose name contains the given text.
+/// # Arguments
+/// * `device_config` - The configuration read from the device, which contains the test modes of its RMD file
+/// * `name` - The lower-case text to look for in the name of the test mode
+/// * `sequence_str` - The executed mode sequence
+///
+/// # Returns
+/// `true` if the sequence is the `SEQUENCE_ON` of a matching test mode, `false` otherwise
+/// or if the configuration was not read.
+pub fn starts_test_mode_named(
+    device_config: Option<&MkDeviceConfig>,
+    name: &str,
+    sequence_str: &str,
+) -> bool {
+    return device_config
+        .and_then(|device_config| device_config.test_mode_started_by(sequence_str))
+        .is_some_and(|test_mode| test_mode.name.to_lowercase().contains(name));
+}
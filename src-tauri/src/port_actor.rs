@@ -0,0 +1,507 @@
+//! This module contains the actor that owns the connection of a session.
+//! The connection is moved into a dedicated thread, which runs the request/response transactions
+//! of all commands one after another. Between transactions it streams received bytes to the
+//! frontend (passive RX), so commands never have to stop and restart the communication task,
+//! and never see bytes that were meant for the passive stream (or vice versa).
+//!
+//! The port is only ever read by a reader thread, which blocks in the read of the port (a `poll`
+//! on the file descriptor for serial ports) and wakes the actor up as soon as bytes arrive.
+//! The actor itself never waits for the port, so it reacts to requests immediately.
+//!
+//! The passively received bytes are grouped into frames by the packetizer of the actor, and every
+//! frame is emitted as a single event (plus a `packet_event` for TinyMesh packets).
+//! While passive RX is off, the received bytes are kept (at most `MAX_UNREAD_BYTES`) and emitted
+//! before the next transaction, unless it reads them itself (`transact_with_received`).
+
+use crate::cc_tool_error::CcToolError;
+use crate::data_types::EventKind;
+use crate::rx_packetizer::{Packetizer, PacketizerConfig, RxFrame};
+use crate::tinymesh_serial_util::{emit_rx_frame, emit_terminal_message};
+use crate::traffic_capture::{current_command_context, enter_command_context};
+use crate::transport::Transport;
+use log::{error, info, warn};
+use std::collections::VecDeque;
+use std::io::{self, ErrorKind, Read, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender, TryRecvError};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tauri::AppHandle;
+
+/// How long the reader thread waits before reading again, when a read returned no bytes right away.
+/// Serial ports and sockets block in the read until bytes arrive (or their read timeout elapses),
+/// this only matters for transports that return immediately when no bytes are available.
+pub const READER_IDLE_WAIT: Duration = Duration::from_millis(5);
+
+/// How long a read of a transaction waits for bytes before returning `io::ErrorKind::TimedOut`.
+/// Matches the default read timeout of the serial port.
+pub const TRANSACTION_READ_TIMEOUT: Duration = Duration::from_millis(10);
+
+/// Size of the buffer of a single read of the reader thread
+const READER_BUFFER_SIZE: usize = 256;
+
+/// How many received bytes are kept for the next transaction while passive RX is off.
+/// Older bytes are discarded.
+pub const MAX_UNREAD_BYTES: usize = 4096;
+
+/// A transaction queued for the actor. It gets `None` if the port is closed.
+type Transaction = Box<dyn FnOnce(Option<&mut (dyn Transport + 'static)>) + Send>;
+
+enum ActorMessage {
+    /// A transaction, and whether it reads the bytes received before it (instead of them being emitted)
+    Transaction {
+        transaction: Transaction,
+        keep_received: bool,
+    },
+    Open(Connection),
+    Close,
+    /// Sent by the reader thread when bytes were received
+    Received,
+    /// Sent by the reader thread when the port can't be read anymore
+    ReaderStopped(CcToolError),
+    /// Wakes the actor up so it notices a change of the passive RX flag
+    Wake,
+    /// Replaces the packetizer settings. The buffered partial frame is emitted first.
+    SetPacketizer(PacketizerConfig),
+}
+
+/// The open connection of the actor.
+/// Writes go straight to the port, reads take the bytes received by the reader thread.
+struct Connection {
+    device: Box<dyn Transport>,
+    /// The chunks read by the reader thread, with the time at which they were read
+    received: Receiver<(Instant, Vec<u8>)>,
+    /// Received bytes that were not read yet
+    pending: VecDeque<u8>,
+    /// Tells the reader thread to stop
+    stop_reader: Arc<AtomicBool>,
+}
+
+impl Connection {
+    /// Starts the reader thread of the device.
+    /// # Arguments
+    /// * `device` - The connection to own
+    /// * `actor_sender` - The sender used for waking the actor up when bytes were received
+    ///
+    /// # Returns
+    /// The connection, or an error if the device can't be cloned for the reader thread.
+    fn open(
+        device: Box<dyn Transport>,
+        actor_sender: Sender<ActorMessage>,
+    ) -> Result<Connection, CcToolError> {
+        let reader = device.try_clone_transport()?;
+        let (received_sender, received) = mpsc::channel();
+        let stop_reader = Arc::new(AtomicBool::new(false));
+        let stop = stop_reader.clone();
+        std::thread::spawn(move || {
+            run_reader(reader, received_sender, actor_sender, stop);
+        });
+        return Ok(Connection {
+            device,
+            received,
+            pending: VecDeque::new(),
+            stop_reader,
+        });
+    }
+
+    /// Takes all bytes received so far, without waiting.
+    /// # Returns
+    /// The received chunks with the time at which they were read, oldest first.
+    fn take_received(&mut self) -> Vec<(Instant, Vec<u8>)> {
+        let mut chunks = vec![];
+        if !self.pending.is_empty() {
+            // Left over by a transaction, the time of the read is not known anymore
+            chunks.push((Instant::now(), self.pending.drain(..).collect()));
+        }
+        loop {
+            match self.received.try_recv() {
+                Ok(chunk) => chunks.push(chunk),
+                Err(TryRecvError::Empty | TryRecvError::Disconnected) => return chunks,
+            }
+        }
+    }
+
+    /// Moves the received chunks to the unread bytes, discarding the oldest ones above `limit`.
+    /// # Returns
+    /// The count of the discarded bytes.
+    fn limit_unread(&mut self, limit: usize) -> usize {
+        while let Ok((_, bytes)) = self.received.try_recv() {
+            self.pending.extend(bytes);
+        }
+        let excess = self.pending.len().saturating_sub(limit);
+        self.pending.drain(..excess);
+        return excess;
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        self.stop_reader.store(true, Ordering::Relaxed);
+    }
+}
+
+impl Read for Connection {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pending.is_empty() {
+            match self.received.recv_timeout(TRANSACTION_READ_TIMEOUT) {
+                Ok((_, bytes)) => self.pending.extend(bytes),
+                Err(RecvTimeoutError::Timeout) => {
+                    return Err(io::Error::new(ErrorKind::TimedOut, "No bytes to read"));
+                }
+                Err(RecvTimeoutError::Disconnected) => {
+                    return Err(io::Error::new(ErrorKind::BrokenPipe, "Reader stopped"));
+                }
+            }
+        }
+        let count = buf.len().min(self.pending.len());
+        for (slot, byte) in buf.iter_mut().zip(self.pending.drain(..count)) {
+            *slot = byte;
+        }
+        Ok(count)
+    }
+}
+
+impl Write for Connection {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.device.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.device.flush()
+    }
+}
+
+impl Transport for Connection {
+    fn name(&self) -> Option<String> {
+        self.device.name()
+    }
+
+    fn clear_output_buffer(&mut self) -> io::Result<()> {
+        self.device.clear_output_buffer()
+    }
+
+    fn try_clone_transport(&self) -> io::Result<Box<dyn Transport>> {
+        // A clone would compete with the reader thread for the received bytes
+        Err(io::Error::new(
+            ErrorKind::Unsupported,
+            "The connection of an actor can't be cloned",
+        ))
+    }
+}
+
+/// Handle of the actor owning the connection of a session.
+/// Dropping the handle closes the connection and stops the actor.
+pub struct PortActor {
+    sender: Mutex<Sender<ActorMessage>>,
+    /// Whether the actor streams received bytes between transactions
+    is_passive_rx_enabled: Arc<Mutex<bool>>,
+    /// Name of the open connection, `None` if the port is closed
+    name: Arc<Mutex<Option<String>>>,
+    /// The settings of the packetizer of the actor
+    packetizer_config: Mutex<PacketizerConfig>,
+}
+
+impl PortActor {
+    /// Starts an actor owning the given connection.
+    /// # Arguments
+    /// * `device` - The connection to own, or `None` to start with a closed port
+    /// * `app_handle` - An optional Tauri application handle, used for emitting the passively received bytes
+    ///
+    /// # Returns
+    /// The handle of the actor, or an error if the reader thread of the connection could not be started.
+    pub fn spawn(
+        device: Option<Box<dyn Transport>>,
+        app_handle: Option<AppHandle>,
+    ) -> Result<PortActor, CcToolError> {
+        let (sender, receiver) = mpsc::channel();
+        let is_passive_rx_enabled = Arc::new(Mutex::new(false));
+        let actor = PortActor {
+            sender: Mutex::new(sender),
+            is_passive_rx_enabled: is_passive_rx_enabled.clone(),
+            name: Default::default(),
+            packetizer_config: Default::default(),
+        };
+        std::thread::spawn(move || {
+            run_actor(receiver, is_passive_rx_enabled, app_handle);
+        });
+        if let Some(device) = device {
+            actor.open(device)?;
+        }
+        return Ok(actor);
+    }
+
+    fn send(&self, message: ActorMessage) -> Result<(), CcToolError> {
+        return self
+            .sender
+            .lock()?
+            .send(message)
+            .map_err(|_| CcToolError::NotConnected);
+    }
+
+    /// Runs a request/response transaction on the connection, after all previously queued ones.
+    /// Passive RX is paused while the transaction runs. The bytes received before the transaction
+    /// are emitted first, so they don't end up in its response.
+    /// # Arguments
+    /// * `transaction` - The function exchanging bytes with the device
+    ///
+    /// # Returns
+    /// The result of the transaction, or `CcToolError::NotConnected` if the port is closed.
+    pub fn transact<R, F>(&self, transaction: F) -> Result<R, CcToolError>
+    where
+        R: Send + 'static,
+        F: FnOnce(&mut dyn Transport) -> Result<R, CcToolError> + Send + 'static,
+    {
+        return self.run_transaction(transaction, false);
+    }
+
+    /// Runs a transaction like `transact`, but leaves the bytes received while passive RX was off
+    /// (at most `MAX_UNREAD_BYTES`) for the transaction to read, e.g. for waiting for a prompt.
+    /// # Arguments
+    /// * `transaction` - The function exchanging bytes with the device
+    ///
+    /// # Returns
+    /// The result of the transaction, or `CcToolError::NotConnected` if the port is closed.
+    pub fn transact_with_received<R, F>(&self, transaction: F) -> Result<R, CcToolError>
+    where
+        R: Send + 'static,
+        F: FnOnce(&mut dyn Transport) -> Result<R, CcToolError> + Send + 'static,
+    {
+        return self.run_transaction(transaction, true);
+    }
+
+    fn run_transaction<R, F>(&self, transaction: F, keep_received: bool) -> Result<R, CcToolError>
+    where
+        R: Send + 'static,
+        F: FnOnce(&mut dyn Transport) -> Result<R, CcToolError> + Send + 'static,
+    {
+        let (reply_sender, reply_receiver) = mpsc::channel();
+        // The bytes of the transaction are captured with the command context of the caller
+        let context = current_command_context();
+        self.send(ActorMessage::Transaction {
+            transaction: Box::new(move |device| {
+                let _context = context.as_deref().map(enter_command_context);
+                let result = match device {
+                    Some(device) => transaction(device),
+                    None => Err(CcToolError::NotConnected),
+                };
+                let _ = reply_sender.send(result);
+            }),
+            keep_received,
+        })?;
+        return reply_receiver
+            .recv()
+            .unwrap_or(Err(CcToolError::NotConnected));
+    }
+
+    /// Hands a new connection to the actor, closing the previous one.
+    /// # Returns
+    /// An `Ok(())` if the connection was handed over, or an error if its reader thread could not be started.
+    pub fn open(&self, device: Box<dyn Transport>) -> Result<(), CcToolError> {
+        let name = device.name();
+        let connection = Connection::open(device, self.sender.lock()?.clone())?;
+        *self.name.lock()? = name;
+        return self.send(ActorMessage::Open(connection));
+    }
+
+    /// Closes the connection. Passive RX is disabled.
+    pub fn close(&self) -> Result<(), CcToolError> {
+        *self.name.lock()? = None;
+        *self.is_passive_rx_enabled.lock()? = false;
+        return self.send(ActorMessage::Close);
+    }
+
+    /// Returns whether the actor has an open connection.
+    pub fn is_open(&self) -> Result<bool, CcToolError> {
+        return Ok(self.name.lock()?.is_some());
+    }
+
+    /// Returns the name of the open connection, `None` if the port is closed.
+    pub fn name(&self) -> Result<Option<String>, CcToolError> {
+        return Ok(self.name.lock()?.clone());
+    }
+
+    /// Enables or disables streaming the received bytes between transactions.
+    /// The change takes effect before the next queued request is handled.
+    pub fn set_passive_rx(&self, enabled: bool) -> Result<(), CcToolError> {
+        *self.is_passive_rx_enabled.lock()? = enabled;
+        return self.send(ActorMessage::Wake);
+    }
+
+    /// Returns whether the received bytes are streamed between transactions.
+    pub fn is_passive_rx_enabled(&self) -> Result<bool, CcToolError> {
+        return Ok(*self.is_passive_rx_enabled.lock()?);
+    }
+
+    /// Changes how the passively received bytes are grouped into frames.
+    /// The change takes effect before the next queued request is handled.
+    pub fn set_packetizer(&self, config: PacketizerConfig) -> Result<(), CcToolError> {
+        *self.packetizer_config.lock()? = config.clone();
+        return self.send(ActorMessage::SetPacketizer(config));
+    }
+
+    /// Returns how the passively received bytes are grouped into frames.
+    pub fn packetizer(&self) -> Result<PacketizerConfig, CcToolError> {
+        return Ok(self.packetizer_config.lock()?.clone());
+    }
+}
+
+impl Drop for PortActor {
+    fn drop(&mut self) {
+        // The reader thread holds a sender too, so the actor only stops once the reader did
+        let _ = self.send(ActorMessage::Close);
+    }
+}
+
+/// The loop of the reader thread. It ends when the connection is closed or can't be read anymore.
+fn run_reader(
+    mut reader: Box<dyn Transport>,
+    received_sender: Sender<(Instant, Vec<u8>)>,
+    actor_sender: Sender<ActorMessage>,
+    stop: Arc<AtomicBool>,
+) {
+    let mut buffer = [0u8; READER_BUFFER_SIZE];
+    while !stop.load(Ordering::Relaxed) {
+        let started_at = Instant::now();
+        match reader.read(&mut buffer) {
+            Ok(0) => {
+                let _ = actor_sender.send(ActorMessage::ReaderStopped(CcToolError::Io {
+                    message: "Connection closed".to_string(),
+                }));
+                return;
+            }
+            Ok(read) => {
+                if received_sender
+                    .send((Instant::now(), buffer[..read].to_vec()))
+                    .is_err()
+                    || actor_sender.send(ActorMessage::Received).is_err()
+                {
+                    return;
+                }
+            }
+            Err(err)
+                if matches!(
+                    err.kind(),
+                    ErrorKind::TimedOut | ErrorKind::WouldBlock | ErrorKind::Interrupted
+                ) =>
+            {
+                if started_at.elapsed() < Duration::from_millis(1) {
+                    std::thread::sleep(READER_IDLE_WAIT);
+                }
+            }
+            Err(err) => {
+                let _ = actor_sender.send(ActorMessage::ReaderStopped(CcToolError::from(err)));
+                return;
+            }
+        }
+    }
+}
+
+/// The loop of the actor thread. It ends when the handle and the reader thread are gone.
+fn run_actor(
+    receiver: Receiver<ActorMessage>,
+    is_passive_rx_enabled: Arc<Mutex<bool>>,
+    app_handle: Option<AppHandle>,
+) {
+    let mut connection: Option<Connection> = None;
+    let mut packetizer = Packetizer::new(PacketizerConfig::default());
+    // The name of the connection the buffered partial frame was received from
+    let mut frame_session_id: Option<String> = None;
+    let emit_frame = |session_id: Option<String>, frame: RxFrame| {
+        emit_rx_frame(app_handle.as_ref(), session_id, frame);
+    };
+    loop {
+        // A partial frame is completed when the line stays quiet, so the actor wakes up for it
+        let message = match packetizer.deadline() {
+            Some(deadline) => {
+                match receiver.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                    Ok(message) => Some(message),
+                    Err(RecvTimeoutError::Timeout) => None,
+                    Err(RecvTimeoutError::Disconnected) => break,
+                }
+            }
+            None => match receiver.recv() {
+                Ok(message) => Some(message),
+                Err(_) => break,
+            },
+        };
+        match message {
+            None => {}
+            Some(ActorMessage::SetPacketizer(config)) => {
+                if let Some(frame) = packetizer.flush() {
+                    emit_frame(frame_session_id.clone(), frame);
+                }
+                packetizer = Packetizer::new(config);
+            }
+            Some(ActorMessage::Transaction {
+                transaction,
+                keep_received,
+            }) => {
+                // The bytes received before the transaction don't belong to its response
+                if let Some(connection) = connection.as_mut().filter(|_| !keep_received) {
+                    frame_session_id = connection.name();
+                    for (received_at, bytes) in connection.take_received() {
+                        for frame in packetizer.push(&bytes, received_at) {
+                            emit_frame(frame_session_id.clone(), frame);
+                        }
+                    }
+                }
+                if let Some(frame) = packetizer.flush() {
+                    emit_frame(frame_session_id.clone(), frame);
+                }
+                transaction(connection.as_mut().map(|connection| connection as _));
+            }
+            Some(ActorMessage::Open(new_connection)) => {
+                info!("Port opened: {}", new_connection.name().unwrap_or_default());
+                connection = Some(new_connection);
+            }
+            Some(ActorMessage::Close) => {
+                connection = None;
+            }
+            Some(ActorMessage::ReaderStopped(err)) => {
+                // The port is gone (e.g. the adapter was unplugged), reading it again won't help
+                error!("Stopping passive RX: {}", err);
+                emit_terminal_message(
+                    app_handle.as_ref(),
+                    connection.as_ref().and_then(|connection| connection.name()),
+                    EventKind::ERROR,
+                    &format!("Stopping passive RX: {}", err),
+                );
+                if let Ok(mut is_passive_rx_enabled) = is_passive_rx_enabled.lock() {
+                    *is_passive_rx_enabled = false;
+                }
+            }
+            Some(ActorMessage::Received | ActorMessage::Wake) => {}
+        }
+
+        let is_streaming = is_passive_rx_enabled
+            .lock()
+            .map(|enabled| *enabled)
+            .unwrap_or(false);
+        match (is_streaming, connection.as_mut()) {
+            (true, Some(connection)) => {
+                frame_session_id = connection.name();
+                for (received_at, bytes) in connection.take_received() {
+                    for frame in packetizer.push(&bytes, received_at) {
+                        emit_frame(frame_session_id.clone(), frame);
+                    }
+                }
+                if let Some(frame) = packetizer.poll(Instant::now()) {
+                    emit_frame(frame_session_id.clone(), frame);
+                }
+            }
+            // Streaming stopped or the port was closed, the partial frame is not completed anymore
+            (_, connection) => {
+                if let Some(frame) = packetizer.flush() {
+                    emit_frame(frame_session_id.clone(), frame);
+                }
+                // Nobody reads the received bytes until the next transaction, so they are bounded
+                if let Some(connection) = connection {
+                    let discarded = connection.limit_unread(MAX_UNREAD_BYTES);
+                    if discarded > 0 {
+                        warn!("Discarded {} unread received bytes", discarded);
+                    }
+                }
+            }
+        }
+    }
+}
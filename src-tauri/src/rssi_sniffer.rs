@@ -0,0 +1,254 @@
+//! This module contains the parser of the output of the RSSI Sniffer test mode, and the RSSI history
+//! and statistics of every source built from it.
+//!
+//! In the RSSI sniffer test mode the module prints the raw RSSI of every valid TinyMesh packet it
+//! receives, one report per line. A report is either the bare raw value (e.g. `72`), or `key:value`
+//! fields like the packet sniffer output (e.g. `ORG:01020304 RSSI:72`), in which case the sender is
+//! kept as the source of the report. The raw values are converted to dBm with the `RSSI_OFFSET` and
+//! `RSSI_SCALE_FACTOR` of the RMD file, like the values of the spectrum analyzer.
+//!
+//! Both report formats are assumptions: the RMD file only says that the mode displays the RSSI of every
+//! received packet, and the `a5` output of a real module has not been checked yet. Lines that match
+//! neither format are skipped, but any other line that is a bare number (e.g. a counter) would be taken
+//! for an RSSI report. The output is therefore only parsed if the user enables it with
+//! `set_sniffer_output_parsing`, like the packet sniffer output. Once a capture of "Test Mode 5, RSSI
+//! Sniffer" is available, add it as a test fixture, fit the parser to it and parse the output by default.
+//!
+//! The statistics of a source are calculated over its last `RSSI_HISTORY_LEN` reports.
+
+use crate::data_types::{unix_timestamp_us, MkDeviceConfig, RssiScaling};
+use crate::packet_sniffer::{
+    parse_decimal, parse_hex, sniffer_fields, starts_test_mode_named, SnifferLineBuffer,
+    ORIGIN_KEYS,
+};
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt::Write;
+use std::time::Instant;
+
+/// Test modes whose name contains this text are parsed as RSSI sniffer output
+pub const RSSI_SNIFFER_TEST_MODE_NAME: &str = "rssi sniffer";
+/// Number of reports of every source that are kept for the statistics and the CSV export
+pub const RSSI_HISTORY_LEN: usize = 1000;
+
+/// An RSSI report of the RSSI sniffer
+#[derive(Clone, Debug, PartialEq, serde::Serialize)]
+pub struct RssiReport {
+    /// The sender of the packet, `None` if the module only printed the RSSI
+    pub source_id: Option<u32>,
+    /// The value printed by the module
+    pub raw_rssi: i32,
+    /// The RSSI in dBm
+    pub rssi: f64,
+}
+
+/// The rolling statistics of the RSSI reports of a single source, in dBm
+#[derive(Clone, Debug, PartialEq, serde::Serialize)]
+pub struct RssiSourceStats {
+    pub source_id: Option<u32>,
+    /// Number of reports since the sniffer was started
+    pub report_count: u64,
+    /// Number of reports the statistics are calculated over
+    pub window_len: usize,
+    pub last_rssi: f64,
+    pub min_rssi: f64,
+    pub max_rssi: f64,
+    pub mean_rssi: f64,
+    /// 10th percentile
+    pub p10_rssi: f64,
+    /// Median
+    pub p50_rssi: f64,
+    /// 90th percentile
+    pub p90_rssi: f64,
+    /// Time at which the last report was received, in microseconds since the UNIX epoch
+    pub last_seen_us: u64,
+}
+
+/// A report in the history of a source
+#[derive(Clone, Debug)]
+struct RssiSample {
+    timestamp_us: u64,
+    raw_rssi: i32,
+    rssi: f64,
+}
+
+/// The history of a source
+#[derive(Clone, Debug, Default)]
+struct RssiHistory {
+    report_count: u64,
+    samples: VecDeque<RssiSample>,
+}
+
+impl RssiHistory {
+    fn stats(&self, source_id: Option<u32>) -> Option<RssiSourceStats> {
+        let last = self.samples.back()?;
+        let mut sorted: Vec<f64> = self.samples.iter().map(|sample| sample.rssi).collect();
+        sorted.sort_by(f64::total_cmp);
+        let percentile = |fraction: f64| {
+            // Nearest-rank percentile
+            let rank = (fraction * sorted.len() as f64).ceil() as usize;
+            return sorted[rank.clamp(1, sorted.len()) - 1];
+        };
+        return Some(RssiSourceStats {
+            source_id,
+            report_count: self.report_count,
+            window_len: sorted.len(),
+            last_rssi: last.rssi,
+            min_rssi: sorted[0],
+            max_rssi: sorted[sorted.len() - 1],
+            mean_rssi: sorted.iter().sum::<f64>() / sorted.len() as f64,
+            p10_rssi: percentile(0.1),
+            p50_rssi: percentile(0.5),
+            p90_rssi: percentile(0.9),
+            last_seen_us: last.timestamp_us,
+        });
+    }
+}
+
+/// The parser of the RSSI sniffer output of a session.
+/// It only parses while the test mode is running, and keeps the history after it was stopped.
+#[derive(Clone, Debug, Default)]
+pub struct RssiSniffer {
+    is_running: bool,
+    scaling: RssiScaling,
+    lines: SnifferLineBuffer,
+    histories: BTreeMap<Option<u32>, RssiHistory>,
+}
+
+impl RssiSniffer {
+    /// Starts or stops parsing. Starting clears the history of the previous run.
+    /// # Arguments
+    /// * `is_running` - Whether the RSSI sniffer test mode is running
+    /// * `scaling` - The conversion of the raw values to dBm, taken from the RMD file of the device
+    pub fn set_running(&mut self, is_running: bool, scaling: RssiScaling) {
+        if is_running && !self.is_running {
+            self.histories.clear();
+        }
+        self.is_running = is_running;
+        self.scaling = scaling;
+        self.lines.clear();
+    }
+
+    /// Returns whether the RSSI sniffer test mode is running.
+    pub fn is_running(&self) -> bool {
+        return self.is_running;
+    }
+
+    /// Parses received bytes and adds the reports to the history. Bytes are ignored while the sniffer is stopped.
+    /// # Arguments
+    /// * `bytes` - The received bytes, which don't have to end at a line break
+    /// * `received_at` - The time at which the bytes were received
+    ///
+    /// # Returns
+    /// The reports of the lines completed by the bytes.
+    pub fn push(&mut self, bytes: &[u8], received_at: Instant) -> Vec<RssiReport> {
+        if !self.is_running {
+            return vec![];
+        }
+        let timestamp_us = unix_timestamp_us(received_at);
+        let mut reports = vec![];
+        for line in self.lines.push(bytes) {
+            let Some(report) = parse_rssi_sniffer_line(&line, &self.scaling) else {
+                continue;
+            };
+            let history = self.histories.entry(report.source_id).or_default();
+            history.report_count += 1;
+            history.samples.push_back(RssiSample {
+                timestamp_us,
+                raw_rssi: report.raw_rssi,
+                rssi: report.rssi,
+            });
+            if history.samples.len() > RSSI_HISTORY_LEN {
+                history.samples.pop_front();
+            }
+            reports.push(report);
+        }
+        return reports;
+    }
+
+    /// Returns the statistics of all sources, the reports without a source first, then ordered by unique ID.
+    pub fn stats(&self) -> Vec<RssiSourceStats> {
+        return self
+            .histories
+            .iter()
+            .filter_map(|(source_id, history)| history.stats(*source_id))
+            .collect();
+    }
+
+    /// Forgets the history of all sources.
+    pub fn clear_history(&mut self) {
+        self.histories.clear();
+    }
+
+    /// This function exports the history of all sources as CSV, ordered by time.
+    /// # Returns
+    /// The CSV text, with the columns `timestamp_us`, `source_id` (hexadecimal, empty if unknown),
+    /// `raw_rssi` and `rssi_dbm`.
+    pub fn to_csv(&self) -> String {
+        let mut samples: Vec<(Option<u32>, &RssiSample)> = self
+            .histories
+            .iter()
+            .flat_map(|(source_id, history)| {
+                history
+                    .samples
+                    .iter()
+                    .map(move |sample| (*source_id, sample))
+            })
+            .collect();
+        samples.sort_by_key(|(_, sample)| sample.timestamp_us);
+        let mut csv = String::from("timestamp_us,source_id,raw_rssi,rssi_dbm\n");
+        for (source_id, sample) in samples {
+            let source_id = source_id.map_or(String::new(), |id| format!("{:08X}", id));
+            let _ = writeln!(
+                csv,
+                "{},{},{},{}",
+                sample.timestamp_us, source_id, sample.raw_rssi, sample.rssi
+            );
+        }
+        return csv;
+    }
+}
+
+/// This function parses a line of RSSI sniffer output.
+/// # Arguments
+/// * `line` - The line, without the line break
+/// * `scaling` - The conversion of the raw value to dBm
+///
+/// # Returns
+/// The report of the line, or `None` if the line does not contain an RSSI or a value is malformed.
+pub fn parse_rssi_sniffer_line(line: &str, scaling: &RssiScaling) -> Option<RssiReport> {
+    let (source_id, raw_rssi) = match parse_decimal(line) {
+        Some(raw_rssi) => (None, raw_rssi),
+        None => {
+            let mut source_id = None;
+            let mut raw_rssi = None;
+            for (key, value) in sniffer_fields(line) {
+                if ORIGIN_KEYS.contains(&key.as_str()) {
+                    source_id = Some(parse_hex(value)?);
+                } else if key == "RSSI" {
+                    raw_rssi = Some(parse_decimal(value)?);
+                }
+            }
+            (source_id, raw_rssi?)
+        }
+    };
+    return Some(RssiReport {
+        source_id,
+        raw_rssi,
+        rssi: scaling.to_dbm(raw_rssi),
+    });
+}
+
+/// This function tells whether a mode sequence starts the RSSI sniffer test mode of the device.
+/// # Arguments
+/// * `device_config` - The configuration read from the device, which contains the test modes of its RMD file
+/// * `sequence_str` - The executed mode sequence
+///
+/// # Returns
+/// `true` if the sequence is the `SEQUENCE_ON` of a test mode named like the RSSI sniffer,
+/// `false` otherwise or if the configuration was not read.
+pub fn is_rssi_sniffer_sequence(
+    device_config: Option<&MkDeviceConfig>,
+    sequence_str: &str,
+) -> bool {
+    return starts_test_mode_named(device_config, RSSI_SNIFFER_TEST_MODE_NAME, sequence_str);
+}
@@ -0,0 +1,180 @@
+//! This module contains the RX packetizer, which groups the received bytes into frames, so a radio
+//! packet is emitted as a single `exchange_bytes_event` even if it was read in several chunks
+//! (or several packets were read in one chunk).
+//!
+//! Every rule also ends a frame when the line stays quiet for the inter-byte timeout, so a partial
+//! frame is never held back for long, and when the frame reaches the maximum frame length.
+
+use std::time::{Duration, Instant};
+
+/// Length bytes below this value can't start a TinyMesh packet (the shortest packet is a command)
+pub const TINYMESH_MIN_PACKET_LEN: usize = 10;
+
+/// How a frame ends
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "kind")]
+pub enum FrameRule {
+    /// A frame ends when no byte arrived for the inter-byte timeout
+    InterByteGap,
+    /// Every frame has the same number of bytes
+    FixedLength { length: usize },
+    /// A frame ends with the delimiter (e.g. `[13, 10]`), which is part of the frame
+    Delimiter { delimiter: Vec<u8> },
+    /// TinyMesh packet mode: the first byte of a packet is its length, including the length byte.
+    /// A byte that can't be a length byte is emitted on its own, so the packetizer resynchronizes.
+    TinyMeshPacket,
+}
+
+/// Settings of the packetizer of a session
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct PacketizerConfig {
+    pub rule: FrameRule,
+    /// A (partial) frame ends when no byte arrived for this long, in milliseconds
+    pub inter_byte_timeout_ms: u64,
+    /// A frame ends when it reaches this number of bytes
+    pub max_frame_len: usize,
+}
+
+impl Default for PacketizerConfig {
+    fn default() -> Self {
+        PacketizerConfig {
+            rule: FrameRule::InterByteGap,
+            inter_byte_timeout_ms: 20,
+            max_frame_len: 256,
+        }
+    }
+}
+
+/// A frame of received bytes
+#[derive(Clone, Debug, PartialEq)]
+pub struct RxFrame {
+    /// The time at which the first byte of the frame was read
+    pub received_at: Instant,
+    pub data: Vec<u8>,
+}
+
+/// Groups received chunks of bytes into frames according to a `PacketizerConfig`
+pub struct Packetizer {
+    config: PacketizerConfig,
+    buffer: Vec<u8>,
+    /// The time at which the first byte of the buffered frame was read
+    first_byte_at: Option<Instant>,
+    /// The time at which the last byte of the buffered frame was read
+    last_byte_at: Option<Instant>,
+}
+
+impl Packetizer {
+    pub fn new(config: PacketizerConfig) -> Packetizer {
+        return Packetizer {
+            config,
+            buffer: vec![],
+            first_byte_at: None,
+            last_byte_at: None,
+        };
+    }
+
+    /// Returns the settings of the packetizer.
+    pub fn config(&self) -> &PacketizerConfig {
+        return &self.config;
+    }
+
+    fn inter_byte_timeout(&self) -> Duration {
+        return Duration::from_millis(self.config.inter_byte_timeout_ms);
+    }
+
+    /// Adds a received chunk.
+    /// # Arguments
+    /// * `bytes` - The received bytes
+    /// * `received_at` - The time at which the chunk was read
+    ///
+    /// # Returns
+    /// The frames completed by the chunk, oldest first. A buffered partial frame is completed first
+    /// if the chunk arrived after the inter-byte timeout.
+    pub fn push(&mut self, bytes: &[u8], received_at: Instant) -> Vec<RxFrame> {
+        let mut frames = vec![];
+        if let Some(frame) = self.poll(received_at) {
+            frames.push(frame);
+        }
+        for byte in bytes {
+            self.first_byte_at.get_or_insert(received_at);
+            self.buffer.push(*byte);
+            if self.is_frame_complete() {
+                frames.extend(self.flush());
+            }
+        }
+        if !self.buffer.is_empty() {
+            self.last_byte_at = Some(received_at);
+        }
+        return frames;
+    }
+
+    /// Completes the buffered partial frame if the line was quiet for the inter-byte timeout.
+    /// # Arguments
+    /// * `now` - The current time
+    ///
+    /// # Returns
+    /// The completed frame, or `None` if there is no partial frame or the timeout did not elapse yet.
+    pub fn poll(&mut self, now: Instant) -> Option<RxFrame> {
+        let deadline = self.deadline()?;
+        if now < deadline {
+            return None;
+        }
+        return self.flush();
+    }
+
+    /// Returns the time at which the buffered partial frame is completed if no more bytes arrive,
+    /// or `None` if there is no partial frame (or the timeout is too long to ever elapse).
+    pub fn deadline(&self) -> Option<Instant> {
+        return self
+            .last_byte_at
+            .and_then(|last_byte_at| last_byte_at.checked_add(self.inter_byte_timeout()));
+    }
+
+    /// Completes the buffered partial frame right away.
+    /// # Returns
+    /// The frame, or `None` if there is no partial frame.
+    pub fn flush(&mut self) -> Option<RxFrame> {
+        let received_at = self.first_byte_at.take()?;
+        self.last_byte_at = None;
+        return Some(RxFrame {
+            received_at,
+            data: std::mem::take(&mut self.buffer),
+        });
+    }
+
+    /// Returns whether the buffered bytes form a complete frame.
+    fn is_frame_complete(&self) -> bool {
+        if self.buffer.len() >= self.config.max_frame_len.max(1) {
+            return true;
+        }
+        return match &self.config.rule {
+            FrameRule::InterByteGap => false,
+            FrameRule::FixedLength { length } => self.buffer.len() >= *length,
+            FrameRule::Delimiter { delimiter } => {
+                !delimiter.is_empty() && self.buffer.ends_with(delimiter)
+            }
+            FrameRule::TinyMeshPacket => {
+                let packet_len = self.buffer[0] as usize;
+                packet_len < TINYMESH_MIN_PACKET_LEN || self.buffer.len() >= packet_len
+            }
+        };
+    }
+}
+
+/// This function splits a sequence of received chunks into frames.
+/// The partial frame left at the end is completed as well.
+/// # Arguments
+/// * `config` - The settings of the packetizer
+/// * `chunks` - The received chunks with the time at which they were read, oldest first
+///
+/// # Returns
+/// The frames, oldest first.
+pub fn split_into_frames(config: &PacketizerConfig, chunks: &[(Instant, Vec<u8>)]) -> Vec<RxFrame> {
+    let mut packetizer = Packetizer::new(config.clone());
+    let mut frames = vec![];
+    for (received_at, bytes) in chunks {
+        frames.extend(packetizer.push(bytes, *received_at));
+    }
+    frames.extend(packetizer.flush());
+    return frames;
+}
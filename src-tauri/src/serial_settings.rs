@@ -0,0 +1,261 @@
+//! This module contains the serial line settings used when connecting to a device,
+//! and the functions for opening a serial port with them and saving them per port.
+
+use crate::cc_tool_error::CcToolError;
+use crate::data_types::MkDeviceConfig;
+use log::info;
+use serialport::SerialPort;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tauri::AppHandle;
+
+/// Name of the file (in the app config directory) the serial settings of each port are saved to
+pub const SERIAL_SETTINGS_FILE_NAME: &str = "serial_settings.json";
+
+/// Parity of the serial line, matching the `Serial Port Parity Selection` cell of the module
+#[derive(Clone, Copy, Debug, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum Parity {
+    #[default]
+    None,
+    Odd,
+    Even,
+}
+
+/// Number of stop bits of the serial line, matching the `UART Stop Bits` cell of the module
+#[derive(Clone, Copy, Debug, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum StopBits {
+    #[default]
+    One,
+    Two,
+}
+
+/// Flow control of the serial line, matching the `UART Flow Control` cell of the module
+#[derive(Clone, Copy, Debug, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum FlowControl {
+    #[default]
+    None,
+    /// XON/XOFF
+    Software,
+    /// RTS/CTS
+    Hardware,
+}
+
+/// Settings of the serial line used when connecting to a device.
+/// Missing fields are filled with the defaults when deserializing.
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct SerialSettings {
+    pub baud_rate: u32,
+    /// Number of data bits (5 to 8)
+    pub data_bits: u8,
+    pub parity: Parity,
+    pub stop_bits: StopBits,
+    pub flow_control: FlowControl,
+    /// Timeout of a single read on the port in milliseconds
+    pub read_timeout_ms: u64,
+    /// State the DTR line is set to after opening the port. `None` leaves it as the driver sets it.
+    pub dtr: Option<bool>,
+    /// State the RTS line is set to after opening the port. `None` leaves it as the driver sets it.
+    /// Ignored when hardware flow control is used.
+    pub rts: Option<bool>,
+}
+
+impl Default for SerialSettings {
+    fn default() -> Self {
+        SerialSettings {
+            baud_rate: 19200,
+            data_bits: 8,
+            parity: Parity::None,
+            stop_bits: StopBits::One,
+            flow_control: FlowControl::None,
+            read_timeout_ms: 10,
+            dtr: None,
+            rts: None,
+        }
+    }
+}
+
+/// Baud rates selected by the values 1 to 11 of the `UART Baud Rate` cell (0x35)
+pub const UART_BAUD_RATES: [u32; 11] = [
+    2400, 4800, 9600, 14400, 19200, 28800, 38400, 56700, 76800, 115200, 230400,
+];
+
+impl SerialSettings {
+    /// Derives the serial settings the module will use from its UART configuration cells
+    /// (0x35 baud rate, 0x36 bits, 0x37 parity, 0x38 stop bits, 0x3A flow control).
+    /// # Arguments
+    /// * `device_config` - The configuration read from the device (or about to be written to it)
+    ///
+    /// # Returns
+    /// The serial settings matching the configuration, or `None` if the configuration
+    /// doesn't contain valid UART cells.
+    pub fn from_device_config(device_config: &MkDeviceConfig) -> Option<SerialSettings> {
+        let cell_value = |address: usize| {
+            device_config
+                .cells
+                .get(address)
+                .map(|cell| cell.current_value)
+        };
+        let baud_rate = *UART_BAUD_RATES.get((cell_value(0x35)? as usize).checked_sub(1)?)?;
+        // 9 bit words are 8 data bits followed by the parity bit
+        let parity = match (cell_value(0x36)?, cell_value(0x37)?) {
+            (9, 0) => Parity::Even,
+            (9, 1) => Parity::Odd,
+            _ => Parity::None,
+        };
+        let stop_bits = match cell_value(0x38)? {
+            2 => StopBits::Two,
+            _ => StopBits::One,
+        };
+        let flow_control_bitmap = cell_value(0x3A)?;
+        let flow_control = if flow_control_bitmap & 0x02 != 0 {
+            FlowControl::Hardware
+        } else if flow_control_bitmap & 0x08 != 0 {
+            FlowControl::Software
+        } else {
+            FlowControl::None
+        };
+        return Some(SerialSettings {
+            baud_rate,
+            parity,
+            stop_bits,
+            flow_control,
+            ..Default::default()
+        });
+    }
+
+    fn serialport_data_bits(&self) -> Result<serialport::DataBits, CcToolError> {
+        return match self.data_bits {
+            5 => Ok(serialport::DataBits::Five),
+            6 => Ok(serialport::DataBits::Six),
+            7 => Ok(serialport::DataBits::Seven),
+            8 => Ok(serialport::DataBits::Eight),
+            data_bits => Err(CcToolError::InvalidInput {
+                message: format!("{} data bits are not supported", data_bits),
+            }),
+        };
+    }
+
+    fn serialport_parity(&self) -> serialport::Parity {
+        return match self.parity {
+            Parity::None => serialport::Parity::None,
+            Parity::Odd => serialport::Parity::Odd,
+            Parity::Even => serialport::Parity::Even,
+        };
+    }
+
+    fn serialport_stop_bits(&self) -> serialport::StopBits {
+        return match self.stop_bits {
+            StopBits::One => serialport::StopBits::One,
+            StopBits::Two => serialport::StopBits::Two,
+        };
+    }
+
+    fn serialport_flow_control(&self) -> serialport::FlowControl {
+        return match self.flow_control {
+            FlowControl::None => serialport::FlowControl::None,
+            FlowControl::Software => serialport::FlowControl::Software,
+            FlowControl::Hardware => serialport::FlowControl::Hardware,
+        };
+    }
+}
+
+/// This function opens the serial port with the given settings and sets the initial DTR/RTS states.
+/// # Arguments
+/// * `device_name` - The name of the serial port
+/// * `serial_settings` - The serial line settings to use
+///
+/// # Returns
+/// The opened serial port, or an error if the settings are invalid or the port could not be opened.
+pub fn open_serial_port(
+    device_name: &str,
+    serial_settings: &SerialSettings,
+) -> Result<Box<dyn SerialPort>, CcToolError> {
+    let mut port = serialport::new(device_name, serial_settings.baud_rate)
+        .data_bits(serial_settings.serialport_data_bits()?)
+        .parity(serial_settings.serialport_parity())
+        .stop_bits(serial_settings.serialport_stop_bits())
+        .flow_control(serial_settings.serialport_flow_control())
+        .timeout(Duration::from_millis(serial_settings.read_timeout_ms))
+        .open()?;
+    if let Some(dtr) = serial_settings.dtr {
+        port.write_data_terminal_ready(dtr)?;
+    }
+    if serial_settings.flow_control != FlowControl::Hardware {
+        if let Some(rts) = serial_settings.rts {
+            port.write_request_to_send(rts)?;
+        }
+    }
+    return Ok(port);
+}
+
+/// Returns the path of the file the serial settings are saved to, if the app config directory is known.
+pub fn serial_settings_file_path(app_handle: &AppHandle) -> Option<PathBuf> {
+    return app_handle
+        .path_resolver()
+        .app_config_dir()
+        .map(|config_dir| config_dir.join(SERIAL_SETTINGS_FILE_NAME));
+}
+
+/// This function reads the saved serial settings of all ports.
+/// # Arguments
+/// * `file_path` - The path of the settings file
+///
+/// # Returns
+/// A map of port names to their settings. A missing or unreadable file results in an empty map.
+pub fn load_serial_settings(file_path: &Path) -> HashMap<String, SerialSettings> {
+    return std::fs::read_to_string(file_path)
+        .ok()
+        .and_then(|file_contents| serde_json::from_str(&file_contents).ok())
+        .unwrap_or_default();
+}
+
+/// This function saves the serial settings of a port, keeping the settings of all other ports.
+/// # Arguments
+/// * `file_path` - The path of the settings file
+/// * `device_name` - The name of the port
+/// * `serial_settings` - The settings to save
+///
+/// # Returns
+/// An `Ok(())` if the settings were saved, or `CcToolError::Io` if the file could not be written.
+pub fn save_serial_settings(
+    file_path: &Path,
+    device_name: &str,
+    serial_settings: &SerialSettings,
+) -> Result<(), CcToolError> {
+    let mut all_settings = load_serial_settings(file_path);
+    all_settings.insert(device_name.to_string(), serial_settings.clone());
+    if let Some(parent) = file_path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let file_contents =
+        serde_json::to_string_pretty(&all_settings).map_err(|err| CcToolError::Io {
+            message: err.to_string(),
+        })?;
+    std::fs::write(file_path, file_contents)?;
+    info!("Saved serial settings of {}", device_name);
+    return Ok(());
+}
+
+/// This function returns the serial settings saved for the given port,
+/// or the default settings if none were saved yet.
+/// # Arguments
+/// * `device_name` - The name of the serial port
+/// * `app_handle` - The Tauri application handle (provided by Tauri)
+///
+/// # Returns
+/// The serial settings to prefill the connection form with.
+#[tauri::command]
+pub fn get_serial_settings(
+    device_name: &str,
+    app_handle: AppHandle,
+) -> Result<SerialSettings, CcToolError> {
+    let saved_settings = serial_settings_file_path(&app_handle)
+        .map(|file_path| load_serial_settings(&file_path))
+        .unwrap_or_default();
+    return Ok(saved_settings
+        .get(device_name)
+        .cloned()
+        .unwrap_or_default());
+}
@@ -0,0 +1,318 @@
+//! This module contains the task manager, which keeps track of the long-running background jobs of a
+//! session (RSSI scan, RX streaming etc.). Every job gets a name, a cancellation token and a status,
+//! and keeps its final result or error after it finished, so the frontend can list and cancel jobs
+//! and the session can shut all of them down in order before its port is closed.
+
+use crate::cc_tool_error::CcToolError;
+use crate::traffic_capture::enter_command_context;
+use log::{error, info};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+/// ID of a task, unique across all sessions
+pub type TaskId = u64;
+
+/// How long a session waits for its tasks to finish after cancelling them
+pub const TASK_SHUTDOWN_TIMEOUT: Duration = Duration::from_millis(2000);
+
+/// Number of finished tasks that are kept for `list_tasks`, per session
+pub const MAX_FINISHED_TASKS: usize = 32;
+
+static NEXT_TASK_ID: AtomicU64 = AtomicU64::new(1);
+
+/// A flag that can be waited for, shared between threads
+#[derive(Default)]
+struct Signal {
+    is_set: Mutex<bool>,
+    condvar: Condvar,
+}
+
+impl Signal {
+    fn set(&self) {
+        if let Ok(mut is_set) = self.is_set.lock() {
+            *is_set = true;
+        }
+        self.condvar.notify_all();
+    }
+
+    fn is_set(&self) -> bool {
+        return self.is_set.lock().map(|is_set| *is_set).unwrap_or(true);
+    }
+
+    /// Waits until the signal is set or the timeout elapsed.
+    /// Returns whether the signal is set.
+    fn wait_timeout(&self, timeout: Duration) -> bool {
+        let Ok(is_set) = self.is_set.lock() else {
+            return true;
+        };
+        return self
+            .condvar
+            .wait_timeout_while(is_set, timeout, |is_set| !*is_set)
+            .map(|(is_set, _)| *is_set)
+            .unwrap_or(true);
+    }
+}
+
+/// Token that tells a task to stop. Clones share the same state.
+/// Tasks are expected to check it between two steps of their work (e.g. between two transactions).
+#[derive(Clone, Default)]
+pub struct CancellationToken {
+    signal: Arc<Signal>,
+}
+
+impl CancellationToken {
+    pub fn new() -> CancellationToken {
+        return CancellationToken::default();
+    }
+
+    /// Asks the task to stop.
+    pub fn cancel(&self) {
+        self.signal.set();
+    }
+
+    /// Returns whether the task was asked to stop.
+    pub fn is_cancelled(&self) -> bool {
+        return self.signal.is_set();
+    }
+
+    /// Sleeps for the given duration, waking up immediately if the task is cancelled in the meantime.
+    /// # Returns
+    /// `true` if the task was cancelled.
+    pub fn wait_timeout(&self, timeout: Duration) -> bool {
+        return self.signal.wait_timeout(timeout);
+    }
+}
+
+/// Lifecycle of a task
+#[derive(Clone, Copy, Debug, PartialEq, serde::Serialize)]
+pub enum TaskStatus {
+    /// The task is running
+    Running,
+    /// The task was asked to stop, but did not finish yet
+    Cancelling,
+    /// The task finished on its own
+    Completed,
+    /// The task finished after it was asked to stop
+    Cancelled,
+    /// The task finished with an error
+    Failed,
+}
+
+impl TaskStatus {
+    /// Returns whether the task finished (successfully or not).
+    pub fn is_finished(&self) -> bool {
+        return !matches!(self, TaskStatus::Running | TaskStatus::Cancelling);
+    }
+}
+
+/// Summary of a task, returned by `list_tasks`
+#[derive(Clone, Debug, PartialEq, serde::Serialize)]
+pub struct TaskInfo {
+    pub task_id: TaskId,
+    /// The kind of job, e.g. `rssi_scan`
+    pub name: String,
+    /// The session the task belongs to (filled in when the tasks of several sessions are listed)
+    pub session_id: Option<String>,
+    pub status: TaskStatus,
+    /// The final result of a task that finished successfully
+    pub result: Option<String>,
+    /// The error of a task that failed
+    pub error: Option<CcToolError>,
+    /// How long the task ran (so far), in milliseconds
+    pub elapsed_ms: u64,
+}
+
+struct TaskEntry {
+    info: TaskInfo,
+    started_at: Instant,
+    token: CancellationToken,
+    finished: Arc<Signal>,
+}
+
+/// The registry of the background jobs of a session
+#[derive(Clone, Default)]
+pub struct TaskManager {
+    tasks: Arc<Mutex<Vec<TaskEntry>>>,
+}
+
+impl TaskManager {
+    /// Starts a job on a background thread and registers it.
+    /// # Arguments
+    /// * `name` - The kind of job, e.g. `rssi_scan`
+    /// * `job` - The job, which gets the cancellation token of the task and returns a final result
+    ///
+    /// # Returns
+    /// The ID of the new task, or an error if the registry could not be accessed.
+    pub fn spawn<F>(&self, name: &str, job: F) -> Result<TaskId, CcToolError>
+    where
+        F: FnOnce(CancellationToken) -> Result<String, CcToolError> + Send + 'static,
+    {
+        let task_id = NEXT_TASK_ID.fetch_add(1, Ordering::Relaxed);
+        let token = CancellationToken::new();
+        let finished = Arc::new(Signal::default());
+        {
+            let mut tasks = self.tasks.lock()?;
+            prune_finished_tasks(&mut tasks);
+            tasks.push(TaskEntry {
+                info: TaskInfo {
+                    task_id,
+                    name: name.to_string(),
+                    session_id: None,
+                    status: TaskStatus::Running,
+                    result: None,
+                    error: None,
+                    elapsed_ms: 0,
+                },
+                started_at: Instant::now(),
+                token: token.clone(),
+                finished: finished.clone(),
+            });
+        }
+        info!("Starting task {} ({})", task_id, name);
+
+        let tasks = self.tasks.clone();
+        let name = name.to_string();
+        tauri::async_runtime::spawn_blocking(move || {
+            // The bytes exchanged by the job are captured with the name of the task as context
+            let context = enter_command_context(&name);
+            let result = job(token.clone());
+            drop(context);
+            match &result {
+                Ok(_) => info!("Task {} finished", task_id),
+                Err(err) => error!("Task {} failed: {}", task_id, err),
+            }
+            if let Ok(mut tasks) = tasks.lock() {
+                if let Some(entry) = tasks.iter_mut().find(|entry| entry.info.task_id == task_id) {
+                    entry.info.elapsed_ms = entry.started_at.elapsed().as_millis() as u64;
+                    match result {
+                        Ok(result) => {
+                            entry.info.status = if token.is_cancelled() {
+                                TaskStatus::Cancelled
+                            } else {
+                                TaskStatus::Completed
+                            };
+                            entry.info.result = Some(result);
+                        }
+                        Err(err) => {
+                            entry.info.status = TaskStatus::Failed;
+                            entry.info.error = Some(err);
+                        }
+                    }
+                }
+            }
+            finished.set();
+        });
+        return Ok(task_id);
+    }
+
+    /// Returns the summaries of all tasks, oldest first.
+    pub fn list(&self) -> Result<Vec<TaskInfo>, CcToolError> {
+        return Ok(self
+            .tasks
+            .lock()?
+            .iter()
+            .map(|entry| {
+                let mut info = entry.info.clone();
+                if !info.status.is_finished() {
+                    info.elapsed_ms = entry.started_at.elapsed().as_millis() as u64;
+                }
+                info
+            })
+            .collect());
+    }
+
+    /// Returns whether the task with the given ID belongs to this manager.
+    pub fn contains(&self, task_id: TaskId) -> Result<bool, CcToolError> {
+        return Ok(self
+            .tasks
+            .lock()?
+            .iter()
+            .any(|entry| entry.info.task_id == task_id));
+    }
+
+    /// Returns the ID of the unfinished task with the given name, if any.
+    pub fn find_running(&self, name: &str) -> Result<Option<TaskId>, CcToolError> {
+        return Ok(self
+            .tasks
+            .lock()?
+            .iter()
+            .find(|entry| entry.info.name == name && !entry.info.status.is_finished())
+            .map(|entry| entry.info.task_id));
+    }
+
+    /// Asks the task to stop. Finished tasks are left as they are.
+    /// # Returns
+    /// An `Ok(())` if the task was found, or `CcToolError::UnknownTask` if it wasn't.
+    pub fn cancel(&self, task_id: TaskId) -> Result<(), CcToolError> {
+        let mut tasks = self.tasks.lock()?;
+        let entry = tasks
+            .iter_mut()
+            .find(|entry| entry.info.task_id == task_id)
+            .ok_or(CcToolError::UnknownTask { task_id })?;
+        if entry.info.status == TaskStatus::Running {
+            info!("Cancelling task {} ({})", task_id, entry.info.name);
+            entry.info.status = TaskStatus::Cancelling;
+            entry.token.cancel();
+        }
+        return Ok(());
+    }
+
+    /// Waits until the task finished or the timeout elapsed.
+    /// # Returns
+    /// Whether the task finished, or `CcToolError::UnknownTask` if it wasn't found.
+    pub fn wait(&self, task_id: TaskId, timeout: Duration) -> Result<bool, CcToolError> {
+        let finished = self
+            .tasks
+            .lock()?
+            .iter()
+            .find(|entry| entry.info.task_id == task_id)
+            .map(|entry| entry.finished.clone())
+            .ok_or(CcToolError::UnknownTask { task_id })?;
+        return Ok(finished.wait_timeout(timeout));
+    }
+
+    /// Cancels all unfinished tasks and waits for them to finish.
+    /// # Arguments
+    /// * `timeout` - How long to wait for all tasks together
+    ///
+    /// # Returns
+    /// Whether all tasks finished in time.
+    pub fn shutdown(&self, timeout: Duration) -> Result<bool, CcToolError> {
+        let deadline = Instant::now() + timeout;
+        let running: Vec<TaskId> = self
+            .list()?
+            .iter()
+            .filter(|info| !info.status.is_finished())
+            .map(|info| info.task_id)
+            .collect();
+        for task_id in &running {
+            self.cancel(*task_id)?;
+        }
+        let mut all_finished = true;
+        for task_id in running {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if !self.wait(task_id, remaining)? {
+                error!("Task {} did not stop in time", task_id);
+                all_finished = false;
+            }
+        }
+        return Ok(all_finished);
+    }
+}
+
+/// Forgets the oldest finished tasks, so at most `MAX_FINISHED_TASKS` finished tasks are kept.
+fn prune_finished_tasks(tasks: &mut Vec<TaskEntry>) {
+    let finished_count = tasks
+        .iter()
+        .filter(|entry| entry.info.status.is_finished())
+        .count();
+    let mut to_remove = finished_count.saturating_sub(MAX_FINISHED_TASKS - 1);
+    tasks.retain(|entry| {
+        if to_remove > 0 && entry.info.status.is_finished() {
+            to_remove -= 1;
+            return false;
+        }
+        return true;
+    });
+}
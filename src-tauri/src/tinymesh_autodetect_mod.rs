@@ -0,0 +1,225 @@
+//! This module contains functions for detecting the serial line settings of a module
+//! that was configured to an unknown UART baud rate or parity.
+//! These functions are used by the Tauri frontend's connection header.
+
+use crate::cc_tool_error::CcToolError;
+use crate::data_types::DeviceEntity;
+use crate::device_config_parser::get_device_information;
+use crate::input_processing::process_input;
+use crate::serial_settings::{
+    open_serial_port, save_serial_settings, serial_settings_file_path, Parity, SerialSettings,
+    UART_BAUD_RATES,
+};
+use crate::tinymesh_serial_util::{
+    read_bytes_from_device_to_buffer, read_frame_from_device, send_bytes_to_device, FrameSpec,
+};
+use crate::traffic_capture::enter_command_context;
+use crate::transport::Transport;
+use log::{error, info};
+use std::time::Duration;
+use tauri::{AppHandle, Manager, State};
+
+/// Time to wait for the configuration memory dump of a single candidate.
+/// A wrong baud rate usually results in garbage or silence, so there is no point in waiting longer.
+pub const PROBE_DEADLINE: Duration = Duration::from_millis(500);
+
+/// Size of the configuration memory dump sent in response to `0`
+const CONFIG_DUMP_LEN: usize = 128;
+
+/// The serial settings a module was found at, together with the device information it reported
+#[derive(Clone, Debug, PartialEq, serde::Serialize)]
+pub struct AutodetectResult {
+    pub serial_settings: SerialSettings,
+    pub model: String,
+    pub hw_version: String,
+    pub firmware_version: String,
+}
+
+/// This struct contains the data that is emitted as a tauri event before each candidate is probed
+#[derive(Clone, serde::Serialize)]
+pub struct AutodetectProgressEvent {
+    pub candidate: usize,
+    pub total: usize,
+    pub serial_settings: SerialSettings,
+}
+
+/// Returns the baud rate / parity combinations a module can be configured to.
+/// The factory default (19200 baud, no parity) is tried first, then the remaining baud rates
+/// of the `UART Baud Rate` cell from fastest to slowest, each without parity, with even and with odd parity.
+pub fn default_autodetect_candidates() -> Vec<SerialSettings> {
+    let default_settings = SerialSettings::default();
+    let mut baud_rates = vec![default_settings.baud_rate];
+    baud_rates.extend(
+        UART_BAUD_RATES
+            .iter()
+            .rev()
+            .filter(|baud_rate| **baud_rate != default_settings.baud_rate),
+    );
+    let mut candidates = vec![];
+    for baud_rate in baud_rates {
+        for parity in [Parity::None, Parity::Even, Parity::Odd] {
+            candidates.push(SerialSettings {
+                baud_rate,
+                parity,
+                ..default_settings.clone()
+            });
+        }
+    }
+    return candidates;
+}
+
+/// This function probes a single candidate: it optionally sends the configuration mode entry sequence,
+/// then requests the configuration memory dump and checks that it contains a valid model string.
+/// # Arguments
+/// * `device` - The transport opened with the candidate settings
+/// * `entry_sequence` - Optional bytes that put the module into configuration mode
+///
+/// # Returns
+/// The model, hardware version and firmware version reported by the module,
+/// or an error if the module did not answer with a valid configuration dump.
+pub fn probe_device(
+    device: &mut dyn Transport,
+    entry_sequence: Option<&[u8]>,
+) -> Result<(String, String, String), CcToolError> {
+    // Discard anything that arrived before the probe
+    read_bytes_from_device_to_buffer(device, &mut vec![], None, None)?;
+    if let Some(entry_sequence) = entry_sequence {
+        send_bytes_to_device(device, entry_sequence, None)?;
+        let _ = read_frame_from_device(
+            device,
+            &FrameSpec::prompt_only().with_deadline(PROBE_DEADLINE),
+            None,
+        );
+    }
+    send_bytes_to_device(device, &[0x30], None)?;
+    let config_bytes_buffer = read_frame_from_device(
+        device,
+        &FrameSpec::memory_dump().with_deadline(PROBE_DEADLINE),
+        None,
+    )?;
+    if config_bytes_buffer.len() != CONFIG_DUMP_LEN {
+        return Err(CcToolError::UnexpectedResponse {
+            expected: format!("{} bytes of configuration memory", CONFIG_DUMP_LEN),
+            got: config_bytes_buffer,
+        });
+    }
+    let (model, hw_version, firmware_version) = get_device_information(&config_bytes_buffer)?;
+    let is_valid_model = !model.is_empty()
+        && model
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, ' ' | '-' | '.' | '_'));
+    if !is_valid_model {
+        return Err(CcToolError::UnexpectedResponse {
+            expected: "a model string in the device information at 0x3C".to_string(),
+            got: config_bytes_buffer,
+        });
+    }
+    return Ok((model, hw_version, firmware_version));
+}
+
+/// This function walks the candidate serial settings and returns the first one the module answers at.
+/// # Arguments
+/// * `candidates` - The serial settings to try, in order
+/// * `open_device` - Opens the port with the given settings
+/// * `entry_sequence` - Optional bytes that put the module into configuration mode
+/// * `app_handle` - An optional Tauri application handle, used for emitting progress events
+///
+/// # Returns
+/// The first matching settings and the device information, or the error of the last candidate
+/// (`CcToolError::Timeout` if no candidates were given).
+pub fn autodetect_serial_settings<F>(
+    candidates: &[SerialSettings],
+    mut open_device: F,
+    entry_sequence: Option<&[u8]>,
+    app_handle: Option<&AppHandle>,
+) -> Result<AutodetectResult, CcToolError>
+where
+    F: FnMut(&SerialSettings) -> Result<Box<dyn Transport>, CcToolError>,
+{
+    let mut last_error = CcToolError::Timeout { received: vec![] };
+    for (index, serial_settings) in candidates.iter().enumerate() {
+        if let Some(app_handle) = app_handle {
+            app_handle
+                .emit_all(
+                    "autodetect_progress",
+                    AutodetectProgressEvent {
+                        candidate: index + 1,
+                        total: candidates.len(),
+                        serial_settings: serial_settings.clone(),
+                    },
+                )
+                .unwrap_or_else(|e| error!("Error emitting: {}", e));
+        }
+        let probe_result = open_device(serial_settings)
+            .and_then(|mut device| probe_device(&mut device, entry_sequence));
+        match probe_result {
+            Ok((model, hw_version, firmware_version)) => {
+                info!("Found {} with {:?}", model, serial_settings);
+                return Ok(AutodetectResult {
+                    serial_settings: serial_settings.clone(),
+                    model,
+                    hw_version,
+                    firmware_version,
+                });
+            }
+            Err(err) => {
+                info!("No module found with {:?}: {}", serial_settings, err);
+                last_error = err;
+            }
+        }
+    }
+    return Err(last_error);
+}
+
+/// This function detects the serial settings of the module on the given port.
+/// The module has to be in configuration mode (CONFIG pin held low), unless an entry sequence is given.
+/// The detected settings are saved for the port, so the next connection uses them.
+/// # Arguments
+/// * `device_name` - The name of the serial port
+/// * `candidates` - The serial settings to try. If `None`, all baud rates of the `UART Baud Rate` cell
+///   are tried without, with even and with odd parity.
+/// * `entry_sequence` - Optional bytes (in the Communication Panel syntax) that put the module into configuration mode
+/// * `device_entity` - The state of the program (provided by Tauri)
+/// * `app_handle` - The Tauri application handle (provided by Tauri)
+///
+/// # Returns
+/// The first matching settings and the device information,
+/// `CcToolError::PortBusy` if the port is currently connected, or the error of the last candidate.
+#[tauri::command]
+pub fn autodetect_connection(
+    device_name: &str,
+    candidates: Option<Vec<SerialSettings>>,
+    entry_sequence: Option<String>,
+    device_entity: State<DeviceEntity>,
+    app_handle: AppHandle,
+) -> Result<AutodetectResult, CcToolError> {
+    let _context = enter_command_context("autodetect_connection");
+    if let Ok(session) = device_entity.session(Some(device_name)) {
+        if session.port.is_open()? {
+            return Err(CcToolError::PortBusy);
+        }
+    }
+    let entry_sequence = entry_sequence
+        .map(|entry_sequence| process_input(&entry_sequence))
+        .transpose()?;
+    let candidates = candidates.unwrap_or_else(default_autodetect_candidates);
+    info!(
+        "Autodetecting serial settings of {} ({} candidates)",
+        device_name,
+        candidates.len()
+    );
+    let result = autodetect_serial_settings(
+        &candidates,
+        |serial_settings| {
+            open_serial_port(device_name, serial_settings)
+                .map(|port| Box::new(port) as Box<dyn Transport>)
+        },
+        entry_sequence.as_deref(),
+        Some(&app_handle),
+    )?;
+    if let Some(settings_file_path) = serial_settings_file_path(&app_handle) {
+        save_serial_settings(&settings_file_path, device_name, &result.serial_settings)
+            .unwrap_or_else(|err| error!("Could not save serial settings: {}", err));
+    }
+    return Ok(result);
+}
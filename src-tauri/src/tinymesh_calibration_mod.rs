@@ -1,20 +1,25 @@
 //! This module contains functions related to getting and setting the device calibration.
 //! These functions are used by the Tauri frontend's calibration tab.
 
+use crate::cc_tool_error::CcToolError;
 use crate::data_types::{DeviceEntity, MkDeviceCell, MkDeviceCalib};
 use crate::device_calibration_parser::parse_device_calib;
-use crate::device_config_parser::parse_device_config;
+use crate::device_config_parser::get_device_information;
 use crate::tinymesh_serial_util::{
-    clear_output_buffer_of_device,
-    read_bytes_till_3e_from_device_to_buffer, send_bytes_to_device,
+    clear_output_buffer_of_device, expect_prompt_from_device, read_frame_from_device,
+    send_bytes_to_device, FrameSpec,
 };
-use serialport::SerialPort;
+use crate::tinymesh_config_mod::{validate_changed_cells, MEMORY_WRITE_DEADLINE};
+use crate::traffic_capture::enter_command_context;
+use crate::transport::Transport;
+use std::path::Path;
 use tauri::{AppHandle, State};
 
 /// This function gets the device calibration from the connected serial device.
 /// It will read the calibration from the device, match it with corresponding module description RMD file
 /// and return it as a `MkDeviceCalib` struct.
 /// # Arguments
+/// * `session_id` - The ID of the session. If `None`, the default session is used.
 /// * `device_entity` - The state of the program (provided by Tauri)
 /// * `app_handle` - The Tauri application handle (provided by Tauri)
 ///
@@ -23,19 +28,17 @@ use tauri::{AppHandle, State};
 /// Returns an error if the calibration could not be retrieved or matched.
 #[tauri::command]
 pub fn get_device_calib(
+    session_id: Option<String>,
     device_entity: State<DeviceEntity>,
     app_handle: AppHandle,
-) -> Result<MkDeviceCalib, String> {
-    let mut device = device_entity.port.lock().map_err(|err| err.to_string())?;
-    let device = device
-        .as_mut()
-        .ok_or("Could not lock the selected device".to_string())?;
-    let device_calib = get_device_calib_from_device(device, &app_handle)?;
-
-    let mut device_calib_from_state = device_entity
-        .device_calib
-        .lock()
-        .map_err(|err| err.to_string())?;
+) -> Result<MkDeviceCalib, CcToolError> {
+    let _context = enter_command_context("get_device_calib");
+    let session = device_entity.session(session_id.as_deref())?;
+    let device_calib = session
+        .port
+        .transact(move |device| get_device_calib_from_device(device, None, Some(&app_handle)))?;
+
+    let mut device_calib_from_state = session.device_calib.lock()?;
     let cloned_config = device_calib.clone();
     *device_calib_from_state = Some(cloned_config);
 
@@ -43,40 +46,42 @@ pub fn get_device_calib(
     return Ok(device_calib);
 }
 
-
+/// This function reads the configuration memory of the device to find out its model,
+/// then reads the calibration memory and decodes it.
+/// # Arguments
+/// * `device` - The transport connected to the device
+/// * `rmd_file_path` - An optional path to the RMD file. If `None`, the RMD file matching the device model is used.
+/// * `app_handle` - An optional Tauri application handle, used for emitting the exchanged bytes
+///
+/// # Returns
+/// A `MkDeviceCalib` struct containing the device calibration, or a `CcToolError`.
 pub fn get_device_calib_from_device(
-    device: &mut Box<dyn SerialPort>,
-    app_handle: &AppHandle,
-) -> Result<MkDeviceCalib, String> {
+    device: &mut dyn Transport,
+    rmd_file_path: Option<&Path>,
+    app_handle: Option<&AppHandle>,
+) -> Result<MkDeviceCalib, CcToolError> {
     // info!("\ntinymesh_calibration_mod::get_device_calib_from_device(device, app_handle)\n");
 
-    let mut config_bytes_buffer = vec![];
-    if clear_output_buffer_of_device(device) && send_bytes_to_device(device, &[0x30], &app_handle) {
-        // info!("\nCALIB_DEVICE ==== {:?}\n", device);
-
-        read_bytes_till_3e_from_device_to_buffer(device, &mut config_bytes_buffer, &app_handle);
-        // info!("\ntinymesh_calibration_mod::get_device_calib_from_device---> config_bytes_buffer = {:?}\n", config_bytes_buffer);
-
-        let device_config_info = parse_device_config(&config_bytes_buffer, None, Some(&app_handle))?;
-        let model = device_config_info.model;
-
-        config_bytes_buffer = vec![];
-        if clear_output_buffer_of_device(device) && send_bytes_to_device(device, &[0x72], &app_handle) {
-            // info!("\nCALIB_DEVICE 2 ==== {:?}\n", device);
-    
-            read_bytes_till_3e_from_device_to_buffer(device, &mut config_bytes_buffer, &app_handle);
-            // info!("\ntinymesh_calibration_mod::get_device_calib_from_device---> config_bytes_buffer = {:?}\n", config_bytes_buffer);
-    
-            let device_calib = parse_device_calib(&config_bytes_buffer, model, None, Some(&app_handle))?;
-    
-            // info!("\ntinymesh_calibration_mod::get_device_calib_from_device---> device_calib = {:?}\n", device_calib);
-            return Ok(device_calib);
-        }
+    clear_output_buffer_of_device(device)?;
+    send_bytes_to_device(device, &[0x30], app_handle)?;
+    // info!("\nCALIB_DEVICE ==== {:?}\n", device);
 
-        // info!("\ntinymesh_calibration_mod::get_device_calib_from_device---> device_calib = {:?}\n", device_calib);
-        return Err("Unable to get calibration. Model not found.".to_string());
-    }
-    return Err("Unable to get calibration. Looks like sending bytes failed.".to_string());
+    let config_bytes_buffer = read_frame_from_device(device, &FrameSpec::memory_dump(), app_handle)?;
+    // info!("\ntinymesh_calibration_mod::get_device_calib_from_device---> config_bytes_buffer = {:?}\n", config_bytes_buffer);
+
+    let (model, _hw_version, _firmware_version) = get_device_information(&config_bytes_buffer)?;
+
+    clear_output_buffer_of_device(device)?;
+    send_bytes_to_device(device, &[0x72], app_handle)?;
+    // info!("\nCALIB_DEVICE 2 ==== {:?}\n", device);
+
+    let calib_bytes_buffer = read_frame_from_device(device, &FrameSpec::memory_dump(), app_handle)?;
+    // info!("\ntinymesh_calibration_mod::get_device_calib_from_device---> calib_bytes_buffer = {:?}\n", calib_bytes_buffer);
+
+    let device_calib = parse_device_calib(&calib_bytes_buffer, model, rmd_file_path, app_handle)?;
+
+    // info!("\ntinymesh_calibration_mod::get_device_calib_from_device---> device_calib = {:?}\n", device_calib);
+    return Ok(device_calib);
 }
 
 /// This function sets the device calibration in the connected serial device.
@@ -84,52 +89,66 @@ pub fn get_device_calib_from_device(
 /// It only sends the new calibration to the device.
 /// # Arguments
 /// * `cells` - A vector of `MkDeviceCell` structs containing the new device calibration
+/// * `session_id` - The ID of the session. If `None`, the default session is used.
 /// * `device_entity` - The state of the program (provided by Tauri)
 /// * `app_handle` - The Tauri application handle (provided by Tauri)
 ///
 /// # Returns
-/// A boolean value indicating whether the device calibration was set successfully.
+/// An `Ok(())` if the device calibration was set, `CcToolError::ConfigNotRead` if the calibration
+/// was not read from the device before, or the error that occurred while writing it.
 #[tauri::command]
 pub fn set_device_calib(
     cells: Vec<MkDeviceCell>,
+    session_id: Option<String>,
     device_entity: State<DeviceEntity>,
     app_handle: AppHandle,
-) -> bool {
-    if let Ok(mut device) = device_entity.port.lock() {
-        if let Some(device) = device.as_mut() {
-            if clear_output_buffer_of_device(device) {
-                let mut bytes_to_send = vec![];
-                if let Ok(device_calib_optional) = device_entity.device_calib.lock() {
-                    if let Some(device_calib) = &*device_calib_optional {
-                        bytes_to_send = get_bytes_to_send_for_calib_change(device_calib, &cells);
-                    }
-                }
-                if bytes_to_send.is_empty() {
-                    return false;
-                }
-                let send_result = send_bytes_to_device(device, &[b'H', b'W'], &app_handle);
-                if send_result {
-                    let mut buffer = vec![];
-                    read_bytes_till_3e_from_device_to_buffer(device, &mut buffer, &app_handle);
-                    clear_output_buffer_of_device(device);
-                    if buffer.len() == 0 {
-                        let send_changes_result =
-                            send_bytes_to_device(device, &bytes_to_send, &app_handle);
-                        if send_changes_result {
-                            let mut buffer2 = vec![];
-                            read_bytes_till_3e_from_device_to_buffer(
-                                device,
-                                &mut buffer2,
-                                &app_handle,
-                            );
-                            return buffer2.len() == 0;
-                        }
-                    }
-                }
-            }
-        }
+) -> Result<(), CcToolError> {
+    let _context = enter_command_context("set_device_calib");
+    let session = device_entity.session(session_id.as_deref())?;
+    let device_calib = session
+        .device_calib
+        .lock()?
+        .clone()
+        .ok_or(CcToolError::ConfigNotRead)?;
+    return session.port.transact(move |device| {
+        set_device_calib_on_device(device, &device_calib, &cells, Some(&app_handle))
+    });
+}
+
+/// This function writes the cells that differ from `device_calib` to the calibration memory of the device,
+/// using the `HW` command.
+/// # Arguments
+/// * `device` - The transport connected to the device
+/// * `device_calib` - The calibration last read from the device
+/// * `cells` - The new calibration cells
+/// * `app_handle` - An optional Tauri application handle, used for emitting the exchanged bytes
+///
+/// # Returns
+/// An `Ok(())` if the device calibration was set, `CcToolError::ValidationFailed` if a changed cell
+/// is outside of the range allowed by the RMD file, or the error that occurred while writing it.
+pub fn set_device_calib_on_device(
+    device: &mut dyn Transport,
+    device_calib: &MkDeviceCalib,
+    cells: &[MkDeviceCell],
+    app_handle: Option<&AppHandle>,
+) -> Result<(), CcToolError> {
+    validate_changed_cells(&device_calib.calibration_cells, cells)?;
+    let bytes_to_send = get_bytes_to_send_for_calib_change(device_calib, cells);
+    if bytes_to_send.is_empty() {
+        return Err(CcToolError::InvalidInput {
+            message: "No calibration cells were changed".to_string(),
+        });
     }
-    return false;
+    clear_output_buffer_of_device(device)?;
+    send_bytes_to_device(device, &[b'H', b'W'], app_handle)?;
+    expect_prompt_from_device(device, &FrameSpec::prompt_only(), app_handle)?;
+    clear_output_buffer_of_device(device)?;
+    send_bytes_to_device(device, &bytes_to_send, app_handle)?;
+    return expect_prompt_from_device(
+        device,
+        &FrameSpec::prompt_only().with_deadline(MEMORY_WRITE_DEADLINE),
+        app_handle,
+    );
 }
 
 /// This function sends a factory reset command to the connected serial device.
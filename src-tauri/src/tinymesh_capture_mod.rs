@@ -0,0 +1,148 @@
+//! This module contains the Tauri commands for capturing the traffic exchanged with the devices to a file
+//! and replaying such captures. The capture format is described in the `traffic_capture` module.
+
+use crate::cc_tool_error::CcToolError;
+use crate::data_types::{DeviceEntity, DeviceSession, EventKind, EventPayload};
+use crate::tinymesh_serial_util::tear_down_session;
+use crate::traffic_capture::{
+    read_capture_file, CaptureDirection, ReplayTransport, TrafficCapture,
+};
+use log::{error, info};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Manager, State};
+
+/// Prefix of the session IDs of replayed captures
+pub const REPLAY_SESSION_PREFIX: &str = "replay://";
+
+/// Name of the task replaying a capture to the UI
+pub const UI_REPLAY_TASK: &str = "capture_replay";
+
+/// Where a capture is replayed to
+#[derive(Clone, Copy, Debug, PartialEq, serde::Deserialize)]
+pub enum ReplayTarget {
+    /// The chunks are emitted as `exchange_bytes_event`, as if they were exchanged again
+    Ui,
+    /// A session is opened whose device plays back the RX chunks in answer to the commands sent to it
+    Session,
+}
+
+/// This function starts capturing every TX and RX chunk to a JSON-lines file.
+/// A running capture is stopped first.
+/// # Arguments
+/// * `file_path` - The path of the capture file, which is replaced if it exists
+/// * `capture` - The traffic capture (provided by Tauri)
+///
+/// # Returns
+/// An `Ok(())` if the capture was started, or an error if the file could not be created.
+#[tauri::command]
+pub fn start_capture(file_path: String, capture: State<TrafficCapture>) -> Result<(), CcToolError> {
+    info!("Starting traffic capture to {}", file_path);
+    return capture.start(Path::new(&file_path));
+}
+
+/// This function stops the running traffic capture.
+/// # Arguments
+/// * `capture` - The traffic capture (provided by Tauri)
+///
+/// # Returns
+/// The number of records written, or `None` if no capture was running.
+#[tauri::command]
+pub fn stop_capture(capture: State<TrafficCapture>) -> Result<Option<usize>, CcToolError> {
+    let Some((path, record_count)) = capture.stop()? else {
+        return Ok(None);
+    };
+    info!(
+        "Stopped traffic capture to {} ({} records)",
+        path.display(),
+        record_count
+    );
+    return Ok(Some(record_count));
+}
+
+/// This function replays a capture file.
+/// # Arguments
+/// * `file_path` - The path of the capture file
+/// * `target` - `Ui` to emit the chunks as `exchange_bytes_event` again (in a `capture_replay` task of the
+///   default session, which `cancel_task` stops), `Session` to open a `replay://<file name>` session
+///   (the new default session) whose device answers the commands with the captured RX chunks
+/// * `session_id` - Only the chunks of this session are replayed. If `None`, all chunks are replayed.
+/// * `speed` - For `Ui`: the playback speed relative to the capture (`1.0` keeps the captured gaps between chunks).
+///   If `None`, the chunks are emitted without delay.
+/// * `device_entity` - The state of the program (provided by Tauri)
+/// * `app_handle` - The Tauri application handle (provided by Tauri)
+///
+/// # Returns
+/// The number of replayed records, or an error if the capture file could not be read
+/// (or, for `Ui`, if no session is connected).
+#[tauri::command]
+pub fn replay_capture(
+    file_path: String,
+    target: ReplayTarget,
+    session_id: Option<String>,
+    speed: Option<f64>,
+    device_entity: State<DeviceEntity>,
+    app_handle: AppHandle,
+) -> Result<usize, CcToolError> {
+    let path = PathBuf::from(&file_path);
+    let records: Vec<_> = read_capture_file(&path)?
+        .into_iter()
+        .filter(|record| session_id.is_none() || record.session_id == session_id)
+        .collect();
+    let record_count = records.len();
+    info!(
+        "Replaying {} records of {} to {:?}",
+        record_count, file_path, target
+    );
+
+    match target {
+        ReplayTarget::Ui => {
+            let session = device_entity.session(None)?;
+            session.tasks.spawn(UI_REPLAY_TASK, move |token| {
+                let mut replayed = 0;
+                let mut previous_timestamp_us =
+                    records.first().map_or(0, |record| record.timestamp_us);
+                for record in records {
+                    if let Some(speed) = speed.filter(|speed| *speed > 0.0) {
+                        let gap_us = record.timestamp_us.saturating_sub(previous_timestamp_us);
+                        let gap = Duration::from_micros((gap_us as f64 / speed) as u64);
+                        if token.wait_timeout(gap) {
+                            return Ok(format!("Cancelled after {} records", replayed));
+                        }
+                    } else if token.is_cancelled() {
+                        return Ok(format!("Cancelled after {} records", replayed));
+                    }
+                    previous_timestamp_us = record.timestamp_us;
+                    let kind = match record.direction {
+                        CaptureDirection::TX => EventKind::TX,
+                        CaptureDirection::RX => EventKind::RX,
+                    };
+                    app_handle
+                        .emit_all(
+                            "exchange_bytes_event",
+                            EventPayload::new(record.session_id, kind, record.data, Instant::now()),
+                        )
+                        .unwrap_or_else(|e| error!("Error emitting: {}", e));
+                    replayed += 1;
+                }
+                return Ok(format!("Replayed {} records", replayed));
+            })?;
+        }
+        ReplayTarget::Session => {
+            let file_name = path
+                .file_name()
+                .map(|file_name| file_name.to_string_lossy().to_string())
+                .unwrap_or(file_path);
+            let replay_session_id = format!("{}{}", REPLAY_SESSION_PREFIX, file_name);
+            let device = ReplayTransport::new(&replay_session_id, records, None);
+            if let Some(session) = device_entity.remove_session(Some(&replay_session_id))? {
+                tear_down_session(&session)?;
+            }
+            device_entity.insert_session(
+                &replay_session_id,
+                DeviceSession::new(Box::new(device), None, Some(app_handle))?,
+            )?;
+        }
+    }
+    return Ok(record_count);
+}
@@ -1,89 +1,136 @@
 //! This module contains functions related to background communication with TinyMesh devices.
 //! These functions are used by the Tauri frontend for processing background communication.
+//! The bytes are received by the actor owning the port, between the transactions of other commands,
+//! so the communication task doesn't have to be stopped around them.
+use crate::cc_tool_error::CcToolError;
 use crate::data_types::DeviceEntity;
-use crate::tinymesh_serial_util::read_bytes_from_device_to_buffer;
+use crate::rx_packetizer::{FrameRule, PacketizerConfig};
+use crate::task_manager::{TaskId, TASK_SHUTDOWN_TIMEOUT};
 
+use log::info;
 use std::time::Duration;
-use tauri::{AppHandle, State};
+use tauri::State;
 
-/// This function starts the background communication task.
-/// It checks if the task is already running and starts it if it isn't.
-/// It also sets the `is_communication_task_running` flag to `true` to indicate that the task is running.
-/// It adds the running task to the `communication_task` field of the `DeviceEntity` state.
+/// Name of the task streaming the received bytes of a session
+pub const RX_STREAM_TASK: &str = "rx_stream";
+
+/// How often the RX streaming task checks whether the actor is still streaming
+const RX_STREAM_CHECK_INTERVAL: Duration = Duration::from_millis(500);
+
+/// This function starts the background communication task (`rx_stream`).
+/// It enables passive RX on the actor owning the port of the session: all bytes received between
+/// transactions are emitted as `exchange_bytes_event`. Passive RX is disabled again when the task is cancelled.
 /// # Arguments
+/// * `session_id` - The ID of the session. If `None`, the default session is used.
 /// * `device_entity` - The state of the program (provided by Tauri)
-/// * `app_handle` - The Tauri application handle (provided by Tauri)
 ///
 /// # Returns
-/// A boolean value indicating whether the communication task was started successfully.
+/// The ID of the task (of the already running one, if the communication task is already running),
+/// or an error if the device is not connected.
 #[tauri::command]
-pub fn start_communication_task(device_entity: State<DeviceEntity>, app_handle: AppHandle) -> bool {
-    if let Ok(mut device) = device_entity.port.lock() {
-        if let Some(device) = device.as_mut() {
-            if let Ok(mut cloned_device) = device.try_clone() {
-                let is_communication_task_running =
-                    device_entity.is_communication_task_running.clone();
-                if let Ok(mut is_communication_task_running) = is_communication_task_running.lock()
-                {
-                    if !*is_communication_task_running {
-                        *is_communication_task_running = true;
-                    } else {
-                        return true;
-                    }
-                }
-                let stream = tauri::async_runtime::spawn(async move {
-                    // info!("Starting communication task");
-                    loop {
-                        std::thread::sleep(Duration::from_millis(100));
-                        if let Ok(is_communication_task_running) =
-                            is_communication_task_running.lock()
-                        {
-                            if !*is_communication_task_running {
-                                // info!("Stopping communication task");
-                                return;
-                            }
-                        }
-                        read_bytes_from_device_to_buffer(
-                            &mut cloned_device,
-                            &mut Vec::new(),
-                            &app_handle,
-                        );
-                    }
+pub fn start_communication_task(
+    session_id: Option<String>,
+    device_entity: State<DeviceEntity>,
+) -> Result<TaskId, CcToolError> {
+    let session = device_entity.session(session_id.as_deref())?;
+    if !session.port.is_open()? {
+        return Err(CcToolError::NotConnected);
+    }
+    if let Some(task_id) = session.tasks.find_running(RX_STREAM_TASK)? {
+        return Ok(task_id);
+    }
+    let port = session.port.clone();
+    port.set_passive_rx(true)?;
+    return session.tasks.spawn(RX_STREAM_TASK, move |token| {
+        while !token.wait_timeout(RX_STREAM_CHECK_INTERVAL) {
+            // The actor stops streaming by itself when the port can't be read anymore
+            if !port.is_passive_rx_enabled()? {
+                return Err(CcToolError::Io {
+                    message: "The port can't be read anymore".to_string(),
                 });
-                if let Ok(mut communication_task) = device_entity.communication_task.lock() {
-                    *communication_task = Some(stream);
-                    return true;
-                }
             }
         }
-    }
-    return false;
+        info!("Stopping RX stream");
+        port.set_passive_rx(false)?;
+        return Ok("Stopped".to_string());
+    });
 }
 
 /// This function stops the background communication task.
-/// It checks if the task is running and stops it if it is.
-/// It also sets the `is_communication_task_running` flag to `false` to indicate that the task is not running.
-/// It removes the running task from the `communication_task` field of the `DeviceEntity` state.
+/// It cancels the `rx_stream` task and waits until passive RX is disabled on the actor owning the port,
+/// so the bytes received afterwards are left for the following commands.
 /// # Arguments
+/// * `session_id` - The ID of the session. If `None`, the default session is used.
 /// * `device_entity` - The state of the program (provided by Tauri)
 ///
 /// # Returns
-/// A boolean value indicating whether the communication task was stopped successfully.
+/// An `Ok(())` if the communication task was stopped (or was not running),
+/// or an error if the program state could not be accessed.
 #[tauri::command]
-pub fn stop_communication_task(device_entity: State<DeviceEntity>) -> bool {
-    if let Ok(mut communication_task) = device_entity.communication_task.lock() {
-        if let Some(communication_task) = communication_task.as_mut() {
-            if let Ok(mut is_communication_task_running) =
-                device_entity.is_communication_task_running.lock()
-            {
-                if *is_communication_task_running {
-                    *is_communication_task_running = false;
-                }
-            }
-            communication_task.abort();
+pub fn stop_communication_task(
+    session_id: Option<String>,
+    device_entity: State<DeviceEntity>,
+) -> Result<(), CcToolError> {
+    let session = device_entity.session(session_id.as_deref())?;
+    if let Some(task_id) = session.tasks.find_running(RX_STREAM_TASK)? {
+        session.tasks.cancel(task_id)?;
+        session.tasks.wait(task_id, TASK_SHUTDOWN_TIMEOUT)?;
+    }
+    return session.port.set_passive_rx(false);
+}
+
+/// This function changes how the received bytes of a session are grouped into frames.
+/// Every frame is emitted as a single `exchange_bytes_event`, both by the communication task and by `read_bytes`.
+/// # Arguments
+/// * `config` - The frame rule (inter-byte gap, fixed length, delimiter or TinyMesh packet),
+///   the inter-byte timeout and the maximum frame length
+/// * `session_id` - The ID of the session. If `None`, the default session is used.
+/// * `device_entity` - The state of the program (provided by Tauri)
+///
+/// # Returns
+/// An `Ok(())` if the packetizer was changed, or `CcToolError::InvalidInput` if the settings can't form a frame.
+#[tauri::command]
+pub fn set_rx_packetizer(
+    config: PacketizerConfig,
+    session_id: Option<String>,
+    device_entity: State<DeviceEntity>,
+) -> Result<(), CcToolError> {
+    if config.inter_byte_timeout_ms == 0 || config.max_frame_len == 0 {
+        return Err(CcToolError::InvalidInput {
+            message: "The inter-byte timeout and the maximum frame length must not be 0"
+                .to_string(),
+        });
+    }
+    match &config.rule {
+        FrameRule::FixedLength { length: 0 } => {
+            return Err(CcToolError::InvalidInput {
+                message: "The frame length must not be 0".to_string(),
+            });
         }
-        *communication_task = None;
-        return true;
+        FrameRule::Delimiter { delimiter } if delimiter.is_empty() => {
+            return Err(CcToolError::InvalidInput {
+                message: "The frame delimiter must not be empty".to_string(),
+            });
+        }
+        _ => {}
     }
-    return false;
+    info!("Setting RX packetizer: {:?}", config);
+    let session = device_entity.session(session_id.as_deref())?;
+    return session.port.set_packetizer(config);
+}
+
+/// This function returns how the received bytes of a session are grouped into frames.
+/// # Arguments
+/// * `session_id` - The ID of the session. If `None`, the default session is used.
+/// * `device_entity` - The state of the program (provided by Tauri)
+///
+/// # Returns
+/// The packetizer settings of the session, or an error if the session does not exist.
+#[tauri::command]
+pub fn get_rx_packetizer(
+    session_id: Option<String>,
+    device_entity: State<DeviceEntity>,
+) -> Result<PacketizerConfig, CcToolError> {
+    let session = device_entity.session(session_id.as_deref())?;
+    return session.port.packetizer();
 }
@@ -1,19 +1,29 @@
 //! This module contains functions related to getting and setting the device configuration.
 //! These functions are used by the Tauri frontend's configuration tab.
 
+use crate::cc_tool_error::CcToolError;
 use crate::data_types::{DeviceEntity, MkDeviceCell, MkDeviceConfig};
 use crate::device_config_parser::parse_device_config;
+use crate::mode_sequence::{parse_mode_sequence, run_mode_sequence, SequenceStepTrace};
 use crate::tinymesh_serial_util::{
-    clear_output_buffer_of_device, read_bytes_from_device_to_buffer,
-    read_bytes_till_3e_from_device_to_buffer, send_bytes_to_device,
+    clear_output_buffer_of_device, expect_prompt_from_device, read_frame_from_device,
+    send_bytes_to_device, FrameSpec,
 };
-use serialport::SerialPort;
+use crate::tinymesh_test_mode_mod::execute_mode_sequence_in_session;
+use crate::traffic_capture::enter_command_context;
+use crate::transport::Transport;
+use std::path::Path;
+use std::time::Duration;
 use tauri::{AppHandle, State};
 
+/// Deadline for the prompt after commands that write to flash (config writes, factory reset).
+pub const MEMORY_WRITE_DEADLINE: Duration = Duration::from_millis(5000);
+
 /// This function gets the device configuration from the connected serial device.
 /// It will read the configuration from the device, match it with corresponding module description RMD file
 /// and return it as a `MkDeviceConfig` struct.
 /// # Arguments
+/// * `session_id` - The ID of the session. If `None`, the default session is used.
 /// * `device_entity` - The state of the program (provided by Tauri)
 /// * `app_handle` - The Tauri application handle (provided by Tauri)
 ///
@@ -22,46 +32,51 @@ use tauri::{AppHandle, State};
 /// Returns an error if the configuration could not be retrieved or matched.
 #[tauri::command]
 pub fn get_device_config(
+    session_id: Option<String>,
     device_entity: State<DeviceEntity>,
     app_handle: AppHandle,
-) -> Result<MkDeviceConfig, String> {
-    let mut device = device_entity.port.lock().map_err(|err| err.to_string())?;
-    let device = device
-        .as_mut()
-        .ok_or("Could not lock the selected device".to_string())?;
-    let device_config = get_device_config_from_device(device, &app_handle)?;
-
-    let mut device_config_from_state = device_entity
-        .device_config
-        .lock()
-        .map_err(|err| err.to_string())?;
+) -> Result<MkDeviceConfig, CcToolError> {
+    let _context = enter_command_context("get_device_config");
+    let session = device_entity.session(session_id.as_deref())?;
+    let device_config = session
+        .port
+        .transact(move |device| get_device_config_from_device(device, None, Some(&app_handle)))?;
+
+    let mut device_config_from_state = session.device_config.lock()?;
     let cloned_config = device_config.clone();
     *device_config_from_state = Some(cloned_config);
+    session.update_packet_mode(Some(&device_config))?;
 
     // info!("\ntinymesh_config_mod::get_device_config---> device_config = {:?}\n", device_config);
     return Ok(device_config);
 }
 
-
+/// This function reads the configuration memory of the device and decodes it.
+/// # Arguments
+/// * `device` - The transport connected to the device
+/// * `rmd_file_path` - An optional path to the RMD file. If `None`, the RMD file matching the device model is used.
+/// * `app_handle` - An optional Tauri application handle, used for emitting the exchanged bytes
+///
+/// # Returns
+/// A `MkDeviceConfig` struct containing the device configuration, or a `CcToolError`.
 pub fn get_device_config_from_device(
-    device: &mut Box<dyn SerialPort>,
-    app_handle: &AppHandle,
-) -> Result<MkDeviceConfig, String> {
+    device: &mut dyn Transport,
+    rmd_file_path: Option<&Path>,
+    app_handle: Option<&AppHandle>,
+) -> Result<MkDeviceConfig, CcToolError> {
     // info!("\nget_device_config_from_device::config_bytes_buffer(device, app_handle)\n");
 
-    let mut config_bytes_buffer = vec![];
-    if clear_output_buffer_of_device(device) && send_bytes_to_device(device, &[0x30], &app_handle) {
-        // info!("\nCONFIG_DEVICE ==== {:?}\n", device);
+    clear_output_buffer_of_device(device)?;
+    send_bytes_to_device(device, &[0x30], app_handle)?;
+    // info!("\nCONFIG_DEVICE ==== {:?}\n", device);
 
-        read_bytes_till_3e_from_device_to_buffer(device, &mut config_bytes_buffer, &app_handle);
-        // info!("\nget_device_config_from_device::config_bytes_buffer, {:?}\n", config_bytes_buffer);
+    let config_bytes_buffer = read_frame_from_device(device, &FrameSpec::memory_dump(), app_handle)?;
+    // info!("\nget_device_config_from_device::config_bytes_buffer, {:?}\n", config_bytes_buffer);
 
-        let device_config = parse_device_config(&config_bytes_buffer, None, Some(&app_handle))?;
-        // info!("\nget_device_config_from_device::device_config, {:?}\n", device_config);
+    let device_config = parse_device_config(&config_bytes_buffer, rmd_file_path, app_handle)?;
+    // info!("\nget_device_config_from_device::device_config, {:?}\n", device_config);
 
-        return Ok(device_config);
-    }
-    return Err("Unable to get config. Looks like sending bytes failed.".to_string());
+    return Ok(device_config);
 }
 
 /// This function sets the device configuration in the connected serial device.
@@ -69,77 +84,151 @@ pub fn get_device_config_from_device(
 /// It only sends the new configuration to the device.
 /// # Arguments
 /// * `cells` - A vector of `MkDeviceCell` structs containing the new device configuration
+/// * `session_id` - The ID of the session. If `None`, the default session is used.
 /// * `device_entity` - The state of the program (provided by Tauri)
 /// * `app_handle` - The Tauri application handle (provided by Tauri)
 ///
 /// # Returns
-/// A boolean value indicating whether the device configuration was set successfully.
+/// An `Ok(())` if the device configuration was set, `CcToolError::ConfigNotRead` if the configuration
+/// was not read from the device before, or the error that occurred while writing it.
 #[tauri::command]
 pub fn set_device_config(
     cells: Vec<MkDeviceCell>,
+    session_id: Option<String>,
     device_entity: State<DeviceEntity>,
     app_handle: AppHandle,
-) -> bool {
-    if let Ok(mut device) = device_entity.port.lock() {
-        if let Some(device) = device.as_mut() {
-            if clear_output_buffer_of_device(device) {
-                let mut bytes_to_send = vec![];
-                if let Ok(device_config_optional) = device_entity.device_config.lock() {
-                    if let Some(device_config) = &*device_config_optional {
-                        bytes_to_send = get_bytes_to_send_for_config_change(device_config, &cells);
-                    }
-                }
-                if bytes_to_send.is_empty() {
-                    return false;
-                }
-                let send_result = send_bytes_to_device(device, &[b'M'], &app_handle);
-                if send_result {
-                    let mut buffer = vec![];
-                    read_bytes_till_3e_from_device_to_buffer(device, &mut buffer, &app_handle);
-                    clear_output_buffer_of_device(device);
-                    if buffer.len() == 0 {
-                        let send_changes_result =
-                            send_bytes_to_device(device, &bytes_to_send, &app_handle);
-                        if send_changes_result {
-                            let mut buffer2 = vec![];
-                            read_bytes_till_3e_from_device_to_buffer(
-                                device,
-                                &mut buffer2,
-                                &app_handle,
-                            );
-                            return buffer2.len() == 0;
-                        }
-                    }
-                }
+) -> Result<(), CcToolError> {
+    let _context = enter_command_context("set_device_config");
+    let session = device_entity.session(session_id.as_deref())?;
+    let device_config = session
+        .device_config
+        .lock()?
+        .clone()
+        .ok_or(CcToolError::ConfigNotRead)?;
+    return session.port.transact(move |device| {
+        set_device_config_on_device(device, &device_config, &cells, Some(&app_handle))
+    });
+}
+
+/// This function writes the cells that differ from `device_config` to the configuration memory of the device,
+/// using the `M` command.
+/// # Arguments
+/// * `device` - The transport connected to the device
+/// * `device_config` - The configuration last read from the device
+/// * `cells` - The new configuration cells
+/// * `app_handle` - An optional Tauri application handle, used for emitting the exchanged bytes
+///
+/// # Returns
+/// An `Ok(())` if the device configuration was set, `CcToolError::ValidationFailed` if a changed cell
+/// is outside of the range allowed by the RMD file, or the error that occurred while writing it.
+pub fn set_device_config_on_device(
+    device: &mut dyn Transport,
+    device_config: &MkDeviceConfig,
+    cells: &[MkDeviceCell],
+    app_handle: Option<&AppHandle>,
+) -> Result<(), CcToolError> {
+    validate_changed_cells(&device_config.cells, cells)?;
+    let bytes_to_send = get_bytes_to_send_for_config_change(device_config, cells);
+    if bytes_to_send.is_empty() {
+        return Err(CcToolError::InvalidInput {
+            message: "No configuration cells were changed".to_string(),
+        });
+    }
+    clear_output_buffer_of_device(device)?;
+    send_bytes_to_device(device, &[b'M'], app_handle)?;
+    expect_prompt_from_device(device, &FrameSpec::prompt_only(), app_handle)?;
+    clear_output_buffer_of_device(device)?;
+    send_bytes_to_device(device, &bytes_to_send, app_handle)?;
+    // Writing the configuration memory involves a flash write, which takes a while
+    return expect_prompt_from_device(
+        device,
+        &FrameSpec::prompt_only().with_deadline(MEMORY_WRITE_DEADLINE),
+        app_handle,
+    );
+}
+
+/// Checks the cells that differ from the ones last read from the device against the
+/// allowed values (or, if there are none, the minimum and maximum value) of the RMD file.
+/// # Arguments
+/// * `current_cells` - The cells last read from the device
+/// * `cells` - The new cells
+///
+/// # Returns
+/// An `Ok(())` if all changed values are allowed, or `CcToolError::ValidationFailed` for the first invalid cell.
+pub fn validate_changed_cells(
+    current_cells: &[MkDeviceCell],
+    cells: &[MkDeviceCell],
+) -> Result<(), CcToolError> {
+    for (cell, current_cell) in cells.iter().zip(current_cells) {
+        if cell.current_value == current_cell.current_value {
+            continue;
+        }
+        let value = cell.current_value;
+        if !current_cell.allowed_values.is_empty() {
+            if !current_cell.allowed_values.contains(&value) {
+                return Err(CcToolError::ValidationFailed {
+                    address: cell.address,
+                    message: format!(
+                        "{} is not one of the allowed values {:?}",
+                        value, current_cell.allowed_values
+                    ),
+                });
             }
+        } else if current_cell.min_value <= current_cell.max_value
+            && (current_cell.min_value, current_cell.max_value) != (0, 0)
+            && (value < current_cell.min_value || value > current_cell.max_value)
+        {
+            return Err(CcToolError::ValidationFailed {
+                address: cell.address,
+                message: format!(
+                    "{} is outside of the range {}..={}",
+                    value, current_cell.min_value, current_cell.max_value
+                ),
+            });
         }
     }
-    return false;
+    return Ok(());
 }
 
 /// This function sends a factory reset command to the connected serial device.
 /// # Arguments
+/// * `session_id` - The ID of the session. If `None`, the default session is used.
 /// * `device_entity` - The state of the program (provided by Tauri)
 /// * `app_handle` - The Tauri application handle (provided by Tauri)
 ///
 /// # Returns
-/// A boolean value indicating whether the factory reset command was successful.
+/// An `Ok(())` if the factory reset command was successful, or the error that occurred.
 #[tauri::command]
-pub fn factory_reset(device_entity: State<DeviceEntity>, app_handle: AppHandle) -> bool {
-    if let Ok(mut device) = device_entity.port.lock() {
-        if let Some(device) = device.as_mut() {
-            clear_output_buffer_of_device(device);
-            let send_result = send_bytes_to_device(device, &[b'@', b'T', b'M'], &app_handle);
-
-            if send_result {
-                let mut buffer = vec![];
-                read_bytes_till_3e_from_device_to_buffer(device, &mut buffer, &app_handle);
+pub fn factory_reset(
+    session_id: Option<String>,
+    device_entity: State<DeviceEntity>,
+    app_handle: AppHandle,
+) -> Result<(), CcToolError> {
+    let _context = enter_command_context("factory_reset");
+    let session = device_entity.session(session_id.as_deref())?;
+    return session
+        .port
+        .transact(move |device| factory_reset_device(device, Some(&app_handle)));
+}
 
-                return buffer.len() == 0;
-            }
-        }
-    }
-    return false;
+/// This function sends the `@TM` factory reset command to the device.
+/// # Arguments
+/// * `device` - The transport connected to the device
+/// * `app_handle` - An optional Tauri application handle, used for emitting the exchanged bytes
+///
+/// # Returns
+/// An `Ok(())` if the factory reset command was successful, or the error that occurred.
+pub fn factory_reset_device(
+    device: &mut dyn Transport,
+    app_handle: Option<&AppHandle>,
+) -> Result<(), CcToolError> {
+    clear_output_buffer_of_device(device)?;
+    send_bytes_to_device(device, &[b'@', b'T', b'M'], app_handle)?;
+    return expect_prompt_from_device(
+        device,
+        &FrameSpec::prompt_only().with_deadline(MEMORY_WRITE_DEADLINE),
+        app_handle,
+    );
 }
 
 fn get_bytes_to_send_for_config_change(
@@ -160,56 +249,52 @@ fn get_bytes_to_send_for_config_change(
 }
 
 /// This function executes a mode sequence on the connected serial device.
-/// It will send the input bytes of the sequence to the device,
-/// and match the device's output to the expected sequence.
+/// It will send the input bytes of every step of the sequence to the device,
+/// and match the device's output to the expected bytes of the step.
 /// For example: The sequence string: `aG #>` means that we should
 /// send bytes `G` to the device and expect to receive `>`.
+/// Steps without an expected answer (e.g. `a6`, which starts a sniffer test mode) are only sent,
+/// and an empty sequence (e.g. a missing `SEQUENCE_OFF`) does nothing.
+/// See `mode_sequence` for delays, escapes, optional and regular expression expectations, timeouts and repeats.
+/// If the sequence starts the packet or RSSI sniffer test mode, its output is parsed into `sniffer_event`s
+/// or `rssi_sniffer_event`s until another sequence is executed, if enabled with `set_sniffer_output_parsing`.
+/// If the sequence starts a test or quick mode of the RMD file, the `SEQUENCE_OFF` of the previously
+/// started mode is executed first (see `tinymesh_test_mode_mod`).
 /// # Arguments
 /// * `sequence_str` - The mode sequence to execute.
+/// * `session_id` - The ID of the session. If `None`, the default session is used.
 /// * `device_entity` - The state of the program (provided by Tauri)
 /// * `app_handle` - The Tauri application handle (provided by Tauri)
 ///
 /// # Returns
-/// A boolean indicating whether the mode sequence was executed successfully.
+/// The trace of the executed steps if the device answered as expected, `CcToolError::InvalidInput` if the
+/// sequence could not be parsed, or `CcToolError::UnexpectedResponse` if the device answered differently.
 #[tauri::command]
 pub fn execute_mode_sequence(
     sequence_str: String,
+    session_id: Option<String>,
     device_entity: State<DeviceEntity>,
     app_handle: AppHandle,
-) -> bool {
-    let mut recv_buffer = vec![];
-    if let Ok(mut device) = device_entity.port.lock() {
-        if let Some(device) = device.as_mut() {
-            clear_output_buffer_of_device(device);
-            if let Some((send_seq, recv_seq)) = extract_send_recv_seq(&sequence_str) {
-                let send_result = send_bytes_to_device(device, &send_seq, &app_handle);
-                if recv_seq.ends_with(&[b'>']) {
-                    read_bytes_till_3e_from_device_to_buffer(device, &mut recv_buffer, &app_handle);
-                    if send_result && recv_buffer == recv_seq[..recv_seq.len() - 1] {
-                        return true;
-                    }
-                } else {
-                    read_bytes_from_device_to_buffer(device, &mut recv_buffer, &app_handle);
-                    if send_result && recv_buffer == recv_seq {
-                        return true;
-                    }
-                }
-            }
-        }
-    }
-    return false;
+) -> Result<Vec<SequenceStepTrace>, CcToolError> {
+    let _context = enter_command_context("execute_mode_sequence");
+    let session = device_entity.session(session_id.as_deref())?;
+    return execute_mode_sequence_in_session(&session, &sequence_str, Some(&app_handle));
 }
 
-fn extract_send_recv_seq(sequence_str: &str) -> Option<(Vec<u8>, Vec<u8>)> {
-    if let [send_seq, recv_seq] = sequence_str
-        .trim()
-        .split_whitespace()
-        .collect::<Vec<&str>>()
-        .as_slice()
-    {
-        let send = send_seq.trim_start_matches('a').as_bytes().to_vec();
-        let recv = recv_seq.trim_start_matches('#').as_bytes().to_vec();
-        return Some((send, recv));
-    }
-    None
+/// This function parses a mode sequence and executes its steps on the device.
+/// # Arguments
+/// * `device` - The transport connected to the device
+/// * `sequence_str` - The mode sequence to execute, e.g. `aG #>`
+/// * `app_handle` - An optional Tauri application handle, used for emitting the exchanged bytes
+///
+/// # Returns
+/// The trace of the executed steps if the device answered as expected, `CcToolError::InvalidInput` if the
+/// sequence could not be parsed, or `CcToolError::UnexpectedResponse` if the device answered differently.
+pub fn execute_mode_sequence_on_device(
+    device: &mut dyn Transport,
+    sequence_str: &str,
+    app_handle: Option<&AppHandle>,
+) -> Result<Vec<SequenceStepTrace>, CcToolError> {
+    let steps = parse_mode_sequence(sequence_str)?;
+    return run_mode_sequence(device, &steps, app_handle);
 }
@@ -1,335 +1,353 @@
 //! This module contains functions for getting information about the connected TinyMesh device.
 //! These functions are used in the Tauri frontend's device info tab.
 
+use crate::cc_tool_error::CcToolError;
 use crate::data_types::DeviceEntity;
+use crate::task_manager::{TaskId, TASK_SHUTDOWN_TIMEOUT};
 use crate::tinymesh_config_mod::get_device_config_from_device;
 use crate::tinymesh_serial_util::{
-    clear_output_buffer_of_device, read_bytes_till_3e_from_device_to_buffer, send_bytes_to_device,
+    clear_output_buffer_of_device, expect_prompt_from_device, read_frame_from_device,
+    send_bytes_to_device, FrameSpec,
 };
-use log::info;
-use serialport::SerialPort;
+use crate::traffic_capture::enter_command_context;
+use crate::transport::Transport;
+use log::{error, info};
 use tauri::{AppHandle, Manager, State};
 
 /// This function gets the RSSI value from the connected serial device for the current channel.
 /// # Arguments
+/// * `session_id` - The ID of the session. If `None`, the default session is used.
 /// * `device_entity` - The state of the program (provided by Tauri)
 /// * `app_handle` - The Tauri application handle (provided by Tauri)
 ///
 /// # Returns
-/// A string containing the RSSI value in dBm or an error message if the RSSI value could not be read.
+/// A string containing the RSSI value in dBm, scaled with the `RSSI_OFFSET` and `RSSI_SCALE_FACTOR` of the
+/// RMD file like the RSSI stream, or an error if the RSSI value could not be read.
 #[tauri::command]
-pub fn get_device_rssi(device_entity: State<DeviceEntity>, app_handle: AppHandle) -> String {
-    if let Ok(mut device) = device_entity.port.lock() {
-        if let Some(device) = device.as_mut() {
-            clear_output_buffer_of_device(device);
-            if let Ok(result) = get_rssi_from_device(device, &app_handle) {
-                return format!(
-                    "RSSI: -{} dBm, DEC: {}",
-                    ((result as f64) * 0.5) as f64,
-                    result
-                );
-            }
-        }
-    }
-    return "RSSI: [UNABLE TO READ]".to_string();
+pub fn get_device_rssi(
+    session_id: Option<String>,
+    device_entity: State<DeviceEntity>,
+    app_handle: AppHandle,
+) -> Result<String, CcToolError> {
+    let _context = enter_command_context("get_device_rssi");
+    let session = device_entity.session(session_id.as_deref())?;
+    let rssi_scaling = session
+        .device_config
+        .lock()?
+        .as_ref()
+        .map(|device_config| device_config.rssi_scaling)
+        .unwrap_or_default();
+    let result = session.port.transact(move |device| {
+        clear_output_buffer_of_device(device)?;
+        get_rssi_from_device(device, Some(&app_handle))
+    })?;
+    return Ok(format!(
+        "RSSI: {} dBm, DEC: {}",
+        rssi_scaling.to_dbm(result as i32),
+        result
+    ));
 }
 
 /// This function gets the analog pins from the connected serial device.
 /// # Arguments
+/// * `session_id` - The ID of the session. If `None`, the default session is used.
 /// * `device_entity` - The state of the program (provided by Tauri)
 /// * `app_handle` - The Tauri application handle (provided by Tauri)
 ///
 /// # Returns
-/// A string containing the analog pin values or an error message if the analog values could not be read.
+/// A string containing the analog pin values or an error if the analog values could not be read.
 #[tauri::command]
-pub fn get_device_analog(device_entity: State<DeviceEntity>, app_handle: AppHandle) -> String {
-    if let Ok(mut device) = device_entity.port.lock() {
-        if let Some(device) = device.as_mut() {
-            clear_output_buffer_of_device(device);
-            if let Ok(analog) = get_analog_from_device(device, &app_handle) {
-                let result_str = analog
-                    .iter()
-                    .map(|byte| format!("{:02X}", byte))
-                    .collect::<Vec<String>>()
-                    .join(" ");
-                return format!("Analog: [{}]", result_str);
-            }
-        }
-    }
-    return "Analog: [UNABLE TO READ]".to_string();
+pub fn get_device_analog(
+    session_id: Option<String>,
+    device_entity: State<DeviceEntity>,
+    app_handle: AppHandle,
+) -> Result<String, CcToolError> {
+    let _context = enter_command_context("get_device_analog");
+    let session = device_entity.session(session_id.as_deref())?;
+    let analog = session.port.transact(move |device| {
+        clear_output_buffer_of_device(device)?;
+        get_analog_from_device(device, Some(&app_handle))
+    })?;
+    let result_str = analog
+        .iter()
+        .map(|byte| format!("{:02X}", byte))
+        .collect::<Vec<String>>()
+        .join(" ");
+    return Ok(format!("Analog: [{}]", result_str));
 }
 
 /// This function gets the digital pins from the connected serial device.
 /// # Arguments
+/// * `session_id` - The ID of the session. If `None`, the default session is used.
 /// * `device_entity` - The state of the program (provided by Tauri)
 /// * `app_handle` - The Tauri application handle (provided by Tauri)
 ///
 /// # Returns
-/// A string containing the digital pin values or an error message if the digital values could not be read.
+/// A string containing the digital pin values or an error if the digital values could not be read.
 #[tauri::command]
-pub fn get_device_digital(device_entity: State<DeviceEntity>, app_handle: AppHandle) -> String {
-    if let Ok(mut device) = device_entity.port.lock() {
-        if let Some(device) = device.as_mut() {
-            clear_output_buffer_of_device(device);
-            if let Ok(digital) = get_digital_from_device(device, &app_handle) {
-                return format!("Digital: {:02X}", digital);
-            }
-        }
-    }
-    return "Digital: [UNABLE TO READ]".to_string();
+pub fn get_device_digital(
+    session_id: Option<String>,
+    device_entity: State<DeviceEntity>,
+    app_handle: AppHandle,
+) -> Result<String, CcToolError> {
+    let _context = enter_command_context("get_device_digital");
+    let session = device_entity.session(session_id.as_deref())?;
+    let digital = session.port.transact(move |device| {
+        clear_output_buffer_of_device(device)?;
+        get_digital_from_device(device, Some(&app_handle))
+    })?;
+    return Ok(format!("Digital: {:02X}", digital));
 }
 
 /// This function gets the temperature from the connected serial device.
 /// # Arguments
+/// * `session_id` - The ID of the session. If `None`, the default session is used.
 /// * `device_entity` - The state of the program (provided by Tauri)
 /// * `app_handle` - The Tauri application handle (provided by Tauri)
 ///
 /// # Returns
-/// A string containing the temperature value or an error message if the temperature value could not be read.
+/// A string containing the temperature value or an error if the temperature value could not be read.
 #[tauri::command]
-pub fn get_device_temperature(device_entity: State<DeviceEntity>, app_handle: AppHandle) -> String {
-    if let Ok(mut device) = device_entity.port.lock() {
-        if let Some(device) = device.as_mut() {
-            clear_output_buffer_of_device(device);
-            if let Ok(temperature_dec) = get_temperature_from_device(device, &app_handle) {
-                return format!("Temperature: {} \u{00B0}C", (temperature_dec as i32) - 128);
-            }
-        }
-    }
-    return "Temperature: [UNABLE TO READ]".to_string();
+pub fn get_device_temperature(
+    session_id: Option<String>,
+    device_entity: State<DeviceEntity>,
+    app_handle: AppHandle,
+) -> Result<String, CcToolError> {
+    let _context = enter_command_context("get_device_temperature");
+    let session = device_entity.session(session_id.as_deref())?;
+    let temperature_dec = session.port.transact(move |device| {
+        clear_output_buffer_of_device(device)?;
+        get_temperature_from_device(device, Some(&app_handle))
+    })?;
+    return Ok(format!(
+        "Temperature: {} \u{00B0}C",
+        (temperature_dec as i32) - 128
+    ));
 }
 
 /// This function gets the power supply voltage from the connected serial device.
 /// # Arguments
+/// * `session_id` - The ID of the session. If `None`, the default session is used.
 /// * `device_entity` - The state of the program (provided by Tauri)
 /// * `app_handle` - The Tauri application handle (provided by Tauri)
 ///
 /// # Returns
-/// A string containing the voltage value or an error message if the voltage value could not be read.
+/// A string containing the voltage value or an error if the voltage value could not be read.
 #[tauri::command]
-pub fn get_device_voltage(device_entity: State<DeviceEntity>, app_handle: AppHandle) -> String {
-    if let Ok(mut device) = device_entity.port.lock() {
-        if let Some(device) = device.as_mut() {
-            clear_output_buffer_of_device(device);
-            if let Ok(voltage) = get_voltage_from_device(device, &app_handle) {
-                return format!("Voltage: {:.2} V", (voltage as f64) * 0.030);
-            }
-        }
-    }
-    return "Voltage: [UNABLE TO READ]".to_string();
+pub fn get_device_voltage(
+    session_id: Option<String>,
+    device_entity: State<DeviceEntity>,
+    app_handle: AppHandle,
+) -> Result<String, CcToolError> {
+    let _context = enter_command_context("get_device_voltage");
+    let session = device_entity.session(session_id.as_deref())?;
+    let voltage = session.port.transact(move |device| {
+        clear_output_buffer_of_device(device)?;
+        get_voltage_from_device(device, Some(&app_handle))
+    })?;
+    return Ok(format!("Voltage: {:.2} V", (voltage as f64) * 0.030));
 }
 
-fn get_rssi_from_device(
-    device: &mut Box<dyn SerialPort>,
-    app_handle: &AppHandle,
-) -> Result<u8, String> {
-    let mut buffer = vec![];
-    let send_result = send_bytes_to_device(device, &[b'S'], app_handle);
-    if send_result {
-        read_bytes_till_3e_from_device_to_buffer(device, &mut buffer, app_handle);
-        if buffer.len() == 1 {
-            return Ok(buffer[0]);
-        }
+/// Sends a telemetry command and reads its single byte response.
+fn get_single_byte_from_device(
+    command: u8,
+    device: &mut dyn Transport,
+    app_handle: Option<&AppHandle>,
+) -> Result<u8, CcToolError> {
+    send_bytes_to_device(device, &[command], app_handle)?;
+    let buffer = read_frame_from_device(device, &FrameSpec::telemetry(Some(1)), app_handle)?;
+    if let [value] = buffer.as_slice() {
+        return Ok(*value);
     }
-    return Err("RSSI: Bad".to_string());
+    return Err(CcToolError::UnexpectedResponse {
+        expected: "a single byte followed by '>'".to_string(),
+        got: buffer,
+    });
 }
 
-fn get_analog_from_device(
-    device: &mut Box<dyn SerialPort>,
-    app_handle: &AppHandle,
-) -> Result<Vec<u8>, String> {
-    let mut buffer = vec![];
-    let send_result = send_bytes_to_device(device, &[b'A'], app_handle);
-    if send_result {
-        read_bytes_till_3e_from_device_to_buffer(device, &mut buffer, app_handle);
-        if buffer.len() > 1 {
-            return Ok(buffer);
-        }
-    }
-    return Err("Analog: [UNABLE TO READ]".to_string());
+/// Reads the raw RSSI byte of the current channel (`S` command).
+pub fn get_rssi_from_device(
+    device: &mut dyn Transport,
+    app_handle: Option<&AppHandle>,
+) -> Result<u8, CcToolError> {
+    return get_single_byte_from_device(b'S', device, app_handle);
 }
 
-fn get_digital_from_device(
-    device: &mut Box<dyn SerialPort>,
-    app_handle: &AppHandle,
-) -> Result<u8, String> {
-    let mut buffer = vec![];
-    let send_result = send_bytes_to_device(device, &[b'D'], app_handle);
-    if send_result {
-        read_bytes_till_3e_from_device_to_buffer(device, &mut buffer, app_handle);
-        if buffer.len() == 1 {
-            return Ok(buffer[0]);
-        }
+/// Reads the raw analog pin bytes (`A` command).
+pub fn get_analog_from_device(
+    device: &mut dyn Transport,
+    app_handle: Option<&AppHandle>,
+) -> Result<Vec<u8>, CcToolError> {
+    send_bytes_to_device(device, &[b'A'], app_handle)?;
+    let buffer = read_frame_from_device(device, &FrameSpec::telemetry(None), app_handle)?;
+    if buffer.len() > 1 {
+        return Ok(buffer);
     }
-    return Err("Digital: [UNABLE TO READ]".to_string());
+    return Err(CcToolError::UnexpectedResponse {
+        expected: "the analog pin values followed by '>'".to_string(),
+        got: buffer,
+    });
 }
 
-fn get_temperature_from_device(
-    device: &mut Box<dyn SerialPort>,
-    app_handle: &AppHandle,
-) -> Result<u8, String> {
-    let mut buffer = vec![];
-    let send_result = send_bytes_to_device(device, &[b'U'], app_handle);
-    if send_result {
-        read_bytes_till_3e_from_device_to_buffer(device, &mut buffer, app_handle);
-        if buffer.len() == 1 {
-            return Ok(buffer[0]);
-        }
-    }
-    return Err("Temperature: [UNABLE TO READ]".to_string());
+/// Reads the digital pin byte (`D` command).
+pub fn get_digital_from_device(
+    device: &mut dyn Transport,
+    app_handle: Option<&AppHandle>,
+) -> Result<u8, CcToolError> {
+    return get_single_byte_from_device(b'D', device, app_handle);
 }
 
-fn get_voltage_from_device(
-    device: &mut Box<dyn SerialPort>,
-    app_handle: &AppHandle,
-) -> Result<u8, String> {
-    let mut buffer = vec![];
-    let send_result = send_bytes_to_device(device, &[b'V'], app_handle);
-    if send_result {
-        read_bytes_till_3e_from_device_to_buffer(device, &mut buffer, app_handle);
-        if buffer.len() == 1 {
-            return Ok(buffer[0]);
-        }
-    }
-    return Err("Voltage: [UNABLE TO READ]".to_string());
+/// Reads the raw temperature byte (`U` command).
+pub fn get_temperature_from_device(
+    device: &mut dyn Transport,
+    app_handle: Option<&AppHandle>,
+) -> Result<u8, CcToolError> {
+    return get_single_byte_from_device(b'U', device, app_handle);
+}
+
+/// Reads the raw power supply voltage byte (`V` command).
+pub fn get_voltage_from_device(
+    device: &mut dyn Transport,
+    app_handle: Option<&AppHandle>,
+) -> Result<u8, CcToolError> {
+    return get_single_byte_from_device(b'V', device, app_handle);
 }
 
 /// This struct contains the data that is emitted as a tauri event in Spectrum Analyzer mode
 #[derive(Clone, serde::Serialize)]
 pub struct RSSIEvent {
+    /// The session the RSSI was measured in
+    pub session_id: Option<String>,
     pub rssi: f64,
     pub channel: u8,
 }
 
-/// This function starts the RSSI stream background process and adds the running task to the `rssi_task` state variable.
-/// It will also set the `is_rssi_task_running` flag.
+/// Name of the task running the RSSI scan of the spectrum analyzer
+pub const RSSI_SCAN_TASK: &str = "rssi_scan";
+
+/// This function starts the RSSI stream background process as a task of the session (`rssi_scan`).
 /// It starts an infinite loop that will circle through all the channels, and read their RSSI.
 /// It will emit an event for each RSSI value that is read.
 ///
 /// # Arguments
+/// * `session_id` - The ID of the session. If `None`, the default session is used.
 /// * `device_entity` - The state of the program (provided by Tauri)
 /// * `app_handle` - The Tauri application handle (provided by Tauri)
+/// # Returns
+/// The ID of the task (of the already running one, if the stream is already running), or an error if the
+/// device configuration needed for the channel range and the RSSI scaling could not be read.
 #[tauri::command]
-pub fn start_rssi_stream(device_entity: State<DeviceEntity>, app_handle: AppHandle) {
+pub fn start_rssi_stream(
+    session_id: Option<String>,
+    device_entity: State<DeviceEntity>,
+    app_handle: AppHandle,
+) -> Result<TaskId, CcToolError> {
+    let _context = enter_command_context("start_rssi_stream");
+    let session = device_entity.session(session_id.as_deref())?;
     // info!("Starting RSSI stream");
-    let device_port = device_entity.port.clone();
-    let device_port_2 = device_entity.port.clone();
-    if let Ok(mut is_rssi_task_running) = device_entity.is_rssi_task_running.lock() {
-        if !*is_rssi_task_running {
-            *is_rssi_task_running = true;
-        } else {
-            return;
-        }
+    let device_port = session.port.clone();
+    if let Some(task_id) = session.tasks.find_running(RSSI_SCAN_TASK)? {
+        return Ok(task_id);
     }
-    let is_rssi_task_running = device_entity.is_rssi_task_running.clone();
     let (mut min_channel, mut max_channel) = (0, 0);
-    if let Ok(mut device_config) = device_entity.device_config.lock() {
-        if let Some(device_config) = device_config.as_ref() {
-            if let Some(channel) = device_config
-                .cells
-                .iter()
-                .find(|cell| cell.name == "RF Channel")
-            {
-                min_channel = channel.min_value as u8;
-                max_channel = channel.max_value as u8;
-            }
-        } else {
-            if let Ok(mut device) = device_port_2.lock() {
-                if let Some(device) = device.as_mut() {
-                    if let Ok(device_config_from_call) =
-                        get_device_config_from_device(device, &app_handle)
-                    {
-                        *device_config = Some(device_config_from_call.clone());
-                        if let Some(channel) = device_config_from_call
-                            .cells
-                            .iter()
-                            .find(|cell| cell.name == "RF Channel")
-                        {
-                            min_channel = channel.min_value as u8;
-                            max_channel = channel.max_value as u8;
-                        }
-                    }
-                }
-            }
+    let rssi_scaling;
+    {
+        let mut device_config = session.device_config.lock()?;
+        if device_config.is_none() {
+            let app_handle = app_handle.clone();
+            *device_config = Some(device_port.transact(move |device| {
+                get_device_config_from_device(device, None, Some(&app_handle))
+            })?);
+            session.update_packet_mode(device_config.as_ref())?;
+        }
+        if let Some(channel) = device_config
+            .as_ref()
+            .and_then(|device_config| {
+                device_config
+                    .cells
+                    .iter()
+                    .find(|cell| cell.name == "RF Channel")
+            })
+        {
+            min_channel = channel.min_value as u8;
+            max_channel = channel.max_value as u8;
         }
+        rssi_scaling = device_config
+            .as_ref()
+            .map(|device_config| device_config.rssi_scaling)
+            .unwrap_or_default();
     }
 
-    let stream = tauri::async_runtime::spawn(async move {
-        if let Ok(mut device) = device_port.lock() {
-            if let Some(mut device) = device.as_mut() {
-                loop {
-                    if let Ok(is_rssi_task_running) = is_rssi_task_running.lock() {
-                        if !*is_rssi_task_running {
-                            info!("Stopping RSSI stream");
-                            return;
-                        }
-                    }
-
-                    for i in min_channel..=max_channel {
-                        if let Ok(is_rssi_task_running) = is_rssi_task_running.lock() {
-                            if !*is_rssi_task_running {
-                                info!("Stopping RSSI stream");
-                                return;
-                            }
-                        }
-                        clear_output_buffer_of_device(&mut device);
-                        let channel_switch_success = switch_to_channel(i, &mut device, &app_handle);
-                        if channel_switch_success {
-                            if let Ok(rssi) = get_rssi_from_device(&mut device, &app_handle) {
-                                app_handle
-                                    .emit_all(
-                                        "rssi_event",
-                                        RSSIEvent {
-                                            rssi: -(rssi as f64) / 2.0,
-                                            channel: i,
-                                        },
-                                    )
-                                    .unwrap();
-                            }
-                        }
+    // Every channel is measured in its own transaction, so other commands can use the port in between
+    return session.tasks.spawn(RSSI_SCAN_TASK, move |token| {
+        let mut sweeps = 0;
+        loop {
+            for i in min_channel..=max_channel {
+                if token.is_cancelled() {
+                    info!("Stopping RSSI stream");
+                    return Ok(format!("{} sweeps", sweeps));
+                }
+                let transaction_app_handle = app_handle.clone();
+                let result = device_port.transact(move |device| {
+                    clear_output_buffer_of_device(device)?;
+                    switch_to_channel(i, device, Some(&transaction_app_handle))?;
+                    let rssi = get_rssi_from_device(device, Some(&transaction_app_handle))?;
+                    Ok((device.name(), rssi))
+                });
+                match result {
+                    Ok((session_id, rssi)) => app_handle
+                        .emit_all(
+                            "rssi_event",
+                            RSSIEvent {
+                                session_id,
+                                rssi: rssi_scaling.to_dbm(rssi as i32),
+                                channel: i,
+                            },
+                        )
+                        .unwrap_or_else(|e| error!("Error emitting: {}", e)),
+                    Err(CcToolError::NotConnected) => {
+                        info!("Stopping RSSI stream, the port was closed");
+                        return Err(CcToolError::NotConnected);
                     }
+                    Err(_) => {}
                 }
             }
+            sweeps += 1;
         }
     });
-    if let Ok(mut rssi_task) = device_entity.rssi_task.lock() {
-        *rssi_task = Some(stream);
-    }
 }
 
-/// This function stops the RSSI stream background process and removes the running task from the `rssi_task` state variable.
-/// It will also set the `is_rssi_task_running` flag to false.
+/// This function stops the RSSI stream background process by cancelling its task.
+/// It waits for the measurement of the current channel to finish, so the port is free when it returns.
 ///
 /// # Arguments
+/// * `session_id` - The ID of the session. If `None`, the default session is used.
 /// * `device_entity` - The state of the program (provided by Tauri)
+///
+/// # Returns
+/// An `Ok(())` if the stream was stopped (or was not running), or an error if the program state could not be accessed.
 #[tauri::command]
-pub fn stop_rssi_stream(device_entity: State<DeviceEntity>) {
+pub fn stop_rssi_stream(
+    session_id: Option<String>,
+    device_entity: State<DeviceEntity>,
+) -> Result<(), CcToolError> {
+    let session = device_entity.session(session_id.as_deref())?;
     info!("Sending signal to stop RSSI stream");
-    if let (Ok(mut rssi_task), Ok(mut is_rssi_task_running)) = (
-        device_entity.rssi_task.lock(),
-        device_entity.is_rssi_task_running.lock(),
-    ) {
-        if let Some(rssi_task) = rssi_task.as_mut() {
-            *is_rssi_task_running = false;
-            rssi_task.abort();
-        }
-        *rssi_task = None;
+    if let Some(task_id) = session.tasks.find_running(RSSI_SCAN_TASK)? {
+        session.tasks.cancel(task_id)?;
+        session.tasks.wait(task_id, TASK_SHUTDOWN_TIMEOUT)?;
     }
+    return Ok(());
 }
 
-fn switch_to_channel(
+/// Switches the device to the given RF channel (`C` command followed by the channel number).
+pub fn switch_to_channel(
     channel: u8,
-    device: &mut Box<dyn SerialPort>,
-    app_handle: &AppHandle,
-) -> bool {
-    let mut read_bytes_result = vec![];
-    let send_bytes_result = send_bytes_to_device(device, &[b'C'], app_handle);
-    read_bytes_till_3e_from_device_to_buffer(device, &mut read_bytes_result, app_handle);
-    if send_bytes_result && read_bytes_result.len() == 0 {
-        let mut read_bytes_result2 = vec![];
-        let send_bytes_result2 = send_bytes_to_device(device, &[channel], app_handle);
-        read_bytes_till_3e_from_device_to_buffer(device, &mut read_bytes_result2, app_handle);
-        return send_bytes_result2 && read_bytes_result2.len() == 0;
-    }
-    return false;
+    device: &mut dyn Transport,
+    app_handle: Option<&AppHandle>,
+) -> Result<(), CcToolError> {
+    send_bytes_to_device(device, &[b'C'], app_handle)?;
+    expect_prompt_from_device(device, &FrameSpec::prompt_only(), app_handle)?;
+    send_bytes_to_device(device, &[channel], app_handle)?;
+    return expect_prompt_from_device(device, &FrameSpec::prompt_only(), app_handle);
 }
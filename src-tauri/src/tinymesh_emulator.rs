@@ -0,0 +1,398 @@
+//! This module contains a software emulator of a TM4070-class TinyMesh module.
+//! It implements `Transport`, so the config, calibration and device info functions
+//! can be run against it in tests, without a physical module attached.
+//!
+//! The emulator answers the configuration mode command set that this tool speaks:
+//! - `0` (0x30): dump the configuration memory
+//! - `r`: dump the calibration memory
+//! - `M` ... `0xFF`: write configuration cells (address, value pairs terminated by 0xFF)
+//! - `HW` ... `0xFF`: write calibration cells (address, value pairs terminated by 0xFF)
+//! - `@TM`: factory reset of the configuration memory
+//! - `S`, `A`, `D`, `U`, `V`: RSSI, analog, digital, temperature and voltage telemetry
+//! - `C<ch>`: switch to RF channel `ch`
+//! - `G`, `R`, `N`: quick modes (gateway, router and end device defaults)
+//! - `1`, `2`, `3`, `5`, `6`: test modes
+//! - `X`: exit configuration mode
+//!
+//! Every completed command is answered with the `>` prompt.
+
+use crate::cc_tool_error::CcToolError;
+use crate::mk_module_description::MkModuleDescription;
+use crate::transport::Transport;
+use std::collections::VecDeque;
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// Size of the configuration memory returned by the `0` command.
+pub const CONFIG_MEMORY_SIZE: usize = 128;
+/// Size of the calibration memory returned by the `r` command.
+pub const CALIBRATION_MEMORY_SIZE: usize = 128;
+
+/// Address of the RF Channel cell in configuration memory.
+const RF_CHANNEL_ADDRESS: usize = 0x00;
+/// Address of the Device Type cell in configuration memory.
+const DEVICE_TYPE_ADDRESS: usize = 0x0E;
+/// Terminator of the address / value pairs of `M` and `HW` commands.
+const WRITE_TERMINATOR: u8 = 0xFF;
+
+/// Test mode that is currently running in the emulator.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EmulatorTestMode {
+    TxCarrier,
+    ModulatedTx,
+    Rx,
+    RssiSniffer,
+    PacketSniffer,
+}
+
+/// What the emulator expects the next received byte(s) to be.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum PendingCommand {
+    None,
+    /// Received `H`, waiting for `W`
+    CalibWriteStart,
+    /// Received `@`, waiting for `T`
+    FactoryResetT,
+    /// Received `@T`, waiting for `M`
+    FactoryResetM,
+    /// Collecting address / value pairs of a `M` command
+    ConfigWrite,
+    /// Collecting address / value pairs of a `HW` command
+    CalibWrite,
+    /// Received `C`, waiting for the channel number
+    ChannelSwitch,
+}
+
+struct EmulatorState {
+    config_defaults: Vec<u8>,
+    config_memory: Vec<u8>,
+    calibration_memory: Vec<u8>,
+    in_config_mode: bool,
+    pending: PendingCommand,
+    pending_pairs: Vec<u8>,
+    test_mode: Option<EmulatorTestMode>,
+    channel: u8,
+    rssi: u8,
+    analog: Vec<u8>,
+    digital: u8,
+    temperature: u8,
+    voltage: u8,
+    to_host: VecDeque<u8>,
+    over_the_air: Vec<u8>,
+}
+
+/// Software emulator of a TinyMesh module. Clones share the same module state.
+#[derive(Clone)]
+pub struct TinyMeshEmulator {
+    name: String,
+    state: Arc<Mutex<EmulatorState>>,
+}
+
+impl TinyMeshEmulator {
+    /// Creates a new emulator whose configuration and calibration memory are initialized
+    /// with the default values (`DEF` entries) of the module description.
+    /// The emulator starts in configuration mode.
+    ///
+    /// # Arguments
+    /// * `name` - The name reported by `Transport::name`.
+    /// * `module_description` - The module description to take the memory image from.
+    pub fn new(name: &str, module_description: &MkModuleDescription) -> TinyMeshEmulator {
+        let config_defaults: Vec<u8> = (0..CONFIG_MEMORY_SIZE)
+            .map(|i| {
+                module_description
+                    .cells
+                    .get(i)
+                    .map_or(0xFF, |cell| cell.default_value)
+            })
+            .collect();
+        let calibration_memory: Vec<u8> = (0..CALIBRATION_MEMORY_SIZE)
+            .map(|i| {
+                module_description
+                    .calibration_cells
+                    .get(i)
+                    .map_or(0xFF, |cell| cell.default_value)
+            })
+            .collect();
+        let channel = config_defaults[RF_CHANNEL_ADDRESS];
+        TinyMeshEmulator {
+            name: name.to_string(),
+            state: Arc::new(Mutex::new(EmulatorState {
+                config_memory: config_defaults.clone(),
+                config_defaults,
+                calibration_memory,
+                in_config_mode: true,
+                pending: PendingCommand::None,
+                pending_pairs: vec![],
+                test_mode: None,
+                channel,
+                rssi: 0xB4,
+                analog: vec![0x01, 0x2C, 0x02, 0x58],
+                digital: 0x00,
+                temperature: 128 + 25,
+                voltage: 110,
+                to_host: VecDeque::new(),
+                over_the_air: vec![],
+            })),
+        }
+    }
+
+    /// Creates a new emulator from the RMD file at the given path.
+    pub fn from_rmd_file(name: &str, rmd_file_path: &Path) -> Result<TinyMeshEmulator, CcToolError> {
+        let file_contents = std::fs::read_to_string(rmd_file_path)?;
+        Ok(TinyMeshEmulator::new(
+            name,
+            &MkModuleDescription::new(&file_contents)?,
+        ))
+    }
+
+    fn lock(&self) -> MutexGuard<'_, EmulatorState> {
+        self.state.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    /// Emulates pulling the CONFIG pin low: the module enters configuration mode and sends the prompt.
+    pub fn enter_config_mode(&self) {
+        let mut state = self.lock();
+        state.in_config_mode = true;
+        state.pending = PendingCommand::None;
+        state.to_host.push_back(b'>');
+    }
+
+    /// Emulates bytes received over the air, which the module forwards to the host.
+    pub fn receive_over_the_air(&self, bytes: &[u8]) {
+        self.lock().to_host.extend(bytes);
+    }
+
+    /// Returns (and forgets) the bytes the module transmitted over the air in communication mode.
+    pub fn take_over_the_air(&self) -> Vec<u8> {
+        std::mem::take(&mut self.lock().over_the_air)
+    }
+
+    pub fn is_in_config_mode(&self) -> bool {
+        self.lock().in_config_mode
+    }
+
+    pub fn config_memory(&self) -> Vec<u8> {
+        self.lock().config_memory.clone()
+    }
+
+    pub fn calibration_memory(&self) -> Vec<u8> {
+        self.lock().calibration_memory.clone()
+    }
+
+    pub fn channel(&self) -> u8 {
+        self.lock().channel
+    }
+
+    pub fn test_mode(&self) -> Option<EmulatorTestMode> {
+        self.lock().test_mode
+    }
+
+    /// Sets the raw RSSI value reported by the `S` command.
+    pub fn set_rssi(&self, rssi: u8) {
+        self.lock().rssi = rssi;
+    }
+
+    /// Sets the raw telemetry values reported by the `A`, `D`, `U` and `V` commands.
+    pub fn set_telemetry(&self, analog: &[u8], digital: u8, temperature: u8, voltage: u8) {
+        let mut state = self.lock();
+        state.analog = analog.to_vec();
+        state.digital = digital;
+        state.temperature = temperature;
+        state.voltage = voltage;
+    }
+}
+
+impl EmulatorState {
+    fn prompt(&mut self) {
+        self.to_host.push_back(b'>');
+    }
+
+    fn respond(&mut self, bytes: &[u8]) {
+        self.to_host.extend(bytes);
+        self.prompt();
+    }
+
+    fn apply_pairs(&mut self, calibration: bool) {
+        let pairs = std::mem::take(&mut self.pending_pairs);
+        for pair in pairs.chunks_exact(2) {
+            let memory = if calibration {
+                &mut self.calibration_memory
+            } else {
+                &mut self.config_memory
+            };
+            if let Some(cell) = memory.get_mut(pair[0] as usize) {
+                *cell = pair[1];
+            }
+        }
+        self.channel = self.config_memory[RF_CHANNEL_ADDRESS];
+    }
+
+    fn process_byte(&mut self, byte: u8) {
+        if !self.in_config_mode {
+            self.over_the_air.push(byte);
+            return;
+        }
+        match self.pending {
+            PendingCommand::ConfigWrite | PendingCommand::CalibWrite => {
+                // An address of 0xFF terminates the sequence, a value of 0xFF is a valid value
+                if self.pending_pairs.len().is_multiple_of(2) && byte == WRITE_TERMINATOR {
+                    self.apply_pairs(self.pending == PendingCommand::CalibWrite);
+                    self.pending = PendingCommand::None;
+                    self.prompt();
+                } else {
+                    self.pending_pairs.push(byte);
+                }
+                return;
+            }
+            PendingCommand::ChannelSwitch => {
+                self.pending = PendingCommand::None;
+                self.channel = byte;
+                self.prompt();
+                return;
+            }
+            PendingCommand::CalibWriteStart => {
+                self.pending = PendingCommand::None;
+                if byte == b'W' {
+                    self.pending = PendingCommand::CalibWrite;
+                    self.pending_pairs.clear();
+                    self.prompt();
+                    return;
+                }
+            }
+            PendingCommand::FactoryResetT => {
+                self.pending = PendingCommand::None;
+                if byte == b'T' {
+                    self.pending = PendingCommand::FactoryResetM;
+                    return;
+                }
+            }
+            PendingCommand::FactoryResetM => {
+                self.pending = PendingCommand::None;
+                if byte == b'M' {
+                    self.config_memory = self.config_defaults.clone();
+                    self.channel = self.config_memory[RF_CHANNEL_ADDRESS];
+                    self.prompt();
+                    return;
+                }
+            }
+            PendingCommand::None => {}
+        }
+        self.process_command(byte);
+    }
+
+    fn process_command(&mut self, command: u8) {
+        match command {
+            b'0' => {
+                let memory = self.config_memory.clone();
+                self.respond(&memory);
+            }
+            b'r' => {
+                let memory = self.calibration_memory.clone();
+                self.respond(&memory);
+            }
+            b'M' => {
+                self.pending = PendingCommand::ConfigWrite;
+                self.pending_pairs.clear();
+                self.prompt();
+            }
+            b'H' => self.pending = PendingCommand::CalibWriteStart,
+            b'@' => self.pending = PendingCommand::FactoryResetT,
+            b'C' => {
+                self.pending = PendingCommand::ChannelSwitch;
+                self.prompt();
+            }
+            b'S' => {
+                // Make the spectrum a bit more interesting than a flat line
+                let rssi = self.rssi.wrapping_sub(self.channel);
+                self.respond(&[rssi]);
+            }
+            b'A' => {
+                let analog = self.analog.clone();
+                self.respond(&analog);
+            }
+            b'D' => {
+                let digital = self.digital;
+                self.respond(&[digital]);
+            }
+            b'U' => {
+                let temperature = self.temperature;
+                self.respond(&[temperature]);
+            }
+            b'V' => {
+                let voltage = self.voltage;
+                self.respond(&[voltage]);
+            }
+            b'G' | b'R' | b'N' => {
+                self.config_memory[DEVICE_TYPE_ADDRESS] = match command {
+                    b'G' => 1,
+                    b'R' => 2,
+                    _ => 3,
+                };
+                self.prompt();
+            }
+            b'1' => {
+                self.test_mode = Some(EmulatorTestMode::TxCarrier);
+                self.prompt();
+            }
+            b'2' => {
+                self.test_mode = Some(EmulatorTestMode::ModulatedTx);
+                self.prompt();
+            }
+            b'3' => {
+                self.test_mode = Some(EmulatorTestMode::Rx);
+                self.prompt();
+            }
+            // Sniffer modes keep streaming their reports instead of returning to the prompt
+            b'5' => self.test_mode = Some(EmulatorTestMode::RssiSniffer),
+            b'6' => self.test_mode = Some(EmulatorTestMode::PacketSniffer),
+            b'X' => {
+                self.in_config_mode = false;
+                self.test_mode = None;
+            }
+            _ => self.prompt(),
+        }
+    }
+}
+
+impl Read for TinyMeshEmulator {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut state = self.lock();
+        if state.to_host.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::TimedOut, "No bytes to read"));
+        }
+        let count = buf.len().min(state.to_host.len());
+        for (slot, byte) in buf.iter_mut().zip(state.to_host.drain(..count)) {
+            *slot = byte;
+        }
+        Ok(count)
+    }
+}
+
+impl Write for TinyMeshEmulator {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut state = self.lock();
+        for byte in buf {
+            state.process_byte(*byte);
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Transport for TinyMeshEmulator {
+    fn name(&self) -> Option<String> {
+        Some(self.name.clone())
+    }
+
+    fn clear_output_buffer(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn try_clone_transport(&self) -> io::Result<Box<dyn Transport>> {
+        Ok(Box::new(self.clone()))
+    }
+}
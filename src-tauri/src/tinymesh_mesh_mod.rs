@@ -0,0 +1,124 @@
+//! This module contains functions for the mesh node table of a gateway session.
+//! The table is updated by the port actor with every decoded packet, so it grows while the gateway is
+//! streaming in packet mode. See `mesh_topology` for how the topology is inferred.
+
+use crate::cc_tool_error::CcToolError;
+use crate::data_types::{DeviceEntity, MeshEventPayload};
+use crate::mesh_topology::{MeshExportFormat, MeshNode};
+use crate::tinymesh_packet::TinyMeshPacket;
+use crate::traffic_capture::enter_command_context;
+use log::{error, info};
+use std::time::Instant;
+use tauri::{AppHandle, Manager, State};
+
+/// This function returns the nodes heard through the gateway of a session.
+/// # Arguments
+/// * `session_id` - The ID of the session. If `None`, the default session is used.
+/// * `device_entity` - The state of the program (provided by Tauri)
+///
+/// # Returns
+/// The nodes ordered by unique ID, or an error if the session could not be accessed.
+#[tauri::command]
+pub fn get_mesh_nodes(
+    session_id: Option<String>,
+    device_entity: State<DeviceEntity>,
+) -> Result<Vec<MeshNode>, CcToolError> {
+    let _context = enter_command_context("get_mesh_nodes");
+    let session = device_entity.session(session_id.as_deref())?;
+    let nodes = session.mesh_table.lock()?.nodes();
+    return Ok(nodes);
+}
+
+/// This function forgets the nodes heard through the gateway of a session, e.g. after the network was changed.
+/// # Arguments
+/// * `session_id` - The ID of the session. If `None`, the default session is used.
+/// * `device_entity` - The state of the program (provided by Tauri)
+///
+/// # Returns
+/// An `Ok(())` if the table was cleared, or an error if the session could not be accessed.
+#[tauri::command]
+pub fn clear_mesh_nodes(
+    session_id: Option<String>,
+    device_entity: State<DeviceEntity>,
+) -> Result<(), CcToolError> {
+    let _context = enter_command_context("clear_mesh_nodes");
+    let session = device_entity.session(session_id.as_deref())?;
+    session.mesh_table.lock()?.clear();
+    return Ok(());
+}
+
+/// This function writes the mesh node table of a session to a file.
+/// # Arguments
+/// * `file_path` - The path of the file, which is replaced if it exists
+/// * `format` - `Json` for the list of nodes, `Dot` for a GraphViz graph of the topology
+/// * `session_id` - The ID of the session. If `None`, the default session is used.
+/// * `device_entity` - The state of the program (provided by Tauri)
+///
+/// # Returns
+/// The number of exported nodes, or an error if the file could not be written.
+#[tauri::command]
+pub fn export_mesh_topology(
+    file_path: String,
+    format: MeshExportFormat,
+    session_id: Option<String>,
+    device_entity: State<DeviceEntity>,
+) -> Result<usize, CcToolError> {
+    let _context = enter_command_context("export_mesh_topology");
+    let session = device_entity.session(session_id.as_deref())?;
+    let (node_count, exported) = {
+        let mesh_table = session.mesh_table.lock()?;
+        (mesh_table.nodes().len(), mesh_table.export(format)?)
+    };
+    std::fs::write(&file_path, exported)?;
+    info!(
+        "Exported {} mesh nodes to {} as {:?}",
+        node_count, file_path, format
+    );
+    return Ok(node_count);
+}
+
+/// Updates the mesh node table of the session with a received packet and emits the node as `mesh_event`.
+/// Packets of sessions that are not registered (yet) are ignored.
+/// # Arguments
+/// * `app_handle` - The Tauri application handle
+/// * `session_id` - The session the packet was received in
+/// * `packet` - The decoded packet
+/// * `received_at` - The time at which the first byte of the packet was read
+pub fn record_mesh_packet(
+    app_handle: &AppHandle,
+    session_id: Option<&str>,
+    packet: &TinyMeshPacket,
+    received_at: Instant,
+) {
+    let Some(session_id) = session_id else {
+        return;
+    };
+    let Some(session) = app_handle
+        .try_state::<DeviceEntity>()
+        .and_then(|device_entity| device_entity.session(Some(session_id)).ok())
+    else {
+        return;
+    };
+    let (change, node) = match session.mesh_table.lock() {
+        Ok(mut mesh_table) => {
+            let change = mesh_table.update(packet, received_at);
+            (change, mesh_table.node(packet.origin_id).cloned())
+        }
+        Err(err) => {
+            error!("Error updating the mesh node table: {}", err);
+            return;
+        }
+    };
+    if let Some(node) = node {
+        app_handle
+            .emit_all(
+                "mesh_event",
+                MeshEventPayload {
+                    session_id: Some(session_id.to_string()),
+                    change,
+                    node,
+                },
+            )
+            .unwrap_or_else(|e| error!("Error emitting: {}", e));
+    }
+}
@@ -0,0 +1,360 @@
+//! This module contains the decoder of the packets a TinyMesh gateway outputs in packet mode
+//! (cell 0x03 Protocol mode = 0).
+//!
+//! # Packet format
+//! Every packet starts with a header of 17 bytes. Multi-byte IDs are sent LSB first, counters MSB first.
+//! ```text
+//! 0      Length of the packet, including the length byte
+//! 1..5   System ID
+//! 5..9   Origin ID (unique ID of the node that sent the packet)
+//! 9      RSSI of the last hop
+//! 10     Network level of the origin node
+//! 11     Number of hops to the gateway
+//! 12..14 Packet number
+//! 14..16 Latency
+//! 16     Packet type: 0x02 event, 0x10 serial data, 0x21 configuration memory
+//! ```
+//! An event packet (35 bytes) continues with the detail of the event (e.g. `0x10` command acknowledge,
+//! `0x12` status), a data byte, the address, temperature, voltage, digital and analogue I/O and the
+//! hardware and firmware revisions of the node. A serial data packet continues with a block counter
+//! and the data received by the serial port of the node. A configuration packet, the answer to a get
+//! configuration command, continues with the configuration memory of the node (laid out like the local
+//! `0` memory dump).
+//!
+//! # Command format
+//! The packets the host sends to the gateway are addressed like the packets it receives:
+//! ```text
+//! 0      Length of the packet, including the length byte
+//! 1..5   System ID
+//! 5..9   Destination (unique ID of the node the packet is sent to)
+//! 9      Command number, returned in the acknowledge of the command
+//! 10     Packet type: 0x03 command, 0x11 serial data
+//! 11..   Command and its two parameters, or the serial data
+//! ```
+//! A set configuration command writes a single cell: its parameters are the address and the value.
+
+use crate::cc_tool_error::CcToolError;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+/// Length of the header shared by all packets
+pub const TINYMESH_HEADER_LEN: usize = 17;
+/// Length of an event packet
+pub const TINYMESH_EVENT_PACKET_LEN: usize = 35;
+
+/// Packet type of event packets
+pub const PACKET_TYPE_EVENT: u8 = 0x02;
+/// Packet type of serial data packets
+pub const PACKET_TYPE_SERIAL_DATA: u8 = 0x10;
+/// Packet type of the packets containing the configuration memory of a node
+pub const PACKET_TYPE_CONFIG_DATA: u8 = 0x21;
+
+/// Address of the Protocol mode cell of the configuration memory
+pub const PROTOCOL_MODE_ADDRESS: usize = 0x03;
+/// Value of the Protocol mode cell of a gateway in packet mode
+pub const PROTOCOL_MODE_PACKET: u8 = 0;
+
+/// Length of the header of the packets sent to the gateway
+pub const TINYMESH_COMMAND_HEADER_LEN: usize = 11;
+/// Maximum number of bytes of a serial data packet sent to a node
+pub const MAX_SERIAL_DATA_LEN: usize = 120;
+
+/// Packet type of command packets sent to the gateway
+pub const PACKET_TYPE_COMMAND: u8 = 0x03;
+/// Packet type of serial data packets sent to the gateway
+pub const PACKET_TYPE_SERIAL_DATA_OUT: u8 = 0x11;
+
+/// Command setting the digital outputs of a node
+pub const COMMAND_SET_OUTPUTS: u8 = 0x01;
+/// Command setting the PWM output of a node
+pub const COMMAND_SET_PWM: u8 = 0x02;
+/// Command asking a node for its status
+pub const COMMAND_GET_STATUS: u8 = 0x11;
+/// Command asking a node for its configuration memory
+pub const COMMAND_GET_CONFIG: u8 = 0x13;
+/// Command writing a cell of the configuration memory of a node
+pub const COMMAND_SET_CONFIG: u8 = 0x14;
+
+static NEXT_COMMAND_NUMBER: AtomicU8 = AtomicU8::new(1);
+
+/// Event detail of the acknowledge of a command
+pub const EVENT_DETAIL_ACK: u8 = 0x10;
+/// Event detail of a command that was not accepted
+pub const EVENT_DETAIL_NAK: u8 = 0x11;
+/// Event detail of the answer to a get status command
+pub const EVENT_DETAIL_STATUS: u8 = 0x12;
+
+/// The status of a node, reported by every event packet
+#[derive(Clone, Debug, PartialEq, serde::Serialize)]
+pub struct TinyMeshNodeStatus {
+    /// What triggered the event, e.g. `0x01` for a change of the digital inputs
+    pub detail: u8,
+    /// Data of the event, e.g. the number of the acknowledged command
+    pub data: u8,
+    pub address: u16,
+    /// Temperature of the node in °C, offset by 128
+    pub temperature: u8,
+    /// Supply voltage of the node in units of 30 mV
+    pub voltage: u8,
+    /// States of the digital I/O pins, one bit per pin
+    pub digital_io: u16,
+    pub analog_io: [u16; 2],
+    pub hardware_revision: u16,
+    pub firmware_revision: u16,
+}
+
+/// The part of a packet that depends on its type
+#[derive(Clone, Debug, PartialEq, serde::Serialize)]
+#[serde(tag = "kind")]
+pub enum TinyMeshPacketBody {
+    /// An event of a node (I/O change, power on etc.)
+    Event(TinyMeshNodeStatus),
+    /// Data received by the serial port of a node
+    SerialData { block_counter: u8, data: Vec<u8> },
+    /// The answer of a node to a command sent through the gateway
+    CommandAck {
+        /// `false` if the node did not accept the command (NAK)
+        acknowledged: bool,
+        command_number: u8,
+        status: TinyMeshNodeStatus,
+    },
+    /// The answer of a node to a get status command
+    Status(TinyMeshNodeStatus),
+    /// The configuration memory of a node, the answer to a get configuration command
+    ConfigData { data: Vec<u8> },
+}
+
+/// A packet received from a TinyMesh gateway in packet mode
+#[derive(Clone, Debug, PartialEq, serde::Serialize)]
+pub struct TinyMeshPacket {
+    pub system_id: u32,
+    /// Unique ID of the node that sent the packet
+    pub origin_id: u32,
+    pub rssi: u8,
+    pub network_level: u8,
+    pub hops: u8,
+    pub packet_number: u16,
+    pub latency: u16,
+    pub body: TinyMeshPacketBody,
+}
+
+fn u32_lsb_first(bytes: &[u8]) -> u32 {
+    return u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+}
+
+fn u16_msb_first(bytes: &[u8]) -> u16 {
+    return u16::from_be_bytes([bytes[0], bytes[1]]);
+}
+
+fn not_a_packet(expected: &str, frame: &[u8]) -> CcToolError {
+    return CcToolError::UnexpectedResponse {
+        expected: expected.to_string(),
+        got: frame.to_vec(),
+    };
+}
+
+/// Decodes the status part of an event packet.
+fn decode_node_status(packet: &[u8]) -> TinyMeshNodeStatus {
+    return TinyMeshNodeStatus {
+        detail: packet[17],
+        data: packet[18],
+        address: u16_msb_first(&packet[19..21]),
+        temperature: packet[21],
+        voltage: packet[22],
+        digital_io: u16_msb_first(&packet[23..25]),
+        analog_io: [
+            u16_msb_first(&packet[25..27]),
+            u16_msb_first(&packet[27..29]),
+        ],
+        hardware_revision: u16_msb_first(&packet[29..31]),
+        firmware_revision: u16_msb_first(&packet[31..33]),
+    };
+}
+
+/// This function decodes a frame received from a gateway in packet mode.
+/// # Arguments
+/// * `frame` - The bytes of a single packet, starting with its length byte
+///
+/// # Returns
+/// The decoded packet, or `CcToolError::UnexpectedResponse` if the frame is not a complete packet
+/// of a known type.
+pub fn decode_tinymesh_packet(frame: &[u8]) -> Result<TinyMeshPacket, CcToolError> {
+    if frame.len() < TINYMESH_HEADER_LEN || frame[0] as usize != frame.len() {
+        return Err(not_a_packet(
+            "TinyMesh packet with a matching length byte",
+            frame,
+        ));
+    }
+    let packet_type = frame[16];
+    let body = match packet_type {
+        PACKET_TYPE_EVENT => {
+            if frame.len() != TINYMESH_EVENT_PACKET_LEN {
+                return Err(not_a_packet("TinyMesh event packet of 35 bytes", frame));
+            }
+            let status = decode_node_status(frame);
+            match status.detail {
+                EVENT_DETAIL_ACK | EVENT_DETAIL_NAK => TinyMeshPacketBody::CommandAck {
+                    acknowledged: status.detail == EVENT_DETAIL_ACK,
+                    command_number: status.data,
+                    status,
+                },
+                EVENT_DETAIL_STATUS => TinyMeshPacketBody::Status(status),
+                _ => TinyMeshPacketBody::Event(status),
+            }
+        }
+        PACKET_TYPE_SERIAL_DATA => {
+            if frame.len() <= TINYMESH_HEADER_LEN {
+                return Err(not_a_packet("TinyMesh serial data packet", frame));
+            }
+            TinyMeshPacketBody::SerialData {
+                block_counter: frame[17],
+                data: frame[18..].to_vec(),
+            }
+        }
+        PACKET_TYPE_CONFIG_DATA => TinyMeshPacketBody::ConfigData {
+            data: frame[TINYMESH_HEADER_LEN..].to_vec(),
+        },
+        _ => {
+            return Err(not_a_packet(
+                "TinyMesh event, serial data or configuration packet",
+                frame,
+            ));
+        }
+    };
+    return Ok(TinyMeshPacket {
+        system_id: u32_lsb_first(&frame[1..5]),
+        origin_id: u32_lsb_first(&frame[5..9]),
+        rssi: frame[9],
+        network_level: frame[10],
+        hops: frame[11],
+        packet_number: u16_msb_first(&frame[12..14]),
+        latency: u16_msb_first(&frame[14..16]),
+        body,
+    });
+}
+
+/// This function decodes all packets of a frame received from a gateway in packet mode.
+/// The frame is split by the length bytes of the packets, so a frame holding several packets (e.g. read
+/// in one chunk) yields all of them. A byte that does not start a complete packet of a known type is skipped,
+/// so the split resynchronizes on the next packet.
+/// # Arguments
+/// * `frame` - The received bytes
+///
+/// # Returns
+/// The decoded packets, oldest first.
+pub fn decode_tinymesh_packets(frame: &[u8]) -> Vec<TinyMeshPacket> {
+    let mut packets = vec![];
+    let mut offset = 0;
+    while offset < frame.len() {
+        let packet_len = frame[offset] as usize;
+        match frame
+            .get(offset..offset + packet_len)
+            .and_then(|bytes| decode_tinymesh_packet(bytes).ok())
+        {
+            Some(packet) => {
+                packets.push(packet);
+                offset += packet_len;
+            }
+            None => offset += 1,
+        }
+    }
+    return packets;
+}
+
+/// A command sent to a node through a gateway in packet mode
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "kind")]
+pub enum TinyMeshCommand {
+    /// Asks the node for its status, answered with a status packet
+    GetStatus,
+    /// Asks the node for its configuration memory
+    GetConfig,
+    /// Writes a cell of the configuration memory of the node
+    SetConfig { address: u8, value: u8 },
+    /// Sets the digital outputs selected by `mask` to the states in `outputs` (one bit per pin)
+    SetOutputs { outputs: u8, mask: u8 },
+    /// Sets the duty cycle of the PWM output, in percent
+    SetPwm { duty_cycle: u8 },
+    /// Sends data to the serial port of the node
+    SerialData { data: Vec<u8> },
+}
+
+/// Builds the packets sent to a node through a gateway in packet mode
+#[derive(Clone, Debug, PartialEq)]
+pub struct TinyMeshCommandBuilder {
+    system_id: u32,
+    destination: u32,
+    command_number: Option<u8>,
+}
+
+impl TinyMeshCommandBuilder {
+    /// Creates a builder for the packets sent to a node.
+    /// # Arguments
+    /// * `system_id` - The System ID of the network
+    /// * `destination` - The unique ID of the node
+    pub fn new(system_id: u32, destination: u32) -> TinyMeshCommandBuilder {
+        return TinyMeshCommandBuilder {
+            system_id,
+            destination,
+            command_number: None,
+        };
+    }
+
+    /// Returns the same builder with a fixed command number.
+    /// Without one, every built packet gets the next number of a global counter.
+    pub fn with_command_number(mut self, command_number: u8) -> TinyMeshCommandBuilder {
+        self.command_number = Some(command_number);
+        self
+    }
+
+    /// Builds the packet of a command.
+    /// # Arguments
+    /// * `command` - The command to send
+    ///
+    /// # Returns
+    /// The command number and the bytes of the packet, or `CcToolError::InvalidInput` if a parameter is out of range.
+    pub fn build(&self, command: &TinyMeshCommand) -> Result<(u8, Vec<u8>), CcToolError> {
+        let (packet_type, body) = match command {
+            TinyMeshCommand::GetStatus => (PACKET_TYPE_COMMAND, vec![COMMAND_GET_STATUS, 0, 0]),
+            TinyMeshCommand::GetConfig => (PACKET_TYPE_COMMAND, vec![COMMAND_GET_CONFIG, 0, 0]),
+            TinyMeshCommand::SetConfig { address, value } => (
+                PACKET_TYPE_COMMAND,
+                vec![COMMAND_SET_CONFIG, *address, *value],
+            ),
+            TinyMeshCommand::SetOutputs { outputs, mask } => (
+                PACKET_TYPE_COMMAND,
+                vec![COMMAND_SET_OUTPUTS, *outputs, *mask],
+            ),
+            TinyMeshCommand::SetPwm { duty_cycle } => {
+                if *duty_cycle > 100 {
+                    return Err(CcToolError::InvalidInput {
+                        message: format!("PWM duty cycle {}% is above 100%", duty_cycle),
+                    });
+                }
+                (PACKET_TYPE_COMMAND, vec![COMMAND_SET_PWM, *duty_cycle, 0])
+            }
+            TinyMeshCommand::SerialData { data } => {
+                if data.is_empty() || data.len() > MAX_SERIAL_DATA_LEN {
+                    return Err(CcToolError::InvalidInput {
+                        message: format!(
+                            "Serial data must be 1 to {} bytes long, got {}",
+                            MAX_SERIAL_DATA_LEN,
+                            data.len()
+                        ),
+                    });
+                }
+                (PACKET_TYPE_SERIAL_DATA_OUT, data.clone())
+            }
+        };
+        let command_number = self
+            .command_number
+            .unwrap_or_else(|| NEXT_COMMAND_NUMBER.fetch_add(1, Ordering::Relaxed));
+        let mut packet = Vec::with_capacity(TINYMESH_COMMAND_HEADER_LEN + body.len());
+        packet.push((TINYMESH_COMMAND_HEADER_LEN + body.len()) as u8);
+        packet.extend(self.system_id.to_le_bytes());
+        packet.extend(self.destination.to_le_bytes());
+        packet.push(command_number);
+        packet.push(packet_type);
+        packet.extend(body);
+        return Ok((command_number, packet));
+    }
+}
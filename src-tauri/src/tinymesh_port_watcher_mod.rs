@@ -0,0 +1,173 @@
+//! This module contains the background port watcher, which notices USB-serial adapters being
+//! plugged in and unplugged. It tears down the connection when the connected adapter disappears
+//! and optionally reconnects when the same adapter reappears.
+
+use crate::cc_tool_error::CcToolError;
+use crate::data_types::{ConnectionInfo, DeviceEntity, SerialPortEntry};
+use crate::serial_settings::open_serial_port;
+use crate::tinymesh_serial_util::{serial_port_entry_from_info, tear_down_session};
+use log::{error, info};
+use std::time::Duration;
+use tauri::{AppHandle, Manager, State};
+
+/// Time between two enumerations of the serial ports
+pub const PORT_WATCH_INTERVAL: Duration = Duration::from_millis(1000);
+
+/// The ports that appeared and disappeared between two enumerations
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PortChanges {
+    pub attached: Vec<SerialPortEntry>,
+    pub detached: Vec<SerialPortEntry>,
+}
+
+/// This function compares two enumerations of the serial ports by port name.
+/// # Arguments
+/// * `previous` - The ports found by the previous enumeration
+/// * `current` - The ports found by the current enumeration
+///
+/// # Returns
+/// The ports that are only in `current` (attached) and the ports that are only in `previous` (detached).
+pub fn diff_ports(previous: &[SerialPortEntry], current: &[SerialPortEntry]) -> PortChanges {
+    let attached = current
+        .iter()
+        .filter(|entry| !previous.iter().any(|old| old.port_name == entry.port_name))
+        .cloned()
+        .collect();
+    let detached = previous
+        .iter()
+        .filter(|old| !current.iter().any(|entry| entry.port_name == old.port_name))
+        .cloned()
+        .collect();
+    return PortChanges { attached, detached };
+}
+
+/// This function looks for the adapter of a lost connection among newly attached ports.
+/// Adapters without a USB serial number can't be told apart, so they are never matched.
+/// # Arguments
+/// * `attached` - The ports that were just attached
+/// * `connection` - The lost connection
+///
+/// # Returns
+/// The attached port with the same USB serial number as the lost connection, if any.
+pub fn find_reconnect_candidate<'a>(
+    attached: &'a [SerialPortEntry],
+    connection: &ConnectionInfo,
+) -> Option<&'a SerialPortEntry> {
+    let serial_number = connection.serial_number.as_ref()?;
+    return attached
+        .iter()
+        .find(|entry| entry.serial_number.as_ref() == Some(serial_number));
+}
+
+/// This function handles the changes of a single enumeration: it emits the attach / detach events,
+/// tears down the sessions whose ports were detached, and reconnects them if enabled and possible.
+fn handle_port_changes(
+    changes: &PortChanges,
+    device_entity: &DeviceEntity,
+    app_handle: &AppHandle,
+) -> Result<(), CcToolError> {
+    for entry in &changes.detached {
+        info!("Port detached: {}", entry.port_name);
+        app_handle
+            .emit_all("device_detached", entry.clone())
+            .unwrap_or_else(|e| error!("Error emitting: {}", e));
+    }
+    for entry in &changes.attached {
+        info!("Port attached: {}", entry.port_name);
+        app_handle
+            .emit_all("device_attached", entry.clone())
+            .unwrap_or_else(|e| error!("Error emitting: {}", e));
+    }
+
+    let auto_reconnect = *device_entity.auto_reconnect.lock()?;
+    for (session_id, session) in device_entity.all_sessions()? {
+        let connection = session.connection.lock()?.clone();
+        let Some(connection) = connection else {
+            continue;
+        };
+        let is_connection_lost = changes
+            .detached
+            .iter()
+            .any(|entry| entry.port_name == connection.port_name);
+        if is_connection_lost {
+            info!("Connected port {} disappeared", connection.port_name);
+            tear_down_session(&session)?;
+            app_handle
+                .emit_all("connection_lost", connection.clone())
+                .unwrap_or_else(|e| error!("Error emitting: {}", e));
+        }
+
+        if !auto_reconnect || session.port.is_open()? {
+            continue;
+        }
+        if let Some(entry) = find_reconnect_candidate(&changes.attached, &connection) {
+            info!(
+                "Reconnecting to {} with {:?}",
+                entry.port_name, connection.serial_settings
+            );
+            let port = open_serial_port(&entry.port_name, &connection.serial_settings)?;
+            let connection = ConnectionInfo {
+                port_name: entry.port_name.clone(),
+                ..connection
+            };
+            session.port.open(Box::new(port))?;
+            *session.connection.lock()? = Some(connection.clone());
+            // The session ID is the port name, which may have changed
+            device_entity.rename_session(&session_id, &entry.port_name)?;
+            app_handle
+                .emit_all("device_reconnected", connection)
+                .unwrap_or_else(|e| error!("Error emitting: {}", e));
+        }
+    }
+    return Ok(());
+}
+
+/// This function starts the background port watcher.
+/// It enumerates the serial ports every `PORT_WATCH_INTERVAL` and emits `device_attached` /
+/// `device_detached` events with the `SerialPortEntry` of the port.
+/// When the port of a session disappears, the session is torn down and `connection_lost` is emitted.
+/// When auto reconnect is enabled and an adapter with the same USB serial number appears,
+/// the session is reconnected with the same settings (and moved to the new port name)
+/// and `device_reconnected` is emitted.
+/// # Arguments
+/// * `app_handle` - The Tauri application handle, used for accessing the program state and emitting events
+pub fn start_port_watcher(app_handle: AppHandle) {
+    tauri::async_runtime::spawn_blocking(move || {
+        let enumerate_ports = || -> Vec<SerialPortEntry> {
+            return serialport::available_ports()
+                .unwrap_or_default()
+                .iter()
+                .map(serial_port_entry_from_info)
+                .collect();
+        };
+        let mut previous = enumerate_ports();
+        loop {
+            std::thread::sleep(PORT_WATCH_INTERVAL);
+            let current = enumerate_ports();
+            let changes = diff_ports(&previous, &current);
+            if changes != PortChanges::default() {
+                let device_entity = app_handle.state::<DeviceEntity>();
+                handle_port_changes(&changes, &device_entity, &app_handle)
+                    .unwrap_or_else(|err| error!("Error handling port changes: {}", err));
+            }
+            previous = current;
+        }
+    });
+}
+
+/// This function enables or disables reconnecting when the adapter of a lost connection reappears.
+/// # Arguments
+/// * `enabled` - Whether to reconnect automatically
+/// * `device_entity` - The state of the program (provided by Tauri)
+///
+/// # Returns
+/// An `Ok(())` if the setting was changed, or an error if the program state could not be accessed.
+#[tauri::command]
+pub fn set_auto_reconnect(
+    enabled: bool,
+    device_entity: State<DeviceEntity>,
+) -> Result<(), CcToolError> {
+    info!("Auto reconnect: {}", enabled);
+    *device_entity.auto_reconnect.lock()? = enabled;
+    return Ok(());
+}
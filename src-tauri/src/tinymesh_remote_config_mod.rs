@@ -0,0 +1,224 @@
+//! This module contains functions for reading and writing the configuration of remote TinyMesh nodes
+//! over the mesh, through a gateway in packet mode.
+//! The configuration memory of a remote node is decoded with the same RMD files as the one of the local
+//! module, so the frontend can show it in the same configuration table.
+
+use crate::cc_tool_error::CcToolError;
+use crate::data_types::{DeviceEntity, MkDeviceCell, MkDeviceConfig};
+use crate::device_config_parser::parse_device_config;
+use crate::tinymesh_config_mod::validate_changed_cells;
+use crate::tinymesh_packet::{TinyMeshCommand, TinyMeshCommandBuilder, TinyMeshPacketBody};
+use crate::tinymesh_serial_util::{
+    clear_output_buffer_of_device, read_tinymesh_packet_from_device, send_bytes_to_device,
+};
+use crate::traffic_capture::enter_command_context;
+use crate::transport::Transport;
+use log::info;
+use std::path::Path;
+use std::time::Duration;
+use tauri::{AppHandle, State};
+
+/// Deadline for the answer of a remote node, which may be several hops away from the gateway.
+pub const REMOTE_RESPONSE_DEADLINE: Duration = Duration::from_millis(5000);
+
+/// This function reads the configuration of a remote node through the connected gateway.
+/// The configuration is kept in the session, so it can be changed with `set_remote_device_config`.
+/// # Arguments
+/// * `system_id` - The System ID of the network
+/// * `unique_id` - The unique ID of the remote node
+/// * `session_id` - The ID of the session. If `None`, the default session is used.
+/// * `device_entity` - The state of the program (provided by Tauri)
+/// * `app_handle` - The Tauri application handle (provided by Tauri)
+///
+/// # Returns
+/// A `MkDeviceConfig` struct containing the configuration of the remote node,
+/// `CcToolError::Timeout` if the node did not answer, or the error that occurred while decoding it.
+#[tauri::command]
+pub fn get_remote_device_config(
+    system_id: u32,
+    unique_id: u32,
+    session_id: Option<String>,
+    device_entity: State<DeviceEntity>,
+    app_handle: AppHandle,
+) -> Result<MkDeviceConfig, CcToolError> {
+    let _context = enter_command_context("get_remote_device_config");
+    let session = device_entity.session(session_id.as_deref())?;
+    let device_config = session.port.transact(move |device| {
+        get_remote_device_config_from_device(device, system_id, unique_id, None, Some(&app_handle))
+    })?;
+    session
+        .remote_configs
+        .lock()?
+        .insert(unique_id, device_config.clone());
+    return Ok(device_config);
+}
+
+/// This function asks a remote node for its configuration memory and decodes it.
+/// # Arguments
+/// * `device` - The transport connected to the gateway
+/// * `system_id` - The System ID of the network
+/// * `unique_id` - The unique ID of the remote node
+/// * `rmd_file_path` - An optional path to the RMD file. If `None`, the RMD file matching the node model is used.
+/// * `app_handle` - An optional Tauri application handle, used for emitting the exchanged bytes
+///
+/// # Returns
+/// A `MkDeviceConfig` struct containing the configuration of the remote node, or a `CcToolError`.
+pub fn get_remote_device_config_from_device(
+    device: &mut dyn Transport,
+    system_id: u32,
+    unique_id: u32,
+    rmd_file_path: Option<&Path>,
+    app_handle: Option<&AppHandle>,
+) -> Result<MkDeviceConfig, CcToolError> {
+    let (_, packet) =
+        TinyMeshCommandBuilder::new(system_id, unique_id).build(&TinyMeshCommand::GetConfig)?;
+    clear_output_buffer_of_device(device)?;
+    send_bytes_to_device(device, &packet, app_handle)?;
+    let answer = read_tinymesh_packet_from_device(
+        device,
+        REMOTE_RESPONSE_DEADLINE,
+        &|packet| {
+            packet.origin_id == unique_id
+                && matches!(packet.body, TinyMeshPacketBody::ConfigData { .. })
+        },
+        app_handle,
+    )?;
+    let TinyMeshPacketBody::ConfigData { data } = answer.body else {
+        return Err(CcToolError::Internal {
+            message: "Accepted a packet without configuration memory".to_string(),
+        });
+    };
+    return parse_device_config(&data, rmd_file_path, app_handle);
+}
+
+/// This function writes the changed cells of the configuration of a remote node through the connected gateway.
+/// The cached configuration of the node is updated with every cell the node acknowledged.
+/// # Arguments
+/// * `system_id` - The System ID of the network
+/// * `unique_id` - The unique ID of the remote node
+/// * `cells` - A vector of `MkDeviceCell` structs containing the new configuration
+/// * `session_id` - The ID of the session. If `None`, the default session is used.
+/// * `device_entity` - The state of the program (provided by Tauri)
+/// * `app_handle` - The Tauri application handle (provided by Tauri)
+///
+/// # Returns
+/// An `Ok(())` if all changed cells were acknowledged, `CcToolError::ConfigNotRead` if the configuration
+/// of the node was not read before, or the error that occurred while writing it.
+#[tauri::command]
+pub fn set_remote_device_config(
+    system_id: u32,
+    unique_id: u32,
+    cells: Vec<MkDeviceCell>,
+    session_id: Option<String>,
+    device_entity: State<DeviceEntity>,
+    app_handle: AppHandle,
+) -> Result<(), CcToolError> {
+    let _context = enter_command_context("set_remote_device_config");
+    let session = device_entity.session(session_id.as_deref())?;
+    let mut device_config = session
+        .remote_configs
+        .lock()?
+        .get(&unique_id)
+        .cloned()
+        .ok_or(CcToolError::ConfigNotRead)?;
+    let (device_config, result) = session.port.transact(move |device| {
+        let result = set_remote_device_config_on_device(
+            device,
+            system_id,
+            unique_id,
+            &mut device_config,
+            &cells,
+            Some(&app_handle),
+        );
+        Ok((device_config, result))
+    })?;
+    // The cached configuration holds the cells the node acknowledged, even if a later one failed
+    session
+        .remote_configs
+        .lock()?
+        .insert(unique_id, device_config);
+    return result;
+}
+
+/// This function writes the cells that differ from `device_config` to a remote node, one set
+/// configuration command per cell, each waiting for the acknowledge of the node.
+/// # Arguments
+/// * `device` - The transport connected to the gateway
+/// * `system_id` - The System ID of the network
+/// * `unique_id` - The unique ID of the remote node
+/// * `device_config` - The configuration last read from the node, updated with every acknowledged cell
+/// * `cells` - The new configuration cells
+/// * `app_handle` - An optional Tauri application handle, used for emitting the exchanged bytes
+///
+/// # Returns
+/// An `Ok(())` if all changed cells were acknowledged, `CcToolError::ValidationFailed` if a changed cell
+/// is outside of the range allowed by the RMD file or was rejected by the node,
+/// or the error that occurred while writing it.
+pub fn set_remote_device_config_on_device(
+    device: &mut dyn Transport,
+    system_id: u32,
+    unique_id: u32,
+    device_config: &mut MkDeviceConfig,
+    cells: &[MkDeviceCell],
+    app_handle: Option<&AppHandle>,
+) -> Result<(), CcToolError> {
+    validate_changed_cells(&device_config.cells, cells)?;
+    let changed_cells: Vec<MkDeviceCell> = cells
+        .iter()
+        .zip(&device_config.cells)
+        .filter(|(cell, current_cell)| cell.current_value != current_cell.current_value)
+        .map(|(cell, _)| cell.clone())
+        .collect();
+    if changed_cells.is_empty() {
+        return Err(CcToolError::InvalidInput {
+            message: "No configuration cells were changed".to_string(),
+        });
+    }
+    let builder = TinyMeshCommandBuilder::new(system_id, unique_id);
+    for cell in changed_cells {
+        let (command_number, packet) = builder.build(&TinyMeshCommand::SetConfig {
+            address: cell.address as u8,
+            value: cell.current_value,
+        })?;
+        info!(
+            "Writing cell 0x{:02X} = {} of node {:08X}",
+            cell.address, cell.current_value, unique_id
+        );
+        clear_output_buffer_of_device(device)?;
+        send_bytes_to_device(device, &packet, app_handle)?;
+        let answer = read_tinymesh_packet_from_device(
+            device,
+            REMOTE_RESPONSE_DEADLINE,
+            &|packet| {
+                packet.origin_id == unique_id
+                    && matches!(
+                        packet.body,
+                        TinyMeshPacketBody::CommandAck { command_number: number, .. }
+                            if number == command_number
+                    )
+            },
+            app_handle,
+        )?;
+        if let TinyMeshPacketBody::CommandAck {
+            acknowledged: false,
+            ..
+        } = answer.body
+        {
+            return Err(CcToolError::ValidationFailed {
+                address: cell.address,
+                message: format!(
+                    "Node {:08X} rejected the value {}",
+                    unique_id, cell.current_value
+                ),
+            });
+        }
+        if let Some(current_cell) = device_config
+            .cells
+            .iter_mut()
+            .find(|current_cell| current_cell.address == cell.address)
+        {
+            *current_cell = cell;
+        }
+    }
+    return Ok(());
+}
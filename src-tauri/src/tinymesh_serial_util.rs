@@ -1,265 +1,877 @@
 //! This module contains functions for interacting with the serial port.
 //! These functions are called by the Tauri frontend to communicate with the serial port.
 
-use crate::data_types::{DeviceEntity, EventPayload};
+use crate::cc_tool_error::CcToolError;
+use crate::data_types::{
+    ConnectionInfo, DeviceEntity, DeviceSession, EventKind, EventPayload, PacketEventPayload,
+    PortType, SerialPortEntry, SessionInfo,
+};
 use crate::input_processing::process_input;
+use crate::rx_packetizer::{split_into_frames, FrameRule, Packetizer, PacketizerConfig, RxFrame};
+use crate::serial_settings::{
+    get_serial_settings, open_serial_port, save_serial_settings, serial_settings_file_path,
+    SerialSettings,
+};
+use crate::task_manager::TASK_SHUTDOWN_TIMEOUT;
+use crate::tinymesh_mesh_mod::record_mesh_packet;
+use crate::tinymesh_packet::{
+    decode_tinymesh_packet, decode_tinymesh_packets, TinyMeshCommand, TinyMeshCommandBuilder,
+    TinyMeshPacket,
+};
+use crate::tinymesh_sniffer_mod::record_sniffer_output;
+use crate::tinymesh_test_mode_mod::turn_off_active_mode;
+use crate::traffic_capture::{enter_command_context, CaptureDirection, TrafficCapture};
+use crate::transport::{TcpTransport, Transport, TCP_TRANSPORT_PREFIX};
 use log::{error, info};
-use serialport::SerialPort;
-use std::time::Duration;
+use std::io::ErrorKind;
+use std::time::{Duration, Instant};
 use tauri::{AppHandle, Manager, State};
 
-/// This function resets the state of the program.
+/// This function stops the background tasks (RSSI scan, RX streaming etc.) of a session and closes its port.
+/// The tasks are cancelled and waited for first, so they can finish their current transaction.
+/// Then the active test or quick mode is turned off, so the module does not keep transmitting in a TX test mode.
+/// The cached configurations, calibration, mesh node table and sniffer statistics are dropped, since the next device may be a different module.
+/// The connection info is kept, so the connection can be restored.
+/// # Arguments
+/// * `session` - The session to tear down
+///
+/// # Returns
+/// An `Ok(())` if the session was torn down, or an error if its state could not be accessed.
+pub fn tear_down_session(session: &DeviceSession) -> Result<(), CcToolError> {
+    session.tasks.shutdown(TASK_SHUTDOWN_TIMEOUT)?;
+    if let Err(err) = turn_off_active_mode(session, None) {
+        error!("Could not turn off the active mode: {}", err);
+        *session.active_mode.lock()? = None;
+    }
+    // Transactions of tasks that did not stop in time fail with `NotConnected`
+    session.port.close()?;
+    *session.device_config.lock()? = None;
+    session.update_packet_mode(None)?;
+    *session.device_calib.lock()? = None;
+    session.remote_configs.lock()?.clear();
+    session.mesh_table.lock()?.clear();
+    *session.packet_sniffer.lock()? = Default::default();
+    *session.rssi_sniffer.lock()? = Default::default();
+    return Ok(());
+}
+
+/// This function resets the state of a session and removes it from the program state.
 /// It is called when a new connection is being made or when the device is disconnected.
 /// # Arguments
+/// * `session_id` - The ID of the session to reset. If `None`, the default session is reset.
 /// * `device_entity` - The state of the program (provided by Tauri)
 ///
 /// # Returns
-/// An `Ok(())` if the program state was reset successfully, or an error if the program state could not be reset.
+/// An `Ok(())` if the session was reset (or there was nothing to reset), or an error if the program state could not be reset.
 #[tauri::command]
-pub fn reset_program_state(device_entity: State<DeviceEntity>) -> Result<(), String> {
+pub fn reset_program_state(
+    session_id: Option<String>,
+    device_entity: State<DeviceEntity>,
+) -> Result<(), CcToolError> {
     info!("Resetting program state");
-    *device_entity.port.lock().map_err(|err| err.to_string())? = None;
-    *device_entity
-        .is_rssi_task_running
-        .lock()
-        .map_err(|err| err.to_string())? = false;
-    *device_entity
-        .is_communication_task_running
-        .lock()
-        .map_err(|err| err.to_string())? = false;
-    *device_entity
-        .device_config
-        .lock()
-        .map_err(|err| err.to_string())? = None;
-    *device_entity
-        .rssi_task
-        .lock()
-        .map_err(|err| err.to_string())? = None;
-    *device_entity
-        .communication_task
-        .lock()
-        .map_err(|err| err.to_string())? = None;
+    if let Some(session) = device_entity.remove_session(session_id.as_deref())? {
+        tear_down_session(&session)?;
+    }
     Ok(())
 }
 
+/// This function tears down all sessions, e.g. when the app is closed.
+/// Sessions that could not be torn down are logged and skipped.
+/// # Arguments
+/// * `device_entity` - The state of the program
+pub fn shut_down_all_sessions(device_entity: &DeviceEntity) {
+    let sessions = match device_entity.all_sessions() {
+        Ok(sessions) => sessions,
+        Err(err) => {
+            error!("Could not access the sessions: {}", err);
+            return;
+        }
+    };
+    for (session_id, session) in sessions {
+        info!("Shutting down session {}", session_id);
+        tear_down_session(&session)
+            .unwrap_or_else(|err| error!("Could not shut down session {}: {}", session_id, err));
+    }
+}
+
+/// USB vendor IDs of the adapters used with TinyMesh modules (FTDI, Silicon Labs CP210x)
+pub const KNOWN_ADAPTER_VIDS: [u16; 2] = [0x0403, 0x10C4];
+
+/// Returns whether the USB adapter is a known TinyMesh / FTDI / CP210x adapter.
+pub fn is_known_adapter(vid: u16, manufacturer: Option<&str>, product: Option<&str>) -> bool {
+    let mentions_tinymesh =
+        |name: Option<&str>| name.is_some_and(|name| name.to_lowercase().contains("tinymesh"));
+    return KNOWN_ADAPTER_VIDS.contains(&vid)
+        || mentions_tinymesh(manufacturer)
+        || mentions_tinymesh(product);
+}
+
+/// Converts the port information returned by `serialport` into the entry sent to the frontend.
+pub fn serial_port_entry_from_info(port_info: &serialport::SerialPortInfo) -> SerialPortEntry {
+    let mut entry = SerialPortEntry {
+        port_name: port_info.port_name.clone(),
+        port_type: PortType::Unknown,
+        vid: None,
+        pid: None,
+        serial_number: None,
+        manufacturer: None,
+        product: None,
+        is_known_adapter: false,
+    };
+    match &port_info.port_type {
+        serialport::SerialPortType::UsbPort(usb_info) => {
+            entry.port_type = PortType::Usb;
+            entry.vid = Some(usb_info.vid);
+            entry.pid = Some(usb_info.pid);
+            entry.serial_number = usb_info.serial_number.clone();
+            entry.manufacturer = usb_info.manufacturer.clone();
+            entry.product = usb_info.product.clone();
+            entry.is_known_adapter = is_known_adapter(
+                usb_info.vid,
+                usb_info.manufacturer.as_deref(),
+                usb_info.product.as_deref(),
+            );
+        }
+        serialport::SerialPortType::PciPort => entry.port_type = PortType::Pci,
+        serialport::SerialPortType::BluetoothPort => entry.port_type = PortType::Bluetooth,
+        serialport::SerialPortType::Unknown => entry.port_type = PortType::Unknown,
+    }
+    return entry;
+}
+
 /// This function returns a list of available serial ports on the system.
+/// # Arguments
+/// * `known_adapters_only` - If `true`, only ports of TinyMesh / FTDI / CP210x USB adapters are returned
 ///
 /// # Returns
-/// A vector of strings containing the names of the available serial ports.
+/// A vector of port entries with the USB metadata of their adapters,
+/// or an error if the ports could not be enumerated.
 #[tauri::command]
-pub fn get_devices() -> Vec<String> {
+pub fn get_devices(known_adapters_only: Option<bool>) -> Result<Vec<SerialPortEntry>, CcToolError> {
     info!("Getting available devices");
-    let ports = serialport::available_ports();
-    if let Ok(ports) = ports {
-        return ports.iter().map(|port| port.port_name.clone()).collect();
-    }
-    return vec![];
+    let ports = serialport::available_ports()?;
+    return Ok(ports
+        .iter()
+        .map(serial_port_entry_from_info)
+        .filter(|entry| !known_adapters_only.unwrap_or(false) || entry.is_known_adapter)
+        .collect());
 }
 
 /// This function returns the serial port name of the connected device.
 /// # Arguments
+/// * `session_id` - The ID of the session. If `None`, the default session is used.
 /// * `device_entity` - The state of the program (provided by Tauri)
 ///
 /// # Returns
 /// An optional string containing the serial port name of the connected device.
 /// If no device is connected, it returns None.
 #[tauri::command]
-pub fn get_connected_device(device_entity: State<DeviceEntity>) -> Option<String> {
+pub fn get_connected_device(
+    session_id: Option<String>,
+    device_entity: State<DeviceEntity>,
+) -> Result<Option<String>, CcToolError> {
     info!("Getting connected device");
-    if let Ok(device) = device_entity.port.lock() {
-        if let Some(device) = device.as_ref() {
-            info!("Connected device: {}", device.name().unwrap_or_default());
-            return device.name();
-        }
+    let session = match device_entity.session(session_id.as_deref()) {
+        Ok(session) => session,
+        Err(CcToolError::NotConnected | CcToolError::UnknownSession { .. }) => return Ok(None),
+        Err(err) => return Err(err),
+    };
+    let device_name = session.port.name()?;
+    info!(
+        "Connected device: {}",
+        device_name.clone().unwrap_or_default()
+    );
+    return Ok(device_name);
+}
+
+/// This function lists the sessions of all connected devices.
+/// # Arguments
+/// * `device_entity` - The state of the program (provided by Tauri)
+///
+/// # Returns
+/// A vector of session summaries sorted by session ID, or an error if the program state could not be accessed.
+#[tauri::command]
+pub fn list_sessions(device_entity: State<DeviceEntity>) -> Result<Vec<SessionInfo>, CcToolError> {
+    let default_session_id = device_entity.default_session_id.lock()?.clone();
+    let mut session_infos = vec![];
+    for (session_id, session) in device_entity.all_sessions()? {
+        session_infos.push(SessionInfo {
+            is_connected: session.port.is_open()?,
+            is_default: default_session_id.as_deref() == Some(session_id.as_str()),
+            connection: session.connection.lock()?.clone(),
+            session_id,
+        });
     }
-    return None;
+    return Ok(session_infos);
 }
 
-/// This function connects to the specified serial port with the specified baud rate.
+/// This function connects to the specified serial port with the specified serial line settings.
+/// Device names starting with `tcp://` are opened as a TCP socket instead (serial settings are ignored).
+/// The settings of a successful serial connection are saved for the port.
+/// The connection gets its own session, with the device name as session ID, which becomes the default session.
+/// An existing session of the same device is torn down first.
 /// # Arguments
-/// * `device_name` - The name of the serial port to connect to.
-/// * `baud_rate` - The baud rate to use when connecting to the serial port.
+/// * `device_name` - The name of the serial port (or `tcp://host:port` address) to connect to.
+/// * `serial_settings` - The serial line settings to use. If `None`, the settings saved for the port
+///   (or the defaults) are used.
 /// * `device_entity` - The state of the program (provided by Tauri)
+/// * `app_handle` - The Tauri application handle (provided by Tauri)
 ///
 /// # Returns
 /// An `Ok(())` if the connection was successful, or an error if the connection failed.
 #[tauri::command]
 pub fn connect_to_device(
     device_name: &str,
-    baud_rate: u32,
+    serial_settings: Option<SerialSettings>,
     device_entity: State<DeviceEntity>,
-) -> Result<(), String> {
-    info!("Connecting to {} with baud rate {}", device_name, baud_rate);
-    let port = serialport::new(device_name, baud_rate)
-        .data_bits(serialport::DataBits::Eight)
-        .timeout(Duration::from_millis(10))
-        .open();
-    let mut device = device_entity.port.lock().map_err(|err| err.to_string())?;
-    let open_port = port.map_err(|err| err.to_string())?;
-    *device = Some(open_port);
+    app_handle: AppHandle,
+) -> Result<(), CcToolError> {
+    let mut connection = None;
+    let port: Box<dyn Transport> = if device_name.starts_with(TCP_TRANSPORT_PREFIX) {
+        info!("Connecting to {}", device_name);
+        Box::new(TcpTransport::connect(
+            device_name,
+            Duration::from_millis(10),
+        )?)
+    } else {
+        let settings_file_path = serial_settings_file_path(&app_handle);
+        let serial_settings = match serial_settings {
+            Some(serial_settings) => serial_settings,
+            None => get_serial_settings(device_name, app_handle.clone())?,
+        };
+        info!("Connecting to {} with {:?}", device_name, serial_settings);
+        let port = open_serial_port(device_name, &serial_settings)?;
+        if let Some(settings_file_path) = settings_file_path {
+            save_serial_settings(&settings_file_path, device_name, &serial_settings)
+                .unwrap_or_else(|err| error!("Could not save serial settings: {}", err));
+        }
+        let serial_number = serialport::available_ports()
+            .unwrap_or_default()
+            .iter()
+            .map(serial_port_entry_from_info)
+            .find(|entry| entry.port_name == device_name)
+            .and_then(|entry| entry.serial_number);
+        connection = Some(ConnectionInfo {
+            port_name: device_name.to_string(),
+            serial_number,
+            serial_settings,
+        });
+        Box::new(port)
+    };
+    if let Some(session) = device_entity.remove_session(Some(device_name))? {
+        tear_down_session(&session)?;
+    }
+    device_entity.insert_session(
+        device_name,
+        DeviceSession::new(port, connection, Some(app_handle))?,
+    )?;
     return Ok(());
 }
 
-/// This function disconnects from the connected serial port.
+/// This function disconnects from the connected serial port and closes its session.
 /// # Arguments
+/// * `session_id` - The ID of the session. If `None`, the default session is used.
 /// * `device_entity` - The state of the program (provided by Tauri)
 ///
 /// # Returns
-/// A boolean value indicating whether the disconnect was successful or not.
+/// An `Ok(())` if the device was disconnected, or an error if the connection could not be accessed.
 #[tauri::command]
-pub fn disconnect_from_device(device_entity: State<DeviceEntity>) -> bool {
-    if let Ok(mut device) = device_entity.port.lock() {
-        info!("Disconnecting from device");
-        *device = None;
-        return true;
-    }
-    return false;
+pub fn disconnect_from_device(
+    session_id: Option<String>,
+    device_entity: State<DeviceEntity>,
+) -> Result<(), CcToolError> {
+    let session = device_entity
+        .remove_session(session_id.as_deref())?
+        .ok_or(CcToolError::NotConnected)?;
+    info!("Disconnecting from device");
+    tear_down_session(&session)?;
+    return Ok(());
 }
 
 /// This function sends bytes to the connected serial port and emits an event if the bytes were successfully sent.
 /// # Arguments
 /// * `input` - The bytes to send to the serial port
+/// * `session_id` - The ID of the session. If `None`, the default session is used.
 /// * `device_entity` - The state of the program (provided by Tauri)
 /// * `app_handle` - The Tauri application handle (provided by Tauri)
 ///
 /// # Returns
-/// A boolean value indicating whether the bytes were successfully sent.
+/// An `Ok(())` if the bytes were sent, `CcToolError::InvalidInput` if the input could not be
+/// converted to bytes, or an error if the device is not connected or the write failed.
 #[tauri::command]
 pub fn send_bytes(
     input: String,
+    session_id: Option<String>,
     device_entity: State<DeviceEntity>,
     app_handle: AppHandle,
-) -> bool {
-    let bytes_to_send: Vec<u8> = process_input(&input).unwrap_or_else(|e| {
-        error!("Error processing input: {:#?}", e);
-        vec![]
-    });
+) -> Result<(), CcToolError> {
+    let _context = enter_command_context("send_bytes");
+    let bytes_to_send: Vec<u8> = process_input(&input)?;
     info!("Sending bytes: {:?}", bytes_to_send);
-    if let Ok(mut device) = device_entity.port.lock() {
-        if let Some(device) = device.as_mut() {
-            let send_result = send_bytes_to_device(device, &bytes_to_send, &app_handle);
-            return send_result;
-        }
-    }
-    return false;
+    let session = device_entity.session(session_id.as_deref())?;
+    return session
+        .port
+        .transact(move |device| send_bytes_to_device(device, &bytes_to_send, Some(&app_handle)));
+}
+
+/// This function sends a command to a node through the connected gateway, which must be in packet mode.
+/// The packet is built from the command and the addresses, so it doesn't have to be assembled by hand.
+/// # Arguments
+/// * `system_id` - The System ID of the network
+/// * `destination` - The unique ID of the node
+/// * `command` - The command to send
+/// * `session_id` - The ID of the session. If `None`, the default session is used.
+/// * `device_entity` - The state of the program (provided by Tauri)
+/// * `app_handle` - The Tauri application handle (provided by Tauri)
+///
+/// # Returns
+/// The command number of the packet, which the node returns in its acknowledge,
+/// `CcToolError::InvalidInput` if a parameter of the command is out of range,
+/// or an error if the device is not connected or the write failed.
+#[tauri::command]
+pub fn send_packet_command(
+    system_id: u32,
+    destination: u32,
+    command: TinyMeshCommand,
+    session_id: Option<String>,
+    device_entity: State<DeviceEntity>,
+    app_handle: AppHandle,
+) -> Result<u8, CcToolError> {
+    let _context = enter_command_context("send_packet_command");
+    let (command_number, packet) =
+        TinyMeshCommandBuilder::new(system_id, destination).build(&command)?;
+    info!(
+        "Sending {:?} to node {:08X}: {:?}",
+        command, destination, packet
+    );
+    let session = device_entity.session(session_id.as_deref())?;
+    session
+        .port
+        .transact(move |device| send_bytes_to_device(device, &packet, Some(&app_handle)))?;
+    return Ok(command_number);
 }
 
 /// This function clears the output buffer of the connected serial device.
 /// # Arguments
+/// * `session_id` - The ID of the session. If `None`, the default session is used.
 /// * `device_entity` - The state of the program (provided by Tauri)
 ///
 /// # Returns
-/// A boolean value indicating whether the output buffer was cleared successfully.
+/// An `Ok(())` if the output buffer was cleared, or an error if it could not be cleared.
 #[tauri::command]
-pub fn clear_buffer(device_entity: State<DeviceEntity>) -> bool {
-    if let Ok(mut device) = device_entity.port.lock() {
-        if let Some(device) = device.as_mut() {
-            return clear_output_buffer_of_device(device);
-        }
-    }
-    return false;
+pub fn clear_buffer(
+    session_id: Option<String>,
+    device_entity: State<DeviceEntity>,
+) -> Result<(), CcToolError> {
+    let _context = enter_command_context("clear_buffer");
+    let session = device_entity.session(session_id.as_deref())?;
+    return session.port.transact(clear_output_buffer_of_device);
 }
 
 /// This function reads bytes from the connected serial device and returns them as a vector of bytes.
+/// The read bytes are emitted as one event per frame, grouped by the packetizer of the session.
 /// # Arguments
+/// * `session_id` - The ID of the session. If `None`, the default session is used.
 /// * `device_entity` - The state of the program (provided by Tauri)
 /// * `app_handle` - The Tauri application handle (provided by Tauri)
 ///
 /// # Returns
-/// A vector of bytes containing the read bytes from the serial device.
+/// A vector of bytes containing the read bytes from the serial device,
+/// or an error if the device is not connected or could not be read.
 #[tauri::command]
-pub fn read_bytes(device_entity: State<DeviceEntity>, app_handle: AppHandle) -> Vec<u8> {
-    let mut result = vec![];
-    if let Ok(mut device) = device_entity.port.lock() {
-        if let Some(device) = device.as_mut() {
-            read_bytes_from_device_to_buffer(device, &mut result, &app_handle);
-            return result;
+pub fn read_bytes(
+    session_id: Option<String>,
+    device_entity: State<DeviceEntity>,
+    app_handle: AppHandle,
+) -> Result<Vec<u8>, CcToolError> {
+    let _context = enter_command_context("read_bytes");
+    let session = device_entity.session(session_id.as_deref())?;
+    let packetizer = session.port.packetizer()?;
+    // The bytes received while passive RX was off are what the caller waits for, e.g. the `>` prompt
+    return session.port.transact_with_received(move |device| {
+        let mut result = vec![];
+        read_bytes_from_device_to_buffer(
+            device,
+            &mut result,
+            Some(&packetizer),
+            Some(&app_handle),
+        )?;
+        Ok(result)
+    });
+}
+
+/// Discards the bytes written to the device that were not transmitted yet.
+pub fn clear_output_buffer_of_device(device: &mut dyn Transport) -> Result<(), CcToolError> {
+    device.clear_output_buffer()?;
+    return Ok(());
+}
+
+/// Emits the exchanged bytes to the frontend logging panel, if we are running inside the app.
+/// TX and RX chunks are also written to the traffic capture, if one is running.
+/// The session ID is the name of the device the bytes were exchanged with.
+/// # Arguments
+/// * `app_handle` - An optional Tauri application handle, used for emitting the event
+/// * `session_id` - The session the bytes were exchanged in
+/// * `kind` - The kind of the event
+/// * `data` - The exchanged bytes
+/// * `captured_at` - The time at which the bytes were written or read
+pub fn emit_exchange_bytes_event(
+    app_handle: Option<&AppHandle>,
+    session_id: Option<String>,
+    kind: EventKind,
+    data: Vec<u8>,
+    captured_at: Instant,
+) {
+    if let Some(app_handle) = app_handle {
+        let direction = match kind {
+            EventKind::TX => Some(CaptureDirection::TX),
+            EventKind::RX => Some(CaptureDirection::RX),
+            EventKind::INFO | EventKind::ERROR => None,
+        };
+        if let (Some(direction), Some(capture)) =
+            (direction, app_handle.try_state::<TrafficCapture>())
+        {
+            capture
+                .record(direction, session_id.clone(), &data, captured_at)
+                .unwrap_or_else(|e| error!("Error capturing traffic: {}", e));
         }
+        app_handle
+            .emit_all(
+                "exchange_bytes_event",
+                EventPayload::new(session_id, kind, data, captured_at),
+            )
+            .unwrap_or_else(|e| error!("Error emitting: {}", e));
+    }
+}
+
+/// Emits a frame of received bytes as a RX event. If the gateway of the session is in packet mode
+/// (cell 0x03 Protocol mode = 0), the packets of the frame are decoded and each one is emitted as
+/// `packet_event` as well.
+/// # Arguments
+/// * `app_handle` - An optional Tauri application handle, used for emitting the events
+/// * `session_id` - The session the frame was received in
+/// * `frame` - The received frame
+pub fn emit_rx_frame(app_handle: Option<&AppHandle>, session_id: Option<String>, frame: RxFrame) {
+    let Some(app_handle) = app_handle else {
+        return;
+    };
+    let packets = if is_session_in_packet_mode(app_handle, session_id.as_deref()) {
+        decode_tinymesh_packets(&frame.data)
+    } else {
+        vec![]
+    };
+    record_sniffer_output(
+        app_handle,
+        session_id.as_deref(),
+        &frame.data,
+        frame.received_at,
+    );
+    emit_exchange_bytes_event(
+        Some(app_handle),
+        session_id.clone(),
+        EventKind::RX,
+        frame.data,
+        frame.received_at,
+    );
+    for packet in packets {
+        record_mesh_packet(app_handle, session_id.as_deref(), &packet, frame.received_at);
+        app_handle
+            .emit_all(
+                "packet_event",
+                PacketEventPayload::new(session_id.clone(), packet, frame.received_at),
+            )
+            .unwrap_or_else(|e| error!("Error emitting: {}", e));
     }
-    return result;
 }
 
-pub fn clear_output_buffer_of_device(device: &mut Box<dyn SerialPort>) -> bool {
-    return match device.clear(serialport::ClearBuffer::Output) {
-        Ok(()) => true,
-        Err(_) => false,
+/// Returns whether the device of the session is a gateway in packet mode.
+/// Sessions whose configuration was not read yet are treated as in transparent mode.
+fn is_session_in_packet_mode(app_handle: &AppHandle, session_id: Option<&str>) -> bool {
+    let Some(session_id) = session_id else {
+        return false;
+    };
+    let Some(session) = app_handle
+        .try_state::<DeviceEntity>()
+        .and_then(|device_entity| device_entity.session(Some(session_id)).ok())
+    else {
+        return false;
     };
+    return session
+        .is_packet_mode
+        .lock()
+        .is_ok_and(|is_packet_mode| *is_packet_mode);
+}
+
+/// Emits a text message (`INFO` or `ERROR`) to the frontend logging panel, if we are running inside the app.
+pub fn emit_terminal_message(
+    app_handle: Option<&AppHandle>,
+    session_id: Option<String>,
+    kind: EventKind,
+    message: &str,
+) {
+    emit_exchange_bytes_event(
+        app_handle,
+        session_id,
+        kind,
+        message.as_bytes().to_vec(),
+        Instant::now(),
+    );
 }
 
+/// Writes the bytes to the device and emits a TX event.
 pub fn send_bytes_to_device(
-    device: &mut Box<dyn SerialPort>,
+    device: &mut dyn Transport,
     bytes_to_send: &[u8],
-    app_handle: &AppHandle,
-) -> bool {
-    return match device.write_all(bytes_to_send) {
-        Ok(()) => {
-            device
-                .flush()
-                .unwrap_or_else(|e| error!("Error flushing: {}", e));
-            app_handle
-                .emit_all(
-                    "exchange_bytes_event",
-                    EventPayload {
-                        data_type: "TX".to_string(),
-                        data: bytes_to_send.to_vec(),
-                    },
-                )
-                .unwrap_or_else(|e| error!("Error emitting: {}", e));
-            true
+    app_handle: Option<&AppHandle>,
+) -> Result<(), CcToolError> {
+    device.write_all(bytes_to_send)?;
+    device
+        .flush()
+        .unwrap_or_else(|e| error!("Error flushing: {}", e));
+    let written_at = Instant::now();
+    emit_exchange_bytes_event(
+        app_handle,
+        device.name(),
+        EventKind::TX,
+        bytes_to_send.to_vec(),
+        written_at,
+    );
+    return Ok(());
+}
+
+/// Length of the configuration and calibration memory dumps (`0`, `r`)
+pub const MEMORY_DUMP_LEN: usize = 128;
+
+/// Describes how the response to a configuration mode command is framed.
+/// Responses are terminated by the `>` prompt, but binary payloads (such as memory dumps)
+/// can contain `0x3E` bytes themselves, so the reader needs a way to tell them apart.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FrameSpec {
+    /// Maximum time to wait for the complete response
+    pub deadline: Duration,
+    /// Maximum number of payload bytes before the read is aborted
+    pub max_len: usize,
+    /// Exact number of payload bytes preceding the prompt, if known.
+    /// `0x3E` bytes within the payload are treated as data.
+    pub payload_len: Option<usize>,
+    /// For payloads of unknown length: a `>` only terminates the frame if the line stays quiet
+    /// for this long after it. Otherwise it was a data byte.
+    pub prompt_quiet_time: Option<Duration>,
+}
+
+impl FrameSpec {
+    /// A response that consists of the prompt only, e.g. after `M`, `C` or a test mode command.
+    pub fn prompt_only() -> FrameSpec {
+        FrameSpec {
+            deadline: Duration::from_millis(1000),
+            max_len: 64,
+            payload_len: None,
+            prompt_quiet_time: None,
         }
-        Err(_) => false,
-    };
+    }
+
+    /// A short telemetry response (`S`, `A`, `D`, `U`, `V`) followed by the prompt.
+    pub fn telemetry(payload_len: Option<usize>) -> FrameSpec {
+        FrameSpec {
+            deadline: Duration::from_millis(1000),
+            max_len: 64,
+            payload_len,
+            prompt_quiet_time: payload_len.map_or(Some(Duration::from_millis(50)), |_| None),
+        }
+    }
+
+    /// A binary memory dump (`0`, `r`) of `MEMORY_DUMP_LEN` bytes followed by the prompt.
+    pub fn memory_dump() -> FrameSpec {
+        FrameSpec {
+            deadline: Duration::from_millis(3000),
+            max_len: 1024,
+            payload_len: Some(MEMORY_DUMP_LEN),
+            prompt_quiet_time: None,
+        }
+    }
+
+    /// Returns the same frame spec with a different deadline.
+    pub fn with_deadline(mut self, deadline: Duration) -> FrameSpec {
+        self.deadline = deadline;
+        self
+    }
 }
 
-pub fn read_bytes_till_3e_from_device_to_buffer(
-    device: &mut Box<dyn SerialPort>,
-    buffer: &mut Vec<u8>,
-    app_handle: &AppHandle,
-) -> usize {
-    // read into buf until we see 0x3e
-    let mut count = 0;
+/// The ways reading a framed response can fail.
+#[derive(Debug, PartialEq)]
+pub enum FrameError {
+    /// The prompt did not arrive before the deadline
+    Timeout { received: Vec<u8> },
+    /// More than `max_len` bytes arrived without a prompt
+    Overflow { received: Vec<u8> },
+    /// The transport failed
+    Io(String),
+}
+
+impl std::fmt::Display for FrameError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FrameError::Timeout { received } => write!(
+                f,
+                "Timed out waiting for the device prompt ({} bytes received)",
+                received.len()
+            ),
+            FrameError::Overflow { received } => write!(
+                f,
+                "Device response exceeded the maximum frame length ({} bytes received)",
+                received.len()
+            ),
+            FrameError::Io(err) => write!(f, "I/O error while reading from device: {}", err),
+        }
+    }
+}
+
+/// Reads a response terminated by the `>` prompt from the device and emits a RX event.
+/// The prompt itself is not part of the returned payload.
+/// # Arguments
+/// * `device` - The transport connected to the device
+/// * `frame_spec` - The deadline, maximum length and framing rules of the expected response
+/// * `app_handle` - An optional Tauri application handle, used for emitting the exchanged bytes
+///
+/// # Returns
+/// The payload preceding the prompt, or a `FrameError` describing why no complete frame was read.
+pub fn read_frame_from_device(
+    device: &mut dyn Transport,
+    frame_spec: &FrameSpec,
+    app_handle: Option<&AppHandle>,
+) -> Result<Vec<u8>, FrameError> {
+    let started_at = Instant::now();
+    let mut buffer = vec![];
+    // Time at which a candidate prompt was received, when waiting for the line to go quiet
+    let mut prompt_received_at: Option<Instant> = None;
     let mut temp_buf = [0u8; 1];
-    while buffer.len() == 0 || buffer[buffer.len() - 1] != 0x3e {
-        match device.read_exact(&mut temp_buf) {
+    // Time at which the first byte of the frame was read, the RX event is stamped with it
+    let mut first_byte_at: Option<Instant> = None;
+    let result = loop {
+        let now = Instant::now();
+        if let (Some(received_at), Some(quiet_time)) =
+            (prompt_received_at, frame_spec.prompt_quiet_time)
+        {
+            if now.duration_since(received_at) >= quiet_time {
+                break Ok(());
+            }
+        }
+        if now.duration_since(started_at) >= frame_spec.deadline {
+            if prompt_received_at.is_some() {
+                break Ok(());
+            }
+            break Err(FrameError::Timeout {
+                received: buffer.clone(),
+            });
+        }
+        match device.read(&mut temp_buf) {
+            Ok(0) => {
+                break Err(FrameError::Io("Connection closed".to_string()));
+            }
             Ok(_) => {
-                if temp_buf[0] == b'>' {
-                    break;
+                first_byte_at.get_or_insert_with(Instant::now);
+                if prompt_received_at.take().is_some() {
+                    // More bytes followed, so the earlier 0x3e was part of the payload
+                    buffer.push(b'>');
+                }
+                let payload_complete = frame_spec
+                    .payload_len
+                    .is_none_or(|payload_len| buffer.len() >= payload_len);
+                if temp_buf[0] == b'>' && payload_complete {
+                    if frame_spec.prompt_quiet_time.is_none() {
+                        break Ok(());
+                    }
+                    prompt_received_at = Some(Instant::now());
                 } else {
                     buffer.push(temp_buf[0]);
                 }
-                count += 1;
+                if buffer.len() > frame_spec.max_len {
+                    break Err(FrameError::Overflow {
+                        received: buffer.clone(),
+                    });
+                }
             }
-            Err(_) => continue,
+            Err(err)
+                if matches!(
+                    err.kind(),
+                    ErrorKind::TimedOut | ErrorKind::WouldBlock | ErrorKind::Interrupted
+                ) =>
+            {
+                continue
+            }
+            Err(err) => break Err(FrameError::Io(err.to_string())),
         }
+    };
+    let received_at = first_byte_at.unwrap_or_else(Instant::now);
+    match result {
+        Ok(()) => {
+            emit_exchange_bytes_event(
+                app_handle,
+                device.name(),
+                EventKind::RX,
+                [buffer.to_vec(), vec![0x3e]].concat(),
+                received_at,
+            );
+            Ok(buffer)
+        }
+        Err(err) => {
+            if !buffer.is_empty() {
+                emit_exchange_bytes_event(
+                    app_handle,
+                    device.name(),
+                    EventKind::RX,
+                    buffer.to_vec(),
+                    received_at,
+                );
+            }
+            error!("Error reading frame: {}", err);
+            emit_terminal_message(
+                app_handle,
+                device.name(),
+                EventKind::ERROR,
+                &format!("Error reading frame: {}", err),
+            );
+            Err(err)
+        }
+    }
+}
+
+/// Reads the packets a gateway in packet mode outputs until one of them is accepted.
+/// Every packet read is emitted, including the ones that are not accepted (e.g. events of other nodes).
+/// # Arguments
+/// * `device` - The transport connected to the gateway
+/// * `deadline` - Maximum time to wait for the accepted packet
+/// * `accept` - Returns whether a packet is the expected one
+/// * `app_handle` - An optional Tauri application handle, used for emitting the exchanged bytes
+///
+/// # Returns
+/// The accepted packet, `CcToolError::Timeout` (with all bytes received meanwhile) if it did not arrive
+/// before the deadline, or the error of the failed read.
+pub fn read_tinymesh_packet_from_device(
+    device: &mut dyn Transport,
+    deadline: Duration,
+    accept: &dyn Fn(&TinyMeshPacket) -> bool,
+    app_handle: Option<&AppHandle>,
+) -> Result<TinyMeshPacket, CcToolError> {
+    let started_at = Instant::now();
+    let mut packetizer = Packetizer::new(PacketizerConfig {
+        rule: FrameRule::TinyMeshPacket,
+        ..Default::default()
+    });
+    let mut received = vec![];
+    let mut accepted = None;
+    let mut temp_buf = [0u8; 256];
+    while accepted.is_none() && started_at.elapsed() < deadline {
+        let mut frames = match device.read(&mut temp_buf) {
+            Ok(0) => {
+                return Err(CcToolError::Io {
+                    message: "Connection closed".to_string(),
+                })
+            }
+            Ok(read) => packetizer.push(&temp_buf[..read], Instant::now()),
+            Err(err)
+                if matches!(
+                    err.kind(),
+                    ErrorKind::TimedOut | ErrorKind::WouldBlock | ErrorKind::Interrupted
+                ) =>
+            {
+                vec![]
+            }
+            Err(err) => return Err(CcToolError::from(err)),
+        };
+        frames.extend(packetizer.poll(Instant::now()));
+        for frame in frames {
+            received.extend_from_slice(&frame.data);
+            let packet = decode_tinymesh_packet(&frame.data).ok();
+            emit_rx_frame(app_handle, device.name(), frame);
+            if accepted.is_none() {
+                accepted = packet.filter(|packet| accept(packet));
+            }
+        }
+    }
+    // The bytes following the accepted packet are not part of any answer, but are shown anyway
+    if let Some(frame) = packetizer.flush() {
+        received.extend_from_slice(&frame.data);
+        emit_rx_frame(app_handle, device.name(), frame);
     }
-    app_handle
-        .emit_all(
-            "exchange_bytes_event",
-            EventPayload {
-                data_type: "RX".to_string(),
-                data: [buffer.to_vec(), vec![0x3e]].concat(),
-            },
-        )
-        .unwrap_or_else(|e| error!("Error emitting: {}", e));
-    count
+    return accepted.ok_or(CcToolError::Timeout { received });
 }
 
+/// Reads whatever bytes are currently available from the device and emits RX events.
+/// A read timeout only means that no (more) bytes are available and is not an error.
+/// # Arguments
+/// * `device` - The transport connected to the device
+/// * `buffer` - The buffer the read bytes are appended to
+/// * `packetizer` - How the read bytes are grouped into frames, one RX event is emitted per frame.
+///   If `None`, all read bytes are emitted as a single event.
+/// * `app_handle` - An optional Tauri application handle, used for emitting the exchanged bytes
+///
+/// # Returns
+/// The length of the buffer, or the error of the failed read.
 pub fn read_bytes_from_device_to_buffer(
-    device: &mut Box<dyn SerialPort>,
+    device: &mut dyn Transport,
     buffer: &mut Vec<u8>,
-    app_handle: &AppHandle,
-) -> usize {
-    let result = device.read_to_end(buffer).unwrap_or(0);
-    if buffer.len() > 0 {
-        app_handle
-            .emit_all(
-                "exchange_bytes_event",
-                EventPayload {
-                    data_type: "RX".to_string(),
-                    data: buffer.to_vec(),
-                },
-            )
-            .unwrap_or_else(|e| error!("Error emitting: {}", e));
+    packetizer: Option<&PacketizerConfig>,
+    app_handle: Option<&AppHandle>,
+) -> Result<usize, CcToolError> {
+    // The read chunks with the time at which they were read, for grouping them into frames
+    let mut chunks: Vec<(Instant, Vec<u8>)> = vec![];
+    let mut temp_buf = [0u8; 256];
+    let result = loop {
+        match device.read(&mut temp_buf) {
+            Ok(0) => break Ok(buffer.len()),
+            Ok(read) => {
+                buffer.extend_from_slice(&temp_buf[..read]);
+                chunks.push((Instant::now(), temp_buf[..read].to_vec()));
+            }
+            Err(err)
+                if matches!(
+                    err.kind(),
+                    ErrorKind::TimedOut | ErrorKind::WouldBlock | ErrorKind::Interrupted
+                ) =>
+            {
+                break Ok(buffer.len())
+            }
+            Err(err) => break Err(CcToolError::from(err)),
+        }
+    };
+    let frames = match packetizer {
+        Some(packetizer) => split_into_frames(packetizer, &chunks),
+        None => chunks
+            .first()
+            .map(|(received_at, _)| RxFrame {
+                received_at: *received_at,
+                data: chunks.iter().flat_map(|(_, bytes)| bytes.clone()).collect(),
+            })
+            .into_iter()
+            .collect(),
+    };
+    for frame in frames {
+        emit_rx_frame(app_handle, device.name(), frame);
+    }
+    return result;
+}
+
+/// Reads a response that should consist of the `>` prompt only.
+/// # Arguments
+/// * `device` - The transport connected to the device
+/// * `frame_spec` - The deadline, maximum length and framing rules of the expected response
+/// * `app_handle` - An optional Tauri application handle, used for emitting the exchanged bytes
+///
+/// # Returns
+/// An `Ok(())` if only the prompt was received, `CcToolError::UnexpectedResponse` if other bytes
+/// preceded it, or the error of the failed read.
+pub fn expect_prompt_from_device(
+    device: &mut dyn Transport,
+    frame_spec: &FrameSpec,
+    app_handle: Option<&AppHandle>,
+) -> Result<(), CcToolError> {
+    let frame = read_frame_from_device(device, frame_spec, app_handle)?;
+    if !frame.is_empty() {
+        return Err(CcToolError::UnexpectedResponse {
+            expected: ">".to_string(),
+            got: [frame, vec![b'>']].concat(),
+        });
     }
-    result
+    return Ok(());
 }
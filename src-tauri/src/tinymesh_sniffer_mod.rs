@@ -0,0 +1,235 @@
+//! This module contains functions for the sniffer test modes of a session.
+//! The sniffers are started and stopped by `execute_mode_sequence`, their output is parsed by the port
+//! actor while it streams the received bytes. See `packet_sniffer` and `rssi_sniffer` for the parsed
+//! output formats. The output is only parsed if enabled with `set_sniffer_output_parsing`.
+
+use crate::cc_tool_error::CcToolError;
+use crate::data_types::{
+    unix_timestamp_us, DeviceEntity, DeviceSession, RssiSnifferEventPayload, SnifferEventPayload,
+};
+use crate::packet_sniffer::{is_packet_sniffer_sequence, SnifferNodeStats};
+use crate::rssi_sniffer::{is_rssi_sniffer_sequence, RssiSourceStats};
+use crate::traffic_capture::enter_command_context;
+use log::{error, info};
+use std::time::Instant;
+use tauri::{AppHandle, Manager, State};
+
+/// This function enables or disables parsing the output of the sniffer test modes.
+/// It applies to all sessions, starting with the next sniffer test mode.
+/// # Arguments
+/// * `enabled` - Whether to parse the output
+/// * `device_entity` - The state of the program (provided by Tauri)
+///
+/// # Returns
+/// An `Ok(())` if the setting was changed, or an error if the program state could not be accessed.
+#[tauri::command]
+pub fn set_sniffer_output_parsing(
+    enabled: bool,
+    device_entity: State<DeviceEntity>,
+) -> Result<(), CcToolError> {
+    let _context = enter_command_context("set_sniffer_output_parsing");
+    info!("Sniffer output parsing: {}", enabled);
+    *device_entity.parse_sniffer_output.lock()? = enabled;
+    return Ok(());
+}
+
+/// This function returns the per-node statistics of the packet sniffer test mode of a session.
+/// The statistics of the last run are kept after the test mode was stopped.
+/// # Arguments
+/// * `session_id` - The ID of the session. If `None`, the default session is used.
+/// * `device_entity` - The state of the program (provided by Tauri)
+///
+/// # Returns
+/// The statistics ordered by unique ID, or an error if the session could not be accessed.
+#[tauri::command]
+pub fn get_sniffer_stats(
+    session_id: Option<String>,
+    device_entity: State<DeviceEntity>,
+) -> Result<Vec<SnifferNodeStats>, CcToolError> {
+    let _context = enter_command_context("get_sniffer_stats");
+    let session = device_entity.session(session_id.as_deref())?;
+    let stats = session.packet_sniffer.lock()?.stats();
+    return Ok(stats);
+}
+
+/// This function clears the per-node statistics of the packet sniffer test mode of a session.
+/// # Arguments
+/// * `session_id` - The ID of the session. If `None`, the default session is used.
+/// * `device_entity` - The state of the program (provided by Tauri)
+///
+/// # Returns
+/// An `Ok(())` if the statistics were cleared, or an error if the session could not be accessed.
+#[tauri::command]
+pub fn clear_sniffer_stats(
+    session_id: Option<String>,
+    device_entity: State<DeviceEntity>,
+) -> Result<(), CcToolError> {
+    let _context = enter_command_context("clear_sniffer_stats");
+    let session = device_entity.session(session_id.as_deref())?;
+    session.packet_sniffer.lock()?.clear_stats();
+    return Ok(());
+}
+
+/// This function returns the rolling RSSI statistics per source of the RSSI sniffer test mode of a session.
+/// The history of the last run is kept after the test mode was stopped.
+/// # Arguments
+/// * `session_id` - The ID of the session. If `None`, the default session is used.
+/// * `device_entity` - The state of the program (provided by Tauri)
+///
+/// # Returns
+/// The statistics in dBm, or an error if the session could not be accessed.
+#[tauri::command]
+pub fn get_rssi_sniffer_stats(
+    session_id: Option<String>,
+    device_entity: State<DeviceEntity>,
+) -> Result<Vec<RssiSourceStats>, CcToolError> {
+    let _context = enter_command_context("get_rssi_sniffer_stats");
+    let session = device_entity.session(session_id.as_deref())?;
+    let stats = session.rssi_sniffer.lock()?.stats();
+    return Ok(stats);
+}
+
+/// This function clears the RSSI history of the RSSI sniffer test mode of a session.
+/// # Arguments
+/// * `session_id` - The ID of the session. If `None`, the default session is used.
+/// * `device_entity` - The state of the program (provided by Tauri)
+///
+/// # Returns
+/// An `Ok(())` if the history was cleared, or an error if the session could not be accessed.
+#[tauri::command]
+pub fn clear_rssi_sniffer_history(
+    session_id: Option<String>,
+    device_entity: State<DeviceEntity>,
+) -> Result<(), CcToolError> {
+    let _context = enter_command_context("clear_rssi_sniffer_history");
+    let session = device_entity.session(session_id.as_deref())?;
+    session.rssi_sniffer.lock()?.clear_history();
+    return Ok(());
+}
+
+/// This function writes the RSSI history of the RSSI sniffer test mode of a session to a CSV file.
+/// # Arguments
+/// * `file_path` - The path of the file, which is replaced if it exists
+/// * `session_id` - The ID of the session. If `None`, the default session is used.
+/// * `device_entity` - The state of the program (provided by Tauri)
+///
+/// # Returns
+/// An `Ok(())` if the file was written, or an error if it could not be written.
+#[tauri::command]
+pub fn export_rssi_sniffer_csv(
+    file_path: String,
+    session_id: Option<String>,
+    device_entity: State<DeviceEntity>,
+) -> Result<(), CcToolError> {
+    let _context = enter_command_context("export_rssi_sniffer_csv");
+    let session = device_entity.session(session_id.as_deref())?;
+    let csv = session.rssi_sniffer.lock()?.to_csv();
+    std::fs::write(&file_path, csv)?;
+    info!("Exported the RSSI sniffer history to {}", file_path);
+    return Ok(());
+}
+
+/// Starts the parser of the sniffer test mode a mode sequence starts, and stops the other one.
+/// Any sequence that does not start a sniffer (e.g. the `SEQUENCE_OFF` of the sniffer) stops both.
+/// # Arguments
+/// * `session` - The session the sequence is executed in
+/// * `sequence_str` - The mode sequence
+/// * `app_handle` - An optional Tauri application handle, used for reading whether the output is parsed.
+///   Without it the output is not parsed.
+///
+/// # Returns
+/// Whether the sequence starts a sniffer, or an error if the state of the session could not be accessed.
+pub fn start_sniffer_of_sequence(
+    session: &DeviceSession,
+    sequence_str: &str,
+    app_handle: Option<&AppHandle>,
+) -> Result<bool, CcToolError> {
+    let parse_sniffer_output =
+        match app_handle.and_then(|app_handle| app_handle.try_state::<DeviceEntity>()) {
+            Some(device_entity) => *device_entity.parse_sniffer_output.lock()?,
+            None => false,
+        };
+    let device_config = session.device_config.lock()?.clone();
+    let is_packet_sniffer = is_packet_sniffer_sequence(device_config.as_ref(), sequence_str);
+    let is_rssi_sniffer = is_rssi_sniffer_sequence(device_config.as_ref(), sequence_str);
+    let rssi_scaling = device_config.map(|device_config| device_config.rssi_scaling);
+    session
+        .packet_sniffer
+        .lock()?
+        .set_running(is_packet_sniffer && parse_sniffer_output);
+    session.rssi_sniffer.lock()?.set_running(
+        is_rssi_sniffer && parse_sniffer_output,
+        rssi_scaling.unwrap_or_default(),
+    );
+    return Ok(is_packet_sniffer || is_rssi_sniffer);
+}
+
+/// Stops the parsers of the sniffer test modes of a session.
+pub fn stop_sniffers(session: &DeviceSession) -> Result<(), CcToolError> {
+    session.packet_sniffer.lock()?.set_running(false);
+    session
+        .rssi_sniffer
+        .lock()?
+        .set_running(false, Default::default());
+    return Ok(());
+}
+
+/// Parses received bytes with the running sniffer of the session, and emits every completed line
+/// as `sniffer_event` (packet sniffer) or `rssi_sniffer_event` (RSSI sniffer).
+/// # Arguments
+/// * `app_handle` - The Tauri application handle
+/// * `session_id` - The session the bytes were received in
+/// * `bytes` - The received bytes
+/// * `received_at` - The time at which the bytes were received
+pub fn record_sniffer_output(
+    app_handle: &AppHandle,
+    session_id: Option<&str>,
+    bytes: &[u8],
+    received_at: Instant,
+) {
+    let Some(session_id) = session_id else {
+        return;
+    };
+    let Some(session) = app_handle
+        .try_state::<DeviceEntity>()
+        .and_then(|device_entity| device_entity.session(Some(session_id)).ok())
+    else {
+        return;
+    };
+    let (packet_reports, rssi_reports) =
+        match (session.packet_sniffer.lock(), session.rssi_sniffer.lock()) {
+            (Ok(mut packet_sniffer), Ok(mut rssi_sniffer)) => (
+                packet_sniffer.push(bytes, received_at),
+                rssi_sniffer.push(bytes, received_at),
+            ),
+            _ => {
+                error!("Error parsing the sniffer output: the sniffer state is poisoned");
+                return;
+            }
+        };
+    let timestamp_us = unix_timestamp_us(received_at);
+    for report in packet_reports {
+        app_handle
+            .emit_all(
+                "sniffer_event",
+                SnifferEventPayload {
+                    session_id: Some(session_id.to_string()),
+                    report,
+                    timestamp_us,
+                },
+            )
+            .unwrap_or_else(|e| error!("Error emitting: {}", e));
+    }
+    for report in rssi_reports {
+        app_handle
+            .emit_all(
+                "rssi_sniffer_event",
+                RssiSnifferEventPayload {
+                    session_id: Some(session_id.to_string()),
+                    report,
+                    timestamp_us,
+                },
+            )
+            .unwrap_or_else(|e| error!("Error emitting: {}", e));
+    }
+}
@@ -0,0 +1,54 @@
+//! This module contains the Tauri commands for inspecting and cancelling the background tasks of the sessions.
+
+use crate::cc_tool_error::CcToolError;
+use crate::data_types::DeviceEntity;
+use crate::task_manager::{TaskId, TaskInfo};
+use tauri::State;
+
+/// This function lists the background tasks (running ones and recently finished ones).
+/// # Arguments
+/// * `session_id` - The ID of the session whose tasks are listed. If `None`, the tasks of all sessions are listed.
+/// * `device_entity` - The state of the program (provided by Tauri)
+///
+/// # Returns
+/// A vector of task summaries (sorted by session ID, oldest task first),
+/// or an error if the session does not exist.
+#[tauri::command]
+pub fn list_tasks(
+    session_id: Option<String>,
+    device_entity: State<DeviceEntity>,
+) -> Result<Vec<TaskInfo>, CcToolError> {
+    let sessions = match session_id {
+        Some(session_id) => vec![(
+            session_id.clone(),
+            device_entity.session(Some(&session_id))?,
+        )],
+        None => device_entity.all_sessions()?,
+    };
+    let mut task_infos = vec![];
+    for (session_id, session) in sessions {
+        for mut task_info in session.tasks.list()? {
+            task_info.session_id = Some(session_id.clone());
+            task_infos.push(task_info);
+        }
+    }
+    return Ok(task_infos);
+}
+
+/// This function asks a background task to stop. It returns without waiting for the task to finish,
+/// `list_tasks` shows the task as `Cancelling` until it did.
+/// # Arguments
+/// * `task_id` - The ID of the task
+/// * `device_entity` - The state of the program (provided by Tauri)
+///
+/// # Returns
+/// An `Ok(())` if the task was found, or `CcToolError::UnknownTask` if no session has a task with this ID.
+#[tauri::command]
+pub fn cancel_task(task_id: TaskId, device_entity: State<DeviceEntity>) -> Result<(), CcToolError> {
+    for (_, session) in device_entity.all_sessions()? {
+        if session.tasks.contains(task_id)? {
+            return session.tasks.cancel(task_id);
+        }
+    }
+    return Err(CcToolError::UnknownTask { task_id });
+}
@@ -0,0 +1,351 @@
+//! This module contains functions for the test and quick mode a session's device was put in.
+//! Every executed mode sequence that is the `SEQUENCE_ON` of a mode of the RMD file makes it the active
+//! mode, until its `SEQUENCE_OFF` is executed. Switching to another mode executes the `SEQUENCE_OFF` of
+//! the active mode first, and tearing down the session (disconnect, `reset_program_state`, app shutdown)
+//! turns the active mode off, so a module is never left transmitting in a TX test mode.
+//! The TX test modes are watched by the session's TX guard, which turns them off when they reach the
+//! maximum on-time or duty cycle of the RMD file or user setting, and emits `tx_guard_event`.
+
+use crate::cc_tool_error::CcToolError;
+use crate::data_types::{
+    ActiveMode, DeviceEntity, DeviceSession, TestModeEventPayload, TxGuardEventPayload,
+};
+use crate::mode_sequence::SequenceStepTrace;
+use crate::tinymesh_config_mod::execute_mode_sequence_on_device;
+use crate::tinymesh_sniffer_mod::{start_sniffer_of_sequence, stop_sniffers};
+use crate::traffic_capture::enter_command_context;
+use crate::tx_guard::{
+    save_tx_guard_config, tx_guard_config_file_path, TxGuardConfig, TxGuardLimit, TxGuardStatus,
+};
+use log::{error, info, warn};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Manager, State};
+
+/// Name of the task that watches the limits of the TX test modes of a session
+pub const TX_GUARD_TASK: &str = "tx_guard";
+
+/// How often the TX guard task checks the limits
+const TX_GUARD_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+/// This function returns the test or quick mode the device of a session is in.
+/// # Arguments
+/// * `session_id` - The ID of the session. If `None`, the default session is used.
+/// * `device_entity` - The state of the program (provided by Tauri)
+///
+/// # Returns
+/// The active mode, `None` if no mode is active, or an error if the session could not be accessed.
+#[tauri::command]
+pub fn get_active_mode(
+    session_id: Option<String>,
+    device_entity: State<DeviceEntity>,
+) -> Result<Option<ActiveMode>, CcToolError> {
+    let _context = enter_command_context("get_active_mode");
+    let session = device_entity.session(session_id.as_deref())?;
+    let active_mode = session.active_mode.lock()?.clone();
+    return Ok(active_mode);
+}
+
+/// This function executes the `SEQUENCE_OFF` of the active mode of a session.
+/// # Arguments
+/// * `session_id` - The ID of the session. If `None`, the default session is used.
+/// * `device_entity` - The state of the program (provided by Tauri)
+/// * `app_handle` - The Tauri application handle (provided by Tauri)
+///
+/// # Returns
+/// The trace of the `SEQUENCE_OFF` (empty if no mode is active), or the error of the failed sequence,
+/// in which case the mode stays active.
+#[tauri::command]
+pub fn stop_active_mode(
+    session_id: Option<String>,
+    device_entity: State<DeviceEntity>,
+    app_handle: AppHandle,
+) -> Result<Vec<SequenceStepTrace>, CcToolError> {
+    let _context = enter_command_context("stop_active_mode");
+    let session = device_entity.session(session_id.as_deref())?;
+    return turn_off_active_mode(&session, Some(&app_handle));
+}
+
+/// This function sets the limits of the TX test modes, replacing the ones of the RMD files.
+/// They apply to all sessions, starting with the next activation of a TX test mode, and are saved
+/// for the next start of the program.
+/// # Arguments
+/// * `config` - The limits, or `None` to use the ones of the RMD files again
+/// * `device_entity` - The state of the program (provided by Tauri)
+/// * `app_handle` - The Tauri application handle (provided by Tauri)
+///
+/// # Returns
+/// An `Ok(())` if the setting was changed, or an error if the program state could not be accessed
+/// or the setting could not be saved.
+#[tauri::command]
+pub fn set_tx_guard_config(
+    config: Option<TxGuardConfig>,
+    device_entity: State<DeviceEntity>,
+    app_handle: AppHandle,
+) -> Result<(), CcToolError> {
+    let _context = enter_command_context("set_tx_guard_config");
+    info!("TX guard config: {:?}", config);
+    if let Some(file_path) = tx_guard_config_file_path(&app_handle) {
+        save_tx_guard_config(&file_path, config.as_ref())?;
+    }
+    // Sessions inserted meanwhile copy the setting while holding the sessions lock
+    let sessions = device_entity.sessions.lock()?;
+    *device_entity.tx_guard_config.lock()? = config;
+    for session in sessions.values() {
+        session.tx_guard.lock()?.set_user_config(config);
+    }
+    return Ok(());
+}
+
+/// This function returns the on-time and duty cycle usage of the TX test modes of a session.
+/// # Arguments
+/// * `session_id` - The ID of the session. If `None`, the default session is used.
+/// * `device_entity` - The state of the program (provided by Tauri)
+///
+/// # Returns
+/// The state of the TX guard, or an error if the session could not be accessed.
+#[tauri::command]
+pub fn get_tx_guard_status(
+    session_id: Option<String>,
+    device_entity: State<DeviceEntity>,
+) -> Result<TxGuardStatus, CcToolError> {
+    let _context = enter_command_context("get_tx_guard_status");
+    let session = device_entity.session(session_id.as_deref())?;
+    let status = session.tx_guard.lock()?.status(Instant::now());
+    return Ok(status);
+}
+
+/// This function executes a mode sequence in a session and keeps track of the mode it puts the device in.
+/// If the sequence starts another mode than the active one, the `SEQUENCE_OFF` of the active mode is
+/// executed first, unless the sequence is that `SEQUENCE_OFF` itself. A sequence can only be tracked
+/// once the configuration was read, so non-empty sequences are refused before. If the sequence starts the packet or RSSI sniffer test mode, its output is streamed
+/// (and parsed if enabled) until another sequence is executed. A TX test mode is not started while its duty cycle budget is used up.
+/// # Arguments
+/// * `session` - The session to execute the sequence in
+/// * `sequence_str` - The mode sequence to execute
+/// * `app_handle` - An optional Tauri application handle, used for emitting the exchanged bytes and `test_mode_event`
+///
+/// # Returns
+/// The trace of the executed steps of the sequence, `CcToolError::ConfigNotRead` if the configuration
+/// was not read, `CcToolError::TxLimitReached` if the TX test mode can't be started, or the error of the
+/// failed sequence. A TX test mode whose sequence failed is
+/// turned off with its `SEQUENCE_OFF`.
+pub fn execute_mode_sequence_in_session(
+    session: &DeviceSession,
+    sequence_str: &str,
+    app_handle: Option<&AppHandle>,
+) -> Result<Vec<SequenceStepTrace>, CcToolError> {
+    let device_config = session.device_config.lock()?.clone();
+    if device_config.is_none() && !sequence_str.trim().is_empty() {
+        return Err(CcToolError::ConfigNotRead);
+    }
+    let active_mode = session.active_mode.lock()?.clone();
+    // A `SEQUENCE_OFF` may also be the `SEQUENCE_ON` of another mode (e.g. TX off is RX on)
+    let started_mode = device_config
+        .as_ref()
+        .and_then(|device_config| device_config.mode_started_by(sequence_str));
+    let is_turning_off = active_mode
+        .as_ref()
+        .is_some_and(|active_mode| active_mode.sequence_off.trim() == sequence_str.trim());
+    if let (Some(started_mode), Some(device_config)) = (&started_mode, &device_config) {
+        if started_mode.is_tx && active_mode.as_ref() != Some(started_mode) {
+            let tx_guard = session.tx_guard.lock()?;
+            let config = tx_guard.config_for(&device_config.tx_guard);
+            tx_guard.check_start(&config, Instant::now())?;
+        }
+    }
+    if started_mode.is_some()
+        && active_mode.is_some()
+        && started_mode != active_mode
+        && !is_turning_off
+    {
+        turn_off_active_mode(session, app_handle)?;
+    }
+
+    // Parsing follows the test mode the device is put in
+    let is_sniffer = start_sniffer_of_sequence(session, sequence_str, app_handle)?;
+    let sequence_str = sequence_str.to_string();
+    let transaction_app_handle = app_handle.cloned();
+    let result = session.port.transact(move |device| {
+        execute_mode_sequence_on_device(device, &sequence_str, transaction_app_handle.as_ref())
+    });
+    if let Err(err) = &result {
+        stop_sniffers(session)?;
+        // The `SEQUENCE_ON` may have reached the device even though the sequence failed (e.g. the prompt
+        // did not come back), so a TX mode is recorded and turned off again
+        if let Some(started_mode) = started_mode.filter(|started_mode| started_mode.is_tx) {
+            warn!(
+                "Turning off {} after its sequence failed: {}",
+                started_mode.name, err
+            );
+            set_active_mode(session, Some(started_mode), app_handle)?;
+            if let Err(err) = turn_off_active_mode(session, app_handle) {
+                // The mode stays active, so the TX guard keeps trying to turn it off
+                error!("Could not turn off the TX test mode: {}", err);
+            }
+        }
+        return result;
+    }
+    if is_sniffer {
+        // The sniffer output arrives between transactions
+        session.port.set_passive_rx(true)?;
+    }
+    if is_turning_off || started_mode.is_some() {
+        set_active_mode(session, started_mode, app_handle)?;
+    }
+    return result;
+}
+
+/// This function executes the `SEQUENCE_OFF` of the active mode of a session and stops the sniffers.
+/// # Arguments
+/// * `session` - The session whose mode to turn off
+/// * `app_handle` - An optional Tauri application handle, used for emitting the exchanged bytes and `test_mode_event`
+///
+/// # Returns
+/// The trace of the `SEQUENCE_OFF` (empty if no mode is active or the port is closed), or the error of the
+/// failed sequence, in which case the mode stays active.
+pub fn turn_off_active_mode(
+    session: &DeviceSession,
+    app_handle: Option<&AppHandle>,
+) -> Result<Vec<SequenceStepTrace>, CcToolError> {
+    let Some(active_mode) = session.active_mode.lock()?.clone() else {
+        return Ok(vec![]);
+    };
+    let mut trace = vec![];
+    // There is nothing to turn off after the adapter was unplugged
+    if session.port.is_open()? {
+        info!("Turning off {}", active_mode.name);
+        let transaction_app_handle = app_handle.cloned();
+        trace = session.port.transact(move |device| {
+            execute_mode_sequence_on_device(
+                device,
+                &active_mode.sequence_off,
+                transaction_app_handle.as_ref(),
+            )
+        })?;
+    }
+    stop_sniffers(session)?;
+    set_active_mode(session, None, app_handle)?;
+    return Ok(trace);
+}
+
+/// This function turns off the TX test mode of a session if it reached a limit of the TX guard,
+/// and emits `tx_guard_event`.
+/// # Arguments
+/// * `session` - The session whose TX test mode to check
+/// * `app_handle` - An optional Tauri application handle, used for emitting the exchanged bytes and the events
+/// * `now` - The current time
+///
+/// # Returns
+/// The limit that was reached (`None` if no TX test mode is on or it is within its limits),
+/// or the error of the failed `SEQUENCE_OFF`, in which case the mode stays active.
+pub fn enforce_tx_guard(
+    session: &DeviceSession,
+    app_handle: Option<&AppHandle>,
+    now: Instant,
+) -> Result<Option<TxGuardLimit>, CcToolError> {
+    let (limit, status) = {
+        let tx_guard = session.tx_guard.lock()?;
+        let Some(limit) = tx_guard.limit_reached(now) else {
+            return Ok(None);
+        };
+        (limit, tx_guard.status(now))
+    };
+    let mode_name = session
+        .active_mode
+        .lock()?
+        .as_ref()
+        .map(|active_mode| active_mode.name.clone())
+        .unwrap_or_default();
+    warn!("{} reached the TX limit {:?}", mode_name, limit);
+    turn_off_active_mode(session, app_handle)?;
+    if let Some(app_handle) = app_handle {
+        app_handle
+            .emit_all(
+                "tx_guard_event",
+                TxGuardEventPayload {
+                    session_id: session.port.name()?,
+                    limit,
+                    mode_name,
+                    status,
+                },
+            )
+            .unwrap_or_else(|e| error!("Error emitting: {}", e));
+    }
+    return Ok(Some(limit));
+}
+
+/// Starts the task that enforces the limits of the TX test modes of a session, unless it is running.
+/// It runs until the session is torn down.
+fn start_tx_guard_task(
+    session: &DeviceSession,
+    app_handle: Option<&AppHandle>,
+) -> Result<(), CcToolError> {
+    if session.tasks.find_running(TX_GUARD_TASK)?.is_some() {
+        return Ok(());
+    }
+    let task_session = session.clone();
+    let app_handle = app_handle.cloned();
+    session.tasks.spawn(TX_GUARD_TASK, move |token| {
+        let mut limits_reached = 0;
+        while !token.wait_timeout(TX_GUARD_CHECK_INTERVAL) {
+            match enforce_tx_guard(&task_session, app_handle.as_ref(), Instant::now()) {
+                Ok(Some(_)) => limits_reached += 1,
+                Ok(None) => {}
+                Err(CcToolError::NotConnected) => return Err(CcToolError::NotConnected),
+                Err(err) => error!("Error turning off TX test mode: {}", err),
+            }
+        }
+        return Ok(format!("{} limits reached", limits_reached));
+    })?;
+    return Ok(());
+}
+
+/// Changes the active mode of a session, starts or stops the TX guard and emits it as `test_mode_event`.
+fn set_active_mode(
+    session: &DeviceSession,
+    active_mode: Option<ActiveMode>,
+    app_handle: Option<&AppHandle>,
+) -> Result<(), CcToolError> {
+    let mut session_active_mode = session.active_mode.lock()?;
+    if *session_active_mode == active_mode {
+        return Ok(());
+    }
+    *session_active_mode = active_mode.clone();
+    drop(session_active_mode);
+
+    let now = Instant::now();
+    let mut tx_guard = session.tx_guard.lock()?;
+    tx_guard.stop(now);
+    if active_mode
+        .as_ref()
+        .is_some_and(|active_mode| active_mode.is_tx)
+    {
+        let rmd_config = session
+            .device_config
+            .lock()?
+            .as_ref()
+            .map(|device_config| device_config.tx_guard)
+            .unwrap_or_default();
+        let config = tx_guard.config_for(&rmd_config);
+        tx_guard.start(config, now);
+        drop(tx_guard);
+        if config.is_enabled() {
+            start_tx_guard_task(session, app_handle)?;
+        }
+    } else {
+        drop(tx_guard);
+    }
+
+    if let Some(app_handle) = app_handle {
+        app_handle
+            .emit_all(
+                "test_mode_event",
+                TestModeEventPayload {
+                    session_id: session.port.name()?,
+                    active_mode,
+                },
+            )
+            .unwrap_or_else(|e| error!("Error emitting: {}", e));
+    }
+    return Ok(());
+}
@@ -0,0 +1,322 @@
+//! This module contains the traffic capture, which writes every chunk of bytes exchanged with the
+//! devices to a file, and the replay of such captures.
+//!
+//! # Capture format
+//! A capture is a JSON-lines file: one JSON object per line, one line per TX or RX chunk, in the order
+//! the chunks were exchanged. For example:
+//! ```text
+//! {"timestamp_us":1520,"direction":"TX","session_id":"/dev/ttyUSB0","context":"get_device_config","data":[48]}
+//! {"timestamp_us":9874,"direction":"RX","session_id":"/dev/ttyUSB0","context":"get_device_config","data":[62]}
+//! ```
+//! * `timestamp_us` - Monotonic time since the capture was started, in microseconds
+//! * `direction` - `TX` (sent to the device) or `RX` (received from the device)
+//! * `session_id` - The session (port name or `tcp://` address) the chunk was exchanged with
+//! * `context` - The command or task the chunk was exchanged for, `null` for passively received bytes
+//! * `data` - The bytes of the chunk
+
+use crate::cc_tool_error::CcToolError;
+use crate::transport::Transport;
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Instant;
+
+/// Direction of a captured chunk
+#[derive(Clone, Copy, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum CaptureDirection {
+    TX,
+    RX,
+}
+
+/// A single line of a capture file
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct CaptureRecord {
+    pub timestamp_us: u64,
+    pub direction: CaptureDirection,
+    pub session_id: Option<String>,
+    pub context: Option<String>,
+    pub data: Vec<u8>,
+}
+
+thread_local! {
+    static COMMAND_CONTEXT: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// Restores the previous command context of the thread when dropped
+pub struct CommandContextGuard {
+    previous: Option<String>,
+}
+
+impl Drop for CommandContextGuard {
+    fn drop(&mut self) {
+        let previous = self.previous.take();
+        COMMAND_CONTEXT.with(|context| *context.borrow_mut() = previous);
+    }
+}
+
+/// Sets the command context of the current thread, which is recorded with the captured chunks,
+/// until the returned guard is dropped.
+/// Transactions of the port actor inherit the context of the thread that queued them.
+/// # Arguments
+/// * `context` - The name of the command or task, e.g. `get_device_config`
+pub fn enter_command_context(context: &str) -> CommandContextGuard {
+    let previous =
+        COMMAND_CONTEXT.with(|current| current.borrow_mut().replace(context.to_string()));
+    return CommandContextGuard { previous };
+}
+
+/// Returns the command context of the current thread.
+pub fn current_command_context() -> Option<String> {
+    return COMMAND_CONTEXT.with(|context| context.borrow().clone());
+}
+
+struct CaptureWriter {
+    file: File,
+    path: PathBuf,
+    started_at: Instant,
+    record_count: usize,
+}
+
+/// The running capture, if any (managed by Tauri)
+#[derive(Default)]
+pub struct TrafficCapture {
+    writer: Mutex<Option<CaptureWriter>>,
+}
+
+impl TrafficCapture {
+    /// Starts capturing to the given file, replacing its contents. A running capture is stopped first.
+    /// # Arguments
+    /// * `path` - The path of the capture file
+    ///
+    /// # Returns
+    /// An `Ok(())` if the file was created, or an error if it could not be.
+    pub fn start(&self, path: &Path) -> Result<(), CcToolError> {
+        let file = File::create(path)?;
+        *self.writer.lock()? = Some(CaptureWriter {
+            file,
+            path: path.to_path_buf(),
+            started_at: Instant::now(),
+            record_count: 0,
+        });
+        return Ok(());
+    }
+
+    /// Stops the running capture.
+    /// # Returns
+    /// The path of the capture file and the number of records written, or `None` if no capture was running.
+    pub fn stop(&self) -> Result<Option<(PathBuf, usize)>, CcToolError> {
+        return Ok(self
+            .writer
+            .lock()?
+            .take()
+            .map(|writer| (writer.path, writer.record_count)));
+    }
+
+    /// Returns whether a capture is running.
+    pub fn is_running(&self) -> Result<bool, CcToolError> {
+        return Ok(self.writer.lock()?.is_some());
+    }
+
+    /// Writes a chunk to the running capture, with the command context of the current thread.
+    /// Does nothing if no capture is running.
+    /// # Arguments
+    /// * `direction` - Whether the chunk was sent or received
+    /// * `session_id` - The session the chunk was exchanged with
+    /// * `data` - The bytes of the chunk
+    /// * `captured_at` - The time at which the chunk was written or read
+    ///
+    /// # Returns
+    /// An `Ok(())` if the chunk was written (or no capture is running), or an error if writing the file failed.
+    pub fn record(
+        &self,
+        direction: CaptureDirection,
+        session_id: Option<String>,
+        data: &[u8],
+        captured_at: Instant,
+    ) -> Result<(), CcToolError> {
+        let mut writer = self.writer.lock()?;
+        let Some(writer) = writer.as_mut() else {
+            return Ok(());
+        };
+        let record = CaptureRecord {
+            timestamp_us: captured_at
+                .saturating_duration_since(writer.started_at)
+                .as_micros() as u64,
+            direction,
+            session_id,
+            context: current_command_context(),
+            data: data.to_vec(),
+        };
+        let line = serde_json::to_string(&record).map_err(|err| CcToolError::Internal {
+            message: err.to_string(),
+        })?;
+        // Every record is written right away, so the capture survives a crash of the app
+        writeln!(writer.file, "{}", line)?;
+        writer.record_count += 1;
+        return Ok(());
+    }
+}
+
+/// This function reads a capture file.
+/// # Arguments
+/// * `path` - The path of the capture file
+///
+/// # Returns
+/// The records of the capture, or `CcToolError::InvalidInput` (with the line number) if a line is not a valid record.
+pub fn read_capture_file(path: &Path) -> Result<Vec<CaptureRecord>, CcToolError> {
+    let reader = BufReader::new(File::open(path)?);
+    let mut records = vec![];
+    for (index, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let record = serde_json::from_str(&line).map_err(|err| CcToolError::InvalidInput {
+            message: format!("Invalid capture record at line {}: {}", index + 1, err),
+        })?;
+        records.push(record);
+    }
+    return Ok(records);
+}
+
+#[derive(Default)]
+struct ReplayState {
+    records: VecDeque<CaptureRecord>,
+    rx: VecDeque<u8>,
+    tx: Vec<u8>,
+}
+
+impl ReplayState {
+    /// Makes the RX chunks up to the next TX chunk available.
+    fn release_rx(&mut self) {
+        while let Some(record) = self.records.front() {
+            if record.direction == CaptureDirection::TX {
+                return;
+            }
+            self.rx.extend(&record.data);
+            self.records.pop_front();
+        }
+    }
+}
+
+/// Transport that plays the device side of a capture back.
+/// The RX chunks captured before the first TX chunk are available right away. Every write moves on to
+/// the next TX chunk of the capture and makes the RX chunks that followed it available, so the
+/// request/response flow of the capture is reproduced when the same commands are run again.
+/// Written bytes are not compared with the capture, they can be inspected with `take_tx`.
+/// Clones share the same state, like clones of a serial port do.
+#[derive(Clone)]
+pub struct ReplayTransport {
+    name: String,
+    state: Arc<Mutex<ReplayState>>,
+}
+
+impl ReplayTransport {
+    /// Creates a transport playing back the records of one session of a capture.
+    /// # Arguments
+    /// * `name` - The name of the transport
+    /// * `records` - The records of the capture
+    /// * `session_id` - The session whose records are played back, or `None` for all records
+    pub fn new(
+        name: &str,
+        records: Vec<CaptureRecord>,
+        session_id: Option<&str>,
+    ) -> ReplayTransport {
+        let mut state = ReplayState {
+            records: records
+                .into_iter()
+                .filter(|record| session_id.is_none() || record.session_id.as_deref() == session_id)
+                .collect(),
+            ..Default::default()
+        };
+        state.release_rx();
+        return ReplayTransport {
+            name: name.to_string(),
+            state: Arc::new(Mutex::new(state)),
+        };
+    }
+
+    fn lock(&self) -> io::Result<MutexGuard<'_, ReplayState>> {
+        return self
+            .state
+            .lock()
+            .map_err(|err| io::Error::other(err.to_string()));
+    }
+
+    /// Returns (and forgets) all bytes written to the transport so far.
+    pub fn take_tx(&self) -> Vec<u8> {
+        return self
+            .lock()
+            .map(|mut state| std::mem::take(&mut state.tx))
+            .unwrap_or_default();
+    }
+
+    /// Returns the number of TX chunks of the capture that were not reached yet.
+    pub fn remaining_tx_chunks(&self) -> usize {
+        return self
+            .lock()
+            .map(|state| {
+                state
+                    .records
+                    .iter()
+                    .filter(|record| record.direction == CaptureDirection::TX)
+                    .count()
+            })
+            .unwrap_or_default();
+    }
+}
+
+impl Read for ReplayTransport {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut state = self.lock()?;
+        if state.rx.is_empty() {
+            return Err(io::Error::new(ErrorKind::TimedOut, "No bytes to read"));
+        }
+        let count = buf.len().min(state.rx.len());
+        for (slot, byte) in buf.iter_mut().zip(state.rx.drain(..count)) {
+            *slot = byte;
+        }
+        Ok(count)
+    }
+}
+
+impl Write for ReplayTransport {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut state = self.lock()?;
+        state.tx.extend_from_slice(buf);
+        // A request may have been captured in several chunks, skip as many as were written now
+        let mut written = buf.len();
+        while written > 0 {
+            match state.records.front() {
+                Some(record) if record.direction == CaptureDirection::TX => {
+                    written = written.saturating_sub(record.data.len());
+                    state.records.pop_front();
+                    state.release_rx();
+                }
+                _ => break,
+            }
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Transport for ReplayTransport {
+    fn name(&self) -> Option<String> {
+        Some(self.name.clone())
+    }
+
+    fn clear_output_buffer(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn try_clone_transport(&self) -> io::Result<Box<dyn Transport>> {
+        Ok(Box::new(self.clone()))
+    }
+}
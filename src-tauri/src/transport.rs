@@ -0,0 +1,210 @@
+//! This module contains the `Transport` abstraction that all protocol helpers work against.
+//! A transport is anything we can write bytes to and read bytes from: a real UART, a PTY,
+//! a TCP socket or an in-memory mock used in tests.
+
+use serialport::SerialPort;
+use std::collections::VecDeque;
+use std::io::{self, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Prefix of device names that should be opened as a TCP socket instead of a serial port.
+/// For example: `tcp://192.168.1.10:4001`
+pub const TCP_TRANSPORT_PREFIX: &str = "tcp://";
+
+/// How long to wait for a TCP connection to be established.
+/// Bridges on other hosts take much longer to answer than the read timeout of a serial port.
+pub const TCP_CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A bidirectional byte stream that the config, calibration and telemetry logic can run over.
+///
+/// Reads are expected to behave like a serial port with a read timeout: they return
+/// `io::ErrorKind::TimedOut` (or `WouldBlock`) if no bytes arrive within the timeout.
+pub trait Transport: Read + Write + Send {
+    /// Returns the name of the transport (port name, socket address etc.), if it has one.
+    fn name(&self) -> Option<String>;
+
+    /// Discards the bytes that were written to the transport but not transmitted yet.
+    fn clear_output_buffer(&mut self) -> io::Result<()>;
+
+    /// Creates a new handle to the same underlying transport.
+    /// Used by the background communication task, which reads while the main thread writes.
+    fn try_clone_transport(&self) -> io::Result<Box<dyn Transport>>;
+}
+
+/// Real UART (or PTY, via `serialport::TTYPort::pair()` on unix) connection.
+impl Transport for Box<dyn SerialPort> {
+    fn name(&self) -> Option<String> {
+        self.as_ref().name()
+    }
+
+    fn clear_output_buffer(&mut self) -> io::Result<()> {
+        Ok(self.clear(serialport::ClearBuffer::Output)?)
+    }
+
+    fn try_clone_transport(&self) -> io::Result<Box<dyn Transport>> {
+        Ok(Box::new(self.try_clone()?))
+    }
+}
+
+/// Lets a boxed transport (as stored in the program state) be passed wherever a transport is expected.
+impl<T: Transport + ?Sized> Transport for Box<T> {
+    fn name(&self) -> Option<String> {
+        self.as_ref().name()
+    }
+
+    fn clear_output_buffer(&mut self) -> io::Result<()> {
+        self.as_mut().clear_output_buffer()
+    }
+
+    fn try_clone_transport(&self) -> io::Result<Box<dyn Transport>> {
+        self.as_ref().try_clone_transport()
+    }
+}
+
+/// Transport over a TCP socket, e.g. a serial-to-ethernet bridge (`ser2net`) or a remote emulator.
+pub struct TcpTransport {
+    address: String,
+    stream: TcpStream,
+}
+
+impl TcpTransport {
+    /// Connects to the given address (`host:port`) within `TCP_CONNECT_TIMEOUT` and applies the read timeout.
+    ///
+    /// # Arguments
+    /// * `address` - The address to connect to, with or without the `tcp://` prefix.
+    /// * `read_timeout` - The read timeout of the socket.
+    ///
+    /// # Returns
+    /// A connected `TcpTransport` or an I/O error if the connection failed.
+    pub fn connect(address: &str, read_timeout: Duration) -> io::Result<TcpTransport> {
+        let address = address.trim_start_matches(TCP_TRANSPORT_PREFIX).to_string();
+        let socket_address = address.to_socket_addrs()?.next().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "Could not resolve address")
+        })?;
+        let stream = TcpStream::connect_timeout(&socket_address, TCP_CONNECT_TIMEOUT)?;
+        stream.set_read_timeout(Some(read_timeout))?;
+        stream.set_nodelay(true)?;
+        Ok(TcpTransport { address, stream })
+    }
+}
+
+impl Read for TcpTransport {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.stream.read(buf)
+    }
+}
+
+impl Write for TcpTransport {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.stream.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream.flush()
+    }
+}
+
+impl Transport for TcpTransport {
+    fn name(&self) -> Option<String> {
+        Some(format!("{}{}", TCP_TRANSPORT_PREFIX, self.address))
+    }
+
+    fn clear_output_buffer(&mut self) -> io::Result<()> {
+        // Bytes handed to the socket are already on their way, there is nothing to discard
+        Ok(())
+    }
+
+    fn try_clone_transport(&self) -> io::Result<Box<dyn Transport>> {
+        Ok(Box::new(TcpTransport {
+            address: self.address.clone(),
+            stream: self.stream.try_clone()?,
+        }))
+    }
+}
+
+#[derive(Default)]
+struct MockTransportState {
+    rx: VecDeque<u8>,
+    tx: Vec<u8>,
+}
+
+/// In-memory transport for tests.
+/// Bytes queued with `push_rx` are returned by reads, bytes written are collected and can be
+/// inspected with `take_tx`. Clones share the same buffers, like clones of a serial port do.
+#[derive(Clone, Default)]
+pub struct MockTransport {
+    name: String,
+    state: Arc<Mutex<MockTransportState>>,
+}
+
+impl MockTransport {
+    pub fn new(name: &str) -> MockTransport {
+        MockTransport {
+            name: name.to_string(),
+            state: Default::default(),
+        }
+    }
+
+    /// Queues bytes that the "device" sends to us.
+    pub fn push_rx(&self, bytes: &[u8]) {
+        if let Ok(mut state) = self.state.lock() {
+            state.rx.extend(bytes);
+        }
+    }
+
+    /// Returns (and forgets) all bytes written to the transport so far.
+    pub fn take_tx(&self) -> Vec<u8> {
+        if let Ok(mut state) = self.state.lock() {
+            return std::mem::take(&mut state.tx);
+        }
+        vec![]
+    }
+}
+
+impl Read for MockTransport {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut state = self
+            .state
+            .lock()
+            .map_err(|err| io::Error::other(err.to_string()))?;
+        if state.rx.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::TimedOut, "No bytes to read"));
+        }
+        let count = buf.len().min(state.rx.len());
+        for (slot, byte) in buf.iter_mut().zip(state.rx.drain(..count)) {
+            *slot = byte;
+        }
+        Ok(count)
+    }
+}
+
+impl Write for MockTransport {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut state = self
+            .state
+            .lock()
+            .map_err(|err| io::Error::other(err.to_string()))?;
+        state.tx.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Transport for MockTransport {
+    fn name(&self) -> Option<String> {
+        Some(self.name.clone())
+    }
+
+    fn clear_output_buffer(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn try_clone_transport(&self) -> io::Result<Box<dyn Transport>> {
+        Ok(Box::new(self.clone()))
+    }
+}
@@ -0,0 +1,288 @@
+//! This module contains the transmit duty-cycle guard of the TX test modes.
+//!
+//! The TX carrier and modulated TX test modes transmit until their `SEQUENCE_OFF` is executed. The guard
+//! limits how long they may stay on: a maximum on-time per activation, and a duty cycle, i.e. a share of a
+//! rolling window (e.g. 1 % of an hour for most 868 MHz sub-bands) in which TX test modes may be on at all.
+//! A TX test mode that reaches a limit is turned off, and a TX test mode can't be started while the duty
+//! cycle budget is used up.
+//!
+//! The limits are read from the RMD file (`TX_MAX_ON_TIME` and `TX_DUTY_CYCLE_WINDOW` in seconds,
+//! `TX_DUTY_CYCLE` in percent) and can be overridden by the user. The user setting is saved in the app
+//! config directory and loaded again at startup. Without limits the guard is disabled.
+
+use crate::cc_tool_error::CcToolError;
+use log::info;
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use tauri::AppHandle;
+
+/// Name of the file (in the app config directory) the limits set by the user are saved to
+pub const TX_GUARD_CONFIG_FILE_NAME: &str = "tx_guard_config.json";
+
+/// Length of the duty cycle window if the RMD file does not give one
+pub const DEFAULT_DUTY_CYCLE_WINDOW_MS: u64 = 3_600_000;
+
+/// The limits of the TX test modes
+#[derive(Clone, Copy, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct TxGuardConfig {
+    /// Longest time a TX test mode may stay on per activation, in milliseconds
+    pub max_on_time_ms: Option<u64>,
+    /// Share of the window in which TX test modes may be on, in percent
+    pub duty_cycle_percent: Option<f64>,
+    /// Length of the rolling duty cycle window, in milliseconds
+    pub duty_cycle_window_ms: u64,
+}
+
+impl Default for TxGuardConfig {
+    fn default() -> Self {
+        TxGuardConfig {
+            max_on_time_ms: None,
+            duty_cycle_percent: None,
+            duty_cycle_window_ms: DEFAULT_DUTY_CYCLE_WINDOW_MS,
+        }
+    }
+}
+
+impl TxGuardConfig {
+    /// Returns whether any limit is set.
+    pub fn is_enabled(&self) -> bool {
+        return self.max_on_time_ms.is_some() || self.duty_cycle_budget().is_some();
+    }
+
+    /// Returns how long TX test modes may be on within the duty cycle window, `None` if there is no duty cycle limit.
+    pub fn duty_cycle_budget(&self) -> Option<Duration> {
+        return self.duty_cycle_percent.map(|percent| {
+            Duration::from_secs_f64(
+                self.duty_cycle_window_ms as f64 / 1000.0 * percent.clamp(0.0, 100.0) / 100.0,
+            )
+        });
+    }
+
+    fn window(&self) -> Duration {
+        return Duration::from_millis(self.duty_cycle_window_ms);
+    }
+}
+
+/// Returns the path of the file the limits set by the user are saved to, if the app config directory is known.
+pub fn tx_guard_config_file_path(app_handle: &AppHandle) -> Option<PathBuf> {
+    return app_handle
+        .path_resolver()
+        .app_config_dir()
+        .map(|config_dir| config_dir.join(TX_GUARD_CONFIG_FILE_NAME));
+}
+
+/// This function reads the saved limits set by the user.
+/// # Arguments
+/// * `file_path` - The path of the settings file
+///
+/// # Returns
+/// The saved limits, or `None` if the file is missing or unreadable (i.e. the limits of the RMD files are used).
+pub fn load_tx_guard_config(file_path: &Path) -> Option<TxGuardConfig> {
+    return std::fs::read_to_string(file_path)
+        .ok()
+        .and_then(|file_contents| serde_json::from_str(&file_contents).ok());
+}
+
+/// This function saves the limits set by the user. Saving `None` removes the file.
+/// # Arguments
+/// * `file_path` - The path of the settings file
+/// * `config` - The limits to save
+///
+/// # Returns
+/// An `Ok(())` if the limits were saved, or `CcToolError::Io` if the file could not be written.
+pub fn save_tx_guard_config(
+    file_path: &Path,
+    config: Option<&TxGuardConfig>,
+) -> Result<(), CcToolError> {
+    let Some(config) = config else {
+        if file_path.exists() {
+            std::fs::remove_file(file_path)?;
+        }
+        info!("Removed the saved TX guard config");
+        return Ok(());
+    };
+    if let Some(parent) = file_path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let file_contents = serde_json::to_string_pretty(config).map_err(|err| CcToolError::Io {
+        message: err.to_string(),
+    })?;
+    std::fs::write(file_path, file_contents)?;
+    info!("Saved TX guard config");
+    return Ok(());
+}
+
+/// The limit a TX test mode reached
+#[derive(Clone, Copy, Debug, PartialEq, serde::Serialize)]
+pub enum TxGuardLimit {
+    /// The TX test mode was on for the maximum on-time
+    MaxOnTime,
+    /// The duty cycle budget of the window is used up
+    DutyCycle,
+}
+
+/// The state of the guard of a session, returned by `get_tx_guard_status`
+#[derive(Clone, Debug, PartialEq, serde::Serialize)]
+pub struct TxGuardStatus {
+    /// The limits of the current (or last) activation
+    pub config: TxGuardConfig,
+    /// Whether a TX test mode is on
+    pub is_on: bool,
+    /// How long the current activation has been on, in milliseconds
+    pub on_time_ms: u64,
+    /// How long TX test modes were on within the duty cycle window, in milliseconds
+    pub duty_cycle_used_ms: u64,
+    /// How long TX test modes may be on within the duty cycle window, in milliseconds
+    pub duty_cycle_budget_ms: Option<u64>,
+}
+
+/// The transmit duty-cycle guard of a session
+#[derive(Clone, Debug, Default)]
+pub struct TxGuard {
+    /// The limits set by the user, which replace the ones of the RMD file
+    user_config: Option<TxGuardConfig>,
+    /// The limits of the current (or last) activation
+    config: TxGuardConfig,
+    /// The time at which the current activation started
+    on_since: Option<Instant>,
+    /// The finished activations that may still be inside the duty cycle window, oldest first
+    periods: VecDeque<(Instant, Instant)>,
+}
+
+impl TxGuard {
+    /// Sets the limits of the user, `None` to use the ones of the RMD file.
+    /// The limits are applied with the next activation.
+    pub fn set_user_config(&mut self, user_config: Option<TxGuardConfig>) {
+        self.user_config = user_config;
+    }
+
+    /// Returns the limits that apply to the next activation.
+    /// # Arguments
+    /// * `rmd_config` - The limits of the RMD file of the device
+    pub fn config_for(&self, rmd_config: &TxGuardConfig) -> TxGuardConfig {
+        return self.user_config.unwrap_or(*rmd_config);
+    }
+
+    /// Returns whether a TX test mode is on.
+    pub fn is_on(&self) -> bool {
+        return self.on_since.is_some();
+    }
+
+    /// This function checks whether a TX test mode may be started.
+    /// # Arguments
+    /// * `config` - The limits of the activation
+    /// * `now` - The current time
+    ///
+    /// # Returns
+    /// An `Ok(())` if it may be started, or `CcToolError::TxLimitReached` with the time until the duty cycle
+    /// budget allows it again.
+    pub fn check_start(&self, config: &TxGuardConfig, now: Instant) -> Result<(), CcToolError> {
+        let Some(budget) = config.duty_cycle_budget() else {
+            return Ok(());
+        };
+        let used = self.duty_cycle_used(config.window(), now);
+        if used < budget {
+            return Ok(());
+        }
+        return Err(CcToolError::TxLimitReached {
+            limit: TxGuardLimit::DutyCycle,
+            retry_after_ms: self
+                .time_until_freed(config.window(), used - budget, now)
+                .as_millis() as u64,
+        });
+    }
+
+    /// Starts an activation. An activation that is already on is kept.
+    pub fn start(&mut self, config: TxGuardConfig, now: Instant) {
+        if self.on_since.is_none() {
+            self.config = config;
+            self.on_since = Some(now);
+        }
+    }
+
+    /// Ends the current activation, if any.
+    pub fn stop(&mut self, now: Instant) {
+        if let Some(on_since) = self.on_since.take() {
+            self.periods.push_back((on_since, now.max(on_since)));
+        }
+        let window = self.config.window();
+        while let Some(&(_, end)) = self.periods.front() {
+            if now.saturating_duration_since(end) < window {
+                break;
+            }
+            self.periods.pop_front();
+        }
+    }
+
+    /// Returns the limit the current activation reached, `None` if it is off or within its limits.
+    pub fn limit_reached(&self, now: Instant) -> Option<TxGuardLimit> {
+        let on_since = self.on_since?;
+        if let Some(max_on_time_ms) = self.config.max_on_time_ms {
+            if now.saturating_duration_since(on_since) >= Duration::from_millis(max_on_time_ms) {
+                return Some(TxGuardLimit::MaxOnTime);
+            }
+        }
+        if let Some(budget) = self.config.duty_cycle_budget() {
+            if self.duty_cycle_used(self.config.window(), now) >= budget {
+                return Some(TxGuardLimit::DutyCycle);
+            }
+        }
+        return None;
+    }
+
+    /// Returns the state of the guard.
+    pub fn status(&self, now: Instant) -> TxGuardStatus {
+        return TxGuardStatus {
+            config: self.config,
+            is_on: self.is_on(),
+            on_time_ms: self.on_since.map_or(0, |on_since| {
+                now.saturating_duration_since(on_since).as_millis() as u64
+            }),
+            duty_cycle_used_ms: self.duty_cycle_used(self.config.window(), now).as_millis() as u64,
+            duty_cycle_budget_ms: self
+                .config
+                .duty_cycle_budget()
+                .map(|budget| budget.as_millis() as u64),
+        };
+    }
+
+    /// Returns the activations (including the current one) clipped to the window ending at `now`.
+    fn periods_in_window(&self, window: Duration, now: Instant) -> Vec<(Instant, Instant)> {
+        let window_start = now.checked_sub(window);
+        return self
+            .periods
+            .iter()
+            .copied()
+            .chain(self.on_since.map(|on_since| (on_since, now)))
+            .filter_map(|(start, end)| {
+                let start = window_start.map_or(start, |window_start| start.max(window_start));
+                (end > start).then_some((start, end))
+            })
+            .collect();
+    }
+
+    fn duty_cycle_used(&self, window: Duration, now: Instant) -> Duration {
+        return self
+            .periods_in_window(window, now)
+            .iter()
+            .map(|(start, end)| end.duration_since(*start))
+            .sum();
+    }
+
+    /// Returns how long it takes until `excess` on-time has left the window, if nothing is transmitted meanwhile.
+    fn time_until_freed(&self, window: Duration, excess: Duration, now: Instant) -> Duration {
+        let Some(window_start) = now.checked_sub(window) else {
+            return window;
+        };
+        let mut freed = Duration::ZERO;
+        for (start, end) in self.periods_in_window(window, now) {
+            let length = end.duration_since(start);
+            if freed + length > excess {
+                return start.duration_since(window_start) + (excess - freed);
+            }
+            freed += length;
+        }
+        return window;
+    }
+}
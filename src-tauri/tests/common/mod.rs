@@ -0,0 +1,27 @@
+//! Helpers shared by the integration tests. Not every test uses every helper.
+#![allow(dead_code)]
+
+use std::path::PathBuf;
+use tinymesh_cc_tool::data_types::DeviceSession;
+use tinymesh_cc_tool::tinymesh_config_mod::get_device_config_from_device;
+use tinymesh_cc_tool::tinymesh_emulator::TinyMeshEmulator;
+
+/// Returns the path of the RMD file of the test module.
+pub fn rmd_file_path() -> PathBuf {
+    return PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("resources/tests/RF TM4070.rmd");
+}
+
+/// Returns an emulator of the test module.
+pub fn emulator() -> TinyMeshEmulator {
+    return TinyMeshEmulator::from_rmd_file("emulator", &rmd_file_path()).unwrap();
+}
+
+/// Returns a session connected to an emulator of the test module, with the configuration read from it.
+pub fn emulator_session() -> (TinyMeshEmulator, DeviceSession) {
+    let mut device = emulator();
+    let device_config =
+        get_device_config_from_device(&mut device, Some(&rmd_file_path()), None).unwrap();
+    let session = DeviceSession::new(Box::new(device.clone()), None, None).unwrap();
+    *session.device_config.lock().unwrap() = Some(device_config);
+    return (device, session);
+}
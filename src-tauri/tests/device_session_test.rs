@@ -0,0 +1,97 @@
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use tinymesh_cc_tool::cc_tool_error::CcToolError;
+    use tinymesh_cc_tool::data_types::{DeviceEntity, DeviceSession};
+    use tinymesh_cc_tool::transport::MockTransport;
+
+    fn session(name: &str) -> DeviceSession {
+        DeviceSession::new(Box::new(MockTransport::new(name)), None, None).unwrap()
+    }
+
+    #[test]
+    fn test_default_session() {
+        let device_entity = DeviceEntity::default();
+        assert!(matches!(
+            device_entity.session(None),
+            Err(CcToolError::NotConnected)
+        ));
+
+        let gateway = device_entity
+            .insert_session("/dev/ttyUSB0", session("/dev/ttyUSB0"))
+            .unwrap();
+        let router = device_entity
+            .insert_session("/dev/ttyUSB1", session("/dev/ttyUSB1"))
+            .unwrap();
+        // The last connected session is the default one
+        assert!(Arc::ptr_eq(&device_entity.session(None).unwrap(), &router));
+        assert!(Arc::ptr_eq(
+            &device_entity.session(Some("/dev/ttyUSB0")).unwrap(),
+            &gateway
+        ));
+        assert_eq!(
+            device_entity.session(Some("/dev/ttyUSB2")).err(),
+            Some(CcToolError::UnknownSession {
+                session_id: "/dev/ttyUSB2".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn test_remove_and_rename_session() {
+        let device_entity = DeviceEntity::default();
+        device_entity
+            .insert_session("/dev/ttyUSB0", session("/dev/ttyUSB0"))
+            .unwrap();
+        device_entity
+            .insert_session("/dev/ttyUSB1", session("/dev/ttyUSB1"))
+            .unwrap();
+
+        device_entity
+            .rename_session("/dev/ttyUSB1", "/dev/ttyUSB2")
+            .unwrap();
+        let session_ids: Vec<String> = device_entity
+            .all_sessions()
+            .unwrap()
+            .into_iter()
+            .map(|(session_id, _)| session_id)
+            .collect();
+        assert_eq!(session_ids, vec!["/dev/ttyUSB0", "/dev/ttyUSB2"]);
+        assert_eq!(
+            device_entity.default_session_id.lock().unwrap().as_deref(),
+            Some("/dev/ttyUSB2")
+        );
+
+        assert!(device_entity.remove_session(None).unwrap().is_some());
+        assert!(matches!(
+            device_entity.session(None),
+            Err(CcToolError::NotConnected)
+        ));
+        assert!(device_entity.session(Some("/dev/ttyUSB0")).is_ok());
+        assert!(device_entity
+            .remove_session(Some("/dev/ttyUSB3"))
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_rename_onto_existing_session_tears_it_down() {
+        let device_entity = DeviceEntity::default();
+        let stale = device_entity
+            .insert_session("/dev/ttyUSB0", session("/dev/ttyUSB0"))
+            .unwrap();
+        let moved = device_entity
+            .insert_session("/dev/ttyUSB1", session("/dev/ttyUSB1"))
+            .unwrap();
+        device_entity
+            .rename_session("/dev/ttyUSB1", "/dev/ttyUSB0")
+            .unwrap();
+        assert!(!stale.port.is_open().unwrap());
+        assert!(moved.port.is_open().unwrap());
+        assert!(Arc::ptr_eq(
+            &device_entity.session(Some("/dev/ttyUSB0")).unwrap(),
+            &moved
+        ));
+        assert_eq!(device_entity.all_sessions().unwrap().len(), 1);
+    }
+}
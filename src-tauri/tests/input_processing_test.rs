@@ -4,31 +4,31 @@ mod tests {
     #[test]
     fn test_invalid_byte_sequence_1() {
         let input = "hello 'world' 0x41";
-        assert_eq!(process_input(input), Err(InputError::InvalidByteSequence("Non-numeric character inside quote".to_string())));
+        assert_eq!(process_input(input), Err(InputError::InvalidByte { column: 8, token: "world".to_string() }));
     }
 
     #[test]
     fn test_invalid_byte_sequence_2() {
         let input = "hello 'world' 0xG";
-        assert_eq!(process_input(input), Err(InputError::InvalidByteSequence("Non-numeric character inside quote".to_string())));
+        assert_eq!(process_input(input), Err(InputError::InvalidByte { column: 8, token: "world".to_string() }));
     }
 
     #[test]
     fn test_unclosed_quote() {
         let input = "hello 'world"; // Unclosed single quote
-        assert_eq!(process_input(input), Err(InputError::InvalidByteSequence("Unclosed single quote".to_string())));
+        assert_eq!(process_input(input), Err(InputError::UnclosedQuote { column: 7 }));
     }
 
     #[test]
     fn test_invalid_byte_sequence_in_quotes_1() {
         let input = "hello '0xffad'"; // Unclosed single quote
-        assert_eq!(process_input(input), Err(InputError::InvalidByteSequence("Non-numeric character inside quote".to_string())));
+        assert_eq!(process_input(input), Err(InputError::InvalidByte { column: 8, token: "0xffad".to_string() }));
     }
     
     #[test]
     fn test_invalid_byte_sequence_in_quotes_2() {
         let input = "hello '256'"; // Unclosed single quote
-        assert_eq!(process_input(input), Err(InputError::InvalidByteSequence("Non-numeric character inside quote".to_string())));
+        assert_eq!(process_input(input), Err(InputError::InvalidByte { column: 8, token: "256".to_string() }));
     }
 
     #[test]
@@ -48,4 +48,40 @@ mod tests {
         let input = "hello '58 59'!";
         assert_eq!(process_input(input), Ok(vec![104, 101, 108, 108, 111, 32, 58, 59, 33]));
     }
+
+    #[test]
+    fn test_escapes() {
+        let input = r"AT\r\n\x7E\t\0\\\'";
+        assert_eq!(process_input(input), Ok(vec![b'A', b'T', 0x0D, 0x0A, 0x7E, 0x09, 0x00, b'\\', b'\'']));
+    }
+
+    #[test]
+    fn test_invalid_escapes() {
+        assert_eq!(process_input(r"ab\q"), Err(InputError::InvalidEscape { column: 3, escape: r"\q".to_string() }));
+        assert_eq!(process_input(r"\x4"), Err(InputError::InvalidEscape { column: 1, escape: r"\x4".to_string() }));
+        // A backslash at the end of the input
+        assert_eq!(process_input(r"abc\\\").unwrap_err().column(), 6);
+    }
+
+    #[test]
+    fn test_utf8() {
+        // Characters above 0x7F are sent as UTF-8 instead of being truncated
+        assert_eq!(process_input("é"), Ok(vec![0xC3, 0xA9]));
+        assert_eq!(process_input(r"\u{20AC}"), Ok(vec![0xE2, 0x82, 0xAC]));
+        assert_eq!(process_input(r"é\u{D800}").unwrap_err().column(), 2);
+    }
+
+    #[test]
+    fn test_binary_and_repeat() {
+        assert_eq!(process_input("'0b1010 0B1 0x00*3 7*2'"), Ok(vec![0b1010, 1, 0, 0, 0, 7, 7]));
+        assert_eq!(process_input("'0x00*0'"), Err(InputError::InvalidRepeat { column: 7, token: "0".to_string() }));
+        assert_eq!(process_input("'0x00 0b102'"), Err(InputError::InvalidByte { column: 7, token: "0b102".to_string() }));
+    }
+
+    #[test]
+    fn test_raw_hex_mode() {
+        assert_eq!(process_input("hex: 0D 0a\t7e"), Ok(vec![0x0D, 0x0A, 0x7E]));
+        assert_eq!(process_input("hex:0d0a7"), Err(InputError::OddHexDigits { column: 9 }));
+        assert_eq!(process_input("hex: 0D 0G"), Err(InputError::InvalidHexDigit { column: 10, character: 'G' }));
+    }
 }
\ No newline at end of file
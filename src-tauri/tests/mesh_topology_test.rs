@@ -0,0 +1,90 @@
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+    use tinymesh_cc_tool::mesh_topology::{
+        MeshChange, MeshDeviceType, MeshExportFormat, MeshTable,
+    };
+    use tinymesh_cc_tool::tinymesh_packet::{decode_tinymesh_packet, TinyMeshPacket};
+
+    fn packet_from(origin_id: u32, network_level: u8, hops: u8) -> TinyMeshPacket {
+        let mut body = vec![0x12, 0x00];
+        body.resize(18, 0);
+        // Hardware and firmware revision
+        body[12..16].copy_from_slice(&[0x00, 0x02, 0x01, 0x13]);
+        let mut frame = vec![35];
+        frame.extend(1u32.to_le_bytes());
+        frame.extend(origin_id.to_le_bytes());
+        frame.extend([0x60, network_level, hops, 0x00, 0x01, 0x00, 0x00, 0x02]);
+        frame.extend(body);
+        return decode_tinymesh_packet(&frame).unwrap();
+    }
+
+    #[test]
+    fn test_node_table() {
+        let mut table = MeshTable::default();
+        let now = Instant::now();
+        assert_eq!(
+            table.update(&packet_from(0x0a, 1, 1), now),
+            MeshChange::Added
+        );
+        assert_eq!(
+            table.update(&packet_from(0x0a, 1, 1), now + Duration::from_secs(1)),
+            MeshChange::Updated
+        );
+        let node = table.node(0x0a).unwrap();
+        assert_eq!(node.packet_count, 2);
+        assert_eq!(node.rssi, 0x60);
+        assert_eq!(node.hardware_revision, Some(0x0002));
+        assert_eq!(node.firmware_revision, Some(0x0113));
+        assert_eq!(node.parent_id, None);
+        assert!(node.last_seen_us > 0);
+
+        table.clear();
+        assert!(table.nodes().is_empty());
+    }
+
+    #[test]
+    fn test_topology() {
+        let mut table = MeshTable::default();
+        let now = Instant::now();
+        table.update(&packet_from(0x0a, 1, 1), now);
+        table.update(&packet_from(0x0b, 1, 1), now + Duration::from_millis(10));
+        table.update(&packet_from(0x0c, 2, 2), now + Duration::from_millis(20));
+        table.update(&packet_from(0x0d, 3, 3), now + Duration::from_millis(30));
+
+        // The level 1 node seen most recently is the parent of the level 2 node
+        assert_eq!(table.node(0x0c).unwrap().parent_id, Some(0x0b));
+        assert_eq!(table.node(0x0d).unwrap().parent_id, Some(0x0c));
+        assert_eq!(table.node(0x0a).unwrap().device_type, MeshDeviceType::Node);
+        assert_eq!(
+            table.node(0x0b).unwrap().device_type,
+            MeshDeviceType::Router
+        );
+        assert_eq!(table.node(0x0d).unwrap().device_type, MeshDeviceType::Node);
+
+        // A later packet of the other level 1 node makes it the parent
+        table.update(&packet_from(0x0a, 1, 1), now + Duration::from_millis(40));
+        assert_eq!(table.node(0x0c).unwrap().parent_id, Some(0x0a));
+        assert_eq!(table.node(0x0b).unwrap().device_type, MeshDeviceType::Node);
+    }
+
+    #[test]
+    fn test_export() {
+        let mut table = MeshTable::default();
+        let now = Instant::now();
+        table.update(&packet_from(0x0a, 1, 1), now);
+        table.update(&packet_from(0x0c, 2, 2), now);
+
+        let dot = table.export(MeshExportFormat::Dot).unwrap();
+        assert!(dot.starts_with("digraph mesh {"));
+        assert!(dot.contains("\"0000000A\" -> gateway"));
+        assert!(dot.contains("\"0000000C\" -> \"0000000A\""));
+
+        let json: serde_json::Value =
+            serde_json::from_str(&table.export(MeshExportFormat::Json).unwrap()).unwrap();
+        assert_eq!(json.as_array().unwrap().len(), 2);
+        assert_eq!(json[1]["unique_id"], 0x0c);
+        assert_eq!(json[1]["parent_id"], 0x0a);
+        assert_eq!(json[0]["device_type"], "Router");
+    }
+}
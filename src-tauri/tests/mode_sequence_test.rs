@@ -0,0 +1,143 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use crate::common::emulator;
+    use std::time::Duration;
+    use tinymesh_cc_tool::cc_tool_error::CcToolError;
+    use tinymesh_cc_tool::mode_sequence::{
+        decode_sequence_bytes, parse_mode_sequence, SequenceExchange, SequenceExpectation,
+        SequenceStep, SequenceStepOutcome, DEFAULT_STEP_TIMEOUT,
+    };
+    use tinymesh_cc_tool::tinymesh_config_mod::execute_mode_sequence_on_device;
+    use tinymesh_cc_tool::tinymesh_emulator::EmulatorTestMode;
+    use tinymesh_cc_tool::transport::MockTransport;
+
+    fn exchange(send: &[u8], expect: Option<SequenceExpectation>) -> SequenceStep {
+        return SequenceStep::Exchange(SequenceExchange {
+            send: send.to_vec(),
+            expect,
+            optional: false,
+            timeout: DEFAULT_STEP_TIMEOUT,
+            repeat: 1,
+        });
+    }
+
+    #[test]
+    fn test_parse_rmd_sequences() {
+        assert_eq!(
+            parse_mode_sequence("aG #>").unwrap(),
+            vec![exchange(
+                b"G",
+                Some(SequenceExpectation::Exact(b">".to_vec()))
+            )]
+        );
+        assert_eq!(
+            parse_mode_sequence("a5 ").unwrap(),
+            vec![exchange(b"5", None)]
+        );
+        assert_eq!(
+            parse_mode_sequence("aG #").unwrap(),
+            vec![exchange(b"G", None)]
+        );
+        assert!(parse_mode_sequence("").unwrap().is_empty());
+        assert!(parse_mode_sequence("6").is_err());
+        assert!(parse_mode_sequence("#>").is_err());
+    }
+
+    #[test]
+    fn test_parse_multi_step_sequence() {
+        let steps =
+            parse_mode_sequence("aM #> a\\x01\\x05\\xFF t5000 #> d100 aS ?~^.>$ x3").unwrap();
+        assert_eq!(steps.len(), 4);
+        assert_eq!(
+            steps[1],
+            SequenceStep::Exchange(SequenceExchange {
+                send: vec![0x01, 0x05, 0xff],
+                expect: Some(SequenceExpectation::Exact(b">".to_vec())),
+                optional: false,
+                timeout: Duration::from_millis(5000),
+                repeat: 1,
+            })
+        );
+        assert_eq!(steps[2], SequenceStep::Delay(Duration::from_millis(100)));
+        let SequenceStep::Exchange(exchange) = &steps[3] else {
+            panic!("Expected an exchange, got {:?}", steps[3]);
+        };
+        assert!(exchange.optional);
+        assert_eq!(exchange.repeat, 3);
+        assert!(exchange.expect.as_ref().unwrap().is_met_by(b"\x40>"));
+
+        // Invalid tokens are reported
+        assert!(parse_mode_sequence("aG #> #>").is_err());
+        assert!(parse_mode_sequence("aG x0").is_err());
+        assert!(parse_mode_sequence("d100 t5").is_err());
+        assert!(parse_mode_sequence("aG ~(").is_err());
+        assert!(parse_mode_sequence("d999999").is_err());
+    }
+
+    #[test]
+    fn test_decode_sequence_bytes() {
+        assert_eq!(
+            decode_sequence_bytes("A\\r\\n\\s\\t\\\\\\x3e"),
+            Ok(b"A\r\n \t\\>".to_vec())
+        );
+        assert_eq!(decode_sequence_bytes("µ"), Ok(vec![0xc2, 0xb5]));
+        assert!(decode_sequence_bytes("\\x4").is_err());
+        assert!(decode_sequence_bytes("\\xZZ").is_err());
+        assert!(decode_sequence_bytes("\\q").is_err());
+        assert!(decode_sequence_bytes("A\\").is_err());
+    }
+
+    #[test]
+    fn test_execute_sequence_trace() {
+        let mut device = emulator();
+        device.set_rssi(0x40);
+        let trace =
+            execute_mode_sequence_on_device(&mut device, "a1 #> d10 aS ~^.>$ x2", None).unwrap();
+        assert_eq!(device.test_mode(), Some(EmulatorTestMode::TxCarrier));
+        assert_eq!(trace.len(), 4);
+        assert_eq!(trace[0].sent, b"1".to_vec());
+        assert_eq!(trace[0].received, b">".to_vec());
+        assert_eq!(trace[0].outcome, SequenceStepOutcome::Matched);
+        assert_eq!(trace[1].outcome, SequenceStepOutcome::Waited);
+        assert_eq!((trace[3].step_index, trace[3].repetition), (2, 2));
+        assert_eq!(trace[3].outcome, SequenceStepOutcome::Matched);
+
+        // A sniffer test mode is only started, its output is left to passive RX
+        let trace = execute_mode_sequence_on_device(&mut device, "a5", None).unwrap();
+        assert_eq!(trace[0].outcome, SequenceStepOutcome::Sent);
+        assert!(execute_mode_sequence_on_device(&mut device, "", None)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_execute_sequence_expectations() {
+        let mut device = MockTransport::new("mock");
+        // An optional expectation that is not met does not stop the sequence
+        device.push_rx(b"ERR\r\n");
+        let trace =
+            execute_mode_sequence_on_device(&mut device, "aX ?#OK\\r\\n t50 aY", None).unwrap();
+        assert_eq!(trace[0].received, b"ERR\r\n".to_vec());
+        assert_eq!(trace[0].outcome, SequenceStepOutcome::NotMatched);
+        assert_eq!(trace[1].outcome, SequenceStepOutcome::Sent);
+        assert_eq!(device.take_tx(), b"XY".to_vec());
+
+        device.push_rx(b"ERR\r\n");
+        assert_eq!(
+            execute_mode_sequence_on_device(&mut device, "aX #OK\\r\\n t50 aY", None),
+            Err(CcToolError::UnexpectedResponse {
+                expected: "OK\r\n".to_string(),
+                got: b"ERR\r\n".to_vec(),
+            })
+        );
+        // The sequence stops at the failed step
+        assert_eq!(device.take_tx(), b"X".to_vec());
+
+        assert_eq!(
+            execute_mode_sequence_on_device(&mut device, "aX ~OK t50", None),
+            Err(CcToolError::Timeout { received: vec![] })
+        );
+    }
+}
@@ -1,5 +1,7 @@
 #[cfg(test)]
 mod tests {
+    use tinymesh_cc_tool::cc_tool_error::CcToolError;
+    use tinymesh_cc_tool::mk_module_description::MkModuleDescription;
     use tinymesh_cc_tool::module_description_parser::parse_module_description;
     use std::fs::read_to_string;
     use std::path::PathBuf;
@@ -17,4 +19,11 @@ mod tests {
         println!("{:#?}", result);
         assert_eq!(result.get("DEVICE_MODEL").unwrap(), "RF TM4070");
     }
+
+    #[test]
+    fn test_invalid_cell_value_reports_line() {
+        let input = "[DEVICE_MODEL]\nRF TM4070\n\n[M 0x00 NAME]\nRF Channel\n\n[M 0x00 DEF]\nfour\n";
+        let result = MkModuleDescription::new(input);
+        assert!(matches!(result, Err(CcToolError::RmdParse { line: 7, .. })));
+    }
 }
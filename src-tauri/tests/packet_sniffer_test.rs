@@ -0,0 +1,92 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use crate::common::{emulator, rmd_file_path};
+    use std::time::Instant;
+    use tinymesh_cc_tool::packet_sniffer::{
+        is_packet_sniffer_sequence, parse_sniffer_line, PacketSniffer, SnifferReport,
+    };
+    use tinymesh_cc_tool::tinymesh_config_mod::{
+        execute_mode_sequence_on_device, get_device_config_from_device,
+    };
+    use tinymesh_cc_tool::tinymesh_emulator::EmulatorTestMode;
+
+    #[test]
+    fn test_parse_sniffer_line() {
+        assert_eq!(
+            parse_sniffer_line("ORG:01020304 RSSI:72 TYPE:02 LEN:35"),
+            Some(SnifferReport {
+                origin_id: 0x01020304,
+                rssi: Some(72),
+                packet_type: Some(0x02),
+                length: Some(35),
+            })
+        );
+        assert_eq!(
+            parse_sniffer_line("src=0x0A, rssi=-16, pt=10"),
+            Some(SnifferReport {
+                origin_id: 0x0a,
+                rssi: Some(-16),
+                packet_type: Some(0x10),
+                length: None,
+            })
+        );
+        // Banners and prompts
+        assert_eq!(parse_sniffer_line("Packet sniffer"), None);
+        assert_eq!(parse_sniffer_line(">"), None);
+        // Malformed values
+        assert_eq!(parse_sniffer_line("ORG:XYZ RSSI:72"), None);
+        assert_eq!(parse_sniffer_line("ORG:01 TYPE:1FF"), None);
+    }
+
+    #[test]
+    fn test_packet_sniffer_stats() {
+        let mut sniffer = PacketSniffer::default();
+        let now = Instant::now();
+        // Output is ignored until the test mode was started
+        assert!(sniffer.push(b"ORG:01 RSSI:70\r\n", now).is_empty());
+
+        sniffer.set_running(true);
+        // A line split over several frames
+        assert!(sniffer.push(b"ORG:01 RSSI:70 TY", now).is_empty());
+        assert_eq!(sniffer.push(b"PE:02 LEN:35\r\n", now).len(), 1);
+        let reports = sniffer.push(b"ORG:01 RSSI:80 TYPE:10 LEN:20\r\nORG:02 RSSI:50\r\n>", now);
+        assert_eq!(reports.len(), 2);
+
+        let stats = sniffer.stats();
+        assert_eq!(stats.len(), 2);
+        assert_eq!(stats[0].origin_id, 1);
+        assert_eq!(stats[0].packet_count, 2);
+        assert_eq!(stats[0].total_length, 55);
+        assert_eq!(stats[0].last_rssi, Some(80));
+        assert_eq!(stats[0].min_rssi, Some(70));
+        assert_eq!(stats[0].max_rssi, Some(80));
+        assert_eq!(stats[0].mean_rssi, Some(75.0));
+        assert_eq!(stats[0].packet_types.get(&0x02), Some(&1));
+        assert_eq!(stats[0].packet_types.get(&0x10), Some(&1));
+
+        // The statistics are kept after stopping, until the next run
+        sniffer.set_running(false);
+        assert_eq!(sniffer.stats().len(), 2);
+        sniffer.set_running(true);
+        assert!(sniffer.stats().is_empty());
+    }
+
+    #[test]
+    fn test_packet_sniffer_sequence() {
+        let mut device = emulator();
+        let device_config =
+            get_device_config_from_device(&mut device, Some(&rmd_file_path()), None).unwrap();
+        assert!(is_packet_sniffer_sequence(Some(&device_config), "a6"));
+        assert!(!is_packet_sniffer_sequence(Some(&device_config), "a5 "));
+        assert!(!is_packet_sniffer_sequence(Some(&device_config), ""));
+        assert!(!is_packet_sniffer_sequence(None, "a6"));
+
+        // The sniffer sequence has no expected answer, and its SEQUENCE_OFF is empty
+        execute_mode_sequence_on_device(&mut device, "a6", None).unwrap();
+        assert_eq!(device.test_mode(), Some(EmulatorTestMode::PacketSniffer));
+        execute_mode_sequence_on_device(&mut device, "", None).unwrap();
+        assert!(execute_mode_sequence_on_device(&mut device, "6", None).is_err());
+    }
+}
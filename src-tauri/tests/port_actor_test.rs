@@ -0,0 +1,151 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use crate::common::{emulator, rmd_file_path};
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+    use tinymesh_cc_tool::cc_tool_error::CcToolError;
+    use tinymesh_cc_tool::port_actor::{PortActor, MAX_UNREAD_BYTES};
+    use tinymesh_cc_tool::tinymesh_config_mod::get_device_config_from_device;
+    use tinymesh_cc_tool::tinymesh_device_info_mod::get_voltage_from_device;
+    use tinymesh_cc_tool::tinymesh_serial_util::send_bytes_to_device;
+    use tinymesh_cc_tool::transport::MockTransport;
+
+    #[test]
+    fn test_concurrent_transactions() {
+        let device = emulator();
+        device.set_telemetry(&[0x01, 0x02, 0x03, 0x04], 0x81, 153, 110);
+        let port = Arc::new(PortActor::spawn(Some(Box::new(device)), None).unwrap());
+        // Passive RX must not steal the responses of the transactions
+        port.set_passive_rx(true).unwrap();
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let port = port.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..5 {
+                        assert_eq!(
+                            port.transact(|device| get_voltage_from_device(device, None)),
+                            Ok(110)
+                        );
+                    }
+                })
+            })
+            .collect();
+        let device_config = port
+            .transact(|device| get_device_config_from_device(device, Some(&rmd_file_path()), None))
+            .unwrap();
+        assert_eq!(device_config.model, "RF TM4070");
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        assert!(port.is_passive_rx_enabled().unwrap());
+    }
+
+    #[test]
+    fn test_passive_rx() {
+        let device = MockTransport::new("mock");
+        let port = PortActor::spawn(Some(Box::new(device.clone())), None).unwrap();
+        device.push_rx(b"unsolicited");
+        port.set_passive_rx(true).unwrap();
+        let started_at = Instant::now();
+        loop {
+            let pending = port
+                .transact(|device| {
+                    let mut buffer = vec![];
+                    let _ = device.read_to_end(&mut buffer);
+                    Ok(buffer)
+                })
+                .unwrap();
+            // The bytes were consumed by the passive stream (or by this transaction)
+            if pending.is_empty() || pending == b"unsolicited" {
+                break;
+            }
+            assert!(started_at.elapsed() < Duration::from_secs(1));
+        }
+
+        port.set_passive_rx(false).unwrap();
+        port.transact(|device| send_bytes_to_device(device, b"X", None))
+            .unwrap();
+        assert_eq!(device.take_tx(), b"X");
+    }
+
+    #[test]
+    fn test_stop_passive_rx() {
+        let device = MockTransport::new("mock");
+        let port = PortActor::spawn(Some(Box::new(device.clone())), None).unwrap();
+        port.set_passive_rx(true).unwrap();
+        port.set_passive_rx(false).unwrap();
+        // Once stopped, the received bytes are left for a transaction that reads them
+        device.push_rx(b"late");
+        let started_at = Instant::now();
+        let mut received = vec![];
+        while received.len() < 4 {
+            received.extend(
+                port.transact_with_received(|device| {
+                    let mut buffer = vec![];
+                    let _ = device.read_to_end(&mut buffer);
+                    Ok(buffer)
+                })
+                .unwrap(),
+            );
+            assert!(started_at.elapsed() < Duration::from_secs(1));
+        }
+        assert_eq!(received, b"late");
+    }
+
+    #[test]
+    fn test_transaction_skips_stale_bytes() {
+        let device = MockTransport::new("mock");
+        let port = PortActor::spawn(Some(Box::new(device.clone())), None).unwrap();
+        device.push_rx(b"stale");
+        std::thread::sleep(Duration::from_millis(100));
+        // The bytes received before the transaction are emitted, not read by it
+        let received = port
+            .transact(|device| {
+                let mut buffer = vec![];
+                let _ = device.read_to_end(&mut buffer);
+                Ok(buffer)
+            })
+            .unwrap();
+        assert!(received.is_empty());
+    }
+
+    #[test]
+    fn test_unread_bytes_are_bounded() {
+        let device = MockTransport::new("mock");
+        let port = PortActor::spawn(Some(Box::new(device.clone())), None).unwrap();
+        let bytes: Vec<u8> = (0..MAX_UNREAD_BYTES + 1000).map(|i| i as u8).collect();
+        device.push_rx(&bytes);
+        std::thread::sleep(Duration::from_millis(200));
+        // Only the newest bytes are kept
+        let received = port
+            .transact_with_received(|device| {
+                let mut buffer = vec![];
+                let _ = device.read_to_end(&mut buffer);
+                Ok(buffer)
+            })
+            .unwrap();
+        assert_eq!(received, bytes[1000..]);
+    }
+
+    #[test]
+    fn test_closed_port() {
+        let port = PortActor::spawn(Some(Box::new(MockTransport::new("mock"))), None).unwrap();
+        assert_eq!(port.name(), Ok(Some("mock".to_string())));
+        port.close().unwrap();
+        assert_eq!(port.is_open(), Ok(false));
+        assert_eq!(
+            port.transact(|device| send_bytes_to_device(device, b"X", None)),
+            Err(CcToolError::NotConnected)
+        );
+
+        let device = MockTransport::new("mock2");
+        port.open(Box::new(device.clone())).unwrap();
+        assert_eq!(
+            port.transact(|device| send_bytes_to_device(device, b"X", None)),
+            Ok(())
+        );
+        assert_eq!(device.take_tx(), b"X");
+    }
+}
@@ -0,0 +1,113 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use crate::common::{emulator, rmd_file_path};
+    use std::time::{Duration, Instant};
+    use tinymesh_cc_tool::data_types::RssiScaling;
+    use tinymesh_cc_tool::rssi_sniffer::{
+        is_rssi_sniffer_sequence, parse_rssi_sniffer_line, RssiReport, RssiSniffer,
+        RSSI_HISTORY_LEN,
+    };
+    use tinymesh_cc_tool::tinymesh_config_mod::get_device_config_from_device;
+
+    const SCALING: RssiScaling = RssiScaling {
+        offset: 0.0,
+        scale_factor: -0.5,
+    };
+
+    #[test]
+    fn test_parse_rssi_sniffer_line() {
+        assert_eq!(
+            parse_rssi_sniffer_line("144", &SCALING),
+            Some(RssiReport {
+                source_id: None,
+                raw_rssi: 144,
+                rssi: -72.0,
+            })
+        );
+        assert_eq!(
+            parse_rssi_sniffer_line("ORG:0A RSSI:0x90", &SCALING),
+            Some(RssiReport {
+                source_id: Some(0x0a),
+                raw_rssi: 144,
+                rssi: -72.0,
+            })
+        );
+        let scaling = RssiScaling {
+            offset: -130.0,
+            scale_factor: 0.5,
+        };
+        assert_eq!(
+            parse_rssi_sniffer_line("RSSI=100", &scaling).unwrap().rssi,
+            -80.0
+        );
+        assert_eq!(parse_rssi_sniffer_line("RSSI Sniffer", &SCALING), None);
+        assert_eq!(parse_rssi_sniffer_line("ORG:0A", &SCALING), None);
+    }
+
+    #[test]
+    fn test_rssi_sniffer_stats() {
+        let mut sniffer = RssiSniffer::default();
+        let now = Instant::now();
+        assert!(sniffer.push(b"100\r\n", now).is_empty());
+
+        sniffer.set_running(true, SCALING);
+        let mut output = String::new();
+        for raw_rssi in 1..=10 {
+            output.push_str(&format!("ORG:01 RSSI:{}\r\n", raw_rssi * 20));
+        }
+        // Split in the middle of a line
+        let (first, second) = output.as_bytes().split_at(25);
+        sniffer.push(first, now);
+        sniffer.push(second, now + Duration::from_millis(1));
+        sniffer.push(b"160\r\n", now + Duration::from_millis(2));
+
+        let stats = sniffer.stats();
+        assert_eq!(stats.len(), 2);
+        assert_eq!(stats[0].source_id, None);
+        assert_eq!(stats[0].last_rssi, -80.0);
+        assert_eq!(stats[1].source_id, Some(1));
+        assert_eq!(stats[1].report_count, 10);
+        assert_eq!(stats[1].last_rssi, -100.0);
+        assert_eq!(stats[1].min_rssi, -100.0);
+        assert_eq!(stats[1].max_rssi, -10.0);
+        assert_eq!(stats[1].mean_rssi, -55.0);
+        assert_eq!(stats[1].p10_rssi, -100.0);
+        assert_eq!(stats[1].p50_rssi, -60.0);
+        assert_eq!(stats[1].p90_rssi, -20.0);
+
+        let csv = sniffer.to_csv();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], "timestamp_us,source_id,raw_rssi,rssi_dbm");
+        assert_eq!(lines.len(), 12);
+        assert!(lines[1].ends_with(",00000001,20,-10"));
+        assert!(lines[11].ends_with(",,160,-80"));
+    }
+
+    #[test]
+    fn test_rssi_history_window() {
+        let mut sniffer = RssiSniffer::default();
+        sniffer.set_running(true, SCALING);
+        let now = Instant::now();
+        sniffer.push(b"0\n", now);
+        for _ in 0..RSSI_HISTORY_LEN {
+            sniffer.push(b"100\n", now);
+        }
+        let stats = &sniffer.stats()[0];
+        assert_eq!(stats.report_count, RSSI_HISTORY_LEN as u64 + 1);
+        assert_eq!(stats.window_len, RSSI_HISTORY_LEN);
+        // The first report dropped out of the window
+        assert_eq!(stats.max_rssi, -50.0);
+    }
+
+    #[test]
+    fn test_rssi_sniffer_sequence() {
+        let mut device = emulator();
+        let device_config =
+            get_device_config_from_device(&mut device, Some(&rmd_file_path()), None).unwrap();
+        assert_eq!(device_config.rssi_scaling, SCALING);
+        assert!(is_rssi_sniffer_sequence(Some(&device_config), "a5 "));
+        assert!(!is_rssi_sniffer_sequence(Some(&device_config), "a6"));
+    }
+}
@@ -0,0 +1,124 @@
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+    use tinymesh_cc_tool::rx_packetizer::{
+        split_into_frames, FrameRule, Packetizer, PacketizerConfig,
+    };
+    use tinymesh_cc_tool::tinymesh_serial_util::read_bytes_from_device_to_buffer;
+    use tinymesh_cc_tool::transport::MockTransport;
+
+    fn config(rule: FrameRule) -> PacketizerConfig {
+        return PacketizerConfig {
+            rule,
+            ..Default::default()
+        };
+    }
+
+    fn frame_data(config: &PacketizerConfig, chunks: &[(Instant, Vec<u8>)]) -> Vec<Vec<u8>> {
+        return split_into_frames(config, chunks)
+            .into_iter()
+            .map(|frame| frame.data)
+            .collect();
+    }
+
+    #[test]
+    fn test_inter_byte_gap() {
+        let start = Instant::now();
+        let mut packetizer = Packetizer::new(config(FrameRule::InterByteGap));
+        // A packet read in two chunks is a single frame
+        assert!(packetizer.push(&[1, 2], start).is_empty());
+        assert!(packetizer
+            .push(&[3], start + Duration::from_millis(5))
+            .is_empty());
+        assert_eq!(
+            packetizer.deadline(),
+            Some(start + Duration::from_millis(25))
+        );
+        assert_eq!(packetizer.poll(start + Duration::from_millis(24)), None);
+        let frame = packetizer.poll(start + Duration::from_millis(25)).unwrap();
+        assert_eq!(frame.data, vec![1, 2, 3]);
+        assert_eq!(frame.received_at, start);
+        assert_eq!(packetizer.deadline(), None);
+
+        // A chunk arriving after the gap completes the buffered frame first
+        assert!(packetizer
+            .push(&[4], start + Duration::from_millis(100))
+            .is_empty());
+        let frames = packetizer.push(&[5], start + Duration::from_millis(150));
+        assert_eq!(frames.len(), 1);
+        assert_eq!(frames[0].data, vec![4]);
+        assert_eq!(packetizer.flush().unwrap().data, vec![5]);
+    }
+
+    #[test]
+    fn test_fixed_length_and_delimiter() {
+        let now = Instant::now();
+        let fixed = config(FrameRule::FixedLength { length: 3 });
+        assert_eq!(
+            frame_data(&fixed, &[(now, vec![1, 2]), (now, vec![3, 4, 5, 6, 7])]),
+            vec![vec![1, 2, 3], vec![4, 5, 6], vec![7]]
+        );
+
+        let delimited = config(FrameRule::Delimiter {
+            delimiter: b"\r\n".to_vec(),
+        });
+        assert_eq!(
+            frame_data(
+                &delimited,
+                &[(now, b"OK\r".to_vec()), (now, b"\nAT\r\nX".to_vec())]
+            ),
+            vec![b"OK\r\n".to_vec(), b"AT\r\n".to_vec(), b"X".to_vec()]
+        );
+
+        let limited = PacketizerConfig {
+            max_frame_len: 2,
+            ..config(FrameRule::InterByteGap)
+        };
+        assert_eq!(
+            frame_data(&limited, &[(now, vec![1, 2, 3])]),
+            vec![vec![1, 2], vec![3]]
+        );
+    }
+
+    #[test]
+    fn test_tinymesh_packets() {
+        let now = Instant::now();
+        let tinymesh = config(FrameRule::TinyMeshPacket);
+        let first: Vec<u8> = [vec![0x0a], vec![0xaa; 9]].concat();
+        let second: Vec<u8> = [vec![0x12], vec![0xbb; 17]].concat();
+        let stream = [vec![0x05], first.clone(), second.clone()].concat();
+        // The packets are split at their length bytes, however they were read
+        assert_eq!(
+            frame_data(
+                &tinymesh,
+                &[(now, stream[..7].to_vec()), (now, stream[7..].to_vec())]
+            ),
+            vec![vec![0x05], first.clone(), second]
+        );
+        // A truncated packet is completed by the inter-byte timeout
+        let mut packetizer = Packetizer::new(tinymesh);
+        assert!(packetizer.push(&first[..4], now).is_empty());
+        assert_eq!(
+            packetizer
+                .poll(now + Duration::from_millis(20))
+                .unwrap()
+                .data,
+            first[..4].to_vec()
+        );
+    }
+
+    #[test]
+    fn test_read_bytes_in_frames() {
+        let mut device = MockTransport::new("mock");
+        device.push_rx(b"AB\r\nCD");
+        let mut buffer = vec![0xff];
+        let delimited = config(FrameRule::Delimiter {
+            delimiter: b"\r\n".to_vec(),
+        });
+        let read =
+            read_bytes_from_device_to_buffer(&mut device, &mut buffer, Some(&delimited), None)
+                .unwrap();
+        assert_eq!(read, 7);
+        assert_eq!(buffer, b"\xffAB\r\nCD".to_vec());
+    }
+}
@@ -0,0 +1,57 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use crate::common::{emulator, rmd_file_path};
+    use tinymesh_cc_tool::serial_settings::{
+        load_serial_settings, save_serial_settings, FlowControl, Parity, SerialSettings, StopBits,
+    };
+    use tinymesh_cc_tool::tinymesh_config_mod::get_device_config_from_device;
+
+    #[test]
+    fn test_serial_settings_from_device_config() {
+        let mut device = emulator();
+        let mut device_config =
+            get_device_config_from_device(&mut device, Some(&rmd_file_path()), None).unwrap();
+        assert_eq!(
+            SerialSettings::from_device_config(&device_config),
+            Some(SerialSettings::default())
+        );
+
+        device_config.cells[0x35].current_value = 10;
+        device_config.cells[0x36].current_value = 9;
+        device_config.cells[0x37].current_value = 1;
+        device_config.cells[0x38].current_value = 2;
+        device_config.cells[0x3A].current_value = 3;
+        let serial_settings = SerialSettings::from_device_config(&device_config).unwrap();
+        assert_eq!(serial_settings.baud_rate, 115200);
+        assert_eq!(serial_settings.data_bits, 8);
+        assert_eq!(serial_settings.parity, Parity::Odd);
+        assert_eq!(serial_settings.stop_bits, StopBits::Two);
+        assert_eq!(serial_settings.flow_control, FlowControl::Hardware);
+
+        device_config.cells[0x35].current_value = 0;
+        assert_eq!(SerialSettings::from_device_config(&device_config), None);
+    }
+
+    #[test]
+    fn test_save_and_load_serial_settings() {
+        let file_path = std::env::temp_dir()
+            .join(format!("cc_tool_serial_settings_{}", std::process::id()))
+            .join("serial_settings.json");
+        let settings = SerialSettings {
+            baud_rate: 115200,
+            parity: Parity::Even,
+            flow_control: FlowControl::Software,
+            dtr: Some(false),
+            ..Default::default()
+        };
+        save_serial_settings(&file_path, "/dev/ttyUSB0", &settings).unwrap();
+        save_serial_settings(&file_path, "COM3", &SerialSettings::default()).unwrap();
+
+        let saved_settings = load_serial_settings(&file_path);
+        assert_eq!(saved_settings.get("/dev/ttyUSB0"), Some(&settings));
+        assert_eq!(saved_settings.get("COM3"), Some(&SerialSettings::default()));
+        std::fs::remove_dir_all(file_path.parent().unwrap()).unwrap();
+    }
+}
@@ -0,0 +1,71 @@
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use tinymesh_cc_tool::cc_tool_error::CcToolError;
+    use tinymesh_cc_tool::task_manager::{TaskManager, TaskStatus};
+
+    const WAIT: Duration = Duration::from_secs(2);
+
+    #[test]
+    fn test_task_lifecycle() {
+        let tasks = TaskManager::default();
+        let completed = tasks.spawn("job", |_| Ok("done".to_string())).unwrap();
+        let failed = tasks
+            .spawn("job", |_| Err(CcToolError::NotConnected))
+            .unwrap();
+        assert!(tasks.wait(completed, WAIT).unwrap());
+        assert!(tasks.wait(failed, WAIT).unwrap());
+
+        let task_infos = tasks.list().unwrap();
+        assert_eq!(task_infos[0].status, TaskStatus::Completed);
+        assert_eq!(task_infos[0].result.as_deref(), Some("done"));
+        assert_eq!(task_infos[1].status, TaskStatus::Failed);
+        assert_eq!(task_infos[1].error, Some(CcToolError::NotConnected));
+        assert_eq!(tasks.find_running("job").unwrap(), None);
+    }
+
+    #[test]
+    fn test_cancel_task() {
+        let tasks = TaskManager::default();
+        let task_id = tasks
+            .spawn("stream", |token| {
+                while !token.wait_timeout(Duration::from_secs(60)) {}
+                Ok("stopped".to_string())
+            })
+            .unwrap();
+        assert_eq!(tasks.find_running("stream").unwrap(), Some(task_id));
+        assert_eq!(tasks.wait(task_id, Duration::from_millis(10)), Ok(false));
+
+        tasks.cancel(task_id).unwrap();
+        // The token wakes the task up right away, it does not sleep until the timeout
+        assert!(tasks.wait(task_id, WAIT).unwrap());
+        assert_eq!(tasks.list().unwrap()[0].status, TaskStatus::Cancelled);
+        assert_eq!(
+            tasks.cancel(task_id + 1000),
+            Err(CcToolError::UnknownTask {
+                task_id: task_id + 1000
+            })
+        );
+    }
+
+    #[test]
+    fn test_shutdown() {
+        let tasks = TaskManager::default();
+        for _ in 0..3 {
+            tasks
+                .spawn("stream", |token| {
+                    while !token.is_cancelled() {
+                        std::thread::sleep(Duration::from_millis(1));
+                    }
+                    Ok("stopped".to_string())
+                })
+                .unwrap();
+        }
+        assert_eq!(tasks.shutdown(WAIT), Ok(true));
+        assert!(tasks
+            .list()
+            .unwrap()
+            .iter()
+            .all(|task_info| task_info.status == TaskStatus::Cancelled));
+    }
+}
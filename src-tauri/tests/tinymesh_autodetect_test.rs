@@ -0,0 +1,82 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use crate::common::{emulator, rmd_file_path};
+    use std::fs::read_to_string;
+    use tinymesh_cc_tool::cc_tool_error::CcToolError;
+    use tinymesh_cc_tool::mk_module_description::MkModuleDescription;
+    use tinymesh_cc_tool::serial_settings::{Parity, SerialSettings};
+    use tinymesh_cc_tool::tinymesh_autodetect_mod::{
+        autodetect_serial_settings, default_autodetect_candidates, probe_device,
+    };
+    use tinymesh_cc_tool::tinymesh_emulator::TinyMeshEmulator;
+    use tinymesh_cc_tool::transport::{MockTransport, Transport};
+
+    #[test]
+    fn test_default_candidates() {
+        let candidates = default_autodetect_candidates();
+        assert_eq!(candidates.len(), 33);
+        assert_eq!(candidates[0], SerialSettings::default());
+        assert_eq!(candidates[3].baud_rate, 230400);
+    }
+
+    #[test]
+    fn test_probe_device_rejects_garbage() {
+        let mock = MockTransport::new("mock");
+        let mut device = mock.clone();
+        assert!(matches!(
+            probe_device(&mut device, None),
+            Err(CcToolError::Timeout { .. })
+        ));
+        assert_eq!(mock.take_tx(), vec![0x30]);
+    }
+
+    #[test]
+    fn test_autodetect_finds_matching_settings() {
+        let device = emulator();
+        let module_settings = SerialSettings {
+            baud_rate: 115200,
+            parity: Parity::Even,
+            ..Default::default()
+        };
+        let candidates = vec![
+            SerialSettings::default(),
+            SerialSettings {
+                baud_rate: 115200,
+                ..Default::default()
+            },
+            module_settings.clone(),
+        ];
+        let mut opened = vec![];
+        let result = autodetect_serial_settings(
+            &candidates,
+            |serial_settings| {
+                opened.push(serial_settings.clone());
+                if *serial_settings == module_settings {
+                    Ok(Box::new(device.clone()) as Box<dyn Transport>)
+                } else {
+                    Ok(Box::new(MockTransport::new("mock")) as Box<dyn Transport>)
+                }
+            },
+            None,
+            None,
+        )
+        .unwrap();
+        assert_eq!(opened, candidates);
+        assert_eq!(result.serial_settings, module_settings);
+        assert_eq!(result.model, "RF TM4070");
+    }
+
+    #[test]
+    fn test_probe_device_accepts_dotted_model() {
+        let mut module_description =
+            MkModuleDescription::new(&read_to_string(rmd_file_path()).unwrap()).unwrap();
+        for (i, &byte) in b"TM4070.2_B,1.00,1.02\xff".iter().enumerate() {
+            module_description.cells[0x3c + i].default_value = byte;
+        }
+        let mut device = TinyMeshEmulator::new("emulator", &module_description);
+        let (model, _, _) = probe_device(&mut device, None).unwrap();
+        assert_eq!(model, "TM4070.2_B");
+    }
+}
@@ -0,0 +1,119 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use crate::common::{emulator, rmd_file_path};
+    use tinymesh_cc_tool::cc_tool_error::CcToolError;
+    use tinymesh_cc_tool::tinymesh_calibration_mod::{
+        get_device_calib_from_device, set_device_calib_on_device,
+    };
+    use tinymesh_cc_tool::tinymesh_config_mod::{
+        factory_reset_device, get_device_config_from_device, set_device_config_on_device,
+    };
+    use tinymesh_cc_tool::tinymesh_device_info_mod::{
+        get_analog_from_device, get_digital_from_device, get_rssi_from_device,
+        get_temperature_from_device, get_voltage_from_device, switch_to_channel,
+    };
+    use tinymesh_cc_tool::tinymesh_emulator::CONFIG_MEMORY_SIZE;
+
+    #[test]
+    fn test_get_device_config() {
+        let mut device = emulator();
+        let device_config =
+            get_device_config_from_device(&mut device, Some(&rmd_file_path()), None).unwrap();
+        assert_eq!(device_config.model, "RF TM4070");
+        assert_eq!(device_config.cells.len(), CONFIG_MEMORY_SIZE);
+        assert_eq!(device_config.cells[0].name, "RF Channel");
+        assert_eq!(device_config.cells[0].current_value, 4);
+    }
+
+    #[test]
+    fn test_packet_mode() {
+        let mut device = emulator();
+        let device_config =
+            get_device_config_from_device(&mut device, Some(&rmd_file_path()), None).unwrap();
+        // The RMD default of the Protocol mode cell is transparent mode
+        assert!(!device_config.is_packet_mode());
+        let mut cells = device_config.cells.clone();
+        cells[0x03].current_value = 0;
+        set_device_config_on_device(&mut device, &device_config, &cells, None).unwrap();
+        let device_config =
+            get_device_config_from_device(&mut device, Some(&rmd_file_path()), None).unwrap();
+        assert!(device_config.is_packet_mode());
+    }
+
+    #[test]
+    fn test_set_device_config_and_factory_reset() {
+        let mut device = emulator();
+        let device_config =
+            get_device_config_from_device(&mut device, Some(&rmd_file_path()), None).unwrap();
+        let mut cells = device_config.cells.clone();
+        cells[0].current_value = 7;
+        cells[1].current_value = 2;
+        assert_eq!(
+            set_device_config_on_device(&mut device, &device_config, &cells, None),
+            Ok(())
+        );
+        assert_eq!(device.config_memory()[0], 7);
+        assert_eq!(device.config_memory()[1], 2);
+
+        assert_eq!(factory_reset_device(&mut device, None), Ok(()));
+        assert_eq!(device.config_memory()[0], 4);
+        assert_eq!(device.config_memory()[1], 5);
+    }
+
+    #[test]
+    fn test_set_device_config_validation() {
+        let mut device = emulator();
+        let device_config =
+            get_device_config_from_device(&mut device, Some(&rmd_file_path()), None).unwrap();
+        let mut cells = device_config.cells.clone();
+        cells[0].current_value = cells[0].max_value + 1;
+        assert!(matches!(
+            set_device_config_on_device(&mut device, &device_config, &cells, None),
+            Err(CcToolError::ValidationFailed { address: 0, .. })
+        ));
+        assert_eq!(device.config_memory()[0], 4);
+    }
+
+    #[test]
+    fn test_get_and_set_device_calib() {
+        let mut device = emulator();
+        let device_calib =
+            get_device_calib_from_device(&mut device, Some(&rmd_file_path()), None).unwrap();
+        assert_eq!(device_calib.model, "RF TM4070");
+        assert_eq!(device_calib.calibration_cells[0].name, "Temp Offset");
+        assert_eq!(device_calib.calibration_cells[0].current_value, 128);
+
+        let mut cells = device_calib.calibration_cells.clone();
+        cells[0].current_value = 130;
+        assert_eq!(
+            set_device_calib_on_device(&mut device, &device_calib, &cells, None),
+            Ok(())
+        );
+        assert_eq!(device.calibration_memory()[0], 130);
+    }
+
+    #[test]
+    fn test_telemetry() {
+        let mut device = emulator();
+        device.set_rssi(0x60);
+        device.set_telemetry(&[0x01, 0x02, 0x03, 0x04], 0x81, 153, 110);
+        assert_eq!(switch_to_channel(0, &mut device, None), Ok(()));
+        assert_eq!(get_rssi_from_device(&mut device, None), Ok(0x60));
+        assert_eq!(
+            get_analog_from_device(&mut device, None),
+            Ok(vec![0x01, 0x02, 0x03, 0x04])
+        );
+        assert_eq!(get_digital_from_device(&mut device, None), Ok(0x81));
+        assert_eq!(get_temperature_from_device(&mut device, None), Ok(153));
+        assert_eq!(get_voltage_from_device(&mut device, None), Ok(110));
+    }
+
+    #[test]
+    fn test_switch_to_channel() {
+        let mut device = emulator();
+        assert_eq!(switch_to_channel(9, &mut device, None), Ok(()));
+        assert_eq!(device.channel(), 9);
+    }
+}
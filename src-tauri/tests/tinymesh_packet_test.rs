@@ -0,0 +1,185 @@
+#[cfg(test)]
+mod tests {
+    use tinymesh_cc_tool::cc_tool_error::CcToolError;
+    use tinymesh_cc_tool::tinymesh_packet::{
+        decode_tinymesh_packet, decode_tinymesh_packets, TinyMeshCommand, TinyMeshCommandBuilder,
+        TinyMeshNodeStatus, TinyMeshPacketBody,
+    };
+
+    fn header(length: u8, packet_type: u8) -> Vec<u8> {
+        return [
+            &[length][..],
+            &[0x01, 0x00, 0x00, 0x00], // system ID
+            &[0x04, 0x03, 0x02, 0x01], // origin ID
+            &[0x7f, 0x02, 0x01],       // RSSI, network level, hops
+            &[0x01, 0x02],             // packet number
+            &[0x00, 0x0a],             // latency
+            &[packet_type],
+        ]
+        .concat();
+    }
+
+    fn event_packet(detail: u8, data: u8) -> Vec<u8> {
+        let mut packet = header(35, 0x02);
+        packet.extend([
+            detail, data, // detail, data
+            0x00, 0x00, // address
+            0x96, // temperature
+            0x6e, // voltage
+            0x00, 0x05, // digital I/O
+            0x01, 0x00, 0x02, 0x00, // analogue I/O
+            0x00, 0x02, // hardware revision
+            0x01, 0x13, // firmware revision
+            0x00, 0x00, // reserved
+        ]);
+        return packet;
+    }
+
+    fn status(detail: u8, data: u8) -> TinyMeshNodeStatus {
+        return TinyMeshNodeStatus {
+            detail,
+            data,
+            address: 0,
+            temperature: 0x96,
+            voltage: 0x6e,
+            digital_io: 0x0005,
+            analog_io: [0x0100, 0x0200],
+            hardware_revision: 0x0002,
+            firmware_revision: 0x0113,
+        };
+    }
+
+    #[test]
+    fn test_event_packets() {
+        let packet = decode_tinymesh_packet(&event_packet(0x01, 0x00)).unwrap();
+        assert_eq!(packet.system_id, 1);
+        assert_eq!(packet.origin_id, 0x01020304);
+        assert_eq!(packet.rssi, 0x7f);
+        assert_eq!(packet.network_level, 2);
+        assert_eq!(packet.hops, 1);
+        assert_eq!(packet.packet_number, 0x0102);
+        assert_eq!(packet.latency, 10);
+        assert_eq!(packet.body, TinyMeshPacketBody::Event(status(0x01, 0x00)));
+
+        assert_eq!(
+            decode_tinymesh_packet(&event_packet(0x10, 0x2a))
+                .unwrap()
+                .body,
+            TinyMeshPacketBody::CommandAck {
+                acknowledged: true,
+                command_number: 0x2a,
+                status: status(0x10, 0x2a),
+            }
+        );
+        assert_eq!(
+            decode_tinymesh_packet(&event_packet(0x11, 0x2b))
+                .unwrap()
+                .body,
+            TinyMeshPacketBody::CommandAck {
+                acknowledged: false,
+                command_number: 0x2b,
+                status: status(0x11, 0x2b),
+            }
+        );
+        assert_eq!(
+            decode_tinymesh_packet(&event_packet(0x12, 0x00))
+                .unwrap()
+                .body,
+            TinyMeshPacketBody::Status(status(0x12, 0x00))
+        );
+    }
+
+    #[test]
+    fn test_serial_data_packet() {
+        let mut frame = header(22, 0x10);
+        frame.extend([0x07, b'A', b'B', b'C', b'D']);
+        assert_eq!(
+            decode_tinymesh_packet(&frame).unwrap().body,
+            TinyMeshPacketBody::SerialData {
+                block_counter: 7,
+                data: b"ABCD".to_vec(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_invalid_frames() {
+        // Length byte doesn't match the frame (e.g. a truncated packet or transparent mode data)
+        let truncated = &event_packet(0x01, 0x00)[..30];
+        assert!(matches!(
+            decode_tinymesh_packet(truncated),
+            Err(CcToolError::UnexpectedResponse { .. })
+        ));
+        assert!(decode_tinymesh_packet(b"Hello").is_err());
+        // Unknown packet type
+        assert!(decode_tinymesh_packet(&header(17, 0x55)).is_err());
+    }
+
+    #[test]
+    fn test_several_packets_in_one_frame() {
+        let mut serial_data = header(22, 0x10);
+        serial_data.extend([0x07, b'A', b'B', b'C', b'D']);
+        // Two packets read in one chunk, with a stray byte in between
+        let frame = [event_packet(0x12, 0x00), vec![0x00], serial_data].concat();
+        let packets = decode_tinymesh_packets(&frame);
+        assert_eq!(packets.len(), 2);
+        assert_eq!(
+            packets[0].body,
+            TinyMeshPacketBody::Status(status(0x12, 0x00))
+        );
+        assert_eq!(
+            packets[1].body,
+            TinyMeshPacketBody::SerialData {
+                block_counter: 7,
+                data: b"ABCD".to_vec(),
+            }
+        );
+        // Transparent mode data and truncated packets yield no packets
+        assert!(decode_tinymesh_packets(b"Hello").is_empty());
+        assert!(decode_tinymesh_packets(&event_packet(0x01, 0x00)[..30]).is_empty());
+    }
+
+    #[test]
+    fn test_command_packets() {
+        let builder = TinyMeshCommandBuilder::new(0x00000001, 0x01020304).with_command_number(0x2a);
+        assert_eq!(
+            builder.build(&TinyMeshCommand::GetStatus).unwrap(),
+            (
+                0x2a,
+                vec![
+                    0x0e, 0x01, 0x00, 0x00, 0x00, 0x04, 0x03, 0x02, 0x01, 0x2a, 0x03, 0x11, 0x00,
+                    0x00
+                ]
+            )
+        );
+        let (_, set_outputs) = builder
+            .build(&TinyMeshCommand::SetOutputs {
+                outputs: 0x05,
+                mask: 0x07,
+            })
+            .unwrap();
+        assert_eq!(set_outputs[11..], [0x01, 0x05, 0x07]);
+        let (_, serial_data) = builder
+            .build(&TinyMeshCommand::SerialData {
+                data: b"ping".to_vec(),
+            })
+            .unwrap();
+        assert_eq!(serial_data[0] as usize, serial_data.len());
+        assert_eq!(serial_data[10], 0x11);
+        assert_eq!(serial_data[11..], *b"ping");
+
+        assert!(matches!(
+            builder.build(&TinyMeshCommand::SetPwm { duty_cycle: 101 }),
+            Err(CcToolError::InvalidInput { .. })
+        ));
+        assert!(builder
+            .build(&TinyMeshCommand::SerialData { data: vec![] })
+            .is_err());
+
+        // Without a fixed command number, every packet gets a new one
+        let builder = TinyMeshCommandBuilder::new(1, 2);
+        let (first, _) = builder.build(&TinyMeshCommand::GetConfig).unwrap();
+        let (second, _) = builder.build(&TinyMeshCommand::GetConfig).unwrap();
+        assert_ne!(first, second);
+    }
+}
@@ -0,0 +1,62 @@
+#[cfg(test)]
+mod tests {
+    use tinymesh_cc_tool::data_types::{ConnectionInfo, PortType, SerialPortEntry};
+    use tinymesh_cc_tool::serial_settings::SerialSettings;
+    use tinymesh_cc_tool::tinymesh_port_watcher_mod::{diff_ports, find_reconnect_candidate};
+
+    fn usb_entry(port_name: &str, serial_number: Option<&str>) -> SerialPortEntry {
+        SerialPortEntry {
+            port_name: port_name.to_string(),
+            port_type: PortType::Usb,
+            vid: Some(0x0403),
+            pid: Some(0x6001),
+            serial_number: serial_number.map(str::to_string),
+            manufacturer: Some("FTDI".to_string()),
+            product: Some("FT232R USB UART".to_string()),
+            is_known_adapter: true,
+        }
+    }
+
+    #[test]
+    fn test_diff_ports() {
+        let previous = vec![
+            usb_entry("/dev/ttyUSB0", Some("A")),
+            usb_entry("/dev/ttyUSB1", Some("B")),
+        ];
+        let current = vec![
+            usb_entry("/dev/ttyUSB1", Some("B")),
+            usb_entry("/dev/ttyUSB2", Some("C")),
+        ];
+        let changes = diff_ports(&previous, &current);
+        assert_eq!(changes.attached, vec![usb_entry("/dev/ttyUSB2", Some("C"))]);
+        assert_eq!(changes.detached, vec![usb_entry("/dev/ttyUSB0", Some("A"))]);
+
+        let changes = diff_ports(&current, &current);
+        assert!(changes.attached.is_empty());
+        assert!(changes.detached.is_empty());
+    }
+
+    #[test]
+    fn test_find_reconnect_candidate() {
+        let connection = ConnectionInfo {
+            port_name: "/dev/ttyUSB0".to_string(),
+            serial_number: Some("A50285BI".to_string()),
+            serial_settings: SerialSettings::default(),
+        };
+        let attached = vec![
+            usb_entry("/dev/ttyUSB1", None),
+            usb_entry("/dev/ttyUSB2", Some("A50285BI")),
+        ];
+        assert_eq!(
+            find_reconnect_candidate(&attached, &connection).map(|entry| entry.port_name.as_str()),
+            Some("/dev/ttyUSB2")
+        );
+        assert_eq!(find_reconnect_candidate(&attached[..1], &connection), None);
+
+        let connection = ConnectionInfo {
+            serial_number: None,
+            ..connection
+        };
+        assert_eq!(find_reconnect_candidate(&attached, &connection), None);
+    }
+}
@@ -0,0 +1,145 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use crate::common::rmd_file_path;
+    use std::fs::read_to_string;
+    use std::path::PathBuf;
+    use std::time::{Duration, Instant};
+    use tinymesh_cc_tool::cc_tool_error::CcToolError;
+    use tinymesh_cc_tool::tinymesh_remote_config_mod::{
+        get_remote_device_config_from_device, set_remote_device_config_on_device,
+    };
+    use tinymesh_cc_tool::transport::MockTransport;
+
+    const SYSTEM_ID: u32 = 0x00000001;
+    const NODE_ID: u32 = 0x01020304;
+
+    /// The configuration memory of the test module, without the prompt
+    fn config_memory() -> Vec<u8> {
+        let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        d.push("resources/tests/config_response.txt");
+        let mut memory: Vec<u8> = read_to_string(d)
+            .unwrap()
+            .split_whitespace()
+            .map(|s| u8::from_str_radix(s, 16).unwrap())
+            .collect();
+        memory.pop();
+        return memory;
+    }
+
+    fn packet_from(origin_id: u32, packet_type: u8, body: &[u8]) -> Vec<u8> {
+        let mut packet = vec![(17 + body.len()) as u8];
+        packet.extend(SYSTEM_ID.to_le_bytes());
+        packet.extend(origin_id.to_le_bytes());
+        packet.extend([0x7f, 0x01, 0x01, 0x00, 0x01, 0x00, 0x00, packet_type]);
+        packet.extend(body);
+        return packet;
+    }
+
+    fn ack_packet(detail: u8, command_number: u8) -> Vec<u8> {
+        let mut body = vec![detail, command_number];
+        body.resize(18, 0);
+        return packet_from(NODE_ID, 0x02, &body);
+    }
+
+    /// Answers the next command written to the mock with an acknowledge of the given detail.
+    fn answer_next_command(mock: &MockTransport, detail: u8) -> std::thread::JoinHandle<Vec<u8>> {
+        let mock = mock.clone();
+        return std::thread::spawn(move || {
+            let started_at = Instant::now();
+            while started_at.elapsed() < Duration::from_secs(2) {
+                let tx = mock.take_tx();
+                if !tx.is_empty() {
+                    mock.push_rx(&ack_packet(detail, tx[9]));
+                    return tx;
+                }
+                std::thread::sleep(Duration::from_millis(1));
+            }
+            return vec![];
+        });
+    }
+
+    #[test]
+    fn test_get_remote_config() {
+        let mock = MockTransport::new("mock");
+        // An event of another node arrives before the answer
+        mock.push_rx(&packet_from(0x0a0b0c0d, 0x02, &[0x01; 18]));
+        mock.push_rx(&packet_from(NODE_ID, 0x21, &config_memory()));
+        let mut device = mock.clone();
+        let device_config = get_remote_device_config_from_device(
+            &mut device,
+            SYSTEM_ID,
+            NODE_ID,
+            Some(&rmd_file_path()),
+            None,
+        )
+        .unwrap();
+        let tx = mock.take_tx();
+        assert_eq!(
+            tx[..9],
+            [0x0e, 0x01, 0x00, 0x00, 0x00, 0x04, 0x03, 0x02, 0x01]
+        );
+        assert_eq!(tx[10..], [0x03, 0x13, 0x00, 0x00]);
+        assert_eq!(device_config.model, "RF TM4070");
+        assert_eq!(device_config.cells[0].current_value, 0x04);
+    }
+
+    #[test]
+    fn test_set_remote_config() {
+        let mock = MockTransport::new("mock");
+        mock.push_rx(&packet_from(NODE_ID, 0x21, &config_memory()));
+        let mut device = mock.clone();
+        let mut device_config = get_remote_device_config_from_device(
+            &mut device,
+            SYSTEM_ID,
+            NODE_ID,
+            Some(&rmd_file_path()),
+            None,
+        )
+        .unwrap();
+        mock.take_tx();
+
+        let mut cells = device_config.cells.clone();
+        cells[0].current_value = 0x05;
+        let answer = answer_next_command(&mock, 0x10);
+        set_remote_device_config_on_device(
+            &mut device,
+            SYSTEM_ID,
+            NODE_ID,
+            &mut device_config,
+            &cells,
+            None,
+        )
+        .unwrap();
+        // A single set configuration command for cell 0x00
+        assert_eq!(answer.join().unwrap()[10..], [0x03, 0x14, 0x00, 0x05]);
+        assert_eq!(device_config.cells[0].current_value, 0x05);
+
+        // The node acknowledges cell 0x00 and rejects cell 0x01
+        cells[0].current_value = 0x06;
+        cells[1].current_value = 0x03;
+        let answers = {
+            let mock = mock.clone();
+            std::thread::spawn(move || {
+                answer_next_command(&mock, 0x10).join().unwrap();
+                answer_next_command(&mock, 0x11).join().unwrap();
+            })
+        };
+        assert!(matches!(
+            set_remote_device_config_on_device(
+                &mut device,
+                SYSTEM_ID,
+                NODE_ID,
+                &mut device_config,
+                &cells,
+                None,
+            ),
+            Err(CcToolError::ValidationFailed { address: 1, .. })
+        ));
+        answers.join().unwrap();
+        // Only the acknowledged cell is taken over
+        assert_eq!(device_config.cells[0].current_value, 0x06);
+        assert_eq!(device_config.cells[1].current_value, 0x05);
+    }
+}
@@ -0,0 +1,183 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use crate::common::{emulator, rmd_file_path};
+    use std::io::{self, Read, Write};
+    use std::time::{Duration, Instant};
+    use tinymesh_cc_tool::tinymesh_config_mod::get_device_config_from_device;
+    use serialport::{SerialPortInfo, SerialPortType, UsbPortInfo};
+    use tinymesh_cc_tool::data_types::{unix_timestamp_us, EventKind, EventPayload, PortType};
+    use tinymesh_cc_tool::tinymesh_serial_util::{
+        read_frame_from_device, serial_port_entry_from_info, FrameError, FrameSpec,
+        MEMORY_DUMP_LEN,
+    };
+    use tinymesh_cc_tool::transport::{MockTransport, Transport};
+
+    struct BrokenTransport;
+
+    impl Read for BrokenTransport {
+        fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize> {
+            Err(io::Error::new(io::ErrorKind::BrokenPipe, "unplugged"))
+        }
+    }
+
+    impl Write for BrokenTransport {
+        fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+            Err(io::Error::new(io::ErrorKind::BrokenPipe, "unplugged"))
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Transport for BrokenTransport {
+        fn name(&self) -> Option<String> {
+            None
+        }
+
+        fn clear_output_buffer(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+
+        fn try_clone_transport(&self) -> io::Result<Box<dyn Transport>> {
+            Ok(Box::new(BrokenTransport))
+        }
+    }
+
+    #[test]
+    fn test_prompt_only() {
+        let mut device = MockTransport::new("mock");
+        device.push_rx(b">");
+        assert_eq!(
+            read_frame_from_device(&mut device, &FrameSpec::prompt_only(), None),
+            Ok(vec![])
+        );
+    }
+
+    #[test]
+    fn test_timeout_without_prompt() {
+        let mut device = MockTransport::new("mock");
+        device.push_rx(&[0x01, 0x02]);
+        let frame_spec = FrameSpec::prompt_only().with_deadline(Duration::from_millis(50));
+        let started_at = Instant::now();
+        assert_eq!(
+            read_frame_from_device(&mut device, &frame_spec, None),
+            Err(FrameError::Timeout {
+                received: vec![0x01, 0x02]
+            })
+        );
+        assert!(started_at.elapsed() < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_overflow() {
+        let mut device = MockTransport::new("mock");
+        device.push_rx(&[0x00; 100]);
+        let frame_spec = FrameSpec {
+            max_len: 10,
+            ..FrameSpec::prompt_only()
+        };
+        assert!(matches!(
+            read_frame_from_device(&mut device, &frame_spec, None),
+            Err(FrameError::Overflow { .. })
+        ));
+    }
+
+    #[test]
+    fn test_io_error() {
+        let mut device = BrokenTransport;
+        assert!(matches!(
+            read_frame_from_device(&mut device, &FrameSpec::prompt_only(), None),
+            Err(FrameError::Io(_))
+        ));
+    }
+
+    #[test]
+    fn test_3e_data_byte_with_known_payload_length() {
+        let mut device = MockTransport::new("mock");
+        device.push_rx(&[0x3e, b'>']);
+        assert_eq!(
+            read_frame_from_device(&mut device, &FrameSpec::telemetry(Some(1)), None),
+            Ok(vec![0x3e])
+        );
+    }
+
+    #[test]
+    fn test_3e_data_byte_inside_memory_dump() {
+        let mut device = MockTransport::new("mock");
+        let mut dump = vec![0x00; MEMORY_DUMP_LEN];
+        dump[..6].copy_from_slice(&[0x01, 0x3e, 0x02, 0x3e, 0x3e, 0x03]);
+        dump[MEMORY_DUMP_LEN - 1] = 0x3e;
+        device.push_rx(&dump);
+        device.push_rx(b">");
+        assert_eq!(
+            read_frame_from_device(&mut device, &FrameSpec::memory_dump(), None),
+            Ok(dump)
+        );
+    }
+
+    #[test]
+    fn test_config_dump_containing_3e() {
+        let mut device = emulator();
+        // Maximum jump level 62 is stored as 0x3e
+        device.write_all(&[b'M', 0x09, 0x3e, 0xff]).unwrap();
+        let mut discard = vec![];
+        let _ = device.read_to_end(&mut discard);
+
+        let device_config =
+            get_device_config_from_device(&mut device, Some(&rmd_file_path()), None).unwrap();
+        assert_eq!(device_config.cells[0x09].current_value, 0x3e);
+        assert_eq!(device_config.model, "RF TM4070");
+    }
+
+    #[test]
+    fn test_serial_port_entry_from_info() {
+        let usb_port = |vid: u16, product: &str| SerialPortInfo {
+            port_name: "/dev/ttyUSB0".to_string(),
+            port_type: SerialPortType::UsbPort(UsbPortInfo {
+                vid,
+                pid: 0x6001,
+                serial_number: Some("A50285BI".to_string()),
+                manufacturer: Some("Vendor".to_string()),
+                product: Some(product.to_string()),
+            }),
+        };
+        let ftdi = serial_port_entry_from_info(&usb_port(0x0403, "FT232R USB UART"));
+        assert_eq!(ftdi.port_type, PortType::Usb);
+        assert_eq!(ftdi.vid, Some(0x0403));
+        assert_eq!(ftdi.pid, Some(0x6001));
+        assert_eq!(ftdi.serial_number.as_deref(), Some("A50285BI"));
+        assert!(ftdi.is_known_adapter);
+        assert!(serial_port_entry_from_info(&usb_port(0x1234, "Tinymesh Gateway")).is_known_adapter);
+        assert!(!serial_port_entry_from_info(&usb_port(0x1234, "Modem")).is_known_adapter);
+
+        let pci = serial_port_entry_from_info(&SerialPortInfo {
+            port_name: "/dev/ttyS0".to_string(),
+            port_type: SerialPortType::PciPort,
+        });
+        assert_eq!(pci.port_type, PortType::Pci);
+        assert_eq!(pci.vid, None);
+        assert!(!pci.is_known_adapter);
+    }
+
+    #[test]
+    fn test_event_payload() {
+        let read_at = Instant::now();
+        let first = EventPayload::new(None, EventKind::TX, b"S".to_vec(), read_at);
+        let second = EventPayload::new(
+            Some("/dev/ttyUSB0".to_string()),
+            EventKind::RX,
+            vec![0x60, b'>'],
+            read_at + Duration::from_micros(1500),
+        );
+        assert!(second.sequence > first.sequence);
+        assert_eq!(second.timestamp_us - first.timestamp_us, 1500);
+        // Earlier readings of the monotonic clock are converted as well
+        assert_eq!(
+            unix_timestamp_us(read_at) - unix_timestamp_us(read_at - Duration::from_millis(3)),
+            3000
+        );
+    }
+}
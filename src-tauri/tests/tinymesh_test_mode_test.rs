@@ -0,0 +1,164 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use crate::common::emulator_session;
+    use std::time::{Duration, Instant};
+    use tinymesh_cc_tool::cc_tool_error::CcToolError;
+    use tinymesh_cc_tool::data_types::{is_tx_mode_name, DeviceSession, MkDeviceConfig, ModeKind};
+    use tinymesh_cc_tool::mk_module_description::MkModuleDescription;
+    use tinymesh_cc_tool::tinymesh_emulator::EmulatorTestMode;
+    use tinymesh_cc_tool::tinymesh_serial_util::tear_down_session;
+    use tinymesh_cc_tool::tinymesh_test_mode_mod::{
+        execute_mode_sequence_in_session, turn_off_active_mode,
+    };
+    use tinymesh_cc_tool::transport::MockTransport;
+
+    fn active_mode_name(session: &DeviceSession) -> Option<String> {
+        return session
+            .active_mode
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map(|active_mode| active_mode.name.clone());
+    }
+
+    #[test]
+    fn test_is_tx_mode_name() {
+        assert!(is_tx_mode_name("Test Mode 1 (TX On)"));
+        assert!(is_tx_mode_name("Test Mode 2 (Modulated TX On)"));
+        assert!(!is_tx_mode_name("Test Mode 3 (RX)"));
+        assert!(!is_tx_mode_name("Test Mode 6 Simple Packet Sniffer"));
+    }
+
+    #[test]
+    fn test_tx_entry_of_rmd_file() {
+        let input = "[DEVICE_MODEL]\nRF TM4070\n\n[TESTMODE NUMBER]\n2\n\n[TESTMODE 1 NAME]\nCarrier\n\n[TESTMODE 1 SEQUENCE_ON]\na1 #>\n\n[TESTMODE 1 TX]\n1\n\n[TESTMODE 2 NAME]\nTX Power Readout\n\n[TESTMODE 2 SEQUENCE_ON]\na7 #>\n\n[TESTMODE 2 TX]\n0\n\n[QUICKMODE NUMBER]\n1\n\n[QUICKMODE 1 NAME]\nBeacon\n\n[QUICKMODE 1 SEQUENCE_ON]\nb1 #>\n\n[QUICKMODE 1 TX]\n1\n";
+        let module_description = MkModuleDescription::new(input).unwrap();
+        let device_config = MkDeviceConfig {
+            test_modes: module_description.testmodes,
+            quick_modes: module_description.quickmodes,
+            ..Default::default()
+        };
+        // The entry wins over the name of the mode
+        assert!(device_config.mode_started_by("a1 #>").unwrap().is_tx);
+        assert!(!device_config.mode_started_by("a7 #>").unwrap().is_tx);
+        assert!(device_config.mode_started_by("b1 #>").unwrap().is_tx);
+
+        let input = "[DEVICE_MODEL]\nRF TM4070\n\n[TESTMODE NUMBER]\n1\n\n[TESTMODE 1 TX]\nyes\n";
+        assert!(matches!(
+            MkModuleDescription::new(input),
+            Err(CcToolError::RmdParse { line: 7, .. })
+        ));
+    }
+
+    #[test]
+    fn test_failed_tx_sequence_is_turned_off() {
+        let (_, emulator_session) = emulator_session();
+        let device_config = emulator_session.device_config.lock().unwrap().clone();
+        let mock = MockTransport::new("mock");
+        let session = DeviceSession::new(Box::new(mock.clone()), None, None).unwrap();
+        *session.device_config.lock().unwrap() = device_config;
+        // The module does not answer the SEQUENCE_ON, but answers the SEQUENCE_OFF
+        let answer = {
+            let mock = mock.clone();
+            std::thread::spawn(move || {
+                let started_at = Instant::now();
+                let mut tx = vec![];
+                while started_at.elapsed() < Duration::from_secs(5) {
+                    tx.extend(mock.take_tx());
+                    if tx.ends_with(b"3") {
+                        mock.push_rx(b">");
+                        break;
+                    }
+                    std::thread::sleep(Duration::from_millis(1));
+                }
+                return tx;
+            })
+        };
+        assert!(execute_mode_sequence_in_session(&session, "a1 #>", None).is_err());
+        assert_eq!(answer.join().unwrap(), b"13");
+        assert!(session.active_mode.lock().unwrap().is_none());
+        assert!(!session.tx_guard.lock().unwrap().is_on());
+    }
+
+    #[test]
+    fn test_switching_modes() {
+        let (device, session) = emulator_session();
+        execute_mode_sequence_in_session(&session, "a1 #>", None).unwrap();
+        let active_mode = session.active_mode.lock().unwrap().clone().unwrap();
+        assert_eq!(active_mode.kind, ModeKind::TestMode);
+        assert_eq!(active_mode.mode_id, 1);
+        assert!(active_mode.is_tx);
+        assert_eq!(device.test_mode(), Some(EmulatorTestMode::TxCarrier));
+
+        // The SEQUENCE_OFF of the TX mode is executed before the packet sniffer is started
+        execute_mode_sequence_in_session(&session, "a6", None).unwrap();
+        assert_eq!(
+            active_mode_name(&session).as_deref(),
+            Some("Test Mode 6 Simple Packet Sniffer")
+        );
+        // The output of the sniffer is not parsed unless enabled
+        assert!(!session.packet_sniffer.lock().unwrap().is_running());
+
+        // The empty SEQUENCE_OFF of the sniffer turns it off
+        execute_mode_sequence_in_session(&session, "", None).unwrap();
+        assert_eq!(active_mode_name(&session), None);
+        assert!(!session.packet_sniffer.lock().unwrap().is_running());
+
+        execute_mode_sequence_in_session(&session, "aG #>", None).unwrap();
+        let active_mode = session.active_mode.lock().unwrap().clone().unwrap();
+        assert_eq!(active_mode.kind, ModeKind::QuickMode);
+        assert!(!active_mode.is_tx);
+        // Sequences that don't belong to a mode leave the active mode alone
+        execute_mode_sequence_in_session(&session, "aS ~>$", None).unwrap();
+        assert_eq!(
+            active_mode_name(&session).as_deref(),
+            Some("Set Gateway Mode")
+        );
+        assert!(turn_off_active_mode(&session, None).unwrap().is_empty());
+        assert_eq!(active_mode_name(&session), None);
+    }
+
+    #[test]
+    fn test_sequence_off_that_starts_a_mode() {
+        let (device, session) = emulator_session();
+        execute_mode_sequence_in_session(&session, "a2 #>", None).unwrap();
+        // The SEQUENCE_OFF of the TX modes is the SEQUENCE_ON of the RX mode, so it is only executed once
+        let trace = execute_mode_sequence_in_session(&session, "a3 #>", None).unwrap();
+        assert_eq!(trace.len(), 1);
+        assert_eq!(
+            active_mode_name(&session).as_deref(),
+            Some("Test Mode 3 (RX)")
+        );
+        assert_eq!(device.test_mode(), Some(EmulatorTestMode::Rx));
+        assert!(!session.tx_guard.lock().unwrap().is_on());
+        execute_mode_sequence_in_session(&session, "a3 #>", None).unwrap();
+        assert_eq!(
+            active_mode_name(&session).as_deref(),
+            Some("Test Mode 3 (RX)")
+        );
+    }
+
+    #[test]
+    fn test_sequence_without_config_is_refused() {
+        let mock = MockTransport::new("mock");
+        let session = DeviceSession::new(Box::new(mock.clone()), None, None).unwrap();
+        assert_eq!(
+            execute_mode_sequence_in_session(&session, "a1 #>", None),
+            Err(CcToolError::ConfigNotRead)
+        );
+        assert!(mock.take_tx().is_empty());
+        assert!(execute_mode_sequence_in_session(&session, "", None).is_ok());
+    }
+
+    #[test]
+    fn test_tear_down_turns_off_tx_mode() {
+        let (device, session) = emulator_session();
+        execute_mode_sequence_in_session(&session, "a2 #>", None).unwrap();
+        assert_eq!(device.test_mode(), Some(EmulatorTestMode::ModulatedTx));
+        tear_down_session(&session).unwrap();
+        assert_eq!(device.test_mode(), Some(EmulatorTestMode::Rx));
+        assert_eq!(active_mode_name(&session), None);
+    }
+}
@@ -0,0 +1,102 @@
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+    use tinymesh_cc_tool::port_actor::PortActor;
+    use tinymesh_cc_tool::tinymesh_device_info_mod::get_rssi_from_device;
+    use tinymesh_cc_tool::traffic_capture::{
+        current_command_context, enter_command_context, read_capture_file, CaptureDirection,
+        CaptureRecord, ReplayTransport, TrafficCapture,
+    };
+    use tinymesh_cc_tool::transport::MockTransport;
+
+    fn record(direction: CaptureDirection, data: &[u8]) -> CaptureRecord {
+        return CaptureRecord {
+            timestamp_us: 0,
+            direction,
+            session_id: Some("/dev/ttyUSB0".to_string()),
+            context: None,
+            data: data.to_vec(),
+        };
+    }
+
+    #[test]
+    fn test_capture_file() {
+        let file_path =
+            std::env::temp_dir().join(format!("cc_tool_capture_test_{}.jsonl", std::process::id()));
+        let capture = TrafficCapture::default();
+        // Nothing is recorded before the capture is started
+        capture
+            .record(CaptureDirection::TX, None, b"lost", Instant::now())
+            .unwrap();
+        capture.start(&file_path).unwrap();
+        let session_id = Some("/dev/ttyUSB0".to_string());
+        let written_at = Instant::now();
+        {
+            let _context = enter_command_context("get_device_rssi");
+            capture
+                .record(CaptureDirection::TX, session_id.clone(), b"S", written_at)
+                .unwrap();
+            capture
+                .record(
+                    CaptureDirection::RX,
+                    session_id.clone(),
+                    &[0x60, b'>'],
+                    written_at + Duration::from_millis(12),
+                )
+                .unwrap();
+        }
+        capture
+            .record(
+                CaptureDirection::RX,
+                session_id.clone(),
+                b"x",
+                written_at + Duration::from_millis(20),
+            )
+            .unwrap();
+        let (_, record_count) = capture.stop().unwrap().unwrap();
+        assert_eq!(record_count, 3);
+        assert!(!capture.is_running().unwrap());
+
+        let records = read_capture_file(&file_path).unwrap();
+        std::fs::remove_file(&file_path).unwrap();
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[0].direction, CaptureDirection::TX);
+        assert_eq!(records[0].context.as_deref(), Some("get_device_rssi"));
+        assert_eq!(records[1].data, vec![0x60, b'>']);
+        assert_eq!(records[2].context, None);
+        // The timestamps are the times of the reads / writes, not the times of the records
+        assert_eq!(records[1].timestamp_us - records[0].timestamp_us, 12_000);
+        assert_eq!(records[2].timestamp_us - records[0].timestamp_us, 20_000);
+    }
+
+    #[test]
+    fn test_replay_transport() {
+        let mut device = ReplayTransport::new(
+            "replay",
+            vec![
+                record(CaptureDirection::TX, b"S"),
+                record(CaptureDirection::RX, &[0x60]),
+                record(CaptureDirection::RX, b">"),
+                record(CaptureDirection::TX, b"S"),
+                record(CaptureDirection::RX, &[0x58, b'>']),
+            ],
+            Some("/dev/ttyUSB0"),
+        );
+        // The responses are only played back once the requests were sent
+        assert_eq!(get_rssi_from_device(&mut device, None), Ok(0x60));
+        assert_eq!(device.remaining_tx_chunks(), 1);
+        assert_eq!(get_rssi_from_device(&mut device, None), Ok(0x58));
+        assert_eq!(device.take_tx(), b"SS");
+        assert_eq!(device.remaining_tx_chunks(), 0);
+    }
+
+    #[test]
+    fn test_context_of_transactions() {
+        let port = PortActor::spawn(Some(Box::new(MockTransport::new("mock"))), None).unwrap();
+        let _context = enter_command_context("get_device_config");
+        assert_eq!(
+            port.transact(|_| Ok(current_command_context())),
+            Ok(Some("get_device_config".to_string()))
+        );
+    }
+}
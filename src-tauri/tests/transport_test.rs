@@ -0,0 +1,80 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use crate::common::rmd_file_path;
+    use std::fs::read_to_string;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::path::PathBuf;
+    use std::time::Duration;
+    use tinymesh_cc_tool::tinymesh_config_mod::get_device_config_from_device;
+    use tinymesh_cc_tool::tinymesh_device_info_mod::{get_rssi_from_device, switch_to_channel};
+    use tinymesh_cc_tool::transport::{MockTransport, TcpTransport, Transport};
+
+    fn config_response() -> Vec<u8> {
+        let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        d.push("resources/tests/config_response.txt");
+        read_to_string(d)
+            .unwrap()
+            .split_whitespace()
+            .map(|s| u8::from_str_radix(s, 16).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_get_device_config_over_mock_transport() {
+        let mock = MockTransport::new("mock");
+        mock.push_rx(&config_response());
+        let mut device = mock.clone();
+        let device_config =
+            get_device_config_from_device(&mut device, Some(&rmd_file_path()), None).unwrap();
+        assert_eq!(mock.take_tx(), vec![0x30]);
+        assert_eq!(device_config.model, "RF TM4070");
+        assert_eq!(device_config.cells[0].current_value, 0x04);
+    }
+
+    #[test]
+    fn test_get_rssi_over_mock_transport() {
+        let mock = MockTransport::new("mock");
+        mock.push_rx(&[0x50, b'>']);
+        let mut device = mock.clone();
+        assert_eq!(get_rssi_from_device(&mut device, None), Ok(0x50));
+        assert_eq!(mock.take_tx(), vec![b'S']);
+    }
+
+    #[test]
+    fn test_switch_to_channel_over_mock_transport() {
+        let mock = MockTransport::new("mock");
+        mock.push_rx(b">>");
+        let mut device = mock.clone();
+        assert_eq!(switch_to_channel(7, &mut device, None), Ok(()));
+        assert_eq!(mock.take_tx(), vec![b'C', 7]);
+    }
+
+    #[test]
+    fn test_tcp_transport_with_slow_listener() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = format!("tcp://{}", listener.local_addr().unwrap());
+        let server = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(200));
+            let (mut stream, _) = listener.accept().unwrap();
+            stream.write_all(b">").unwrap();
+        });
+        let mut transport = TcpTransport::connect(&address, Duration::from_millis(10)).unwrap();
+        assert_eq!(transport.name(), Some(address));
+        // The read timeout stays short, the prompt only arrives once the listener accepted
+        let mut buffer = [0u8; 1];
+        let mut timeouts = 0;
+        loop {
+            match transport.read(&mut buffer) {
+                Ok(1) => break,
+                Ok(_) => panic!("Connection closed"),
+                Err(_) => timeouts += 1,
+            }
+        }
+        assert_eq!(&buffer, b">");
+        assert!(timeouts > 0);
+        server.join().unwrap();
+    }
+}
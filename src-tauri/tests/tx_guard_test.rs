@@ -0,0 +1,178 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use crate::common::emulator_session;
+    use std::time::{Duration, Instant};
+    use tinymesh_cc_tool::cc_tool_error::CcToolError;
+    use tinymesh_cc_tool::mk_module_description::MkModuleDescription;
+    use tinymesh_cc_tool::tinymesh_emulator::EmulatorTestMode;
+    use tinymesh_cc_tool::tinymesh_test_mode_mod::{
+        enforce_tx_guard, execute_mode_sequence_in_session,
+    };
+    use tinymesh_cc_tool::tx_guard::{
+        load_tx_guard_config, save_tx_guard_config, TxGuard, TxGuardConfig, TxGuardLimit,
+    };
+
+    /// 10 % of a 10 s window, i.e. 1 s of TX
+    const DUTY_CYCLE: TxGuardConfig = TxGuardConfig {
+        max_on_time_ms: None,
+        duty_cycle_percent: Some(10.0),
+        duty_cycle_window_ms: 10_000,
+    };
+
+    #[test]
+    fn test_max_on_time() {
+        let config = TxGuardConfig {
+            max_on_time_ms: Some(500),
+            ..Default::default()
+        };
+        let mut guard = TxGuard::default();
+        let start = Instant::now();
+        guard.start(config, start);
+        assert_eq!(
+            guard.limit_reached(start + Duration::from_millis(499)),
+            None
+        );
+        assert_eq!(
+            guard.limit_reached(start + Duration::from_millis(500)),
+            Some(TxGuardLimit::MaxOnTime)
+        );
+        guard.stop(start + Duration::from_millis(500));
+        assert!(!guard.is_on());
+        assert_eq!(guard.limit_reached(start + Duration::from_secs(1)), None);
+        // Without a duty cycle the next activation is always allowed
+        assert!(guard
+            .check_start(&config, start + Duration::from_secs(1))
+            .is_ok());
+    }
+
+    #[test]
+    fn test_duty_cycle() {
+        let mut guard = TxGuard::default();
+        let start = Instant::now();
+        guard.start(DUTY_CYCLE, start);
+        assert_eq!(
+            guard.limit_reached(start + Duration::from_millis(1000)),
+            Some(TxGuardLimit::DutyCycle)
+        );
+        guard.stop(start + Duration::from_millis(1000));
+        let status = guard.status(start + Duration::from_secs(2));
+        assert_eq!(status.duty_cycle_used_ms, 1000);
+        assert_eq!(status.duty_cycle_budget_ms, Some(1000));
+
+        // The activation leaves the window 10 s after it started
+        assert_eq!(
+            guard.check_start(&DUTY_CYCLE, start + Duration::from_secs(2)),
+            Err(CcToolError::TxLimitReached {
+                limit: TxGuardLimit::DutyCycle,
+                retry_after_ms: 8000,
+            })
+        );
+        assert!(guard
+            .check_start(&DUTY_CYCLE, start + Duration::from_millis(10_500))
+            .is_ok());
+
+        // Only the parts of the activations inside the window count: 300 ms of the first one and 200 ms
+        guard.start(DUTY_CYCLE, start + Duration::from_millis(10_500));
+        assert_eq!(
+            guard
+                .status(start + Duration::from_millis(10_700))
+                .duty_cycle_used_ms,
+            500
+        );
+        assert_eq!(
+            guard.limit_reached(start + Duration::from_millis(10_700)),
+            None
+        );
+    }
+
+    #[test]
+    fn test_enforce_tx_guard_turns_off_tx_mode() {
+        let (device, session) = emulator_session();
+        session
+            .device_config
+            .lock()
+            .unwrap()
+            .as_mut()
+            .unwrap()
+            .tx_guard
+            .max_on_time_ms = Some(60_000);
+
+        execute_mode_sequence_in_session(&session, "a1 #>", None).unwrap();
+        assert!(session.tx_guard.lock().unwrap().is_on());
+        assert_eq!(
+            enforce_tx_guard(&session, None, Instant::now()).unwrap(),
+            None
+        );
+        assert_eq!(device.test_mode(), Some(EmulatorTestMode::TxCarrier));
+
+        let later = Instant::now() + Duration::from_secs(61);
+        assert_eq!(
+            enforce_tx_guard(&session, None, later).unwrap(),
+            Some(TxGuardLimit::MaxOnTime)
+        );
+        assert_eq!(device.test_mode(), Some(EmulatorTestMode::Rx));
+        assert!(session.active_mode.lock().unwrap().is_none());
+        assert!(!session.tx_guard.lock().unwrap().is_on());
+    }
+
+    #[test]
+    fn test_user_config_replaces_rmd_config() {
+        let (_, session) = emulator_session();
+        let device_config = session.device_config.lock().unwrap().clone().unwrap();
+        assert!(!device_config.tx_guard.is_enabled());
+        let user_config = TxGuardConfig {
+            max_on_time_ms: Some(60_000),
+            ..Default::default()
+        };
+        session
+            .tx_guard
+            .lock()
+            .unwrap()
+            .set_user_config(Some(user_config));
+
+        execute_mode_sequence_in_session(&session, "a2 #>", None).unwrap();
+        let status = session.tx_guard.lock().unwrap().status(Instant::now());
+        assert_eq!(status.config, user_config);
+        // Non-TX modes are not guarded
+        execute_mode_sequence_in_session(&session, "a3 #>", None).unwrap();
+        execute_mode_sequence_in_session(&session, "a3 #>", None).unwrap();
+        assert!(!session.tx_guard.lock().unwrap().is_on());
+    }
+
+    #[test]
+    fn test_parse_tx_guard_entries() {
+        let input = "[DEVICE_MODEL]\nRF TM4070\n\n[TX_MAX_ON_TIME]\n30\n\n[TX_DUTY_CYCLE]\n1\n\n[TX_DUTY_CYCLE_WINDOW]\n3600\n";
+        let module_description = MkModuleDescription::new(input).unwrap();
+        assert_eq!(
+            module_description.tx_guard,
+            TxGuardConfig {
+                max_on_time_ms: Some(30_000),
+                duty_cycle_percent: Some(1.0),
+                duty_cycle_window_ms: 3_600_000,
+            }
+        );
+
+        let input = "[DEVICE_MODEL]\nRF TM4070\n\n[TX_DUTY_CYCLE]\n150\n";
+        assert!(matches!(
+            MkModuleDescription::new(input),
+            Err(CcToolError::RmdParse { line: 4, .. })
+        ));
+    }
+
+    #[test]
+    fn test_save_and_load_tx_guard_config() {
+        let file_path = std::env::temp_dir()
+            .join(format!("cc_tool_tx_guard_config_{}", std::process::id()))
+            .join("tx_guard_config.json");
+        assert_eq!(load_tx_guard_config(&file_path), None);
+
+        save_tx_guard_config(&file_path, Some(&DUTY_CYCLE)).unwrap();
+        assert_eq!(load_tx_guard_config(&file_path), Some(DUTY_CYCLE));
+
+        save_tx_guard_config(&file_path, None).unwrap();
+        assert_eq!(load_tx_guard_config(&file_path), None);
+        std::fs::remove_dir_all(file_path.parent().unwrap()).unwrap();
+    }
+}
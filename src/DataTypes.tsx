@@ -7,6 +7,14 @@ type MkDeviceConfig = {
   cells: MkDeviceCell[];
   test_modes: MkDeviceTestMode[];
   quick_modes: MkDeviceQuickMode[];
+  rssi_scaling: RssiScaling;
+  tx_guard: TxGuardConfig;
+};
+
+/** Conversion of the raw RSSI values of a device to dBm, from `RSSI_OFFSET` and `RSSI_SCALE_FACTOR` of the RMD file */
+type RssiScaling = {
+  offset: number;
+  scale_factor: number;
 };
 
 type MkDeviceCalib = {
@@ -32,6 +40,8 @@ type MkDeviceTestMode = {
   description: string;
   sequence_on: string;
   sequence_off: string;
+  /** Whether the module transmits in this mode, from the `TESTMODE n TX` entry of the RMD file */
+  is_tx: boolean | null;
 };
 
 type MkDeviceQuickMode = {
@@ -40,12 +50,310 @@ type MkDeviceQuickMode = {
   description: string;
   sequence_on: string;
   sequence_off: string;
+  /** Whether the module transmits in this mode, from the `QUICKMODE n TX` entry of the RMD file */
+  is_tx: boolean | null;
+};
+
+type SerialSettings = {
+  baud_rate: number;
+  data_bits: number;
+  parity: "None" | "Odd" | "Even";
+  stop_bits: "One" | "Two";
+  flow_control: "None" | "Software" | "Hardware";
+  read_timeout_ms: number;
+  dtr: boolean | null;
+  rts: boolean | null;
+};
+
+type FrameRule =
+  | { kind: "InterByteGap" }
+  | { kind: "FixedLength"; length: number }
+  | { kind: "Delimiter"; delimiter: number[] }
+  | { kind: "TinyMeshPacket" };
+
+type PacketizerConfig = {
+  rule: FrameRule;
+  inter_byte_timeout_ms: number;
+  max_frame_len: number;
+};
+
+type TinyMeshNodeStatus = {
+  detail: number;
+  data: number;
+  address: number;
+  temperature: number;
+  voltage: number;
+  digital_io: number;
+  analog_io: [number, number];
+  hardware_revision: number;
+  firmware_revision: number;
+};
+
+type TinyMeshPacketBody =
+  | ({ kind: "Event" } & TinyMeshNodeStatus)
+  | { kind: "SerialData"; block_counter: number; data: number[] }
+  | { kind: "CommandAck"; acknowledged: boolean; command_number: number; status: TinyMeshNodeStatus }
+  | ({ kind: "Status" } & TinyMeshNodeStatus)
+  | { kind: "ConfigData"; data: number[] };
+
+type TinyMeshPacket = {
+  system_id: number;
+  origin_id: number;
+  rssi: number;
+  network_level: number;
+  hops: number;
+  packet_number: number;
+  latency: number;
+  body: TinyMeshPacketBody;
+};
+
+type TinyMeshCommand =
+  | { kind: "GetStatus" }
+  | { kind: "GetConfig" }
+  | { kind: "SetConfig"; address: number; value: number }
+  | { kind: "SetOutputs"; outputs: number; mask: number }
+  | { kind: "SetPwm"; duty_cycle: number }
+  | { kind: "SerialData"; data: number[] };
+
+/** Payload of the `packet_event` */
+type PacketEventPayload = {
+  session_id: string | null;
+  packet: TinyMeshPacket;
+  timestamp_us: number;
+  sequence: number;
+};
+
+/** A node of the mesh node table, see `mesh_topology.rs` for how the parent is inferred */
+type MeshNode = {
+  unique_id: number;
+  system_id: number;
+  device_type: "Router" | "Node";
+  network_level: number;
+  hops: number;
+  rssi: number;
+  last_seen_us: number;
+  hardware_revision: number | null;
+  firmware_revision: number | null;
+  packet_count: number;
+  /** `null` if the node is connected to the gateway */
+  parent_id: number | null;
+};
+
+/** Payload of the `mesh_event` */
+type MeshEventPayload = {
+  session_id: string | null;
+  change: "Added" | "Updated";
+  node: MeshNode;
+};
+
+/** A line of the packet sniffer test mode output, see `packet_sniffer.rs` for the parsed format */
+type SnifferReport = {
+  origin_id: number;
+  rssi: number | null;
+  packet_type: number | null;
+  length: number | null;
+};
+
+/** Payload of the `sniffer_event` */
+type SnifferEventPayload = {
+  session_id: string | null;
+  report: SnifferReport;
+  timestamp_us: number;
+};
+
+/** Statistics of the packets of a single sender, reported by the packet sniffer test mode */
+type SnifferNodeStats = {
+  origin_id: number;
+  packet_count: number;
+  total_length: number;
+  last_rssi: number | null;
+  min_rssi: number | null;
+  max_rssi: number | null;
+  mean_rssi: number | null;
+  /** Number of packets per packet type */
+  packet_types: Record<number, number>;
+  first_seen_us: number;
+  last_seen_us: number;
+};
+
+/** A line of the RSSI sniffer test mode output, see `rssi_sniffer.rs` for the parsed format */
+type RssiReport = {
+  source_id: number | null;
+  raw_rssi: number;
+  /** The RSSI in dBm */
+  rssi: number;
+};
+
+/** Payload of the `rssi_sniffer_event` */
+type RssiSnifferEventPayload = {
+  session_id: string | null;
+  report: RssiReport;
+  timestamp_us: number;
+};
+
+/** Rolling RSSI statistics of a single source in dBm, reported by the RSSI sniffer test mode */
+type RssiSourceStats = {
+  source_id: number | null;
+  report_count: number;
+  /** Number of reports the statistics are calculated over */
+  window_len: number;
+  last_rssi: number;
+  min_rssi: number;
+  max_rssi: number;
+  mean_rssi: number;
+  p10_rssi: number;
+  p50_rssi: number;
+  p90_rssi: number;
+  last_seen_us: number;
+};
+
+/** The test or quick mode a device was put in by executing its `sequence_on` */
+type ActiveMode = {
+  kind: "TestMode" | "QuickMode";
+  mode_id: number;
+  name: string;
+  sequence_on: string;
+  sequence_off: string;
+  /** Whether the module transmits in this mode */
+  is_tx: boolean;
+};
+
+/** Payload of the `test_mode_event` */
+type TestModeEventPayload = {
+  session_id: string | null;
+  active_mode: ActiveMode | null;
+};
+
+/** Limits of the TX test modes, from `TX_MAX_ON_TIME`, `TX_DUTY_CYCLE` and `TX_DUTY_CYCLE_WINDOW` of the RMD file or set by the user */
+type TxGuardConfig = {
+  max_on_time_ms: number | null;
+  duty_cycle_percent: number | null;
+  duty_cycle_window_ms: number;
+};
+
+type TxGuardLimit = "MaxOnTime" | "DutyCycle";
+
+/** On-time and duty cycle usage of the TX test modes of a session */
+type TxGuardStatus = {
+  config: TxGuardConfig;
+  is_on: boolean;
+  on_time_ms: number;
+  duty_cycle_used_ms: number;
+  duty_cycle_budget_ms: number | null;
+};
+
+/** Payload of the `tx_guard_event`, emitted when a TX test mode was turned off at a limit */
+type TxGuardEventPayload = {
+  session_id: string | null;
+  limit: TxGuardLimit;
+  mode_name: string;
+  status: TxGuardStatus;
+};
+
+/** Trace of an executed step of a mode sequence, a repeated step has one trace per repetition */
+type SequenceStepTrace = {
+  step_index: number;
+  repetition: number;
+  sent: number[];
+  received: number[];
+  outcome: "Sent" | "Matched" | "NotMatched" | "Waited";
+  elapsed_ms: number;
 };
 
+type SerialPortEntry = {
+  port_name: string;
+  port_type: "Usb" | "Pci" | "Bluetooth" | "Unknown";
+  vid: number | null;
+  pid: number | null;
+  serial_number: string | null;
+  manufacturer: string | null;
+  product: string | null;
+  is_known_adapter: boolean;
+};
+
+type AutodetectResult = {
+  serial_settings: SerialSettings;
+  model: string;
+  hw_version: string;
+  firmware_version: string;
+};
+
+type ConnectionInfo = {
+  port_name: string;
+  serial_number: string | null;
+  serial_settings: SerialSettings;
+};
+
+type SessionInfo = {
+  session_id: string;
+  is_connected: boolean;
+  is_default: boolean;
+  connection: ConnectionInfo | null;
+};
+
+type TaskStatus = "Running" | "Cancelling" | "Completed" | "Cancelled" | "Failed";
+
+type TaskInfo = {
+  task_id: number;
+  name: string;
+  session_id: string | null;
+  status: TaskStatus;
+  result: string | null;
+  error: CcToolError | null;
+  elapsed_ms: number;
+};
+
+type CcToolError =
+  | { kind: "NotConnected" }
+  | { kind: "UnknownSession"; session_id: string }
+  | { kind: "UnknownTask"; task_id: number }
+  | { kind: "PortBusy" }
+  | { kind: "Timeout"; received: number[] }
+  | { kind: "UnexpectedResponse"; expected: string; got: number[] }
+  | { kind: "RmdNotFound"; model: string; searched_paths: string[] }
+  | { kind: "RmdParse"; line: number; message: string }
+  | { kind: "ConfigNotRead" }
+  | { kind: "ValidationFailed"; address: number; message: string }
+  | { kind: "TxLimitReached"; limit: TxGuardLimit; retry_after_ms: number }
+  | { kind: "InvalidInput"; message: string }
+  | { kind: "Io"; message: string }
+  | { kind: "Internal"; message: string };
+
 export type {
+  ActiveMode,
+  AutodetectResult,
+  CcToolError,
+  ConnectionInfo,
+  FrameRule,
+  MeshEventPayload,
+  MeshNode,
   MkDeviceConfig,
   MkDeviceCell,
   MkDeviceTestMode,
   MkDeviceQuickMode,
-  MkDeviceCalib
+  MkDeviceCalib,
+  PacketEventPayload,
+  PacketizerConfig,
+  RssiReport,
+  RssiScaling,
+  RssiSnifferEventPayload,
+  RssiSourceStats,
+  SequenceStepTrace,
+  SerialPortEntry,
+  SerialSettings,
+  SessionInfo,
+  SnifferEventPayload,
+  SnifferNodeStats,
+  SnifferReport,
+  TaskInfo,
+  TaskStatus,
+  TestModeEventPayload,
+  TinyMeshCommand,
+  TinyMeshNodeStatus,
+  TinyMeshPacket,
+  TinyMeshPacketBody,
+  TxGuardConfig,
+  TxGuardEventPayload,
+  TxGuardLimit,
+  TxGuardStatus
 };
@@ -13,10 +13,14 @@ import React, {
   useContext,
   createContext,
 } from "react";
-import { invoke } from "@tauri-apps/api";
 import { ask, message } from "@tauri-apps/api/dialog";
 import { Tooltip } from "flowbite-react";
-import { getDeviceCalib, setDeviceCalib } from "../utils/device_info_util";
+import {
+  factoryReset,
+  getDeviceCalib,
+  setDeviceCalib,
+} from "../utils/device_info_util";
+import { formatError } from "../utils/error_util";
 import { error } from "tauri-plugin-log-api";
 
 import { MkDeviceCell } from "../DataTypes";
@@ -51,9 +55,7 @@ const Calibration: React.FC = () => {
   }, [isConnected, currentMode]);
 
   const readCalibFunc = async () => {
-    await invoke("stop_communication_task", {});
     await readCalib();
-    await invoke("start_communication_task", {});
   };
 
   const readCalib = () => {
@@ -70,8 +72,8 @@ const Calibration: React.FC = () => {
         setLocked(result.c_locked_cells);
       })
       .catch((err) => {
-        alert("No matching RMD file available");
-        error(`Error occurred while trying to read device calibration: ${err}`);
+        alert(formatError(err));
+        error(`Error occurred while trying to read device calibration: ${formatError(err)}`);
       });
 
     return data;
@@ -90,12 +92,10 @@ const Calibration: React.FC = () => {
       );
       return;
     }
-    await invoke("stop_communication_task", {});
     let success = await setDeviceCalib(data);
     if (success) {
       await readCalib();
     }
-    await invoke("start_communication_task", {});
   };
 
   const factoryResetBtnFunc = async () => {
@@ -107,11 +107,9 @@ const Calibration: React.FC = () => {
       }
     );
     if (result) {
-      await invoke("stop_communication_task", {});
-      if (await invoke("factory_reset", {})) {
+      if (await factoryReset()) {
         await readCalib();
       }
-      await invoke("start_communication_task", {});
     }
   };
 
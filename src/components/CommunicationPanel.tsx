@@ -1,6 +1,9 @@
 import { useState, useEffect } from "react";
 import { invoke } from "@tauri-apps/api";
 import { Tooltip } from "flowbite-react";
+import { message } from "@tauri-apps/api/dialog";
+import { error } from "tauri-plugin-log-api";
+import { formatError } from "../utils/error_util";
 
 function CommunicationPanel() {
   const [communicationInput, setCommunicationInput] = useState<string>("");
@@ -9,9 +12,17 @@ function CommunicationPanel() {
   const [intervalId, setIntervalId] = useState<NodeJS.Timeout | null>(null);
 
   const handleSubmit = async () => {
-    await invoke("send_bytes", {
-      input: communicationInput,
-    });
+    try {
+      await invoke("send_bytes", {
+        input: communicationInput,
+      });
+    } catch (err) {
+      error(`Error occurred while sending bytes: ${formatError(err)}`);
+      await message(formatError(err), {
+        title: "Tinymesh CC Tool",
+        type: "error",
+      });
+    }
   };
 
   const handleRepeatToggle = () => {
@@ -47,7 +58,7 @@ function CommunicationPanel() {
             rows={4}
             spellCheck={false}
             className="w-full px-0 text-xs text-gray-900 bg-white border-0 dark:bg-gray-800 focus:ring-0 dark:text-white dark:placeholder-gray-400"
-            placeholder="Write a message..."
+            placeholder="Write a message... (escapes: \r \n \xNN, bytes: '0x0D 0b1010 0x00*16', raw hex: hex: 0D 0A)"
             onChange={(e) => setCommunicationInput(e.target.value)}
             disabled={intervalRunning}
             required
@@ -13,11 +13,15 @@ import React, {
   useContext,
   createContext,
 } from "react";
-import { invoke } from "@tauri-apps/api";
 import { ask, message } from "@tauri-apps/api/dialog";
 import TestModeSelect from "./TestModeSelect";
 import { Tooltip } from "flowbite-react";
-import { getDeviceConfig, setDeviceConfig } from "../utils/device_info_util";
+import {
+  factoryReset,
+  getDeviceConfig,
+  setDeviceConfig,
+} from "../utils/device_info_util";
+import { formatError } from "../utils/error_util";
 import { error } from "tauri-plugin-log-api";
 
 import {
@@ -60,9 +64,7 @@ const ConfigPanel: React.FC = () => {
   }, [isConnected, currentMode]);
 
   const readConfigBtnFunc = async () => {
-    await invoke("stop_communication_task", {});
     await readConfig();
-    await invoke("start_communication_task", {});
   };
 
   const readConfig = () => {
@@ -84,8 +86,8 @@ const ConfigPanel: React.FC = () => {
         setLocked(result.locked_cells);
       })
       .catch((err) => {
-        alert("No matching RMD file available");
-        error(`Error occurred while trying to read device config: ${err}`);
+        alert(formatError(err));
+        error(`Error occurred while trying to read device config: ${formatError(err)}`);
       });
   };
 
@@ -102,12 +104,10 @@ const ConfigPanel: React.FC = () => {
       );
       return;
     }
-    await invoke("stop_communication_task", {});
     let success = await setDeviceConfig(data);
     if (success) {
       await readConfig();
     }
-    await invoke("start_communication_task", {});
   };
 
   const factoryResetBtnFunc = async () => {
@@ -119,11 +119,9 @@ const ConfigPanel: React.FC = () => {
       }
     );
     if (result) {
-      await invoke("stop_communication_task", {});
-      if (await invoke("factory_reset", {})) {
+      if (await factoryReset()) {
         await readConfig();
       }
-      await invoke("start_communication_task", {});
     }
   };
 
@@ -1,6 +1,5 @@
 import ButtonComp from "./ButtonComp";
 import RSSIChart from "./RSSIChart";
-import { invoke } from "@tauri-apps/api";
 import { useState, createContext } from "react";
 
 import {
@@ -12,38 +11,23 @@ import {
 } from "../utils/device_info_util";
 
 const getRSSIBtnFunc = async () => {
-  await invoke("stop_communication_task", {});
-  let result = await getRSSI();
-  await invoke("start_communication_task", {});
-  return result;
+  return await getRSSI();
 };
 
 const getAnalogBtnFunc = async () => {
-  await invoke("stop_communication_task", {});
-  let result = await getAnalog();
-  await invoke("start_communication_task", {});
-  return result;
+  return await getAnalog();
 };
 
 const getDigitalBtnFunc = async () => {
-  await invoke("stop_communication_task", {});
-  let result = await getDigital();
-  await invoke("start_communication_task", {});
-  return result;
+  return await getDigital();
 };
 
 const getTemperatureBtnFunc = async () => {
-  await invoke("stop_communication_task", {});
-  let result = await getTemperature();
-  await invoke("start_communication_task", {});
-  return result;
+  return await getTemperature();
 };
 
 const getVoltageBtnFunc = async () => {
-  await invoke("stop_communication_task", {});
-  let result = await getVoltage();
-  await invoke("start_communication_task", {});
-  return result;
+  return await getVoltage();
 };
 
 export const RssiStreamContext = createContext({
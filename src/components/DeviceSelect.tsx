@@ -1,8 +1,29 @@
 import React, { useState, useEffect } from "react";
 import { invoke } from "@tauri-apps/api";
+import { listen } from "@tauri-apps/api/event";
+import { SerialPortEntry } from "../DataTypes";
 
-async function fetchOptions() {
-  return (await invoke("get_devices")) as string[];
+async function fetchOptions(knownAdaptersOnly: boolean) {
+  return (await invoke("get_devices", {
+    knownAdaptersOnly: knownAdaptersOnly,
+  })) as SerialPortEntry[];
+}
+
+function toHex(value: number) {
+  return value.toString(16).padStart(4, "0").toUpperCase();
+}
+
+function describePort(port: SerialPortEntry) {
+  if (port.port_type !== "Usb") {
+    return port.port_name;
+  }
+  const name = [port.manufacturer, port.product]
+    .filter((part) => part)
+    .join(" ");
+  const serialNumber = port.serial_number ? ` S/N ${port.serial_number}` : "";
+  return `${port.port_name} - ${name} [${toHex(port.vid ?? 0)}:${toHex(
+    port.pid ?? 0
+  )}]${serialNumber}`;
 }
 
 interface SelectProps {
@@ -16,15 +37,30 @@ const DeviceSelect: React.FC<SelectProps> = ({
   className,
   value,
 }) => {
-  const [options, setOptions] = useState<string[]>([]);
+  const [options, setOptions] = useState<SerialPortEntry[]>([]);
   const [selectedOption, setSelectedOption] = useState<string>(value);
+  const [knownAdaptersOnly, setKnownAdaptersOnly] = useState(false);
 
   useEffect(() => {
     fetchOptionsData();
-  }, [fetchOptions]);
+  }, [knownAdaptersOnly]);
+
+  // Refresh the list whenever the port watcher reports an adapter being plugged in or unplugged
+  useEffect(() => {
+    const unlistenAttached = listen<SerialPortEntry>("device_attached", () => {
+      fetchOptionsData();
+    });
+    const unlistenDetached = listen<SerialPortEntry>("device_detached", () => {
+      fetchOptionsData();
+    });
+    return () => {
+      unlistenAttached.then((f) => f());
+      unlistenDetached.then((f) => f());
+    };
+  }, [knownAdaptersOnly]);
 
   const fetchOptionsData = async () => {
-    const data = await fetchOptions(); // Call the function to fetch options
+    const data = await fetchOptions(knownAdaptersOnly); // Call the function to fetch options
     setOptions(data);
   };
 
@@ -50,11 +86,22 @@ const DeviceSelect: React.FC<SelectProps> = ({
             Select Device
           </option>
           {options.map((option, index) => (
-            <option key={index} value={option}>
-              {option}
+            <option key={index} value={option.port_name}>
+              {describePort(option)}
             </option>
           ))}
         </select>
+        <label
+          className="h-[5vh] flex items-center gap-1 text-xs"
+          title="Only show TinyMesh, FTDI and CP210x adapters"
+        >
+          <input
+            type="checkbox"
+            checked={knownAdaptersOnly}
+            onChange={(e) => setKnownAdaptersOnly(e.target.checked)}
+          />
+          TinyMesh only
+        </label>
         <button
           type="button"
           onClick={handleReloadOptions}
@@ -1,20 +1,58 @@
-import { useState, useContext } from "react";
+import { useState, useContext, useEffect } from "react";
 import DeviceSelect from "./DeviceSelect";
 import InputWithDatalist from "./InputWithDatalist";
+import SerialSettingsSelect from "./SerialSettingsSelect";
 import {
+  autodetectConnection,
   connectToDevice,
+  defaultSerialSettings,
   disconnectFromDevice,
+  getSerialSettings,
+  setAutoReconnect,
 } from "../utils/connection_util";
+import { ConnectionInfo, SerialSettings } from "../DataTypes";
 
 import ConnectDisconnectButton from "./ConnectDisconnectButton";
 import ConfigModeToggle from "./ConfigModeToggle";
 import { ConnectionContext } from "../App";
 import { invoke } from "@tauri-apps/api";
 import { message } from "@tauri-apps/api/dialog";
+import { listen } from "@tauri-apps/api/event";
 
 function Header() {
-  const [baudRate, setBaudRate] = useState<number>(19200);
+  const [serialSettings, setSerialSettings] = useState<SerialSettings>(
+    defaultSerialSettings
+  );
   const [deviceName, setDeviceName] = useState("");
+  const [isAutodetecting, setIsAutodetecting] = useState(false);
+  const [autoReconnect, setAutoReconnectState] = useState(false);
+
+  const autodetectBtnFunc = async () => {
+    if (deviceName === "") {
+      return;
+    }
+    setIsAutodetecting(true);
+    try {
+      let result = await autodetectConnection(deviceName);
+      setSerialSettings(result.serial_settings);
+      await message(
+        `Found ${result.model} at ${result.serial_settings.baud_rate} baud, parity ${result.serial_settings.parity}`,
+        {
+          title: "Tinymesh CC Tool",
+          type: "info",
+        }
+      );
+    } catch (error) {
+      await message(
+        `Could not detect the serial settings. Make sure the module is in configuration mode.\n${error}`,
+        {
+          title: "Tinymesh CC Tool",
+          type: "error",
+        }
+      );
+    }
+    setIsAutodetecting(false);
+  };
   const {
     isConnected,
     setIsConnected,
@@ -28,6 +66,46 @@ function Header() {
     setHardware,
   } = useContext(ConnectionContext);
 
+  // The port watcher tears the connection down when the adapter is unplugged
+  // and restores it (if enabled) when the same adapter is plugged in again
+  useEffect(() => {
+    const unlistenLost = listen<ConnectionInfo>("connection_lost", (event) => {
+      setIsConnected(false);
+      setCurrentMode("communication");
+      setModel("");
+      setFirmware("");
+      setHardware("");
+      message(`The connection to ${event.payload.port_name} was lost`, {
+        title: "Tinymesh CC Tool",
+        type: "error",
+      });
+    });
+    const unlistenReconnected = listen<ConnectionInfo>(
+      "device_reconnected",
+      async (event) => {
+        setDeviceName(event.payload.port_name);
+        setSerialSettings(event.payload.serial_settings);
+        try {
+          await invoke("send_bytes", { input: "X" });
+          await invoke("start_communication_task", {});
+          setIsConnected(true);
+        } catch (error) {
+          await message(
+            `Encountered an error while reconnecting to ${event.payload.port_name}: ${error}`,
+            {
+              title: "Tinymesh CC Tool",
+              type: "error",
+            }
+          );
+        }
+      }
+    );
+    return () => {
+      unlistenLost.then((f) => f());
+      unlistenReconnected.then((f) => f());
+    };
+  }, []);
+
   function buttonsAndDeviceInfo(isConnected: boolean) {
     if (isConnected && currentMode === "configuration") {
       return (
@@ -77,9 +155,10 @@ function Header() {
           <div className="">
             <InputWithDatalist
               optionsProvider={() => BaudRateList}
+              value={`${serialSettings.baud_rate}`}
               onValueChanged={(value) => {
                 let parsedValue = parseInt(value);
-                setBaudRate(parsedValue);
+                setSerialSettings({ ...serialSettings, baud_rate: parsedValue });
               }}
               className="rounded-md border-gray-300 text-xs text-black h-[5vh] p-0 px-[5px]"
               placeholder="Baud Rate"
@@ -89,9 +168,31 @@ function Header() {
             <DeviceSelect
               value={deviceName}
               className="rounded-md p-0 px-[5px] text-xs border-gray-300 h-[5vh] text-black"
-              onSelected={setDeviceName}
+              onSelected={async (selectedDeviceName) => {
+                setDeviceName(selectedDeviceName);
+                if (selectedDeviceName !== "") {
+                  setSerialSettings(await getSerialSettings(selectedDeviceName));
+                }
+              }}
             />
           </div>
+          <div className="">
+            <SerialSettingsSelect
+              value={serialSettings}
+              onChanged={setSerialSettings}
+              className="rounded-md p-0 px-[5px] text-xs border-gray-300 h-[5vh] text-black"
+            />
+          </div>
+          <div className="">
+            <button
+              type="button"
+              onClick={autodetectBtnFunc}
+              disabled={deviceName === "" || isAutodetecting}
+              className="h-[5vh] rounded-lg bg-blue-700 px-[5px] text-xs font-medium text-white hover:bg-blue-800 disabled:opacity-50"
+            >
+              {isAutodetecting ? "Detecting..." : "Autodetect"}
+            </button>
+          </div>
         </div>
       );
     }
@@ -106,7 +207,7 @@ function Header() {
             connectFunction={async () => {
               try {
                 await invoke("reset_program_state", {});
-                await connectToDevice(deviceName, baudRate);
+                await connectToDevice(deviceName, serialSettings);
                 await invoke("send_bytes", { input: "X" });
                 setCurrentMode("communication");
                 await invoke("start_communication_task", {});
@@ -140,6 +241,22 @@ function Header() {
             className="h-[5vh] inline-flex items-center rounded-lg bg-blue-700 px-[5px] py-2.5 text-center text-xs font-medium text-white hover:bg-blue-800 focus:outline-none focus:ring-4 focus:ring-blue-300 dark:bg-blue-600 dark:hover:bg-blue-700 dark:focus:ring-blue-800"
           />
         </div>
+        <label
+          className="h-[5vh] flex items-center gap-1 text-xs"
+          title="Reconnect with the same settings when the unplugged adapter is plugged in again"
+        >
+          <input
+            type="checkbox"
+            checked={autoReconnect}
+            onChange={async (e) => {
+              const enabled = e.target.checked;
+              if (await setAutoReconnect(enabled)) {
+                setAutoReconnectState(enabled);
+              }
+            }}
+          />
+          Auto reconnect
+        </label>
         <div className={`${isConnected ? "" : "hidden"}`}>
           <ConfigModeToggle retries={10} interval={1} />
         </div>
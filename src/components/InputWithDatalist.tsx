@@ -1,18 +1,26 @@
-import React, { useState } from "react";
+import React, { useState, useEffect } from "react";
 
 interface InputWithDatalistProps {
   optionsProvider: () => string[];
   onValueChanged: (value: string) => void;
   className: string;
   placeholder?: string;
+  value?: string;
 }
 const InputWithDatalist: React.FC<InputWithDatalistProps> = ({
   optionsProvider,
   onValueChanged,
   className,
   placeholder = "",
+  value,
 }) => {
-  const [inputValue, setInputValue] = useState("19200");
+  const [inputValue, setInputValue] = useState(value ?? "19200");
+
+  useEffect(() => {
+    if (value !== undefined) {
+      setInputValue(value);
+    }
+  }, [value]);
 
   const handleInputChange = (e: React.ChangeEvent<HTMLInputElement>) => {
     const newValue = e.target.value;
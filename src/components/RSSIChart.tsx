@@ -4,8 +4,11 @@ import { useState, useEffect, useContext } from "react";
 import { invoke } from "@tauri-apps/api";
 import { listen } from "@tauri-apps/api/event";
 import { RssiStreamContext } from "./DeviceInfo";
+import { error } from "tauri-plugin-log-api";
+import { formatError } from "../utils/error_util";
 
 type RSSIEvent = {
+  session_id: string | null;
   rssi: number;
   channel: number;
 };
@@ -63,12 +66,14 @@ const RSSIChart: React.FC = () => {
   const handleRepeatToggle = async () => {
     if (rssiStreamRunning) {
       await invoke("stop_rssi_stream", {});
-      await invoke("start_communication_task", {});
       setRssiStreamRunning(false);
     } else {
-      await invoke("stop_communication_task", {});
-      await invoke("start_rssi_stream", {});
-      setRssiStreamRunning(true);
+      try {
+        await invoke("start_rssi_stream", {});
+        setRssiStreamRunning(true);
+      } catch (err) {
+        error(`Error occurred while starting RSSI stream: ${formatError(err)}`);
+      }
     }
   };
 
@@ -0,0 +1,123 @@
+import React from "react";
+import { SerialSettings } from "../DataTypes";
+
+interface SerialSettingsSelectProps {
+  value: SerialSettings;
+  onChanged: (serialSettings: SerialSettings) => void;
+  className: string;
+}
+
+const lineStateOptions: [string, boolean | null][] = [
+  ["Default", null],
+  ["On", true],
+  ["Off", false],
+];
+
+function lineStateToString(state: boolean | null) {
+  return lineStateOptions.find(([_, value]) => value === state)?.[0] ?? "Default";
+}
+
+function lineStateFromString(label: string) {
+  return lineStateOptions.find(([option, _]) => option === label)?.[1] ?? null;
+}
+
+const SerialSettingsSelect: React.FC<SerialSettingsSelectProps> = ({
+  value,
+  onChanged,
+  className,
+}) => {
+  const update = (changes: Partial<SerialSettings>) => {
+    onChanged({ ...value, ...changes });
+  };
+
+  return (
+    <div className="flex space-x-1">
+      <select
+        title="Data Bits"
+        className={className}
+        value={value.data_bits}
+        onChange={(e) => update({ data_bits: parseInt(e.target.value) })}
+      >
+        {[8, 7, 6, 5].map((dataBits) => (
+          <option key={dataBits} value={dataBits}>
+            {dataBits}
+          </option>
+        ))}
+      </select>
+      <select
+        title="Parity"
+        className={className}
+        value={value.parity}
+        onChange={(e) =>
+          update({ parity: e.target.value as SerialSettings["parity"] })
+        }
+      >
+        <option value="None">No Parity</option>
+        <option value="Even">Even</option>
+        <option value="Odd">Odd</option>
+      </select>
+      <select
+        title="Stop Bits"
+        className={className}
+        value={value.stop_bits}
+        onChange={(e) =>
+          update({ stop_bits: e.target.value as SerialSettings["stop_bits"] })
+        }
+      >
+        <option value="One">1 Stop</option>
+        <option value="Two">2 Stop</option>
+      </select>
+      <select
+        title="Flow Control"
+        className={className}
+        value={value.flow_control}
+        onChange={(e) =>
+          update({
+            flow_control: e.target.value as SerialSettings["flow_control"],
+          })
+        }
+      >
+        <option value="None">No Flow Control</option>
+        <option value="Hardware">RTS/CTS</option>
+        <option value="Software">XON/XOFF</option>
+      </select>
+      <input
+        type="number"
+        title="Read Timeout (ms)"
+        min={1}
+        className={`${className} w-[60px]`}
+        value={value.read_timeout_ms}
+        onChange={(e) =>
+          update({ read_timeout_ms: Math.max(1, parseInt(e.target.value) || 1) })
+        }
+      />
+      <select
+        title="DTR"
+        className={className}
+        value={lineStateToString(value.dtr)}
+        onChange={(e) => update({ dtr: lineStateFromString(e.target.value) })}
+      >
+        {lineStateOptions.map(([label, _]) => (
+          <option key={label} value={label}>
+            DTR {label}
+          </option>
+        ))}
+      </select>
+      <select
+        title="RTS"
+        className={className}
+        value={lineStateToString(value.rts)}
+        disabled={value.flow_control === "Hardware"}
+        onChange={(e) => update({ rts: lineStateFromString(e.target.value) })}
+      >
+        {lineStateOptions.map(([label, _]) => (
+          <option key={label} value={label}>
+            RTS {label}
+          </option>
+        ))}
+      </select>
+    </div>
+  );
+};
+
+export default SerialSettingsSelect;
@@ -2,24 +2,29 @@ import { listen } from "@tauri-apps/api/event";
 import { useEffect, useRef, useState } from "react";
 import React from "react";
 
+type EventKind = "TX" | "RX" | "INFO" | "ERROR";
+
 interface EventPayload {
-  data_type: string;
+  session_id: string | null;
+  kind: EventKind;
   data: Array<number>;
-  time: string;
+  /** Time of the serial read / write, in microseconds since the UNIX epoch */
+  timestamp_us: number;
+  sequence: number;
 }
 
 interface TerminalPanelProps {
   size: number;
 }
 
-function getCurrentTime() {
-  const now = new Date();
-  const hours = now.getHours().toString().padStart(2, "0");
-  const minutes = now.getMinutes().toString().padStart(2, "0");
-  const seconds = now.getSeconds().toString().padStart(2, "0");
-  const milliseconds = now.getMilliseconds().toString().padStart(3, "0");
+function formatTimestamp(timestampUs: number) {
+  const time = new Date(Math.floor(timestampUs / 1000));
+  const hours = time.getHours().toString().padStart(2, "0");
+  const minutes = time.getMinutes().toString().padStart(2, "0");
+  const seconds = time.getSeconds().toString().padStart(2, "0");
+  const microseconds = (timestampUs % 1000000).toString().padStart(6, "0");
 
-  const formattedTime = `${hours}:${minutes}:${seconds}.${milliseconds}`;
+  const formattedTime = `${hours}:${minutes}:${seconds}.${microseconds}`;
   return formattedTime;
 }
 
@@ -34,13 +39,14 @@ const TerminalPanel: React.FC<TerminalPanelProps> = ({ }) => {
 
   useEffect(() => {
     const unlisten = listen<EventPayload>("exchange_bytes_event", (event) => {
-      setLogs((logs) =>
-        logs.concat({
-          data: event.payload.data,
-          data_type: event.payload.data_type,
-          time: getCurrentTime(),
-        })
-      );
+      setLogs((logs) => {
+        const newLogs = logs.concat(event.payload);
+        // Events may arrive out of order, the sequence number tells their real order
+        if (logs.length > 0 && logs[logs.length - 1].sequence > event.payload.sequence) {
+          newLogs.sort((a, b) => a.sequence - b.sequence);
+        }
+        return newLogs;
+      });
     });
     scrollToBottom();
     return () => {
@@ -82,19 +88,21 @@ const TerminalPanel: React.FC<TerminalPanelProps> = ({ }) => {
       <div className={`h-[21vh] overflow-y-auto`}>
         {logs.map((log, index) => (
             <p className="h-fit" key={index}>
-              <b>[{log.data_type}]</b>&nbsp;
-              <b>[{log.time}]</b>&nbsp;
-              {log.data
-                .map((elem, _index) => {
-                  if (displayMode == "hex") {
-                    return elem.toString(16).padStart(2, "0").toUpperCase();
-                  } else if (displayMode == "decimal") {
-                    return elem.toString(10).padStart(3, "0");
-                  } else if (displayMode == "ascii") {
-                    return String.fromCharCode(elem);
-                  }
-                })
-                .join(" ")}
+              <b>[{log.kind}]</b>&nbsp;
+              <b>[{formatTimestamp(log.timestamp_us)}]</b>&nbsp;
+              {log.kind == "INFO" || log.kind == "ERROR"
+                ? new TextDecoder().decode(new Uint8Array(log.data))
+                : log.data
+                    .map((elem, _index) => {
+                      if (displayMode == "hex") {
+                        return elem.toString(16).padStart(2, "0").toUpperCase();
+                      } else if (displayMode == "decimal") {
+                        return elem.toString(10).padStart(3, "0");
+                      } else if (displayMode == "ascii") {
+                        return String.fromCharCode(elem);
+                      }
+                    })
+                    .join(" ")}
             </p>
         ))}
         <div ref={messagesEndRef} />
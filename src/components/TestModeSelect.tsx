@@ -1,7 +1,7 @@
 import { useState } from "react";
 
 import { MkDeviceTestMode, MkDeviceQuickMode } from "../DataTypes";
-import { invoke } from "@tauri-apps/api";
+import { executeModeSequence } from "../utils/device_info_util";
 
 type TestModeSelectOptions = {
   testModeOptions: MkDeviceTestMode[];
@@ -26,11 +26,8 @@ const TestModeSelect: React.FC<TestModeSelectOptions> = ({
       lastExecutedOption.startsWith("TESTMODE_")
     ) {
       const testModeId = parseInt(lastExecutedOption.split("_")[1]);
-      let sequenceOffExecutionSuccess: boolean = await invoke(
-        "execute_mode_sequence",
-        {
-          sequenceStr: testModeOptions[testModeId].sequence_off,
-        }
+      let sequenceOffExecutionSuccess = await executeModeSequence(
+        testModeOptions[testModeId].sequence_off
       );
       if (sequenceOffExecutionSuccess) {
         setLastExecutedOption("");
@@ -40,11 +37,8 @@ const TestModeSelect: React.FC<TestModeSelectOptions> = ({
       lastExecutedOption.startsWith("QUICKMODE_")
     ) {
       const quickModeId = parseInt(lastExecutedOption.split("_")[1]);
-      let sequenceOffExecutionSuccess: boolean = await invoke(
-        "execute_mode_sequence",
-        {
-          sequenceStr: quickOptions[quickModeId].sequence_off,
-        }
+      let sequenceOffExecutionSuccess = await executeModeSequence(
+        quickOptions[quickModeId].sequence_off
       );
       if (sequenceOffExecutionSuccess) {
         setLastExecutedOption("");
@@ -53,32 +47,24 @@ const TestModeSelect: React.FC<TestModeSelectOptions> = ({
   };
 
   const handleExecuteSelectedTestMode = async () => {
-    await invoke("stop_communication_task", {});
     await executeSequenceOffForLastOption();
     if (selectedOption.startsWith("TESTMODE_")) {
       const testModeId = parseInt(selectedOption.split("_")[1]);
-      let sequenceOnExecutionSuccess: boolean = await invoke(
-        "execute_mode_sequence",
-        {
-          sequenceStr: testModeOptions[testModeId].sequence_on,
-        }
+      let sequenceOnExecutionSuccess = await executeModeSequence(
+        testModeOptions[testModeId].sequence_on
       );
       if (sequenceOnExecutionSuccess) {
         setLastExecutedOption(selectedOption);
       }
     } else if (selectedOption.startsWith("QUICKMODE_")) {
       const quickModeId = parseInt(selectedOption.split("_")[1]);
-      let sequenceOnExecutionSuccess: boolean = await invoke(
-        "execute_mode_sequence",
-        {
-          sequenceStr: quickOptions[quickModeId].sequence_on,
-        }
+      let sequenceOnExecutionSuccess = await executeModeSequence(
+        quickOptions[quickModeId].sequence_on
       );
       if (sequenceOnExecutionSuccess) {
         setLastExecutedOption(selectedOption);
       }
     }
-    await invoke("start_communication_task", {});
   };
   return (
     <>
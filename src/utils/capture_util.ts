@@ -0,0 +1,45 @@
+import { invoke } from "@tauri-apps/api";
+import { error, info } from "tauri-plugin-log-api";
+import { formatError } from "./error_util";
+
+export type ReplayTarget = "Ui" | "Session";
+
+export async function startCapture(filePath: string) {
+  try {
+    await invoke("start_capture", { filePath: filePath });
+    return true;
+  } catch (err) {
+    error(`Error occurred while starting the capture: ${formatError(err)}`);
+    return false;
+  }
+}
+
+export async function stopCapture() {
+  try {
+    let result: number | null = await invoke("stop_capture", {});
+    info(`Capture stopped, ${result ?? 0} records written`);
+    return result;
+  } catch (err) {
+    error(`Error occurred while stopping the capture: ${formatError(err)}`);
+    return null;
+  }
+}
+
+export async function replayCapture(
+  filePath: string,
+  target: ReplayTarget,
+  sessionId: string | null = null,
+  speed: number | null = null
+) {
+  try {
+    let result: number = await invoke("replay_capture", {
+      filePath: filePath,
+      target: target,
+      sessionId: sessionId,
+      speed: speed,
+    });
+    return result;
+  } catch (err) {
+    throw formatError(err);
+  }
+}
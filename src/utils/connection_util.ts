@@ -1,20 +1,41 @@
 import { invoke } from "@tauri-apps/api";
-import { info } from "tauri-plugin-log-api";
+import { error, info } from "tauri-plugin-log-api";
+import { formatError } from "./error_util";
+import { AutodetectResult, SerialSettings } from "../DataTypes";
+
+export const defaultSerialSettings: SerialSettings = {
+  baud_rate: 19200,
+  data_bits: 8,
+  parity: "None",
+  stop_bits: "One",
+  flow_control: "None",
+  read_timeout_ms: 10,
+  dtr: null,
+  rts: null,
+};
 
 export async function connectToDevice(
   selectedDevice: string,
-  selectedBaudRate: number
+  serialSettings: SerialSettings
 ) {
-  let result: boolean = await invoke("connect_to_device", {
-    deviceName: selectedDevice,
-    baudRate: selectedBaudRate,
-  });
-  return result;
+  try {
+    await invoke("connect_to_device", {
+      deviceName: selectedDevice,
+      serialSettings: serialSettings,
+    });
+  } catch (err) {
+    throw formatError(err);
+  }
 }
 
 export async function disconnectFromDevice() {
-  let result: boolean = await invoke("disconnect_from_device", {});
-  return result;
+  try {
+    await invoke("disconnect_from_device", {});
+    return true;
+  } catch (err) {
+    error(`Error occurred while disconnecting: ${formatError(err)}`);
+    return false;
+  }
 }
 
 export async function getConnectedDevice() {
@@ -22,3 +43,37 @@ export async function getConnectedDevice() {
   info(`Connected device: ${result}`);
   return result;
 }
+
+export async function getSerialSettings(deviceName: string) {
+  try {
+    let result: SerialSettings = await invoke("get_serial_settings", {
+      deviceName: deviceName,
+    });
+    return result;
+  } catch (err) {
+    error(`Error occurred while loading serial settings: ${formatError(err)}`);
+    return defaultSerialSettings;
+  }
+}
+
+export async function autodetectConnection(deviceName: string) {
+  try {
+    let result: AutodetectResult = await invoke("autodetect_connection", {
+      deviceName: deviceName,
+    });
+    info(`Detected ${result.model} on ${deviceName}`);
+    return result;
+  } catch (err) {
+    throw formatError(err);
+  }
+}
+
+export async function setAutoReconnect(enabled: boolean) {
+  try {
+    await invoke("set_auto_reconnect", { enabled: enabled });
+    return true;
+  } catch (err) {
+    error(`Error occurred while setting auto reconnect: ${formatError(err)}`);
+    return false;
+  }
+}
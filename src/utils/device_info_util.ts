@@ -1,24 +1,45 @@
 import { invoke } from "@tauri-apps/api";
-import { MkDeviceConfig, MkDeviceCell, MkDeviceCalib} from "../DataTypes";
+import { message } from "@tauri-apps/api/dialog";
+import { error, info } from "tauri-plugin-log-api";
+import { MkDeviceConfig, MkDeviceCell, MkDeviceCalib, SequenceStepTrace } from "../DataTypes";
+import { formatError } from "./error_util";
+
+async function invokeDeviceInfo(command: string, label: string) {
+  try {
+    let result: string = await invoke(command);
+    return result;
+  } catch (err) {
+    error(`Error occurred while calling ${command}: ${formatError(err)}`);
+    return `${label}: [UNABLE TO READ] ${formatError(err)}`;
+  }
+}
+
+async function showError(title: string, err: unknown) {
+  error(`${title}: ${formatError(err)}`);
+  await message(`${title}:\n${formatError(err)}`, {
+    title: "Tinymesh CC Tool",
+    type: "error",
+  });
+}
 
 export async function getRSSI() {
-  return await invoke("get_device_rssi");
+  return await invokeDeviceInfo("get_device_rssi", "RSSI");
 }
 
 export async function getTemperature() {
-  return await invoke("get_device_temperature");
+  return await invokeDeviceInfo("get_device_temperature", "Temperature");
 }
 
 export async function getVoltage() {
-  return await invoke("get_device_voltage");
+  return await invokeDeviceInfo("get_device_voltage", "Voltage");
 }
 
 export async function getAnalog() {
-  return await invoke("get_device_analog");
+  return await invokeDeviceInfo("get_device_analog", "Analog");
 }
 
 export async function getDigital() {
-  return await invoke("get_device_digital");
+  return await invokeDeviceInfo("get_device_digital", "Digital");
 }
 
 export async function getDeviceConfig() {
@@ -33,11 +54,44 @@ export async function getDeviceCalib() {
 }
 
 export async function setDeviceConfig(cells: MkDeviceCell[]) {
-  let result: boolean = await invoke("set_device_config", { cells: cells });
-  return result;
+  try {
+    await invoke("set_device_config", { cells: cells });
+    return true;
+  } catch (err) {
+    await showError("Could not write the device config", err);
+    return false;
+  }
 }
 
 export async function setDeviceCalib(cells: MkDeviceCell[]) {
-  let result: boolean = await invoke("set_device_calib", { cells: cells });
-  return result;
+  try {
+    await invoke("set_device_calib", { cells: cells });
+    return true;
+  } catch (err) {
+    await showError("Could not write the device calibration", err);
+    return false;
+  }
+}
+
+export async function factoryReset() {
+  try {
+    await invoke("factory_reset", {});
+    return true;
+  } catch (err) {
+    await showError("Could not factory reset the device", err);
+    return false;
+  }
+}
+
+export async function executeModeSequence(sequenceStr: string) {
+  try {
+    let trace: SequenceStepTrace[] = await invoke("execute_mode_sequence", {
+      sequenceStr: sequenceStr,
+    });
+    info(`Executed mode sequence ${sequenceStr} in ${trace.length} steps`);
+    return true;
+  } catch (err) {
+    error(`Error occurred while executing mode sequence ${sequenceStr}: ${formatError(err)}`);
+    return false;
+  }
 }
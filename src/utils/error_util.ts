@@ -0,0 +1,40 @@
+import { CcToolError } from "../DataTypes";
+
+function toHex(bytes: number[]) {
+  return bytes.map((byte) => byte.toString(16).padStart(2, "0").toUpperCase()).join(" ");
+}
+
+export function formatError(err: unknown) {
+  if (typeof err !== "object" || err === null || !("kind" in err)) {
+    return `${err}`;
+  }
+  const ccToolError = err as CcToolError;
+  switch (ccToolError.kind) {
+    case "NotConnected":
+      return "No device connected";
+    case "UnknownSession":
+      return `No session with ID '${ccToolError.session_id}'`;
+    case "UnknownTask":
+      return `No task with ID ${ccToolError.task_id}`;
+    case "PortBusy":
+      return "The port is busy with another operation";
+    case "Timeout":
+      return `Timed out waiting for the device (${ccToolError.received.length} bytes received)`;
+    case "UnexpectedResponse":
+      return `Unexpected response from device: expected ${ccToolError.expected}, got [${toHex(ccToolError.got)}]`;
+    case "RmdNotFound":
+      return `No matching RMD file available for ${ccToolError.model}. Searched:\n${ccToolError.searched_paths.join("\n")}`;
+    case "RmdParse":
+      return `Error in RMD file at line ${ccToolError.line}: ${ccToolError.message}`;
+    case "ConfigNotRead":
+      return "Read the device memory first";
+    case "ValidationFailed":
+      return `Invalid value for address 0x${ccToolError.address.toString(16).toUpperCase()}: ${ccToolError.message}`;
+    case "InvalidInput":
+      return `Invalid input: ${ccToolError.message}`;
+    case "Io":
+      return `I/O error: ${ccToolError.message}`;
+    case "Internal":
+      return `Internal error: ${ccToolError.message}`;
+  }
+}
@@ -0,0 +1,45 @@
+import { invoke } from "@tauri-apps/api";
+import { formatError } from "./error_util";
+import { MeshNode } from "../DataTypes";
+
+/** Returns the nodes heard through the connected gateway (packet mode). */
+export async function getMeshNodes(sessionId: string | null = null) {
+  try {
+    let result: MeshNode[] = await invoke("get_mesh_nodes", {
+      sessionId: sessionId,
+    });
+    return result;
+  } catch (err) {
+    throw formatError(err);
+  }
+}
+
+/** Forgets the nodes heard through the connected gateway. */
+export async function clearMeshNodes(sessionId: string | null = null) {
+  try {
+    await invoke("clear_mesh_nodes", { sessionId: sessionId });
+  } catch (err) {
+    throw formatError(err);
+  }
+}
+
+/**
+ * Writes the node table to a file, as a JSON list of nodes or as a GraphViz DOT graph of the topology.
+ * Returns the number of exported nodes.
+ */
+export async function exportMeshTopology(
+  filePath: string,
+  format: "Json" | "Dot",
+  sessionId: string | null = null
+) {
+  try {
+    let result: number = await invoke("export_mesh_topology", {
+      filePath: filePath,
+      format: format,
+      sessionId: sessionId,
+    });
+    return result;
+  } catch (err) {
+    throw formatError(err);
+  }
+}
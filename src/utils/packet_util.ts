@@ -0,0 +1,26 @@
+import { invoke } from "@tauri-apps/api";
+import { formatError } from "./error_util";
+import { TinyMeshCommand } from "../DataTypes";
+
+/**
+ * Sends a command to a node through the connected gateway (packet mode).
+ * Returns the command number, which the node returns in its acknowledge.
+ */
+export async function sendPacketCommand(
+  systemId: number,
+  destination: number,
+  command: TinyMeshCommand,
+  sessionId: string | null = null
+) {
+  try {
+    let result: number = await invoke("send_packet_command", {
+      systemId: systemId,
+      destination: destination,
+      command: command,
+      sessionId: sessionId,
+    });
+    return result;
+  } catch (err) {
+    throw formatError(err);
+  }
+}
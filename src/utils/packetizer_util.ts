@@ -0,0 +1,24 @@
+import { invoke } from "@tauri-apps/api";
+import { error } from "tauri-plugin-log-api";
+import { formatError } from "./error_util";
+import { PacketizerConfig } from "../DataTypes";
+
+export async function getRxPacketizer(sessionId: string | null = null) {
+  try {
+    let result: PacketizerConfig = await invoke("get_rx_packetizer", { sessionId: sessionId });
+    return result;
+  } catch (err) {
+    error(`Error occurred while reading the RX packetizer: ${formatError(err)}`);
+    return null;
+  }
+}
+
+export async function setRxPacketizer(config: PacketizerConfig, sessionId: string | null = null) {
+  try {
+    await invoke("set_rx_packetizer", { config: config, sessionId: sessionId });
+    return true;
+  } catch (err) {
+    error(`Error occurred while setting the RX packetizer: ${formatError(err)}`);
+    return false;
+  }
+}
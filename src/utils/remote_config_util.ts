@@ -0,0 +1,40 @@
+import { invoke } from "@tauri-apps/api";
+import { formatError } from "./error_util";
+import { MkDeviceCell, MkDeviceConfig } from "../DataTypes";
+
+/** Reads the configuration of a remote node through the connected gateway (packet mode). */
+export async function getRemoteDeviceConfig(
+  systemId: number,
+  uniqueId: number,
+  sessionId: string | null = null
+) {
+  try {
+    let result: MkDeviceConfig = await invoke("get_remote_device_config", {
+      systemId: systemId,
+      uniqueId: uniqueId,
+      sessionId: sessionId,
+    });
+    return result;
+  } catch (err) {
+    throw formatError(err);
+  }
+}
+
+/** Writes the changed cells of a remote node, which must have been read with `getRemoteDeviceConfig` before. */
+export async function setRemoteDeviceConfig(
+  systemId: number,
+  uniqueId: number,
+  cells: MkDeviceCell[],
+  sessionId: string | null = null
+) {
+  try {
+    await invoke("set_remote_device_config", {
+      systemId: systemId,
+      uniqueId: uniqueId,
+      cells: cells,
+      sessionId: sessionId,
+    });
+  } catch (err) {
+    throw formatError(err);
+  }
+}
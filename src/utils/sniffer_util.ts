@@ -0,0 +1,78 @@
+import { invoke } from "@tauri-apps/api";
+import { formatError } from "./error_util";
+import { RssiSourceStats, SnifferNodeStats } from "../DataTypes";
+
+/**
+ * Enables or disables parsing the output of the sniffer test modes into `sniffer_event`s and statistics.
+ * Off by default, as the output format has not been verified against a real module.
+ */
+export async function setSnifferOutputParsing(enabled: boolean) {
+  try {
+    await invoke("set_sniffer_output_parsing", { enabled: enabled });
+  } catch (err) {
+    throw formatError(err);
+  }
+}
+
+/**
+ * Returns the per-node statistics of the packet sniffer test mode.
+ * The test mode is started with `executeModeSequence`, the statistics of the last run are kept after it was stopped.
+ */
+export async function getSnifferStats(sessionId: string | null = null) {
+  try {
+    let result: SnifferNodeStats[] = await invoke("get_sniffer_stats", {
+      sessionId: sessionId,
+    });
+    return result;
+  } catch (err) {
+    throw formatError(err);
+  }
+}
+
+/** Clears the per-node statistics of the packet sniffer test mode. */
+export async function clearSnifferStats(sessionId: string | null = null) {
+  try {
+    await invoke("clear_sniffer_stats", { sessionId: sessionId });
+  } catch (err) {
+    throw formatError(err);
+  }
+}
+
+/**
+ * Returns the rolling RSSI statistics per source of the RSSI sniffer test mode, in dBm.
+ * The history of the last run is kept after the test mode was stopped.
+ */
+export async function getRssiSnifferStats(sessionId: string | null = null) {
+  try {
+    let result: RssiSourceStats[] = await invoke("get_rssi_sniffer_stats", {
+      sessionId: sessionId,
+    });
+    return result;
+  } catch (err) {
+    throw formatError(err);
+  }
+}
+
+/** Clears the RSSI history of the RSSI sniffer test mode. */
+export async function clearRssiSnifferHistory(sessionId: string | null = null) {
+  try {
+    await invoke("clear_rssi_sniffer_history", { sessionId: sessionId });
+  } catch (err) {
+    throw formatError(err);
+  }
+}
+
+/** Writes the RSSI history of the RSSI sniffer test mode to a CSV file. */
+export async function exportRssiSnifferCsv(
+  filePath: string,
+  sessionId: string | null = null
+) {
+  try {
+    await invoke("export_rssi_sniffer_csv", {
+      filePath: filePath,
+      sessionId: sessionId,
+    });
+  } catch (err) {
+    throw formatError(err);
+  }
+}
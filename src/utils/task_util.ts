@@ -0,0 +1,24 @@
+import { invoke } from "@tauri-apps/api";
+import { error } from "tauri-plugin-log-api";
+import { formatError } from "./error_util";
+import { TaskInfo } from "../DataTypes";
+
+export async function listTasks(sessionId: string | null = null) {
+  try {
+    let result: TaskInfo[] = await invoke("list_tasks", { sessionId: sessionId });
+    return result;
+  } catch (err) {
+    error(`Error occurred while listing tasks: ${formatError(err)}`);
+    return [];
+  }
+}
+
+export async function cancelTask(taskId: number) {
+  try {
+    await invoke("cancel_task", { taskId: taskId });
+    return true;
+  } catch (err) {
+    error(`Error occurred while cancelling task ${taskId}: ${formatError(err)}`);
+    return false;
+  }
+}
@@ -0,0 +1,57 @@
+import { invoke } from "@tauri-apps/api";
+import { formatError } from "./error_util";
+import { ActiveMode, SequenceStepTrace, TxGuardConfig, TxGuardStatus } from "../DataTypes";
+
+/**
+ * Returns the test or quick mode the device was put in, `null` if no mode is active.
+ * Changes of the active mode are also emitted as `test_mode_event`.
+ */
+export async function getActiveMode(sessionId: string | null = null) {
+  try {
+    let result: ActiveMode | null = await invoke("get_active_mode", {
+      sessionId: sessionId,
+    });
+    return result;
+  } catch (err) {
+    throw formatError(err);
+  }
+}
+
+/** Executes the `sequence_off` of the active mode. */
+export async function stopActiveMode(sessionId: string | null = null) {
+  try {
+    let result: SequenceStepTrace[] = await invoke("stop_active_mode", {
+      sessionId: sessionId,
+    });
+    return result;
+  } catch (err) {
+    throw formatError(err);
+  }
+}
+
+/**
+ * Sets the limits of the TX test modes for all sessions, replacing the ones of the RMD files.
+ * Pass `null` to use the limits of the RMD files again. The limits are kept across restarts.
+ */
+export async function setTxGuardConfig(config: TxGuardConfig | null) {
+  try {
+    await invoke("set_tx_guard_config", { config: config });
+  } catch (err) {
+    throw formatError(err);
+  }
+}
+
+/**
+ * Returns the on-time and duty cycle usage of the TX test modes.
+ * TX test modes turned off at a limit are emitted as `tx_guard_event`.
+ */
+export async function getTxGuardStatus(sessionId: string | null = null) {
+  try {
+    let result: TxGuardStatus = await invoke("get_tx_guard_status", {
+      sessionId: sessionId,
+    });
+    return result;
+  } catch (err) {
+    throw formatError(err);
+  }
+}